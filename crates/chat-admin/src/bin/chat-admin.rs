@@ -0,0 +1,214 @@
+use std::{env, str::FromStr};
+
+use chat_core::{
+    models::Role,
+    paths::Paths,
+    protocol::{UserFilter, UserSort},
+};
+use chat_server::{
+    establish_connection,
+    fixtures::{self, Fixtures},
+    gc, get_user_by_name, get_user_directory, set_role,
+};
+use chrono::{DateTime, Duration, Utc};
+use eyre::{eyre, Result};
+
+const USAGE: &str = "Usage: chat-admin <command> [options]
+
+Commands:
+  seed   Fills the database with generated users and messages
+  gc     Removes the attachment files no message uses anymore
+  role   Gives a user a role, like chat-admin role alice admin
+  users  Lists the users, like chat-admin users --sort active --role moderator
+
+Options of seed:
+  --users <count>      Users to create (default 20)
+  --messages <count>   Messages to create (default 1000)
+  --days <days>        Spreads the messages over this many days up to now (default 30)
+  --seed <number>      The same seed gives the same data (default 0)
+  --password <text>    Password of every created user (default \"password\")
+  --database <file>    Database to fill, relative to the data directory (default data.db)
+  --data-dir <dir>     Directory holding the database
+
+Options of gc:
+  --database <file>    Database the attachments are in, relative to the data directory (default data.db)
+  --attachments <dir>  Directory the files are in, relative to the data directory (default attachments)
+  --data-dir <dir>     Directory holding the database and the files
+
+Files changed within the last hour are kept, as uploads in progress may not have written their message yet.
+
+Usage of role: chat-admin role <username> <user|moderator|admin> [options]
+
+Options of role:
+  --database <file>    Database the user is in, relative to the data directory (default data.db)
+  --data-dir <dir>     Directory holding the database
+
+Options of users:
+  --sort <order>       name, joined for the newest first or active for who was active last first (default name)
+  --role <role>        Only lists users with this role
+  --banned <yes|no>    Only lists the banned users, or the ones not banned
+  --limit <count>      Users to list at most (default 100)
+  --offset <count>     Users to skip, to list the ones after them (default 0)
+  --database <file>    Database the users are in, relative to the data directory (default data.db)
+  --data-dir <dir>     Directory holding the database
+
+Who is online is only known to the running server, which lists it with GET /admin/users.";
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("seed") => seed(&args[1..]),
+        Some("gc") => collect_garbage(&args[1..]),
+        Some("role") => assign_role(&args[1..]),
+        Some("users") => list_users(&args[1..]),
+        _ => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+fn seed(args: &[String]) -> Result<()> {
+    let paths = Paths::resolve(args)?;
+    paths.create()?;
+    let defaults = Fixtures::default();
+    let days: i64 = option(args, "--days", 30)?;
+    let end = Utc::now();
+    let fixtures = Fixtures {
+        users: option(args, "--users", defaults.users)?,
+        messages: option(args, "--messages", defaults.messages)?,
+        start: end - Duration::days(days),
+        end,
+        password: option(args, "--password", defaults.password)?,
+        seed: option(args, "--seed", defaults.seed)?,
+    };
+    let database = paths
+        .data
+        .join(option(args, "--database", "data.db".to_string())?);
+
+    let conn = &mut establish_connection(&database)?;
+    let users = fixtures::populate(conn, &fixtures)?;
+    println!(
+        "Created {} users and {} messages in {}. Every user has the password \"{}\".",
+        users.len(),
+        if users.is_empty() {
+            0
+        } else {
+            fixtures.messages
+        },
+        database.display(),
+        fixtures.password
+    );
+    Ok(())
+}
+
+fn collect_garbage(args: &[String]) -> Result<()> {
+    let paths = Paths::resolve(args)?;
+    let database = paths
+        .data
+        .join(option(args, "--database", "data.db".to_string())?);
+    let attachments = paths
+        .data
+        .join(option(args, "--attachments", "attachments".to_string())?);
+    if !attachments.is_dir() {
+        return Err(eyre!(
+            "There is no attachment directory at {}",
+            attachments.display()
+        ));
+    }
+
+    let conn = &mut establish_connection(&database)?;
+    let collected = gc::collect(conn, &attachments)?;
+    println!(
+        "Removed {} unused files from {}, which freed {:.1} MiB ({} bytes).",
+        collected.files,
+        attachments.display(),
+        collected.bytes as f64 / (1024.0 * 1024.0),
+        collected.bytes
+    );
+    Ok(())
+}
+
+fn assign_role(args: &[String]) -> Result<()> {
+    let (Some(username), Some(role)) = (args.first(), args.get(1)) else {
+        return Err(eyre!("Give the name of the user and the role"));
+    };
+    let role = Role::from_key(role)
+        .ok_or_else(|| eyre!("{role} is not a role. Roles are user, moderator and admin"))?;
+    let paths = Paths::resolve(&args[2..])?;
+    let database = paths
+        .data
+        .join(option(args, "--database", "data.db".to_string())?);
+
+    let conn = &mut establish_connection(&database)?;
+    let user = get_user_by_name(conn, username)?;
+    set_role(conn, user.id, role)?;
+    println!("{} is now {}.", user.username, role.key());
+    Ok(())
+}
+
+fn list_users(args: &[String]) -> Result<()> {
+    let sort: String = option(args, "--sort", "name".to_string())?;
+    let sort = UserSort::from_key(&sort).ok_or_else(|| {
+        eyre!("{sort} is not an order. Users are sorted by name, joined or active")
+    })?;
+    let role: String = option(args, "--role", String::new())?;
+    let role = match role.as_str() {
+        "" => None,
+        key => Some(
+            Role::from_key(key)
+                .ok_or_else(|| eyre!("{key} is not a role. Roles are user, moderator and admin"))?,
+        ),
+    };
+    let banned: String = option(args, "--banned", String::new())?;
+    let banned = match banned.as_str() {
+        "" => None,
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => return Err(eyre!("--banned takes yes or no")),
+    };
+    let filter = UserFilter {
+        role,
+        online: None,
+        banned,
+    };
+    let limit: i64 = option(args, "--limit", 100)?;
+    let offset: i64 = option(args, "--offset", 0)?;
+    let paths = Paths::resolve(args)?;
+    let database = paths
+        .data
+        .join(option(args, "--database", "data.db".to_string())?);
+
+    let conn = &mut establish_connection(&database)?;
+    let users = get_user_directory(conn, sort, &filter, &[], offset, limit)?;
+    println!(
+        "{:>6}  {:<24} {:<10} {:<7} {:<16} Last active",
+        "Id", "Name", "Role", "Banned", "Joined"
+    );
+    let format = |date: Option<DateTime<Utc>>, missing: &str| match date {
+        Some(date) => date.format("%Y-%m-%d %H:%M").to_string(),
+        None => missing.to_string(),
+    };
+    for entry in &users {
+        println!(
+            "{:>6}  {:<24} {:<10} {:<7} {:<16} {}",
+            entry.user.id,
+            entry.user.username,
+            entry.user.role.key(),
+            if entry.banned { "yes" } else { "no" },
+            format(entry.createddate, "unknown"),
+            format(entry.lastactive, "never")
+        );
+    }
+    Ok(())
+}
+
+/// Reads the value following the option, or returns the default if the option is not given.
+fn option<T: FromStr>(args: &[String], name: &str, default: T) -> Result<T> {
+    let Some(index) = args.iter().position(|arg| arg == name) else {
+        return Ok(default);
+    };
+    args.get(index + 1)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| eyre!("{name} needs a valid value"))
+}
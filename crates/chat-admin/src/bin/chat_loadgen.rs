@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use std::{env, str::FromStr};
+
+use chat_core::models::Message;
+use chat_core::protocol::{Credentials, LoginResult, ServerInfo, SYSTEM_EVENT};
+use eyre::{eyre, Result};
+use futures_util::StreamExt;
+use reqwest::{Client, RequestBuilder};
+use reqwest_eventsource::{Event, EventSource};
+use tokio::sync::oneshot;
+use tokio::time::MissedTickBehavior;
+
+const USAGE: &str = "Usage: chat_loadgen [options]
+
+Simulates clients that login, listen for new messages and send messages at a steady rate, then reports how long the
+server took and how many requests failed.
+
+Options:
+  --server <address>   Server to connect to (default 127.0.0.1:8000)
+  --clients <count>    Clients to simulate (default 10)
+  --rate <count>       Messages each client sends per minute (default 10)
+  --duration <secs>    How long each client sends messages once connected (default 60)
+  --prefix <name>      Clients login as this name followed by their number, registering if needed (default loadgen)
+  --password <text>    Password of the simulated users (default \"password\")";
+
+/// How long to wait for the event stream to open.
+const EVENTS_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to keep listening for the last messages after sending stopped.
+const DELIVERY_GRACE: Duration = Duration::from_secs(5);
+
+struct Options {
+    base_url: String,
+    clients: usize,
+    rate: f64,
+    duration: Duration,
+    prefix: String,
+    password: String,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self> {
+        let address: String = option(args, "--server", "127.0.0.1:8000".to_string())?;
+        let base_url = if address.contains("://") {
+            address.trim_end_matches('/').to_string()
+        } else {
+            format!("http://{}", address.trim_end_matches('/'))
+        };
+        let rate: f64 = option(args, "--rate", 10.0)?;
+        if rate <= 0.0 {
+            return Err(eyre!("--rate has to be above 0"));
+        }
+        Ok(Self {
+            base_url,
+            clients: option(args, "--clients", 10)?,
+            rate,
+            duration: Duration::from_secs(option(args, "--duration", 60)?),
+            prefix: option(args, "--prefix", "loadgen".to_string())?,
+            password: option(args, "--password", "password".to_string())?,
+        })
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}{endpoint}", self.base_url)
+    }
+}
+
+/// How long each kind of operation took and why operations failed, collected from all clients.
+#[derive(Default)]
+struct Report {
+    latencies: HashMap<&'static str, Vec<Duration>>,
+    errors: HashMap<&'static str, HashMap<String, u32>>,
+}
+
+impl Report {
+    fn record(&mut self, operation: &'static str, outcome: Result<Duration, String>) {
+        match outcome {
+            Ok(latency) => self.latencies.entry(operation).or_default().push(latency),
+            Err(reason) => {
+                *self
+                    .errors
+                    .entry(operation)
+                    .or_default()
+                    .entry(reason)
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    fn print(&mut self) {
+        println!(
+            "{:<12}{:>8}{:>9}{:>12}{:>12}{:>12}{:>12}",
+            "operation", "count", "errors", "p50", "p90", "p99", "max"
+        );
+        for operation in ["login", "send", "delivery", "events"] {
+            let latencies = self.latencies.entry(operation).or_default();
+            let errors: u32 = self
+                .errors
+                .get(operation)
+                .map_or(0, |errors| errors.values().sum());
+            if latencies.is_empty() && errors == 0 {
+                continue;
+            }
+            latencies.sort();
+            let total = latencies.len() + errors as usize;
+            #[allow(clippy::cast_precision_loss)]
+            let error_rate = f64::from(errors) / total as f64 * 100.0;
+            println!(
+                "{operation:<12}{total:>8}{error_rate:>8.1}%{:>12}{:>12}{:>12}{:>12}",
+                percentile(latencies, 0.5),
+                percentile(latencies, 0.9),
+                percentile(latencies, 0.99),
+                percentile(latencies, 1.0),
+            );
+        }
+
+        if !self.errors.is_empty() {
+            println!("\nErrors:");
+            for (operation, errors) in &self.errors {
+                for (reason, count) in errors {
+                    println!("  {operation}: {reason} ({count}x)");
+                }
+            }
+        }
+    }
+}
+
+/// Formats the latency below which the given share of the sorted latencies fall.
+fn percentile(sorted: &[Duration], share: f64) -> String {
+    if sorted.is_empty() {
+        return "-".into();
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let index = ((sorted.len() - 1) as f64 * share).round() as usize;
+    format!("{:.1?}", sorted[index])
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let options = Arc::new(Options::parse(&args)?);
+    let http = Client::new();
+
+    let info: ServerInfo = http
+        .get(options.url("/info"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    println!(
+        "Simulating {} clients sending {} messages per minute each for {:?} against {} ({} {})\n",
+        options.clients, options.rate, options.duration, options.base_url, info.name, info.version
+    );
+
+    let report = Arc::new(Mutex::new(Report::default()));
+    let clients: Vec<_> = (0..options.clients)
+        .map(|index| {
+            tokio::spawn(simulate(
+                index,
+                Arc::clone(&options),
+                http.clone(),
+                Arc::clone(&report),
+                info.terms_version.clone(),
+            ))
+        })
+        .collect();
+    for client in clients {
+        client.await?;
+    }
+
+    lock(&report).print();
+    Ok(())
+}
+
+/// Acts like one user: logs in, listens for new messages and sends messages for the configured duration. Records how long
+/// each step took, including how long it took for sent messages to come back over the event stream.
+async fn simulate(
+    index: usize,
+    options: Arc<Options>,
+    http: Client,
+    report: Arc<Mutex<Report>>,
+    terms: Option<String>,
+) {
+    let username = format!("{}{index}", options.prefix);
+    let start = Instant::now();
+    let login = match login(&http, &options, &username, terms).await {
+        Ok(login) => {
+            lock(&report).record("login", Ok(start.elapsed()));
+            login
+        }
+        Err(reason) => {
+            lock(&report).record("login", Err(reason));
+            return;
+        }
+    };
+
+    // Messages sent but not seen on the event stream yet, keyed by their text
+    let pending: Arc<Mutex<HashMap<String, Instant>>> = Arc::default();
+    let request = http.get(options.url("/events")).bearer_auth(&login.token);
+    let mut events = match EventSource::new(request) {
+        Ok(events) => events,
+        Err(e) => {
+            lock(&report).record("events", Err(e.to_string()));
+            return;
+        }
+    };
+    let (opened, open) = oneshot::channel();
+    let listener = {
+        let pending = Arc::clone(&pending);
+        let report = Arc::clone(&report);
+        let mut opened = Some(opened);
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                match event {
+                    Ok(Event::Open) => {
+                        if let Some(opened) = opened.take() {
+                            let _ = opened.send(());
+                        }
+                    }
+                    Ok(Event::Message(event)) if event.event != SYSTEM_EVENT => {
+                        let Ok(message) = serde_json::from_str::<Message>(&event.data) else {
+                            continue;
+                        };
+                        if let Some(sent) = lock(&pending).remove(&message.messagetext) {
+                            lock(&report).record("delivery", Ok(sent.elapsed()));
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => lock(&report).record("events", Err(e.to_string())),
+                }
+            }
+        })
+    };
+
+    // Messages sent before the stream is open would never arrive
+    if !matches!(tokio::time::timeout(EVENTS_TIMEOUT, open).await, Ok(Ok(()))) {
+        listener.abort();
+        lock(&report).record("events", Err("did not open in time".into()));
+        return;
+    }
+
+    let deadline = Instant::now() + options.duration;
+    let interval = Duration::from_secs_f64(60.0 / options.rate);
+    // Spread the clients out, so they do not all send at the same moment
+    #[allow(clippy::cast_precision_loss)]
+    let offset = interval.mul_f64(index as f64 / options.clients as f64);
+    tokio::time::sleep(offset).await;
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut sequence = 0;
+    loop {
+        ticker.tick().await;
+        if Instant::now() >= deadline {
+            break;
+        }
+        let text = format!("{username} says hello #{sequence}");
+        sequence += 1;
+        let start = Instant::now();
+        lock(&pending).insert(text.clone(), start);
+        let request = http
+            .post(options.url("/message"))
+            .bearer_auth(&login.token)
+            .body(text.clone());
+        let outcome = send(request).await.map(|()| start.elapsed());
+        if outcome.is_err() {
+            lock(&pending).remove(&text);
+        }
+        lock(&report).record("send", outcome);
+    }
+
+    tokio::time::sleep(DELIVERY_GRACE).await;
+    listener.abort();
+    let missing = lock(&pending).len();
+    for _ in 0..missing {
+        lock(&report).record("delivery", Err("never arrived".into()));
+    }
+}
+
+/// Logs in as the user, registering it first if that fails.
+async fn login(
+    http: &Client,
+    options: &Options,
+    username: &str,
+    terms: Option<String>,
+) -> Result<LoginResult, String> {
+    let credentials = Credentials {
+        username: username.to_string(),
+        password: options.password.clone(),
+        remember_me: false,
+        accepted_tos_version: terms,
+        invite_code: None,
+    };
+    let attempt = || async {
+        let response = http
+            .post(options.url("/auth/login"))
+            .json(&credentials)
+            .send()
+            .await
+            .map_err(|e| describe(&e))?;
+        if !response.status().is_success() {
+            return Err(response.status().to_string());
+        }
+        response
+            .json::<LoginResult>()
+            .await
+            .map_err(|e| describe(&e))
+    };
+
+    if let Ok(login) = attempt().await {
+        return Ok(login);
+    }
+    send(http.post(options.url("/register")).json(&credentials)).await?;
+    attempt().await
+}
+
+/// Sends the request, turning failures and error responses into a short reason.
+async fn send(request: RequestBuilder) -> Result<(), String> {
+    let response = request.send().await.map_err(|e| describe(&e))?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(response.status().to_string())
+    }
+}
+
+fn describe(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        "timed out".into()
+    } else if error.is_connect() {
+        "could not connect".into()
+    } else if error.is_decode() {
+        "invalid response".into()
+    } else {
+        "request failed".into()
+    }
+}
+
+/// Reads the value following the option, or returns the default if the option is not given.
+fn option<T: FromStr>(args: &[String], name: &str, default: T) -> Result<T> {
+    let Some(index) = args.iter().position(|arg| arg == name) else {
+        return Ok(default);
+    };
+    args.get(index + 1)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| eyre!("{name} needs a valid value"))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
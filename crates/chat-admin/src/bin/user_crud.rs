@@ -1,6 +1,7 @@
-use std::{io::stdin, process::exit};
+use std::{env, io::stdin, path::Path, process::exit};
 
-use chat_app::{
+use chat_core::paths::Paths;
+use chat_server::{
     change_username, check_password, create_user, delete_user, establish_connection, get_all_users,
     get_user_by_name, set_password,
 };
@@ -29,6 +30,8 @@ enum CrudError {
 }
 
 fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let database = Paths::resolve(&args)?.data.join("data.db");
     loop {
         let result = loop {
             let option = try_menu_main();
@@ -38,13 +41,13 @@ fn main() -> Result<()> {
         };
 
         let result = match result {
-            MenuOption::Create => menu_create_user(),
-            MenuOption::Read => menu_read_user(),
-            MenuOption::Update => menu_update_user(),
-            MenuOption::Delete => menu_delete_user(),
+            MenuOption::Create => menu_create_user(&database),
+            MenuOption::Read => menu_read_user(&database),
+            MenuOption::Update => menu_update_user(&database),
+            MenuOption::Delete => menu_delete_user(&database),
             MenuOption::Exit => exit(0),
-            MenuOption::SetPassword => menu_set_password(),
-            MenuOption::CheckPassword => menu_check_password(),
+            MenuOption::SetPassword => menu_set_password(&database),
+            MenuOption::CheckPassword => menu_check_password(&database),
         };
 
         if let Err(e) = result {
@@ -91,16 +94,16 @@ fn try_menu_read() -> Result<ReadOption> {
     }
 }
 
-fn menu_create_user() -> Result<()> {
+fn menu_create_user(database: &Path) -> Result<()> {
     println!("What name should the user have?");
     let name = read_string()?;
-    let conn = &mut establish_connection()?;
+    let conn = &mut establish_connection(database)?;
 
     create_user(conn, &name)?;
     Ok(())
 }
-fn menu_read_user() -> Result<()> {
-    let conn = &mut establish_connection()?;
+fn menu_read_user(database: &Path) -> Result<()> {
+    let conn = &mut establish_connection(database)?;
     match try_menu_read()? {
         ReadOption::Single => {
             println!("What user should be looked up?");
@@ -121,8 +124,8 @@ fn menu_read_user() -> Result<()> {
     Ok(())
 }
 
-fn menu_update_user() -> Result<()> {
-    let conn = &mut establish_connection()?;
+fn menu_update_user(database: &Path) -> Result<()> {
+    let conn = &mut establish_connection(database)?;
 
     println!("Type the name of the user you want to update.");
     let cur_username = read_string()?;
@@ -135,8 +138,8 @@ fn menu_update_user() -> Result<()> {
     Ok(())
 }
 
-fn menu_delete_user() -> Result<()> {
-    let conn = &mut establish_connection()?;
+fn menu_delete_user(database: &Path) -> Result<()> {
+    let conn = &mut establish_connection(database)?;
 
     println!("Which user do you want to delete?");
     let username = read_string()?;
@@ -146,25 +149,25 @@ fn menu_delete_user() -> Result<()> {
     Ok(())
 }
 
-fn menu_set_password() -> Result<()> {
+fn menu_set_password(database: &Path) -> Result<()> {
     println!("What user do you want to set a password for?");
     let username = read_string()?;
     println!("What do you want to set the password to?");
     let password = read_string()?;
 
-    let conn = &mut establish_connection()?;
+    let conn = &mut establish_connection(database)?;
     set_password(conn, &username, &password)?;
 
     Ok(())
 }
 
-fn menu_check_password() -> Result<()> {
+fn menu_check_password(database: &Path) -> Result<()> {
     println!("What user do you want to check the password of?");
     let username = read_string()?;
     println!("Enter their password.");
     let password = read_string()?;
 
-    let conn = &mut establish_connection()?;
+    let conn = &mut establish_connection(database)?;
     if check_password(conn, &username, &password)? {
         println!("Correct password!");
     } else {
@@ -0,0 +1,59 @@
+//! How the client talks to servers, as read from the configuration of the application using it.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Controls how the client talks to servers.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// The server address filled in on the login screen.
+    pub server: Option<String>,
+    /// Proxy all requests go through. Supports ``http://``, ``https://``, ``socks5://`` and ``socks5h://`` urls, or
+    /// ``none`` to not use any. Without one, the proxy of the system is used, like the one in ``HTTPS_PROXY``.
+    pub proxy: Option<String>,
+    /// Additional headers sent with every request.
+    pub headers: HashMap<String, String>,
+    /// Seconds to wait for a connection to the server to be established.
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for the server to respond to a request.
+    pub request_timeout: Option<u64>,
+    /// Prepended to the user agent, which always contains the client version.
+    pub user_agent: Option<String>,
+    pub retry: RetryConfig,
+    /// Where to look for a newer release of the client, once on every start. Nothing is looked up if not set.
+    pub update_url: Option<String>,
+    /// Settings for single servers, keyed by their address. They take precedence over the ones above.
+    pub servers: HashMap<String, ServerConfig>,
+}
+
+/// Controls how the client talks to one server.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Proxy the requests to this server go through instead of ``NetworkConfig::proxy``. Servers with an
+    /// ``.onion`` address can only be reached through the SOCKS5 proxy of a TOR client.
+    pub proxy: Option<String>,
+}
+
+/// Controls how requests that only read data are retried after transient failures.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// How often a request is sent at most, including the first attempt. 1 disables retrying.
+    pub max_attempts: u32,
+    /// Milliseconds to wait before the first retry. Doubles with every further retry.
+    pub base_delay: u64,
+    /// The most milliseconds to wait between two attempts.
+    pub max_delay: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: 250,
+            max_delay: 4000,
+        }
+    }
+}
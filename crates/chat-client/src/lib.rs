@@ -0,0 +1,2094 @@
+//! A client for the HTTP API of the chat server: logging in, sending and fetching messages and following the
+//! event stream. Requests are cancelled on shutdown and reads are retried after transient failures.
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex, MutexGuard, PoisonError, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use chat_core::{
+    models::{
+        AddressBan, ApiKey, Attachment, AuditEntry, Channel, Invite, Message, Motd, Profile, Role,
+        User, Webhook,
+    },
+    protocol::{
+        AddressBanRequest, ApiError, ChannelNotifications, Conversation, CreateApiKey, CreateBot,
+        CreateChannel, Credentials, Draft, ErrorCode, Feature, ForwardRequest, ForwardTarget,
+        GuestLogin, GuestRequest, ImpersonationLogin, ImpersonationRequest, InitialSync,
+        IssuedApiKey, LoginResult, MessageContext, MessageFilter, MotdRequest, NotificationLevel,
+        NotificationRequest, Overview, Page, PageRequest, Presence, ProfileUpdate, PushSettings,
+        ReadMarker, ReadReceipt, RefreshRequest, SavedMessage, SearchFilter, ServerInfo, Session,
+        SlowModeRequest, SystemEvent, Terms, TopicRequest, UserEntry, UserFilter, UserSort,
+        WebhookRequest, IDEMPOTENCY_KEY, SERVER_TIME, SYSTEM_EVENT,
+    },
+    LoginToken, RefreshToken,
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use rand::Rng;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING,
+        CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+    },
+    Client as HttpClient, Proxy, RequestBuilder, Response, StatusCode, Url,
+};
+use reqwest_eventsource::{Event, EventSource};
+use serde::{de::DeserializeOwned, Deserialize};
+use thiserror::Error;
+use tokio::sync::mpsc::{channel, Receiver};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::NetworkConfig,
+    retry::{RetryPolicy, RetryState},
+};
+
+pub mod config;
+pub mod retry;
+mod shutdown;
+mod transport;
+mod upload;
+
+pub use shutdown::ShutdownHandler;
+pub use transport::ChatTransport;
+pub use upload::FileUpload;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Could not create HTTP client.")]
+    ClientCreationFailed(reqwest::Error),
+    #[error("The configured header {0} is not valid.")]
+    InvalidHeader(String),
+    #[error("Could not create EventSource handler.")]
+    EventSourceCreationFailed(reqwest_eventsource::CannotCloneRequestError),
+    #[error("The server returned a unexpected status {code} when acessing the {endpoint} endpoint. This is a server bug.")]
+    UnexpectedStatusCode { code: StatusCode, endpoint: String },
+    #[error("The server returned a invalid or malformed response. This is a server bug.")]
+    InvalidRespone(reqwest::Error),
+    #[error("A error occured whilst performing a request to the server.")]
+    Generic(reqwest::Error),
+    #[error("Connection failed. Check your connection or the server address")]
+    ConnectionFailure(reqwest::Error),
+    #[error("The server did not respond in time. Check your connection or try again later.")]
+    TimedOut,
+    #[error("The request was cancelled, as the application is shutting down.")]
+    Cancelled,
+    #[error("Too many requests. Slow down and try again in a moment.")]
+    RateLimited,
+    /// Holds when a message can be sent again, if the server told.
+    #[error("You sent as many messages as your quota allows. Try again later.")]
+    QuotaExceeded(Option<DateTime<Utc>>),
+    /// Holds how many seconds are left before a message can be sent to the channel again, if the server told.
+    #[error("The channel is in slow mode. Wait a moment before sending another message.")]
+    SlowMode(Option<u64>),
+    #[error("You sent this message several times already. Wait a moment before sending it again.")]
+    DuplicateMessage,
+    /// Holds the largest size the server accepts in bytes, if it told.
+    #[error("The file is larger than the server accepts.")]
+    FileTooLarge(Option<u64>),
+    /// Holds the type the server recognized the file as, if it told.
+    #[error("The server does not accept files of this type.")]
+    FileTypeNotAllowed(Option<String>),
+    #[error("Your role does not allow this.")]
+    PermissionDenied,
+    #[error("Authentication failed. Login again and try again.")]
+    NotAuthorized,
+    #[error("Failed to login. Check your credentials or try again later.")]
+    LoginFailed,
+    #[error("This account is banned from the server.")]
+    AccountBanned,
+    #[error("Failed to deserialize data received from the server. This is a bug.")]
+    DeserializingFailed(reqwest::Error),
+    #[error("Failed to deserialize data received from the server. This is a bug.")]
+    InvalidJson(serde_json::Error),
+    #[error("Failed to deserialize data received from the server. This is a bug.")]
+    InvalidMsgpack(rmp_serde::decode::Error),
+    #[error("Failed to decompress data received from the server. This is a bug.")]
+    DecompressionFailed(std::io::Error),
+    #[error("Could not register. The username is already in use.")]
+    UsernameInUse,
+    #[error("The terms of service have changed. Submit again to read the new version.")]
+    TermsNotAccepted,
+    #[error("The server only accepts new accounts with a valid invite code.")]
+    InviteInvalid,
+    #[error("{}", .0.message)]
+    Api(ApiError),
+    #[error("The server address is not valid.")]
+    InvalidAddress(AddressProblem),
+    #[error("Something answered at this address, but it is not a chat server.")]
+    NotAChatServer,
+    #[error("This session is replayed from a recording, nothing can be sent to the server.")]
+    Replaying,
+    #[error("This server can only be reached through TOR. Set a SOCKS5 proxy for it.")]
+    OnionWithoutProxy,
+}
+
+/// What is wrong with a server address entered by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressProblem {
+    Empty,
+    UnsupportedScheme(String),
+    MissingHost,
+    InvalidHost,
+    InvalidPort(String),
+    HasPath,
+}
+
+impl Error {
+    /// Returns the error code sent by the server, if the error originated from it.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::NotAuthorized => Some(ErrorCode::NotAuthorized),
+            Error::LoginFailed => Some(ErrorCode::LoginFailed),
+            Error::AccountBanned => Some(ErrorCode::AccountBanned),
+            Error::UsernameInUse => Some(ErrorCode::UsernameTaken),
+            Error::TermsNotAccepted => Some(ErrorCode::TermsNotAccepted),
+            Error::InviteInvalid => Some(ErrorCode::InviteInvalid),
+            Error::QuotaExceeded(_) => Some(ErrorCode::QuotaExceeded),
+            Error::SlowMode(_) => Some(ErrorCode::SlowMode),
+            Error::DuplicateMessage => Some(ErrorCode::DuplicateMessage),
+            Error::FileTooLarge(_) => Some(ErrorCode::FileTooLarge),
+            Error::FileTypeNotAllowed(_) => Some(ErrorCode::FileTypeNotAllowed),
+            Error::PermissionDenied => Some(ErrorCode::PermissionDenied),
+            Error::Api(error) => Some(error.code),
+            _ => None,
+        }
+    }
+
+    /// Tells apart the common reasons for a failed connection, which all look the same at first. Returns ``None``
+    /// if the error is not an ``Error::ConnectionFailure``.
+    pub fn connection_failure(&self) -> Option<ConnectionFailure> {
+        let Error::ConnectionFailure(error) = self else {
+            return None;
+        };
+        let mut source = std::error::Error::source(error);
+        while let Some(cause) = source {
+            if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+                if io_error.kind() == ErrorKind::ConnectionRefused {
+                    return Some(ConnectionFailure::Refused);
+                }
+            }
+            // The resolver errors have no kind of their own
+            if cause.to_string().starts_with("dns error") {
+                return Some(ConnectionFailure::UnknownHost);
+            }
+            source = cause.source();
+        }
+        Some(ConnectionFailure::Other)
+    }
+}
+
+/// Why a connection to the server failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionFailure {
+    /// Nothing listens on the port.
+    Refused,
+    /// The host name could not be resolved.
+    UnknownHost,
+    Other,
+}
+
+/// Turns a server address, like ``chat.example.com:8000``, into the url requests are sent to. Addresses without a
+/// scheme use ``http://``. The only path allowed is ``/t/<key>``, which selects a community on servers hosting
+/// several of them.
+///
+/// # Errors
+///
+/// This function will return an ``Error::InvalidAddress`` describing what is wrong with the address.
+pub fn parse_address(address: &str) -> Result<String, Error> {
+    let address = address.trim();
+    if address.is_empty() {
+        return Err(Error::InvalidAddress(AddressProblem::Empty));
+    }
+    let (scheme, rest) = address.split_once("://").unwrap_or(("http", address));
+    let scheme = scheme.to_lowercase();
+    if scheme != "http" && scheme != "https" {
+        return Err(Error::InvalidAddress(AddressProblem::UnsupportedScheme(
+            scheme,
+        )));
+    }
+    let rest = rest.strip_suffix('/').unwrap_or(rest);
+    let (rest, path) = match rest.split_once('/') {
+        Some((rest, path)) => (rest, Some(path)),
+        None => (rest, None),
+    };
+    if let Some(path) = path {
+        let is_tenant = path
+            .strip_prefix("t/")
+            .is_some_and(|key| !key.is_empty() && !key.contains('/'));
+        if !is_tenant {
+            return Err(Error::InvalidAddress(AddressProblem::HasPath));
+        }
+    }
+
+    // IPv6 addresses are in brackets, the colons within them do not start a port
+    let (host, port) = match rest.rfind(':') {
+        Some(index) if !rest[index..].contains(']') => (&rest[..index], Some(&rest[index + 1..])),
+        _ => (rest, None),
+    };
+    if host.is_empty() {
+        return Err(Error::InvalidAddress(AddressProblem::MissingHost));
+    }
+    if let Some(port) = port {
+        if !port.parse::<u16>().is_ok_and(|port| port != 0) {
+            return Err(Error::InvalidAddress(AddressProblem::InvalidPort(
+                port.to_string(),
+            )));
+        }
+    }
+
+    let url = format!("{scheme}://{rest}");
+    if Url::parse(&url).is_err() {
+        return Err(Error::InvalidAddress(AddressProblem::InvalidHost));
+    }
+    Ok(match path {
+        Some(path) => format!("{url}/{path}"),
+        None => url,
+    })
+}
+
+/// Whether the url from ``parse_address`` points to a TOR onion service.
+fn is_onion(base_url: &str) -> bool {
+    Url::parse(base_url)
+        .is_ok_and(|url| url.host_str().is_some_and(|host| host.ends_with(".onion")))
+}
+
+/// What the release endpoint answers with.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Release {
+    /// The version of the newest release.
+    pub version: String,
+    /// Where to get it.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// What the event stream of a ``Client`` reports.
+#[derive(Debug)]
+pub enum StreamEvent {
+    Message(Message),
+    System(SystemEvent),
+    /// The connection to the server was lost. The client keeps trying to reconnect.
+    Disconnected,
+    /// The connection to the server was established again after it was lost.
+    Reconnected,
+}
+
+/// A logged in session with a server. Can be shared between tasks, as all methods only need a reference.
+pub struct Client {
+    token: RwLock<LoginToken>,
+    refresh_token: Mutex<Option<RefreshToken>>,
+    /// The id of the logged in user.
+    user_id: i32,
+    connection: Connection,
+    /// The last response received from endpoints supporting ETags, keyed by endpoint.
+    cache: Mutex<HashMap<String, CachedResponse>>,
+    /// What the server told about itself before logging in, if it was asked.
+    server: Option<ServerInfo>,
+}
+
+/// Everything needed to send requests to a server.
+struct Connection {
+    http_client: HttpClient,
+    /// The scheme, host and port of the server, as returned by ``parse_address``.
+    base_url: String,
+    request_timeout: Duration,
+    /// Applied to requests that only read data, as those can safely be sent more than once.
+    retry: RetryPolicy,
+    retry_state: RetryState,
+    /// Requests in flight get cancelled once this signals a shutdown.
+    shutdown: ShutdownHandler,
+    /// Set for replayed sessions, whose requests fail right away instead of reaching the server.
+    replaying: bool,
+    /// How many milliseconds the clock of the server is ahead of ours, as of the last response.
+    clock_offset: AtomicI64,
+}
+
+/// A response body together with the ETag the server sent for it.
+struct CachedResponse {
+    etag: String,
+    body: Vec<u8>,
+}
+
+/// The file of an attachment, arriving a part at a time from ``GET /attachments/<id>`` so progress can be shown.
+pub struct AttachmentDownload {
+    response: Response,
+    endpoint: String,
+    /// How many bytes arrived so far.
+    pub received: u64,
+}
+
+impl AttachmentDownload {
+    /// Waits for the next part of the file. Returns ``None`` once all of it arrived.
+    pub async fn next(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let chunk = self
+            .response
+            .chunk()
+            .await
+            .map_err(|e| Client::handle_error(e, &self.endpoint))?;
+        Ok(chunk.map(|chunk| {
+            self.received += chunk.len() as u64;
+            chunk.to_vec()
+        }))
+    }
+}
+
+/// Messages arriving one line at a time from ``POST /messages/stream``.
+pub struct MessageStream {
+    response: Response,
+    /// The start of a line that did not arrive completely yet.
+    partial: Vec<u8>,
+}
+
+impl MessageStream {
+    const ENDPOINT: &'static str = "/messages/stream";
+
+    /// Waits for the next part of the stream and returns the messages it completed, which can be none. Returns
+    /// ``None`` once the stream ended.
+    pub async fn next(&mut self) -> Result<Option<Vec<Message>>, Error> {
+        let chunk = self
+            .response
+            .chunk()
+            .await
+            .map_err(|e| Client::handle_error(e, Self::ENDPOINT))?;
+        let Some(chunk) = chunk else {
+            return Ok(None);
+        };
+        self.partial.extend_from_slice(&chunk);
+        let Some(end) = self.partial.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Some(Vec::new()));
+        };
+        let lines: Vec<u8> = self.partial.drain(..=end).collect();
+        lines
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).map_err(Error::InvalidJson))
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+}
+
+pub struct AuthDetails {
+    pub address: String,
+    pub credentials: Credentials,
+}
+
+impl AuthDetails {
+    pub fn new(address: &str, username: &str, password: &str, remember_me: bool) -> Self {
+        Self {
+            address: address.to_string(),
+            credentials: Credentials {
+                username: username.to_string(),
+                password: password.to_string(),
+                remember_me,
+                accepted_tos_version: None,
+                invite_code: None,
+            },
+        }
+    }
+
+    /// Sends along the version of the terms of service the user accepted, which registering may need.
+    #[must_use]
+    pub fn accepted_terms(mut self, version: Option<String>) -> Self {
+        self.credentials.accepted_tos_version = version;
+        self
+    }
+
+    /// Sends along the invite code, which registering needs on servers that only accept invited users.
+    #[must_use]
+    pub fn invite_code(mut self, code: Option<String>) -> Self {
+        self.credentials.invite_code = code.filter(|code| !code.trim().is_empty());
+        self
+    }
+}
+
+/// How long checking the server address may take at most.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Given as the proxy to not use any, not even the one of the system.
+pub const NO_PROXY: &str = "none";
+
+/// Configures the connection to the server and creates a ``Client`` by logging in or registering.
+pub struct ClientBuilder {
+    proxy: Option<String>,
+    /// Proxies used instead of ``proxy`` for single servers, keyed by the url from ``parse_address``.
+    server_proxies: HashMap<String, String>,
+    headers: Vec<(String, String)>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Duration,
+    retry: RetryPolicy,
+    user_agent: String,
+    shutdown: Option<ShutdownHandler>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    /// Creates a new ``ClientBuilder`` with the default settings.
+    pub fn new() -> Self {
+        Self {
+            proxy: None,
+            server_proxies: HashMap::new(),
+            headers: Vec::new(),
+            connect_timeout: None,
+            request_timeout: Duration::from_secs(30),
+            retry: RetryPolicy::default(),
+            user_agent: Self::base_user_agent(),
+            shutdown: None,
+        }
+    }
+
+    /// Creates a new ``ClientBuilder`` using the settings from the client configuration.
+    pub fn from_config(config: &NetworkConfig) -> Self {
+        let mut builder = Self::new();
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(proxy);
+        }
+        for (address, server) in &config.servers {
+            if let Some(proxy) = &server.proxy {
+                builder = builder.server_proxy(address, proxy);
+            }
+        }
+        for (name, value) in &config.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = config.request_timeout {
+            builder = builder.request_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(user_agent) = &config.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+
+        builder.retry(RetryPolicy::from(&config.retry))
+    }
+
+    /// Sends all requests through the proxy. Supports ``http://``, ``https://``, ``socks5://`` and ``socks5h://``
+    /// urls, or ``NO_PROXY`` to not use any. Without one, the proxy of the system is used.
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    /// Sends the requests to the server at the address through the proxy, instead of the one set with ``proxy``.
+    /// Addresses that are not valid are ignored, as no requests can be sent to them anyway.
+    pub fn server_proxy(mut self, address: &str, url: &str) -> Self {
+        if let Ok(base_url) = parse_address(address) {
+            self.server_proxies.insert(base_url, url.to_string());
+        }
+        self
+    }
+
+    /// Adds a header that is sent with every request.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets how long to wait for a connection to the server to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long to wait for the server to respond to a request. Does not apply to the event stream.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how requests that only read data are retried after transient failures.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Cancels all requests of the client once a shutdown gets signaled.
+    pub fn shutdown(mut self, shutdown: ShutdownHandler) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Prepends the given string to the user agent. The user agent always ends with the client version.
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = format!("{user_agent} {}", Self::base_user_agent());
+        self
+    }
+
+    pub async fn login(self, auth_details: AuthDetails) -> Result<Client, Error> {
+        let connection = self.build_connection(&auth_details.address)?;
+        Client::inner_login(connection, auth_details.credentials).await
+    }
+
+    /// Checks that a chat server answers at the address, returning what it tells about itself. Gives up after
+    /// ``PROBE_TIMEOUT`` at most, so a wrong address is noticed before sending any credentials.
+    pub async fn probe(self, address: &str) -> Result<ServerInfo, Error> {
+        let mut connection = self.build_connection(address)?;
+        connection.request_timeout = connection.request_timeout.min(PROBE_TIMEOUT);
+        let endpoint = "/info";
+        let request = connection.get(endpoint);
+        let response = match connection.send(request, endpoint).await {
+            Ok(response) => response,
+            Err(Error::UnexpectedStatusCode { .. }) => return Err(Error::NotAChatServer),
+            Err(e) => return Err(e),
+        };
+        response.json().await.map_err(|_| Error::NotAChatServer)
+    }
+
+    /// Fetches the terms of service of the server at the address, which have to be accepted to register.
+    pub async fn terms(self, address: &str) -> Result<Terms, Error> {
+        let connection = self.build_connection(address)?;
+        let endpoint = "/terms";
+        let request = connection.get(endpoint);
+        connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Creates a temporary guest account on the server and logs in as it, returning the client and the details
+    /// of the guest, like its generated name.
+    pub async fn guest(
+        self,
+        address: &str,
+        accepted_terms: Option<String>,
+    ) -> Result<(Client, GuestLogin), Error> {
+        let connection = self.build_connection(address)?;
+        let endpoint = "/auth/guest";
+        let request = connection.post(endpoint).json(&GuestRequest {
+            accepted_tos_version: accepted_terms,
+        });
+        let guest: GuestLogin = connection
+            .send(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)?;
+
+        let client = Client {
+            token: RwLock::new(LoginToken(guest.token.clone())),
+            refresh_token: Mutex::new(None),
+            user_id: guest.userid,
+            connection,
+            cache: Mutex::new(HashMap::new()),
+            server: None,
+        };
+        Ok((client, guest))
+    }
+
+    /// Fetches the newest release of the client from the release endpoint at the url, which unlike the server
+    /// address is used as it is.
+    pub async fn latest_release(self, url: &str) -> Result<Release, Error> {
+        let http_client = self.build_http_client(self.proxy.as_deref())?;
+        let shutdown = self.shutdown.unwrap_or_else(|| ShutdownHandler::new().0);
+        let request = http_client.get(url).timeout(self.request_timeout).send();
+        let response = tokio::select! {
+            response = request => response.map_err(|e| Client::handle_error(e, url))?,
+            () = shutdown.cancelled() => return Err(Error::Cancelled),
+        };
+        Client::check_status(response, url)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Creates a client for replaying a recorded session, as the user with that id on the server at the address.
+    /// It never sends anything, all requests fail with ``Error::Replaying``.
+    pub fn replay(self, address: &str, user_id: i32) -> Result<Client, Error> {
+        let mut connection = self.build_connection(address)?;
+        connection.replaying = true;
+        Ok(Client {
+            token: RwLock::new(LoginToken(String::new())),
+            refresh_token: Mutex::new(None),
+            user_id,
+            connection,
+            cache: Mutex::new(HashMap::new()),
+            server: None,
+        })
+    }
+
+    pub async fn register(self, auth_details: AuthDetails) -> Result<Client, Error> {
+        let connection = self.build_connection(&auth_details.address)?;
+        let endpoint = "/register";
+        let request = connection.post(endpoint).json(&auth_details.credentials);
+        connection.send(request, endpoint).await?;
+
+        Client::inner_login(connection, auth_details.credentials).await
+    }
+
+    fn base_user_agent() -> String {
+        format!("chat_app-client/{}", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn build_connection(self, address: &str) -> Result<Connection, Error> {
+        let base_url = parse_address(address)?;
+        let mut proxy = self
+            .server_proxies
+            .get(&base_url)
+            .or(self.proxy.as_ref())
+            .cloned();
+        if is_onion(&base_url) {
+            // Onion addresses are in no DNS, only TOR itself can resolve them
+            proxy = match proxy.filter(|proxy| proxy != NO_PROXY) {
+                Some(proxy) => match proxy.strip_prefix("socks5://") {
+                    Some(rest) => Some(format!("socks5h://{rest}")),
+                    None => Some(proxy),
+                },
+                None => return Err(Error::OnionWithoutProxy),
+            };
+        }
+        let http_client = self.build_http_client(proxy.as_deref())?;
+        Ok(Connection {
+            http_client,
+            base_url,
+            request_timeout: self.request_timeout,
+            retry: self.retry,
+            retry_state: RetryState::default(),
+            shutdown: self.shutdown.unwrap_or_else(|| ShutdownHandler::new().0),
+            replaying: false,
+            clock_offset: AtomicI64::new(0),
+        })
+    }
+
+    fn build_http_client(&self, proxy: Option<&str>) -> Result<HttpClient, Error> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value))
+            else {
+                return Err(Error::InvalidHeader(name.clone()));
+            };
+            headers.insert(name, value);
+        }
+
+        let mut builder = HttpClient::builder()
+            .gzip(true)
+            .brotli(true)
+            .user_agent(&self.user_agent)
+            .default_headers(headers);
+        match proxy {
+            Some(NO_PROXY) => builder = builder.no_proxy(),
+            Some(proxy) => {
+                builder = builder.proxy(Proxy::all(proxy).map_err(Error::ClientCreationFailed)?);
+            }
+            None => {}
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        builder.build().map_err(Error::ClientCreationFailed)
+    }
+}
+
+impl Connection {
+    fn get(&self, endpoint: &str) -> RequestBuilder {
+        self.http_client.get(format!("{}{endpoint}", self.base_url))
+    }
+
+    fn post(&self, endpoint: &str) -> RequestBuilder {
+        self.http_client
+            .post(format!("{}{endpoint}", self.base_url))
+    }
+
+    fn put(&self, endpoint: &str) -> RequestBuilder {
+        self.http_client.put(format!("{}{endpoint}", self.base_url))
+    }
+
+    fn patch(&self, endpoint: &str) -> RequestBuilder {
+        self.http_client
+            .patch(format!("{}{endpoint}", self.base_url))
+    }
+
+    fn delete(&self, endpoint: &str) -> RequestBuilder {
+        self.http_client
+            .delete(format!("{}{endpoint}", self.base_url))
+    }
+
+    /// Sends the request, giving up once the request timeout passed or the application shuts down.
+    async fn send(&self, request: RequestBuilder, endpoint: &str) -> Result<Response, Error> {
+        if self.replaying {
+            return Err(Error::Replaying);
+        }
+        let start = Instant::now();
+        let request = request.timeout(self.request_timeout).send();
+        let result = tokio::select! {
+            response = request => match response {
+                Ok(response) => Client::check_status(response, endpoint).await,
+                Err(e) => Err(Client::handle_error(e, endpoint)),
+            },
+            () = self.shutdown.cancelled() => Err(Error::Cancelled),
+        };
+        match &result {
+            Ok(response) => {
+                self.track_clock(response);
+                debug!(endpoint, status = %response.status(), elapsed = ?start.elapsed(), "Request sent")
+            }
+            Err(e) => warn!(endpoint, error = ?e, elapsed = ?start.elapsed(), "Request failed"),
+        }
+
+        result
+    }
+
+    /// Remembers how far the clock of the server is off from ours, if the response tells its time. The time the
+    /// response took to arrive is not accounted for, which is close enough for filtering messages.
+    fn track_clock(&self, response: &Response) {
+        let server_time = response
+            .headers()
+            .get(SERVER_TIME)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok());
+        if let Some(server_time) = server_time {
+            let offset = server_time.with_timezone(&Utc) - Utc::now();
+            self.clock_offset
+                .store(offset.num_milliseconds(), Ordering::Relaxed);
+        }
+    }
+
+    /// Like ``send``, but sends the request again after transient failures, waiting longer each time.
+    /// Only use this for requests that can be repeated without side effects.
+    async fn send_idempotent(
+        &self,
+        request: RequestBuilder,
+        endpoint: &str,
+    ) -> Result<Response, Error> {
+        let mut retry = 0;
+        let result = loop {
+            // Requests with a streaming body can not be cloned, so they only get a single attempt
+            let Some(attempt) = request.try_clone() else {
+                break self.send(request, endpoint).await;
+            };
+            let result = self.send(attempt, endpoint).await;
+            retry += 1;
+            match result {
+                Err(e) if RetryPolicy::is_transient(&e) && retry < self.retry.max_attempts() => {
+                    debug!(endpoint, retry, "Retrying request");
+                    self.retry_state.set(retry);
+                    tokio::select! {
+                        () = tokio::time::sleep(self.retry.delay(retry)) => {},
+                        () = self.shutdown.cancelled() => break Err(Error::Cancelled),
+                    }
+                }
+                result => break result,
+            }
+        };
+        self.retry_state.clear();
+
+        result
+    }
+}
+
+impl Client {
+    async fn inner_login(connection: Connection, credentials: Credentials) -> Result<Self, Error> {
+        let endpoint = "/auth/login";
+        let request = connection.post(endpoint).json(&credentials);
+        let login: LoginResult = connection
+            .send(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)?;
+
+        Ok(Self {
+            token: RwLock::new(LoginToken(login.token)),
+            refresh_token: Mutex::new(login.refresh_token.map(RefreshToken)),
+            user_id: login.userid,
+            connection,
+            cache: Mutex::new(HashMap::new()),
+            server: None,
+        })
+    }
+
+    /// Returns the id of the logged in user.
+    pub fn user_id(&self) -> i32 {
+        self.user_id
+    }
+
+    /// Remembers what the server told about itself, so ``supports`` can check for its features.
+    pub fn set_server_info(&mut self, info: ServerInfo) {
+        self.server = Some(info);
+    }
+
+    /// Returns what the server told about itself, if it is known.
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server.as_ref()
+    }
+
+    /// Returns whether the server offers the feature. Without knowing about the server, everything is assumed to
+    /// be there, and requests fail if it is not.
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.server
+            .as_ref()
+            .is_none_or(|info| info.supports(feature))
+    }
+
+    /// Returns the current time by the clock of the server, as far as it is known from its responses. Servers that
+    /// do not tell their time are assumed to agree with ours.
+    pub fn server_now(&self) -> DateTime<Utc> {
+        let offset = self.connection.clock_offset.load(Ordering::Relaxed);
+        Utc::now() + chrono::Duration::milliseconds(offset)
+    }
+
+    /// Returns the url requests are sent to, as returned by ``parse_address``.
+    pub fn address(&self) -> &str {
+        &self.connection.base_url
+    }
+
+    /// Returns the retry in progress, if the client is currently retrying a failed request.
+    pub fn retrying(&self) -> Option<u32> {
+        self.connection.retry_state.current()
+    }
+
+    /// Returns true if the client holds a refresh token it can use to login again.
+    pub fn can_refresh(&self) -> bool {
+        lock(&self.refresh_token).is_some()
+    }
+
+    /// Exchanges the refresh token for a new login token, without needing the users password.
+    pub async fn refresh(&self) -> Result<(), Error> {
+        let Some(refresh_token) = lock(&self.refresh_token).clone() else {
+            return Err(Error::NotAuthorized);
+        };
+        let endpoint = "/auth/refresh";
+        let request = self.connection.post(endpoint).json(&RefreshRequest {
+            refresh_token: refresh_token.0,
+        });
+        let login: LoginResult = match self.connection.send(request, endpoint).await {
+            Ok(response) => response.json().await.map_err(Error::DeserializingFailed)?,
+            Err(e) if e.code().is_some() => {
+                // The server rejected the refresh token, so there is no point in trying it again
+                *lock(&self.refresh_token) = None;
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+
+        *self.token.write().unwrap_or_else(PoisonError::into_inner) = LoginToken(login.token);
+        *lock(&self.refresh_token) = login.refresh_token.map(RefreshToken);
+        Ok(())
+    }
+
+    pub async fn logout(&self) -> Result<(), Error> {
+        // A replayed session was never logged in
+        if self.connection.replaying {
+            return Ok(());
+        }
+        let endpoint = "/auth/logout";
+        let request = self.connection.get(endpoint).auth(self);
+        self.connection.send(request, endpoint).await?;
+        Ok(())
+    }
+
+    /// Sends a message with a newly generated idempotency key. The key stays the same when the request is retried,
+    /// so a retry after a lost response does not send the message twice. Servers that do not know the keys only
+    /// get a single attempt.
+    pub async fn send_message(&self, message: &str) -> Result<(), Error> {
+        self.send_to(Conversation::Chat, message).await
+    }
+
+    /// Like ``send_message``, but sends the message to the conversation, which may also be a channel the user is
+    /// a member of or another user.
+    pub async fn send_to(&self, conversation: Conversation, message: &str) -> Result<(), Error> {
+        let endpoint = match conversation {
+            Conversation::Chat => "/message".to_string(),
+            Conversation::Channel(id) => format!("/channels/{id}/message"),
+            Conversation::Direct(userid) => format!("/direct/{userid}/message"),
+        };
+        let key = format!("{:032x}", rand::thread_rng().gen::<u128>());
+        let request = self
+            .connection
+            .post(&endpoint)
+            .auth(self)
+            .header(IDEMPOTENCY_KEY, key)
+            .body(message.to_string());
+        if self.supports(Feature::IdempotencyKeys) {
+            self.connection.send_idempotent(request, &endpoint).await?;
+        } else {
+            self.connection.send(request, &endpoint).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a message with the files attached to the conversation. Like ``send_to``, it goes out with an
+    /// idempotency key, so retrying after a lost response does not send it twice.
+    pub async fn send_attachments(
+        &self,
+        conversation: Conversation,
+        text: &str,
+        files: &[FileUpload],
+    ) -> Result<(), Error> {
+        let endpoint = format!("/attachments/{}", conversation.key());
+        let key = format!("{:032x}", rand::thread_rng().gen::<u128>());
+        let (content_type, body) = upload::multipart_body(text, files);
+        let request = self
+            .connection
+            .post(&endpoint)
+            .auth(self)
+            .header(IDEMPOTENCY_KEY, key)
+            .header(CONTENT_TYPE, content_type)
+            .body(body);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Lists the files attached to the message with that id.
+    pub async fn get_attachments(&self, message_id: i32) -> Result<Vec<Attachment>, Error> {
+        let endpoint = format!("/message/{message_id}/attachments");
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Starts downloading the file of the attachment with that id. Its size is known from the attachment, which
+    /// the download can be compared against to show the progress.
+    pub async fn download_attachment(
+        &self,
+        attachment_id: i32,
+    ) -> Result<AttachmentDownload, Error> {
+        let endpoint = format!("/attachments/{attachment_id}");
+        let request = self.connection.get(&endpoint).auth(self);
+        let response = self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(AttachmentDownload {
+            response,
+            endpoint,
+            received: 0,
+        })
+    }
+
+    /// Sends a copy of the message with that id to the target, which keeps pointing to the original and its author.
+    pub async fn forward_message(
+        &self,
+        message_id: i32,
+        target: ForwardTarget,
+    ) -> Result<(), Error> {
+        let endpoint = "/message/forward";
+        let request = self
+            .connection
+            .post(endpoint)
+            .auth(self)
+            .json(&ForwardRequest { message_id, target });
+        self.connection.send(request, endpoint).await?;
+        Ok(())
+    }
+
+    pub async fn get_messages(&self, filter: MessageFilter) -> Result<Vec<Message>, Error> {
+        self.get_messages_of(Conversation::Chat, filter, None).await
+    }
+
+    /// Like ``get_messages``, but for the conversation, which may also be a channel the user is a member of or the
+    /// direct messages with another user. ``limit`` asks for that many messages instead of the page size of the
+    /// server, which may return fewer if that is more than it allows. Older servers ignore it.
+    pub async fn get_messages_of(
+        &self,
+        conversation: Conversation,
+        filter: MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, Error> {
+        let endpoint = match conversation {
+            Conversation::Chat => "/messages".to_string(),
+            Conversation::Channel(id) => format!("/channels/{id}/messages"),
+            Conversation::Direct(userid) => format!("/direct/{userid}/messages"),
+        };
+        let mut request = self.connection.post(&endpoint).auth(self).json(&filter);
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Streams every message before or after the date, so they can be handled as they arrive.
+    pub async fn stream_messages(&self, filter: MessageFilter) -> Result<MessageStream, Error> {
+        let endpoint = MessageStream::ENDPOINT;
+        let request = self.connection.post(endpoint).auth(self).json(&filter);
+        let response = self.connection.send_idempotent(request, endpoint).await?;
+        Ok(MessageStream {
+            response,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Fetches the message with that id together with the messages sent right before and after it. `around` asks
+    /// for that many on each side, which servers that do not offer it ignore, sending a page instead.
+    pub async fn get_message_context(
+        &self,
+        message_id: i32,
+        around: Option<u32>,
+    ) -> Result<MessageContext, Error> {
+        let (endpoint, request) = match around {
+            Some(around) if self.supports(Feature::ContextAround) => {
+                let endpoint = format!("/message/{message_id}/context");
+                let request = self.connection.get(&endpoint).query(&[("around", around)]);
+                (endpoint, request)
+            }
+            _ => {
+                let endpoint = format!("/message/{message_id}");
+                let request = self.connection.get(&endpoint);
+                (endpoint, request)
+            }
+        };
+        let request = request.auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Searches the messages the user can read for ones with every word of the query, the newest first, one page
+    /// at a time. The query may hold filters like `from:alice`, as read by `SearchQuery::parse`, which the server
+    /// applies in place of those in `filter`.
+    pub async fn search_messages(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+        page: &PageRequest,
+    ) -> Result<Page<Message>, Error> {
+        let endpoint = "/messages/search";
+        let mut parameters = vec![("q", query.to_string())];
+        if let Some(conversation) = filter.conversation {
+            parameters.push(("conversation", conversation.key()));
+        }
+        if let Some(from) = filter.from {
+            parameters.push(("from", from.to_string()));
+        }
+        if let Some(before) = filter.before {
+            parameters.push(("before", before.to_rfc3339()));
+        }
+        if let Some(after) = filter.after {
+            parameters.push(("after", after.to_rfc3339()));
+        }
+        if filter.has_attachment {
+            parameters.push(("has", "attachment".to_string()));
+        }
+        if let Some(limit) = page.limit {
+            parameters.push(("limit", limit.to_string()));
+        }
+        if let Some(cursor) = &page.cursor {
+            parameters.push(("cursor", cursor.clone()));
+        }
+        let request = self.connection.get(endpoint).auth(self).query(&parameters);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Saves the message with that id, so it can be found again in the saved messages.
+    pub async fn save_message(&self, message_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/saved/{message_id}");
+        let request = self.connection.put(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Removes the message with that id from the saved messages.
+    pub async fn unsave_message(&self, message_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/saved/{message_id}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches one page of the saved messages, the most recently saved first.
+    pub async fn get_saved_page(&self, page: &PageRequest) -> Result<Page<SavedMessage>, Error> {
+        let endpoint = format!("/saved{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches all saved messages, the most recently saved first, a page at a time. Servers without pagination
+    /// send them all at once.
+    pub async fn get_saved_messages(&self) -> Result<Vec<SavedMessage>, Error> {
+        if !self.supports(Feature::Pagination) {
+            let endpoint = "/saved";
+            let request = self.connection.get(endpoint).auth(self);
+            return self
+                .connection
+                .send_idempotent(request, endpoint)
+                .await?
+                .json()
+                .await
+                .map_err(Error::DeserializingFailed);
+        }
+
+        let mut saved = Vec::new();
+        let mut request = PageRequest::default();
+        loop {
+            let page = self.get_saved_page(&request).await?;
+            saved.extend(page.items);
+            match page.next {
+                Some(next) => request = PageRequest::after(next),
+                None => return Ok(saved),
+            }
+        }
+    }
+
+    /// Fetches the drafts of the user, the most recently changed first.
+    pub async fn get_drafts(&self) -> Result<Vec<Draft>, Error> {
+        let endpoint = "/drafts";
+        let request = self.connection.get(endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Replaces the draft of the user in the conversation, so it can be picked up on other devices. Empty text
+    /// removes it, like ``delete_draft``.
+    pub async fn save_draft(&self, conversation: Conversation, text: &str) -> Result<(), Error> {
+        let endpoint = format!("/drafts/{}", conversation.key());
+        let request = self
+            .connection
+            .put(&endpoint)
+            .auth(self)
+            .body(text.to_string());
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Removes the draft of the user in the conversation.
+    pub async fn delete_draft(&self, conversation: Conversation) -> Result<(), Error> {
+        let endpoint = format!("/drafts/{}", conversation.key());
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Deletes the message with that id. Anyone may delete their own messages, moderators and admins those of
+    /// everyone.
+    pub async fn delete_message(&self, message_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/message/{message_id}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Renames the user with that id. Needs a role that may rename users and outranks the one of the user.
+    pub async fn rename_user(&self, user_id: i32, name: &str) -> Result<(), Error> {
+        let endpoint = format!("/admin/users/{user_id}/name");
+        let request = self
+            .connection
+            .put(&endpoint)
+            .auth(self)
+            .body(name.to_string());
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Bans the user with that id, who is logged out and can not log in again. Needs a role that may ban users and
+    /// outranks the one of the user.
+    pub async fn ban_user(&self, user_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/users/{user_id}/ban");
+        let request = self.connection.post(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Lifts the ban of the user with that id, who can log in again. Needs a role that may ban users and outranks
+    /// the one of the user.
+    pub async fn unban_user(&self, user_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/users/{user_id}/ban");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Logs the user with that id out everywhere, without banning them. Needs a role that may ban users and
+    /// outranks the one of the user.
+    pub async fn kick_user(&self, user_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/users/{user_id}/kick");
+        let request = self.connection.post(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Gives the user with that id the role, returning the updated user. Only admins may do this.
+    pub async fn set_role(&self, user_id: i32, role: Role) -> Result<User, Error> {
+        let endpoint = format!("/admin/users/{user_id}/role");
+        let request = self.connection.put(&endpoint).auth(self).json(&role);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Logs in as the user with that id, to help them or look into a problem. Only admins may do this, and the
+    /// reason is recorded in the audit log. The login does not replace the one of this client.
+    pub async fn impersonate(
+        &self,
+        user_id: i32,
+        reason: &str,
+    ) -> Result<ImpersonationLogin, Error> {
+        let endpoint = format!("/admin/impersonate/{user_id}");
+        let body = ImpersonationRequest {
+            reason: reason.to_string(),
+        };
+        let request = self.connection.post(&endpoint).auth(self).json(&body);
+        self.connection
+            .send(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches a page of the audit log, the newest entries first. Only admins may do this.
+    pub async fn get_audit_log(&self, page: &PageRequest) -> Result<Page<AuditEntry>, Error> {
+        let endpoint = format!("/admin/audit{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches one page of the users matching the filter, in the given order. Only moderators and admins may do
+    /// this.
+    pub async fn get_user_directory(
+        &self,
+        sort: UserSort,
+        filter: &UserFilter,
+        page: &PageRequest,
+    ) -> Result<Page<UserEntry>, Error> {
+        let endpoint = "/admin/users";
+        let mut parameters = vec![("sort", sort.key().to_string())];
+        if let Some(role) = filter.role {
+            parameters.push(("role", role.key().to_string()));
+        }
+        if let Some(online) = filter.online {
+            parameters.push(("online", online.to_string()));
+        }
+        if let Some(banned) = filter.banned {
+            parameters.push(("banned", banned.to_string()));
+        }
+        if let Some(limit) = page.limit {
+            parameters.push(("limit", limit.to_string()));
+        }
+        if let Some(cursor) = &page.cursor {
+            parameters.push(("cursor", cursor.clone()));
+        }
+        let request = self.connection.get(endpoint).auth(self).query(&parameters);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches a page of the banned ranges of addresses, the oldest first. Only admins may do this.
+    pub async fn get_address_bans(&self, page: &PageRequest) -> Result<Page<AddressBan>, Error> {
+        let endpoint = format!("/admin/addresses{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Bans a range of addresses, like ``192.0.2.0/24``, from registering and logging in. Only admins may do this.
+    pub async fn ban_address(&self, network: &str, reason: &str) -> Result<AddressBan, Error> {
+        let endpoint = "/admin/addresses";
+        let body = AddressBanRequest {
+            network: network.to_string(),
+            reason: reason.to_string(),
+        };
+        let request = self.connection.post(endpoint).auth(self).json(&body);
+        // Banning a range again returns the ban it already has
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Lifts the ban of a range of addresses with that id. Only admins may do this.
+    pub async fn unban_address(&self, ban_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/addresses/{ban_id}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches a page of the invite codes, the newest first. Only admins may do this.
+    pub async fn get_invites(&self, page: &PageRequest) -> Result<Page<Invite>, Error> {
+        let endpoint = format!("/admin/invites{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Makes a new invite code, which lets one person register. Only admins may do this.
+    pub async fn create_invite(&self) -> Result<Invite, Error> {
+        let endpoint = "/admin/invites";
+        let request = self.connection.post(endpoint).auth(self);
+        self.connection
+            .send(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Withdraws the unused invite code with that id. Only admins may do this.
+    pub async fn delete_invite(&self, invite_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/invites/{invite_id}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches a page of the bot accounts, the newest first. Only admins may do this.
+    pub async fn get_bots(&self, page: &PageRequest) -> Result<Page<User>, Error> {
+        let endpoint = format!("/admin/bots{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Makes a bot account with that name, which logs in with the API keys made for it. Only admins may do this.
+    pub async fn create_bot(&self, username: &str) -> Result<User, Error> {
+        let endpoint = "/admin/bots";
+        let request = self.connection.post(endpoint).auth(self).json(&CreateBot {
+            username: username.to_string(),
+        });
+        self.connection
+            .send(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches the API keys of the bot with that id, the newest first. Only admins may do this.
+    pub async fn get_api_keys(&self, bot_id: i32) -> Result<Vec<ApiKey>, Error> {
+        let endpoint = format!("/admin/bots/{bot_id}/keys");
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Makes an API key for the bot with that id. The key can not be fetched again, so it has to be handed to the
+    /// bot right away. Only admins may do this.
+    pub async fn create_api_key(&self, bot_id: i32, name: &str) -> Result<IssuedApiKey, Error> {
+        let endpoint = format!("/admin/bots/{bot_id}/keys");
+        let request = self
+            .connection
+            .post(&endpoint)
+            .auth(self)
+            .json(&CreateApiKey {
+                name: name.to_string(),
+            });
+        self.connection
+            .send(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Revokes the API key with that id of the bot with that id. Only admins may do this.
+    pub async fn revoke_api_key(&self, bot_id: i32, key_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/bots/{bot_id}/keys/{key_id}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches a page of the webhooks new messages are posted to, the newest first. Only admins may do this.
+    pub async fn get_webhooks(&self, page: &PageRequest) -> Result<Page<Webhook>, Error> {
+        let endpoint = format!("/admin/webhooks{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Registers the url as a webhook, which every new message in the chat and the channels is posted to. Only
+    /// admins may do this.
+    pub async fn create_webhook(&self, url: &str) -> Result<Webhook, Error> {
+        let endpoint = "/admin/webhooks";
+        let request = self
+            .connection
+            .post(endpoint)
+            .auth(self)
+            .json(&WebhookRequest {
+                url: url.to_string(),
+            });
+        self.connection
+            .send(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Removes the webhook with that id. Only admins may do this.
+    pub async fn delete_webhook(&self, webhook_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/admin/webhooks/{webhook_id}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches the logins of the user, including those of admins acting as them.
+    pub async fn get_sessions(&self) -> Result<Vec<Session>, Error> {
+        let endpoint = "/auth/sessions";
+        let request = self.connection.get(endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches the profile of the user with that id, or of the logged in user if it is ``None``.
+    pub async fn get_profile(&self, user_id: Option<i32>) -> Result<Profile, Error> {
+        let endpoint = match user_id {
+            Some(id) => format!("/profile/{id}"),
+            None => "/profile".to_string(),
+        };
+        self.get_cached(&endpoint).await
+    }
+
+    /// Changes the fields of the profile of the user that the update gives, returning the changed profile.
+    pub async fn update_profile(&self, update: &ProfileUpdate) -> Result<Profile, Error> {
+        let endpoint = "/profile";
+        let request = self.connection.patch(endpoint).auth(self).json(update);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches who is online. Changes after that arrive as system events.
+    pub async fn get_presence(&self) -> Result<Presence, Error> {
+        let endpoint = "/presence";
+        let request = self.connection.get(endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches where notifications for the user are pushed to. ``None`` if they did not set that up.
+    pub async fn get_push_settings(&self) -> Result<Option<PushSettings>, Error> {
+        let endpoint = "/push";
+        let request = self.connection.get(endpoint).auth(self);
+        match self.connection.send_idempotent(request, endpoint).await {
+            Ok(response) => response
+                .json()
+                .await
+                .map(Some)
+                .map_err(Error::DeserializingFailed),
+            Err(e) if e.code() == Some(ErrorCode::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Has the server push notifications about mentions and direct messages to the ntfy topic or Gotify server,
+    /// so the user is told about them while no client runs.
+    pub async fn set_push_settings(&self, settings: &PushSettings) -> Result<(), Error> {
+        let endpoint = "/push";
+        let request = self.connection.put(endpoint).auth(self).json(settings);
+        self.connection.send_idempotent(request, endpoint).await?;
+        Ok(())
+    }
+
+    /// Stops pushing notifications for the user.
+    pub async fn delete_push_settings(&self) -> Result<(), Error> {
+        let endpoint = "/push";
+        let request = self.connection.delete(endpoint).auth(self);
+        self.connection.send_idempotent(request, endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches everything needed to show a session at once. As this can carry a lot of history, it is asked for
+    /// as zstd-compressed ``MessagePack``, which the server may or may not honour.
+    pub async fn get_initial_sync(&self) -> Result<InitialSync, Error> {
+        let endpoint = "/sync/initial";
+        let request = self
+            .connection
+            .get(endpoint)
+            .auth(self)
+            .header(ACCEPT, "application/msgpack, application/json;q=0.9")
+            // Setting this by hand means zstd is decoded here, brotli and gzip are still handled by reqwest
+            .header(ACCEPT_ENCODING, "zstd, br, gzip");
+        let response = self.connection.send_idempotent(request, endpoint).await?;
+        Self::decode_bulk(response).await
+    }
+
+    /// Fetches how many messages were not read yet in a page of the conversations. The main chat always comes
+    /// first on the first page. Servers from before the overview was paged send all conversations at once.
+    pub async fn get_overview(&self, page: &PageRequest) -> Result<Overview, Error> {
+        let endpoint = format!("/sync/overview{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Marks the messages of the conversation as read, up to and including the one with that id. Servers without
+    /// read receipts only keep read markers for the main chat.
+    pub async fn mark_read(
+        &self,
+        conversation: Conversation,
+        message_id: i32,
+    ) -> Result<(), Error> {
+        let marker = ReadMarker {
+            conversation,
+            message_id,
+        };
+        let (request, endpoint) = if self.supports(Feature::ReadReceipts) {
+            (self.connection.post("/read"), "/read")
+        } else {
+            (self.connection.put("/sync/read"), "/sync/read")
+        };
+        let request = request.auth(self).json(&marker);
+        // Marking a message as read twice does nothing, so this is safe to retry
+        self.connection.send_idempotent(request, endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches how far the users read the conversation.
+    pub async fn get_read_receipts(
+        &self,
+        conversation: Conversation,
+    ) -> Result<Vec<ReadReceipt>, Error> {
+        let endpoint = format!("/read?conversation={}", conversation.key());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Returns the link pointing to the message with that id on this server.
+    pub fn permalink(&self, message_id: i32) -> String {
+        format!("{}/message/{message_id}", self.connection.base_url)
+    }
+
+    /// Looks for a link to a message on this server in the text and returns the id of the message.
+    pub fn find_permalink(&self, text: &str) -> Option<i32> {
+        let prefix = format!("{}/message/", self.connection.base_url);
+        let start = text.find(&prefix)? + prefix.len();
+        let id: String = text[start..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        id.parse().ok()
+    }
+
+    /// Fetches one page of the registered users, ordered by id.
+    pub async fn get_user_page(&self, page: &PageRequest) -> Result<Page<User>, Error> {
+        self.get_cached(&format!("/users{}", page.query())).await
+    }
+
+    /// Fetches all registered users, a page at a time. Servers without pagination send them all at once.
+    pub async fn get_all_users(&self) -> Result<Vec<User>, Error> {
+        if !self.supports(Feature::Pagination) {
+            return self.get_cached("/users").await;
+        }
+
+        let mut users = Vec::new();
+        let mut request = PageRequest::default();
+        loop {
+            let page = self.get_user_page(&request).await?;
+            users.extend(page.items);
+            match page.next {
+                Some(next) => request = PageRequest::after(next),
+                None => return Ok(users),
+            }
+        }
+    }
+
+    /// Fetches one page of the channels, ordered by id. With ``joined``, only the ones the user is a member of.
+    pub async fn get_channel_page(
+        &self,
+        page: &PageRequest,
+        joined: bool,
+    ) -> Result<Page<Channel>, Error> {
+        // The whole query is part of the endpoint, so each page and filter is cached on its own
+        let endpoint = match page.query().as_str() {
+            "" => format!("/channels?joined={joined}"),
+            query => format!("/channels{query}&joined={joined}"),
+        };
+        self.get_cached(&endpoint).await
+    }
+
+    /// Fetches all channels, or only the ones the user is a member of, a page at a time.
+    pub async fn get_channels(&self, joined: bool) -> Result<Vec<Channel>, Error> {
+        let mut channels = Vec::new();
+        let mut request = PageRequest::default();
+        loop {
+            let page = self.get_channel_page(&request, joined).await?;
+            channels.extend(page.items);
+            match page.next {
+                Some(next) => request = PageRequest::after(next),
+                None => return Ok(channels),
+            }
+        }
+    }
+
+    /// Creates a channel with that name, which the user joins right away.
+    pub async fn create_channel(&self, name: &str) -> Result<Channel, Error> {
+        let endpoint = "/channels";
+        let request = self
+            .connection
+            .post(endpoint)
+            .auth(self)
+            .json(&CreateChannel {
+                name: name.to_string(),
+                topic: String::new(),
+            });
+        self.connection
+            .send(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Joins the channel with that id, so its messages can be read and written.
+    pub async fn join_channel(&self, channel_id: i32) -> Result<Channel, Error> {
+        let endpoint = format!("/channels/{channel_id}/members");
+        let request = self.connection.put(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Leaves the channel with that id.
+    pub async fn leave_channel(&self, channel_id: i32) -> Result<(), Error> {
+        let endpoint = format!("/channels/{channel_id}/members");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Makes the members of the channel with that id wait that many seconds between their messages, or lets them
+    /// send freely again with 0. Needs a role allowed to do so.
+    pub async fn set_slow_mode(&self, channel_id: i32, seconds: u32) -> Result<Channel, Error> {
+        let endpoint = format!("/channels/{channel_id}/slowmode");
+        let request = self
+            .connection
+            .put(&endpoint)
+            .auth(self)
+            .json(&SlowModeRequest { seconds });
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Changes the topic of the channel with that id. Needs a role allowed to do so.
+    pub async fn set_channel_topic(&self, channel_id: i32, topic: &str) -> Result<Channel, Error> {
+        let endpoint = format!("/channels/{channel_id}/topic");
+        let request = self
+            .connection
+            .put(&endpoint)
+            .auth(self)
+            .json(&TopicRequest {
+                topic: topic.to_string(),
+            });
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches the message of the day, whose text is empty if there is none.
+    pub async fn get_motd(&self) -> Result<Motd, Error> {
+        self.get_cached("/motd").await
+    }
+
+    /// Changes the message of the day, or removes it with an empty text. Only admins may do this.
+    pub async fn set_motd(&self, text: &str) -> Result<Motd, Error> {
+        let endpoint = "/motd";
+        let request = self.connection.put(endpoint).auth(self).json(&MotdRequest {
+            text: text.to_string(),
+        });
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches one page of the users the user blocked, ordered by id.
+    pub async fn get_blocked_page(&self, page: &PageRequest) -> Result<Page<User>, Error> {
+        let endpoint = format!("/blocks{}", page.query());
+        let request = self.connection.get(&endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, &endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Fetches all users the user blocked, ordered by id, a page at a time. Servers without pagination send them
+    /// all at once.
+    pub async fn get_blocked_users(&self) -> Result<Vec<User>, Error> {
+        if !self.supports(Feature::Pagination) {
+            let endpoint = "/blocks";
+            let request = self.connection.get(endpoint).auth(self);
+            return self
+                .connection
+                .send_idempotent(request, endpoint)
+                .await?
+                .json()
+                .await
+                .map_err(Error::DeserializingFailed);
+        }
+
+        let mut users = Vec::new();
+        let mut request = PageRequest::default();
+        loop {
+            let page = self.get_blocked_page(&request).await?;
+            users.extend(page.items);
+            match page.next {
+                Some(next) => request = PageRequest::after(next),
+                None => return Ok(users),
+            }
+        }
+    }
+
+    /// Blocks the user with that id, so the server no longer sends their messages.
+    pub async fn block_user(&self, userid: i32) -> Result<(), Error> {
+        let endpoint = format!("/blocks/{userid}");
+        let request = self.connection.put(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Unblocks the user with that id.
+    pub async fn unblock_user(&self, userid: i32) -> Result<(), Error> {
+        let endpoint = format!("/blocks/{userid}");
+        let request = self.connection.delete(&endpoint).auth(self);
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Fetches the channels the user gets fewer notifications for than every message.
+    pub async fn get_channel_notifications(&self) -> Result<Vec<ChannelNotifications>, Error> {
+        let endpoint = "/channels/notifications";
+        let request = self.connection.get(endpoint).auth(self);
+        self.connection
+            .send_idempotent(request, endpoint)
+            .await?
+            .json()
+            .await
+            .map_err(Error::DeserializingFailed)
+    }
+
+    /// Sets how the user is told about new messages in the channel with that id.
+    pub async fn set_channel_notifications(
+        &self,
+        channel_id: i32,
+        level: NotificationLevel,
+    ) -> Result<(), Error> {
+        let endpoint = format!("/channels/{channel_id}/notifications");
+        let request = self
+            .connection
+            .put(&endpoint)
+            .auth(self)
+            .json(&NotificationRequest { level });
+        self.connection.send_idempotent(request, &endpoint).await?;
+        Ok(())
+    }
+
+    /// Performs a GET request, sending along the ETag of the previous response. If the server reports that
+    /// nothing changed, the previous response is used instead of downloading it again.
+    async fn get_cached<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T, Error> {
+        let mut request = self.connection.get(endpoint).auth(self);
+        if let Some(cached) = self.lock_cache().get(endpoint) {
+            request = request.header(IF_NONE_MATCH, &cached.etag);
+        }
+
+        let response = self.connection.send_idempotent(request, endpoint).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = self.lock_cache().get(endpoint) {
+                return serde_json::from_slice(&cached.body).map_err(Error::InvalidJson);
+            }
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await.map_err(Error::DeserializingFailed)?;
+        let value = serde_json::from_slice(&body).map_err(Error::InvalidJson)?;
+
+        if let Some(etag) = etag {
+            let cached = CachedResponse {
+                etag,
+                body: body.to_vec(),
+            };
+            self.lock_cache().insert(endpoint.to_string(), cached);
+        }
+
+        Ok(value)
+    }
+
+    /// Reads a response that may be zstd-compressed and may be ``MessagePack`` instead of json.
+    async fn decode_bulk<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let zstd = header(CONTENT_ENCODING).is_some_and(|encoding| encoding.trim() == "zstd");
+        let msgpack = header(CONTENT_TYPE)
+            .is_some_and(|content_type| content_type.starts_with("application/msgpack"));
+
+        let mut body = response
+            .bytes()
+            .await
+            .map_err(Error::DeserializingFailed)?
+            .to_vec();
+        if zstd {
+            body = zstd::decode_all(body.as_slice()).map_err(Error::DecompressionFailed)?;
+        }
+        if msgpack {
+            rmp_serde::from_slice(&body).map_err(Error::InvalidMsgpack)
+        } else {
+            serde_json::from_slice(&body).map_err(Error::InvalidJson)
+        }
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, HashMap<String, CachedResponse>> {
+        lock(&self.cache)
+    }
+
+    /// Subscribes to new messages. Reconnects by itself if the connection is lost. The channel closes if the
+    /// server refuses the connection or the application shuts down.
+    pub fn get_events(&self) -> Result<Receiver<StreamEvent>, Error> {
+        let endpoint = "/events";
+
+        let request = self.connection.get(endpoint).auth(self);
+        let mut event_source =
+            EventSource::new(request).map_err(Error::EventSourceCreationFailed)?;
+
+        let (tx, rx) = channel(8);
+        let shutdown = self.connection.shutdown.child();
+
+        tokio::spawn(async move {
+            let mut disconnected = false;
+            loop {
+                let event = tokio::select! {
+                    event = event_source.next() => event,
+                    () = shutdown.cancelled() => None,
+                };
+                let event = match event {
+                    Some(Ok(Event::Message(message))) if message.event == SYSTEM_EVENT => {
+                        match serde_json::from_str::<SystemEvent>(&message.data) {
+                            Ok(event) => StreamEvent::System(event),
+                            Err(e) => {
+                                warn!(data = message.data, error = %e, "Ignored an unreadable system event");
+                                continue;
+                            }
+                        }
+                    }
+                    Some(Ok(Event::Message(message))) => {
+                        match serde_json::from_str::<Message>(&message.data) {
+                            Ok(message) => StreamEvent::Message(message),
+                            Err(e) => {
+                                warn!(data = message.data, error = %e, "Ignored an unreadable message");
+                                continue;
+                            }
+                        }
+                    }
+                    Some(Ok(Event::Open)) if disconnected => {
+                        info!("Event stream reconnected");
+                        disconnected = false;
+                        StreamEvent::Reconnected
+                    }
+                    Some(Ok(Event::Open)) => {
+                        info!("Event stream opened");
+                        continue;
+                    }
+                    // The event source retries on its own, so only report the first failure
+                    Some(Err(e)) if !disconnected => {
+                        warn!(error = %e, "Event stream disconnected");
+                        disconnected = true;
+                        StreamEvent::Disconnected
+                    }
+                    Some(Err(e)) => {
+                        debug!(error = %e, "Event stream could not reconnect yet");
+                        continue;
+                    }
+                    None => break,
+                };
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+            event_source.close();
+        });
+
+        Ok(rx)
+    }
+
+    /// Turns responses with a unsuccessful status code into an error, using the error code sent by the server.
+    async fn check_status(response: Response, endpoint: &str) -> Result<Response, Error> {
+        let code = response.status();
+        if code.is_success() || code == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+
+        let Ok(error) = response.json::<ApiError>().await else {
+            if code == StatusCode::TOO_MANY_REQUESTS {
+                return Err(Error::RateLimited);
+            }
+            return Err(Error::UnexpectedStatusCode {
+                code,
+                endpoint: endpoint.to_string(),
+            });
+        };
+
+        Err(match error.code {
+            ErrorCode::NotAuthorized | ErrorCode::RefreshTokenInvalid => Error::NotAuthorized,
+            ErrorCode::LoginFailed => Error::LoginFailed,
+            ErrorCode::AccountBanned => Error::AccountBanned,
+            ErrorCode::UsernameTaken => Error::UsernameInUse,
+            ErrorCode::TermsNotAccepted => Error::TermsNotAccepted,
+            ErrorCode::InviteInvalid => Error::InviteInvalid,
+            ErrorCode::RateLimited => Error::RateLimited,
+            ErrorCode::QuotaExceeded => Error::QuotaExceeded(
+                error
+                    .details
+                    .and_then(|details| DateTime::parse_from_rfc3339(&details).ok())
+                    .map(|date| date.with_timezone(&Utc)),
+            ),
+            ErrorCode::SlowMode => {
+                Error::SlowMode(error.details.and_then(|details| details.parse().ok()))
+            }
+            ErrorCode::DuplicateMessage => Error::DuplicateMessage,
+            ErrorCode::FileTooLarge => {
+                Error::FileTooLarge(error.details.and_then(|details| details.parse().ok()))
+            }
+            ErrorCode::FileTypeNotAllowed => Error::FileTypeNotAllowed(error.details),
+            ErrorCode::PermissionDenied => Error::PermissionDenied,
+            _ => Error::Api(error),
+        })
+    }
+
+    fn handle_error(error: reqwest::Error, endpoint: &str) -> Error {
+        if error.is_timeout() {
+            return Error::TimedOut;
+        }
+        if error.is_connect() {
+            return Error::ConnectionFailure(error);
+        }
+        if error.is_request() {
+            return Error::InvalidRespone(error);
+        }
+        if error.is_status() {
+            if let Some(code) = error.status() {
+                if code == StatusCode::UNAUTHORIZED {
+                    return Error::NotAuthorized;
+                }
+                return Error::UnexpectedStatusCode {
+                    code,
+                    endpoint: endpoint.to_string(),
+                };
+            }
+        }
+
+        Error::Generic(error)
+    }
+}
+
+/// Locks the mutex. Everything guarded by one in the client is only ever replaced as a whole, so it is still usable
+/// if a thread panicked whilst holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+trait AuthResponse {
+    fn auth(self, client: &Client) -> RequestBuilder;
+}
+
+impl AuthResponse for RequestBuilder {
+    fn auth(self, client: &Client) -> RequestBuilder {
+        let token = client.token.read().unwrap_or_else(PoisonError::into_inner);
+        self.bearer_auth(&token.0)
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rand::Rng;
+use reqwest::StatusCode;
+
+use crate::{config::RetryConfig, Error};
+
+/// Decides whether and when failed requests are sent again.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// How often a request is sent at most, including the first attempt.
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns how long to wait before the given retry, starting at 1. The delay doubles with each retry
+    /// up to the maximum, with a random part so clients that failed together do not retry together.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let capped = exponential.min(self.max_delay);
+        let millis = u64::try_from(capped.as_millis()).unwrap_or(u64::MAX);
+
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+    }
+
+    /// Returns true if the error is likely to go away by itself, so sending the request again might succeed.
+    pub fn is_transient(error: &Error) -> bool {
+        match error {
+            Error::TimedOut | Error::ConnectionFailure(_) => true,
+            Error::UnexpectedStatusCode { code, .. } => matches!(
+                *code,
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::from(&RetryConfig::default())
+    }
+}
+
+impl From<&RetryConfig> for RetryPolicy {
+    fn from(config: &RetryConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            base_delay: Duration::from_millis(config.base_delay),
+            max_delay: Duration::from_millis(config.max_delay),
+        }
+    }
+}
+
+/// Shares whether a client is currently retrying a request, so it can be shown to the user.
+#[derive(Debug, Clone, Default)]
+pub struct RetryState {
+    /// The retry currently in progress, or 0 if the client is not retrying.
+    retry: Arc<AtomicU32>,
+}
+
+impl RetryState {
+    /// Returns the retry currently in progress, if any.
+    pub fn current(&self) -> Option<u32> {
+        match self.retry.load(Ordering::Relaxed) {
+            0 => None,
+            retry => Some(retry),
+        }
+    }
+
+    pub fn set(&self, retry: u32) {
+        self.retry.store(retry, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.set(0);
+    }
+}
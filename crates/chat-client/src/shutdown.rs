@@ -0,0 +1,34 @@
+//! Cancelling what a client is doing when the application shuts down.
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+
+/// Signals the shutdown of the application to running tasks.
+pub struct ShutdownHandler {
+    token: CancellationToken,
+    sender: Sender<()>,
+}
+
+impl ShutdownHandler {
+    /// Creates a handler together with a receiver that is closed once the handler and all of its children are
+    /// dropped, so the application can wait for its tasks to finish.
+    pub fn new() -> (Self, Receiver<()>) {
+        let (sender, receiver) = channel(2);
+        let token = CancellationToken::new();
+        (Self { token, sender }, receiver)
+    }
+
+    pub fn child(&self) -> Self {
+        Self {
+            token: self.token.child_token(),
+            sender: self.sender.clone(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.token.cancel()
+    }
+
+    pub fn cancelled(&self) -> WaitForCancellationFuture<'_> {
+        self.token.cancelled()
+    }
+}
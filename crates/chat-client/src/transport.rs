@@ -0,0 +1,164 @@
+//! What a chat session needs from the server, so it can be driven by something other than the HTTP client.
+use std::future::Future;
+
+use chat_core::{
+    models::{Message, Motd, User},
+    protocol::{
+        ChannelNotifications, Conversation, Draft, Feature, InitialSync, MessageContext,
+        MessageFilter, Overview, PageRequest, Presence, ReadReceipt,
+    },
+};
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::Receiver;
+
+use crate::{Client, Error, MessageStream, StreamEvent};
+
+/// The requests a session makes in the background. ``Client`` sends them to the server, tests can answer them
+/// from a script instead.
+pub trait ChatTransport: Send + Sync + 'static {
+    /// Returns the id of the logged in user.
+    fn user_id(&self) -> i32;
+
+    /// Returns whether the server offers the feature.
+    fn supports(&self, feature: Feature) -> bool;
+
+    /// Returns the current time by the clock of the server.
+    fn server_now(&self) -> DateTime<Utc>;
+
+    /// Subscribes to new messages and system events.
+    fn get_events(&self) -> Result<Receiver<StreamEvent>, Error>;
+
+    fn get_initial_sync(&self) -> impl Future<Output = Result<InitialSync, Error>> + Send;
+
+    /// Fetches the first page of the overview, which always starts with the main chat.
+    fn get_overview(&self) -> impl Future<Output = Result<Overview, Error>> + Send;
+
+    fn get_messages_of(
+        &self,
+        conversation: Conversation,
+        filter: MessageFilter,
+        limit: Option<u32>,
+    ) -> impl Future<Output = Result<Vec<Message>, Error>> + Send;
+
+    fn stream_messages(
+        &self,
+        filter: MessageFilter,
+    ) -> impl Future<Output = Result<MessageStream, Error>> + Send;
+
+    fn get_message_context(
+        &self,
+        message_id: i32,
+        around: Option<u32>,
+    ) -> impl Future<Output = Result<MessageContext, Error>> + Send;
+
+    fn get_all_users(&self) -> impl Future<Output = Result<Vec<User>, Error>> + Send;
+
+    fn get_drafts(&self) -> impl Future<Output = Result<Vec<Draft>, Error>> + Send;
+
+    fn save_draft(
+        &self,
+        conversation: Conversation,
+        text: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn get_presence(&self) -> impl Future<Output = Result<Presence, Error>> + Send;
+
+    fn get_motd(&self) -> impl Future<Output = Result<Motd, Error>> + Send;
+
+    fn mark_read(
+        &self,
+        conversation: Conversation,
+        message_id: i32,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn get_read_receipts(
+        &self,
+        conversation: Conversation,
+    ) -> impl Future<Output = Result<Vec<ReadReceipt>, Error>> + Send;
+
+    fn get_channel_notifications(
+        &self,
+    ) -> impl Future<Output = Result<Vec<ChannelNotifications>, Error>> + Send;
+}
+
+impl ChatTransport for Client {
+    fn user_id(&self) -> i32 {
+        Client::user_id(self)
+    }
+
+    fn supports(&self, feature: Feature) -> bool {
+        Client::supports(self, feature)
+    }
+
+    fn server_now(&self) -> DateTime<Utc> {
+        Client::server_now(self)
+    }
+
+    fn get_events(&self) -> Result<Receiver<StreamEvent>, Error> {
+        Client::get_events(self)
+    }
+
+    async fn get_initial_sync(&self) -> Result<InitialSync, Error> {
+        Client::get_initial_sync(self).await
+    }
+
+    async fn get_overview(&self) -> Result<Overview, Error> {
+        Client::get_overview(self, &PageRequest::default()).await
+    }
+
+    async fn get_messages_of(
+        &self,
+        conversation: Conversation,
+        filter: MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, Error> {
+        Client::get_messages_of(self, conversation, filter, limit).await
+    }
+
+    async fn stream_messages(&self, filter: MessageFilter) -> Result<MessageStream, Error> {
+        Client::stream_messages(self, filter).await
+    }
+
+    async fn get_message_context(
+        &self,
+        message_id: i32,
+        around: Option<u32>,
+    ) -> Result<MessageContext, Error> {
+        Client::get_message_context(self, message_id, around).await
+    }
+
+    async fn get_all_users(&self) -> Result<Vec<User>, Error> {
+        Client::get_all_users(self).await
+    }
+
+    async fn get_drafts(&self) -> Result<Vec<Draft>, Error> {
+        Client::get_drafts(self).await
+    }
+
+    async fn save_draft(&self, conversation: Conversation, text: &str) -> Result<(), Error> {
+        Client::save_draft(self, conversation, text).await
+    }
+
+    async fn get_presence(&self) -> Result<Presence, Error> {
+        Client::get_presence(self).await
+    }
+
+    async fn get_motd(&self) -> Result<Motd, Error> {
+        Client::get_motd(self).await
+    }
+
+    async fn mark_read(&self, conversation: Conversation, message_id: i32) -> Result<(), Error> {
+        Client::mark_read(self, conversation, message_id).await
+    }
+
+    async fn get_read_receipts(
+        &self,
+        conversation: Conversation,
+    ) -> Result<Vec<ReadReceipt>, Error> {
+        Client::get_read_receipts(self, conversation).await
+    }
+
+    async fn get_channel_notifications(&self) -> Result<Vec<ChannelNotifications>, Error> {
+        Client::get_channel_notifications(self).await
+    }
+}
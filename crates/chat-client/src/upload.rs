@@ -0,0 +1,91 @@
+use std::{fs, io, path::Path};
+
+use rand::Rng;
+
+/// A file to attach to a message, read into memory so the request can be sent again after transient failures.
+#[derive(Debug, Clone)]
+pub struct FileUpload {
+    pub name: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+impl FileUpload {
+    /// Reads the file at the path. Its type is guessed from the extension.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let name = path
+            .file_name()
+            .map_or_else(|| "file".into(), |name| name.to_string_lossy().into_owned());
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        Ok(Self {
+            name,
+            mime: mime_type(extension.as_deref()).into(),
+            data,
+        })
+    }
+}
+
+/// Guesses the media type of a file from its extension. Only common types are known, anything else is sent as
+/// plain bytes.
+fn mime_type(extension: Option<&str>) -> &'static str {
+    match extension {
+        Some("txt" | "log" | "md") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Builds a ``multipart/form-data`` body with the text of a message and its files, the way
+/// ``POST /attachments/<conversation>`` expects it. Returns the content type along with the body.
+pub(crate) fn multipart_body(text: &str, files: &[FileUpload]) -> (String, Vec<u8>) {
+    let boundary = format!("chat-{:032x}", rand::thread_rng().gen::<u128>());
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        format!("--{boundary}\r\nContent-Disposition: form-data; name=\"text\"\r\n\r\n{text}\r\n")
+            .as_bytes(),
+    );
+    for file in files {
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: {}\r\n\r\n",
+                quote(&file.name),
+                file.mime
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&file.data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+    (format!("multipart/form-data; boundary={boundary}"), body)
+}
+
+/// Escapes a file name for a quoted header parameter.
+fn quote(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '"' => "%22".to_string(),
+            '\r' | '\n' => " ".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
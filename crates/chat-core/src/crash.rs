@@ -0,0 +1,46 @@
+//! Reports of panics, written to files so they can be looked into and sent along with bug reports after the
+//! program is gone.
+use std::backtrace::Backtrace;
+use std::fs;
+use std::io;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use chrono::Utc;
+
+/// The directory crash reports are written to, inside the data directory.
+pub const CRASH_DIR: &str = "crashes";
+
+/// Describes a panic of the program: the message, where it happened and the backtrace leading there. The backtrace
+/// is always captured, regardless of `RUST_BACKTRACE`.
+pub fn describe(program: &str, info: &PanicHookInfo<'_>) -> String {
+    let message = info.payload_as_str().unwrap_or("Box<dyn Any>");
+    let location = info
+        .location()
+        .map_or_else(|| "an unknown location".to_string(), ToString::to_string);
+    let thread = thread::current();
+    let thread = thread.name().unwrap_or("<unnamed>");
+    format!(
+        "{program} {} panicked in thread '{thread}' at {location}:\n{message}\n\nBacktrace:\n{}",
+        env!("CARGO_PKG_VERSION"),
+        Backtrace::force_capture()
+    )
+}
+
+/// Writes the description of a panic to a new file in `CRASH_DIR` below the data directory, returning the path of
+/// the file.
+///
+/// # Errors
+///
+/// This function will return an error if the directory or the file could not be created.
+pub fn write_report(data: &Path, program: &str, description: &str) -> io::Result<PathBuf> {
+    let dir = data.join(CRASH_DIR);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{program}-{}.txt",
+        Utc::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+    fs::write(&path, description)?;
+    Ok(path)
+}
@@ -0,0 +1,54 @@
+//! The types shared by the server and its clients: the models, the protocol they are exchanged with, the paths
+//! files are kept in and a few helpers both sides need. Nothing in here depends on a web framework, and the database
+//! mappings of the models are only compiled with the `diesel` feature.
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+pub mod crash;
+pub mod models;
+pub mod paths;
+pub mod protocol;
+#[cfg(feature = "diesel")]
+pub mod schema;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginToken(pub String);
+
+/// A long-lived token that can be exchanged for a new `LoginToken`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshToken(pub String);
+
+/// A version number like `1.4.2`, compared part by part, so `0.10.0` is newer than `0.9.0`. Missing parts count as
+/// zero and anything after a `-` or `+`, like `-beta`, is ignored.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(Vec<u64>);
+
+impl Version {
+    /// The version of this build.
+    pub fn current() -> Self {
+        env!("CARGO_PKG_VERSION")
+            .parse()
+            .expect("the package version is valid")
+    }
+}
+
+impl FromStr for Version {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let release = s
+            .trim()
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default();
+        let mut parts = release
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<Vec<u64>, _>>()?;
+        while parts.last() == Some(&0) {
+            parts.pop();
+        }
+        Ok(Self(parts))
+    }
+}
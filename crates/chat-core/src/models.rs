@@ -0,0 +1,391 @@
+//! The things the chat is made of, as stored by the server and sent to clients.
+use chrono::{DateTime, Utc};
+#[cfg(feature = "diesel")]
+use diesel::{Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "diesel")]
+use crate::schema::{addressbans, apikeys, auditlog, channels, invites, motd, users, webhooks};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    /// What the user may do besides chatting. Servers without roles do not send it, everyone is a `user` there.
+    #[serde(default)]
+    #[cfg_attr(feature = "diesel", diesel(deserialize_as = String))]
+    pub role: Role,
+    /// The name shown in place of the username, if the user chose one. Users are still logged in and mentioned
+    /// with their username.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displayname: Option<String>,
+}
+
+/// What a user tells others about themselves, as returned by `GET /profile`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = users))]
+pub struct Profile {
+    pub id: i32,
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displayname: Option<String>,
+    /// A few words about the user, empty if they wrote none.
+    #[serde(default)]
+    pub bio: String,
+    /// The http or https address of an image showing the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// When the user last used the server, unless they keep it to themselves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = lastactive))]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Whether others are shown when the user last used the server. Servers that do not track it do not send it.
+    #[serde(default = "shown")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = showlastseen))]
+    pub show_last_seen: bool,
+    /// When the account was made. Left out for accounts older than the server keeping track of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = createddate))]
+    pub joined: Option<DateTime<Utc>>,
+    /// Goes up with every change to the profile. Sending it as `If-Match` makes a change fail if the profile
+    /// changed since. Servers that do not count changes send none.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = profileversion))]
+    pub version: i32,
+}
+
+fn shown() -> bool {
+    true
+}
+
+/// What a user may do besides chatting. Higher roles may do everything lower ones may.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    User,
+    /// Keeps the chat tidy, by deleting messages, renaming users and banning them.
+    Moderator,
+    /// Runs the server and decides who the moderators are.
+    Admin,
+}
+
+impl Role {
+    /// The name of the role as it is stored.
+    pub fn key(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// The role with that name, as returned by `Role::key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "user" => Some(Role::User),
+            "moderator" => Some(Role::Moderator),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    /// Whether users with the role have the permission.
+    pub fn can(self, permission: Permission) -> bool {
+        match permission {
+            Permission::DeleteMessages
+            | Permission::RenameUsers
+            | Permission::BanUsers
+            | Permission::ListUsers
+            | Permission::SetSlowMode
+            | Permission::SetTopics => self >= Role::Moderator,
+            Permission::AssignRoles
+            | Permission::Impersonate
+            | Permission::ViewAuditLog
+            | Permission::BanAddresses
+            | Permission::CreateInvites
+            | Permission::ManageBots
+            | Permission::ManageWebhooks
+            | Permission::SetMotd => self == Role::Admin,
+        }
+    }
+
+    /// Whether users with the role may act on users with the other role, by renaming or banning them. Moderators
+    /// may only act on users, admins on anyone.
+    pub fn outranks(self, other: Role) -> bool {
+        self == Role::Admin || self > other
+    }
+}
+
+// Stored as text, which the database makes sure is one of the keys
+impl From<String> for Role {
+    fn from(key: String) -> Self {
+        Role::from_key(&key).unwrap_or_default()
+    }
+}
+
+/// Something only some roles may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Deleting messages of other users. Everyone may delete their own.
+    DeleteMessages,
+    RenameUsers,
+    /// Banning users, lifting their bans and logging them out everywhere.
+    BanUsers,
+    /// Listing every user along with whether they are banned, online and when they were last active.
+    ListUsers,
+    /// Making users moderators or admins, or users again.
+    AssignRoles,
+    /// Logging in as another user, to help them or look into a problem.
+    Impersonate,
+    ViewAuditLog,
+    /// Banning ranges of addresses, which may hit many users at once.
+    BanAddresses,
+    /// Making members of a channel wait between their messages. Those who may do so never have to wait.
+    SetSlowMode,
+    /// Making the codes people need to register on servers where registering needs an invite.
+    CreateInvites,
+    /// Making bot accounts and the API keys they log in with, and revoking the keys.
+    ManageBots,
+    /// Registering the urls every new message in the chat and the channels is posted to.
+    ManageWebhooks,
+    /// Changing the topics of channels.
+    SetTopics,
+    /// Changing the message of the day everyone is shown.
+    SetMotd,
+}
+
+/// Something an admin or moderator did, as recorded in the audit log.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = auditlog))]
+pub struct AuditEntry {
+    pub id: i32,
+    pub date: DateTime<Utc>,
+    /// The user who did it.
+    pub actorid: i32,
+    #[cfg_attr(feature = "diesel", diesel(deserialize_as = String))]
+    pub action: AuditAction,
+    /// The user it was done to, if it was done to one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targetid: Option<i32>,
+    /// What else is worth knowing, like the reason given for an impersonation or the new name of a user.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub details: String,
+}
+
+/// What an entry of the audit log records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// An admin logged in as the target.
+    Impersonate,
+    /// A moderator deleted a message of the target. Authors deleting their own messages are not recorded.
+    DeleteMessage,
+    RenameUser,
+    BanUser,
+    UnbanUser,
+    /// A moderator logged the target out everywhere, without banning them.
+    KickUser,
+    SetRole,
+    /// An admin banned the range of addresses in the details.
+    BanAddress,
+    UnbanAddress,
+    /// An action added by a newer server.
+    #[serde(other)]
+    Unknown,
+}
+
+impl AuditAction {
+    /// The name of the action as it is stored.
+    pub fn key(self) -> &'static str {
+        match self {
+            AuditAction::Impersonate => "impersonate",
+            AuditAction::DeleteMessage => "delete_message",
+            AuditAction::RenameUser => "rename_user",
+            AuditAction::BanUser => "ban_user",
+            AuditAction::UnbanUser => "unban_user",
+            AuditAction::KickUser => "kick_user",
+            AuditAction::SetRole => "set_role",
+            AuditAction::BanAddress => "ban_address",
+            AuditAction::UnbanAddress => "unban_address",
+            AuditAction::Unknown => "unknown",
+        }
+    }
+}
+
+// Stored as text, which the database makes sure is one of the keys
+impl From<String> for AuditAction {
+    fn from(key: String) -> Self {
+        match key.as_str() {
+            "impersonate" => AuditAction::Impersonate,
+            "delete_message" => AuditAction::DeleteMessage,
+            "rename_user" => AuditAction::RenameUser,
+            "ban_user" => AuditAction::BanUser,
+            "unban_user" => AuditAction::UnbanUser,
+            "kick_user" => AuditAction::KickUser,
+            "set_role" => AuditAction::SetRole,
+            "ban_address" => AuditAction::BanAddress,
+            "unban_address" => AuditAction::UnbanAddress,
+            _ => AuditAction::Unknown,
+        }
+    }
+}
+
+/// A range of addresses nobody may register or log in from, banned by an admin.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = addressbans))]
+pub struct AddressBan {
+    pub id: i32,
+    /// The range in CIDR notation, like `192.0.2.0/24` or `2001:db8::/32`.
+    pub network: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reason: String,
+    pub date: DateTime<Utc>,
+    /// The admin who banned it.
+    pub createdby: i32,
+}
+
+/// A code that lets one person register on a server where registering needs an invite.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = invites))]
+pub struct Invite {
+    pub id: i32,
+    pub code: String,
+    /// The admin who made it.
+    pub createdby: i32,
+    pub created: DateTime<Utc>,
+    /// The code can not be used anymore after this point. Codes without one do not expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+    /// The user who registered with the code, once someone did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usedby: Option<i32>,
+}
+
+/// A key a bot account logs in with, as listed to admins. The key itself is only shown once, when it is made, as
+/// the server only stores a hash of it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = apikeys))]
+pub struct ApiKey {
+    pub id: i32,
+    /// The bot the key belongs to.
+    pub userid: i32,
+    /// What the key is used for, to tell the keys of a bot apart.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub created: DateTime<Utc>,
+}
+
+/// A url the server posts every new message in the chat and the channels to, registered by an admin.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = webhooks))]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    /// The admin who registered it.
+    pub createdby: i32,
+    pub created: DateTime<Utc>,
+}
+
+/// A message as sent to clients. The server stores it in the `messages` table, but turns the rows into this
+/// before sending them, so changes to the table do not change what clients receive.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Message {
+    pub id: i32,
+    pub date: DateTime<Utc>,
+    pub messagetext: String,
+    pub userid: i32,
+    /// The message this one is a forwarded copy of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwardedid: Option<i32>,
+    /// The author of the forwarded message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwardeduserid: Option<i32>,
+    /// The position of the message in its conversation, counting up from 1 as messages are sent. Unlike the date
+    /// it never collides, so it is what history is ordered by. Servers that do not number messages leave it at 0.
+    #[serde(default)]
+    pub seq: i64,
+    /// The channel the message was sent in. Messages without a channel or recipient are in the main chat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channelid: Option<i32>,
+    /// The user a direct message was sent to. Only they and the sender can read it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipientid: Option<i32>,
+    /// The files sent along with the message. Only their descriptions are sent with it, the files themselves are
+    /// downloaded on demand. Not stored in the `messages` table, so the server fills them in after loading.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A conversation users join to read and write in it, apart from the main chat.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+pub struct Channel {
+    pub id: i32,
+    pub name: String,
+    pub topic: String,
+    /// Seconds members have to wait between their messages in the channel, 0 if they do not. Moderators and admins
+    /// never have to wait.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub slowmode: i32,
+    /// When the channel was made. Left out for channels older than the server keeping track of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = createddate))]
+    pub created: Option<DateTime<Utc>>,
+    /// When the name, topic or slow mode of the channel last changed, or when it was made if they never did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = updateddate))]
+    pub updated: Option<DateTime<Utc>>,
+    /// Goes up with every change to the name, topic or slow mode. Sending it as `If-Match` makes a change fail if
+    /// the channel changed since. Servers that do not count changes send none.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub version: i32,
+}
+
+/// The message of the day, which clients show everyone when they log in.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "diesel", derive(Queryable, Selectable))]
+#[cfg_attr(feature = "diesel", diesel(table_name = motd))]
+pub struct Motd {
+    /// Empty if there is none.
+    pub text: String,
+    /// When an admin last changed it, or `None` if no one ever did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "diesel", diesel(column_name = updateddate))]
+    pub updated: Option<DateTime<Utc>>,
+    /// Goes up with every change. Sending it as `If-Match` makes a change fail if it changed since.
+    pub version: i32,
+}
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
+}
+
+/// A file sent along with a message. This only describes the file, which is downloaded separately with
+/// `GET /attachments/<id>`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub id: i32,
+    pub messageid: i32,
+    /// The name of the file as it was uploaded, without any directories.
+    pub name: String,
+    /// The media type the server recognized the file as, `application/octet-stream` if it did not.
+    pub mime: String,
+    /// The size of the file in bytes.
+    pub size: i64,
+    /// Where a small preview of an image can be fetched, relative to the server address. Left out by servers that
+    /// do not make previews.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    /// The BLAKE2b-256 hash of the content, hex encoded, which downloads carry as their `ETag`. Clients can use it
+    /// to tell whether they have the file already. Left out for files stored before the server hashed them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
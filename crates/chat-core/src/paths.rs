@@ -0,0 +1,82 @@
+//! Where the server and the client keep their files.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PathError {
+    #[error("{0} needs a directory")]
+    MissingArgument(String),
+    #[error("Could not create the directory {path}")]
+    CreationFailed { path: PathBuf, source: io::Error },
+}
+
+/// The directories for persistent data, configuration and cached files.
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub data: PathBuf,
+    pub config: PathBuf,
+    pub cache: PathBuf,
+}
+
+impl Paths {
+    /// Resolves the directories. Each one is taken from the command line (`--data-dir`, `--config-dir`,
+    /// `--cache-dir`), then from the environment (`CHAT_APP_DATA_DIR`, `CHAT_APP_CONFIG_DIR`, `CHAT_APP_CACHE_DIR`)
+    /// and otherwise from the platform, which means the XDG base directories on Linux. Falls back to the working
+    /// directory if the platform has no home directory.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an option on the command line is missing its directory.
+    pub fn resolve(args: &[String]) -> Result<Self, PathError> {
+        let project = ProjectDirs::from("", "", "chat_app");
+        Ok(Self {
+            data: Self::pick(args, "--data-dir", "CHAT_APP_DATA_DIR", || {
+                project.as_ref().map(|dirs| dirs.data_dir().to_path_buf())
+            })?,
+            config: Self::pick(args, "--config-dir", "CHAT_APP_CONFIG_DIR", || {
+                project.as_ref().map(|dirs| dirs.config_dir().to_path_buf())
+            })?,
+            cache: Self::pick(args, "--cache-dir", "CHAT_APP_CACHE_DIR", || {
+                project.as_ref().map(|dirs| dirs.cache_dir().to_path_buf())
+            })?,
+        })
+    }
+
+    /// Creates the data and config directories if they do not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a directory could not be created.
+    pub fn create(&self) -> Result<(), PathError> {
+        for path in [&self.data, &self.config] {
+            fs::create_dir_all(path).map_err(|source| PathError::CreationFailed {
+                path: path.clone(),
+                source,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn pick(
+        args: &[String],
+        option: &str,
+        variable: &str,
+        platform: impl FnOnce() -> Option<PathBuf>,
+    ) -> Result<PathBuf, PathError> {
+        if let Some(index) = args.iter().position(|arg| arg == option) {
+            return args
+                .get(index + 1)
+                .map(PathBuf::from)
+                .ok_or_else(|| PathError::MissingArgument(option.to_string()));
+        }
+        if let Some(path) = env::var_os(variable).filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(platform().unwrap_or_else(|| PathBuf::from(".")))
+    }
+}
@@ -0,0 +1,1689 @@
+//! The protocol spoken between the server and its clients: every request and response body, the payloads of the
+//! event stream and the error codes. The server handlers and `chat_client::Client` both use these types, so the
+//! two can not drift apart.
+//!
+//! Bodies that are not listed here are plain text: `POST /message` takes the text of the message.
+//!
+//! Fields are only ever added, and new ones are optional, so clients ignore the fields of responses they do not
+//! know and keep working with newer servers. The server is strict the other way around: request bodies with fields
+//! it does not know are refused with `ErrorCode::MalformedRequest`, so a misspelled field is not silently dropped.
+//! Bodies that are also sent back, like `PushSettings`, are lenient both ways. Servers tell the version of the
+//! protocol they speak in `PROTOCOL_VERSION_HEADER`, which only goes up when something is removed or changes
+//! meaning.
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::{ApiKey, Message, Role, User};
+
+/// The name of the server-sent events carrying a `SystemEvent`. Events without a name carry a `Message`.
+pub const SYSTEM_EVENT: &str = "system";
+
+/// The header clients can send `POST /message` with, holding a key they generated for the message. Sending the
+/// message again with the same key does not send it twice, so requests can be retried when a response got lost.
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// The version of the protocol spoken by this build. Servers send it in `PROTOCOL_VERSION_HEADER` and
+/// `ServerInfo::protocol_version`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The header the server sends `PROTOCOL_VERSION` in with every response.
+pub const PROTOCOL_VERSION_HEADER: &str = "Protocol-Version";
+
+/// The header the server sends its current time in with every response, as RFC 3339 with milliseconds. Clients
+/// compare it to their own clocks, which may be off.
+pub const SERVER_TIME: &str = "Server-Time";
+
+/// Which messages `POST /messages` and `POST /messages/stream` return, relative to a date or to the sequence number
+/// of a message. Messages sent at the same time are only told apart by their sequence numbers. Dates are compared
+/// to the dates the server gave the messages, so a client whose clock is off should ask by sequence number or for
+/// the latest messages instead.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum MessageFilter {
+    Before(DateTime<Utc>),
+    After(DateTime<Utc>),
+    BeforeSeq(i64),
+    AfterSeq(i64),
+    /// The newest messages, at most that many, as the server knows them. Needs no clock on the client.
+    Latest(u32),
+}
+
+impl MessageFilter {
+    /// The messages sent before this one. Uses the sequence number if the server sent one, so servers that do not
+    /// know them are only asked by date.
+    pub fn before(message: &Message) -> Self {
+        if message.seq > 0 {
+            MessageFilter::BeforeSeq(message.seq)
+        } else {
+            MessageFilter::Before(message.date)
+        }
+    }
+
+    /// The messages sent after this one, like `MessageFilter::before`.
+    pub fn after(message: &Message) -> Self {
+        if message.seq > 0 {
+            MessageFilter::AfterSeq(message.seq)
+        } else {
+            MessageFilter::After(message.date)
+        }
+    }
+
+    /// Whether the filter asks for older messages.
+    pub fn is_before(&self) -> bool {
+        matches!(
+            self,
+            MessageFilter::Before(_) | MessageFilter::BeforeSeq(_) | MessageFilter::Latest(_)
+        )
+    }
+}
+
+/// The response of `POST /user`, which takes a list of user ids: the name of each user, or `None` if there is no
+/// user with that id.
+pub type Usernames = HashMap<i32, Option<String>>;
+
+/// The response of `GET /presence`: who is online, meaning they hold a valid login token or have an event stream open.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    /// The ids of the users that are online, ordered by id.
+    pub online: Vec<i32>,
+}
+
+/// Which part of a collection to return, sent as the `limit` and `cursor` query parameters of `GET /users`,
+/// `GET /saved`, `GET /blocks`, `GET /sync/overview` and the lists under `/admin`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageRequest {
+    /// How many items to return at most. Left out or above `PageRequest::MAX_LIMIT`, the server uses its own
+    /// limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// The `next` cursor of the previous page. Left out for the first page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl PageRequest {
+    /// How many items a page holds if the request does not say.
+    pub const DEFAULT_LIMIT: u32 = 100;
+    /// The most items a page can hold.
+    pub const MAX_LIMIT: u32 = 500;
+
+    /// Asks for the page after the one the cursor came from.
+    pub fn after(cursor: impl Into<String>) -> Self {
+        Self {
+            limit: None,
+            cursor: Some(cursor.into()),
+        }
+    }
+
+    /// The number of items to return, between 1 and `PageRequest::MAX_LIMIT`.
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(Self::DEFAULT_LIMIT)
+            .clamp(1, Self::MAX_LIMIT)
+    }
+
+    /// The request as a query string, starting with `?`, or an empty string for the first page with the default
+    /// limit. Cursors only ever contain characters that need no escaping.
+    pub fn query(&self) -> String {
+        let mut parameters = Vec::new();
+        if let Some(limit) = self.limit {
+            parameters.push(format!("limit={limit}"));
+        }
+        if let Some(cursor) = &self.cursor {
+            parameters.push(format!("cursor={cursor}"));
+        }
+        if parameters.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", parameters.join("&"))
+        }
+    }
+}
+
+/// One page of a collection. Collections are never returned whole, so a response stays small no matter how much
+/// the server holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The cursor to ask for the following page with, or `None` if this is the last one. Cursors are opaque to
+    /// clients and only valid for the collection they came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+/// Something that happened in the chat, other than a user writing a message. Sent over the event stream as
+/// `system` events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemEvent {
+    pub date: DateTime<Utc>,
+    pub kind: SystemEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemEventKind {
+    UserJoined {
+        userid: i32,
+        username: String,
+    },
+    UserRenamed {
+        userid: i32,
+        old_name: String,
+        new_name: String,
+    },
+    UserBanned {
+        userid: i32,
+        username: String,
+    },
+    /// The topic of the channel changed. Servers from before topics could be edited send no channel.
+    TopicChanged {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        channelid: Option<i32>,
+        topic: String,
+    },
+    /// An admin changed the message of the day, which is empty if there is none anymore.
+    MotdChanged {
+        text: String,
+    },
+    /// The message was deleted by its author or a moderator. Clients drop it from what they show.
+    MessageDeleted {
+        messageid: i32,
+    },
+    /// The user chose another display name, or none to be shown with their username again.
+    DisplayNameChanged {
+        userid: i32,
+        username: String,
+        displayname: Option<String>,
+    },
+    /// The user came online or went offline.
+    PresenceChanged {
+        userid: i32,
+        username: String,
+        online: bool,
+    },
+    /// A moderator put the channel in slow mode, or took it out of it with 0 seconds.
+    SlowModeChanged {
+        channelid: i32,
+        seconds: u32,
+    },
+    /// The user read the conversation up to the message. Only sent to those who can read the conversation, which
+    /// is named as they see it.
+    MessagesRead {
+        userid: i32,
+        conversation: Conversation,
+        message_id: i32,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginResult {
+    pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// The id of the user that logged in, so clients can tell their own messages apart.
+    pub userid: i32,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    /// Whether a refresh token should be issued alongside the login token.
+    #[serde(default)]
+    pub remember_me: bool,
+    /// The version of the terms of service the user accepted. Needed to register on servers that have them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_tos_version: Option<String>,
+    /// A code made by an admin. Needed to register on servers where registering needs an invite, and used up by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<String>,
+}
+
+/// The body of a request for a guest account.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuestRequest {
+    /// The version of the terms of service the guest accepted. Needed on servers that have them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_tos_version: Option<String>,
+}
+
+/// A login for a newly created guest account.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuestLogin {
+    pub token: String,
+    pub userid: i32,
+    /// The name generated for the guest.
+    pub username: String,
+    /// The account can not be used anymore after this point.
+    pub valid_until: DateTime<Utc>,
+}
+
+/// The body of `POST /admin/impersonate/<user>`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImpersonationRequest {
+    /// Why the admin needs to act as the user. Recorded in the audit log.
+    pub reason: String,
+}
+
+/// A login as another user, made by an admin to help them or look into a problem. The user sees it in their
+/// sessions, and it can not be renewed with a refresh token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImpersonationLogin {
+    pub token: String,
+    pub userid: i32,
+    pub username: String,
+    /// The login can not be used anymore after this point.
+    pub valid_until: DateTime<Utc>,
+}
+
+/// The body of `POST /admin/addresses`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddressBanRequest {
+    /// The range to ban in CIDR notation, like `192.0.2.0/24`. A single address bans only that one.
+    pub network: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// The body of `POST /admin/bots`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateBot {
+    pub username: String,
+}
+
+/// The body of `POST /admin/bots/<id>/keys`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateApiKey {
+    /// What the key is used for, to tell the keys of a bot apart.
+    #[serde(default)]
+    pub name: String,
+}
+
+/// A key made by `POST /admin/bots/<id>/keys`. The bot sends it in `Authorization: ApiKey <key>` instead of a
+/// login token. It does not expire, but can be revoked.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub info: ApiKey,
+    /// The key itself, which can not be looked up again later.
+    pub key: String,
+}
+
+/// The body of `POST /admin/webhooks`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookRequest {
+    /// Where new messages are posted to, an `http://` or `https://` url.
+    pub url: String,
+}
+
+/// What the server posts to webhooks, as json. Direct messages are never posted, as they are only meant for their
+/// recipient.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookPayload {
+    /// A message was sent to the chat or a channel. Messages are posted in parallel, so they may arrive out of
+    /// order, which their `seq` tells apart.
+    MessageCreated {
+        message: Message,
+        /// The name of the sender, so the receiver does not have to look it up.
+        username: String,
+    },
+}
+
+/// How `GET /admin/users` orders the users.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    /// By username, alphabetically.
+    #[default]
+    Name,
+    /// The ones who registered last first.
+    Joined,
+    /// The ones who used the server last first. Users it does not know of come last.
+    Active,
+}
+
+impl UserSort {
+    /// The name of the order, as sent in the `sort` query parameter.
+    pub fn key(self) -> &'static str {
+        match self {
+            UserSort::Name => "name",
+            UserSort::Joined => "joined",
+            UserSort::Active => "active",
+        }
+    }
+
+    /// The order with that name, as returned by `UserSort::key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "name" => Some(UserSort::Name),
+            "joined" => Some(UserSort::Joined),
+            "active" => Some(UserSort::Active),
+            _ => None,
+        }
+    }
+}
+
+/// Narrows down the users listed by `GET /admin/users`. Only users matching every part that is given are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserFilter {
+    pub role: Option<Role>,
+    /// Whether the users are online right now.
+    pub online: Option<bool>,
+    pub banned: Option<bool>,
+}
+
+/// A user as listed by `GET /admin/users`, with what admins and moderators need to know to look after them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserEntry {
+    #[serde(flatten)]
+    pub user: User,
+    pub banned: bool,
+    pub online: bool,
+    /// When the user last used the server, whether they show it to others or not. Left out for users who did not
+    /// since the server started keeping track.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_active: Option<DateTime<Utc>>,
+    /// When the account was made. Left out for accounts older than the server keeping track of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// When the name, role, ban or profile of the user last changed, or when the account was made if they never did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Utc>>,
+}
+
+/// A login of the user, as listed by `GET /auth/sessions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub created: DateTime<Utc>,
+    /// The login ends at this point, unless it is used before. Using it can not extend it past `expires_at`.
+    pub valid_until: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the login the sessions were asked for with.
+    pub current: bool,
+    /// The name of the admin acting as the user with this login. `None` for the logins of the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<String>,
+}
+
+/// A time span messages are counted in for a quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPeriod {
+    Minute,
+    Day,
+}
+
+impl QuotaPeriod {
+    pub fn duration(self) -> chrono::Duration {
+        match self {
+            QuotaPeriod::Minute => chrono::Duration::minutes(1),
+            QuotaPeriod::Day => chrono::Duration::days(1),
+        }
+    }
+}
+
+/// How much of a quota a user has used up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    pub limit: u32,
+    /// Messages sent within the last period.
+    pub used: u32,
+    /// When enough messages stop counting to send one again. Only set if the quota is used up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available_at: Option<DateTime<Utc>>,
+}
+
+/// The quotas of a user, as returned by `GET /auth/limits`. Quotas the server does not enforce are left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Limits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages_per_minute: Option<Quota>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages_per_day: Option<Quota>,
+}
+
+/// A message along with the messages sent right before and after it, as returned by `GET /message/<id>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageContext {
+    /// The messages sent before, oldest first.
+    pub before: Vec<Message>,
+    pub message: Message,
+    /// The messages sent after, oldest first.
+    pub after: Vec<Message>,
+}
+
+/// A message the user saved, as returned by `GET /saved`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMessage {
+    /// When the user saved it.
+    pub saved: DateTime<Utc>,
+    pub message: Message,
+}
+
+/// A conversation messages are sent in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conversation {
+    /// The main chat of the server, which every user reads.
+    #[default]
+    Chat,
+    /// The channel with that id.
+    Channel(i32),
+    /// The direct messages with the user with that id.
+    Direct(i32),
+}
+
+impl Conversation {
+    /// The conversation the message was sent in, as seen by the user with that id. For direct messages this is
+    /// the conversation with the other user.
+    pub fn of(message: &Message, viewer: i32) -> Self {
+        match (message.channelid, message.recipientid) {
+            (Some(channel), _) => Conversation::Channel(channel),
+            (None, Some(recipient)) if recipient == viewer => Conversation::Direct(message.userid),
+            (None, Some(recipient)) => Conversation::Direct(recipient),
+            (None, None) => Conversation::Chat,
+        }
+    }
+
+    /// The name of the conversation in paths like `/drafts/<conversation>`: `chat`, `channel-<id>` or
+    /// `direct-<userid>`.
+    pub fn key(self) -> String {
+        match self {
+            Conversation::Chat => "chat".to_string(),
+            Conversation::Channel(id) => format!("channel-{id}"),
+            Conversation::Direct(userid) => format!("direct-{userid}"),
+        }
+    }
+
+    /// The conversation with that name, as returned by `Conversation::key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        if key == "chat" {
+            return Some(Conversation::Chat);
+        }
+        if let Some(id) = key.strip_prefix("channel-") {
+            return id.parse().ok().map(Conversation::Channel);
+        }
+        key.strip_prefix("direct-")
+            .and_then(|id| id.parse().ok())
+            .map(Conversation::Direct)
+    }
+}
+
+/// Narrows down a search with `GET /messages/search`. Only messages matching every part that is given are found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    /// The conversation to search, instead of every conversation the user can read.
+    pub conversation: Option<Conversation>,
+    /// The id of the user who wrote the messages.
+    pub from: Option<i32>,
+    /// Only messages sent before this point.
+    pub before: Option<DateTime<Utc>>,
+    /// Only messages sent at this point or later.
+    pub after: Option<DateTime<Utc>>,
+    /// Only messages with files attached.
+    pub has_attachment: bool,
+}
+
+/// A search as users type it, with the words to search for told apart from the filters among them: `from:<user>`,
+/// `in:<channel>`, `before:<date>`, `after:<date>` and `has:attachment`. `GET /messages/search` reads these from
+/// `q`, so clients can pass on what users typed. Words that look like filters but are not, like `http://`, are
+/// searched for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// The words to search for, separated by spaces.
+    pub words: String,
+    /// The name of the user who wrote the messages.
+    pub from: Option<String>,
+    /// The name of the channel to search.
+    pub channel: Option<String>,
+    pub before: Option<DateTime<Utc>>,
+    pub after: Option<DateTime<Utc>>,
+    pub has_attachment: bool,
+}
+
+/// Why a search could not be read.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SearchSyntaxError {
+    #[error("{0} is not a date, write it like 2024-05-31")]
+    InvalidDate(String),
+    #[error("has:{0} is not known, only has:attachment is")]
+    UnknownHas(String),
+}
+
+impl SearchQuery {
+    /// Separates the filters of the search from its words. A filter given twice counts the last time.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a date can not be read or `has:` is not followed by `attachment`.
+    pub fn parse(query: &str) -> Result<Self, SearchSyntaxError> {
+        let mut parsed = SearchQuery::default();
+        let mut words = Vec::new();
+        for word in query.split_whitespace() {
+            match word.split_once(':') {
+                Some(("from", name)) if !name.is_empty() => parsed.from = Some(name.to_string()),
+                Some(("in", channel)) if !channel.is_empty() => {
+                    parsed.channel = Some(channel.to_string())
+                }
+                Some(("before", date)) if !date.is_empty() => {
+                    parsed.before = Some(
+                        search_date(date, false)
+                            .ok_or_else(|| SearchSyntaxError::InvalidDate(date.to_string()))?,
+                    );
+                }
+                Some(("after", date)) if !date.is_empty() => {
+                    parsed.after = Some(
+                        search_date(date, true)
+                            .ok_or_else(|| SearchSyntaxError::InvalidDate(date.to_string()))?,
+                    );
+                }
+                Some(("has", "attachment" | "attachments" | "file")) => {
+                    parsed.has_attachment = true
+                }
+                Some(("has", other)) if !other.is_empty() => {
+                    return Err(SearchSyntaxError::UnknownHas(other.to_string()))
+                }
+                _ => words.push(word),
+            }
+        }
+        parsed.words = words.join(" ");
+        Ok(parsed)
+    }
+}
+
+/// Returns true if the text mentions the user with `@` followed by their name. The name has to end there, so
+/// `@bobby` does not mention `bob`.
+pub fn mentions(text: &str, username: &str) -> bool {
+    let mention = format!("@{username}");
+    text.match_indices(&mention).any(|(index, _)| {
+        !matches!(
+            text[index + mention.len()..].chars().next(),
+            Some(c) if c.is_alphanumeric() || c == '_' || c == '-'
+        )
+    })
+}
+
+/// Reads a date of a search, either an RFC 3339 time or a day like `2024-05-31`. Days are UTC and, so `after:` does
+/// not find messages of the day itself, count from the start of the following day if `after` is set.
+pub fn search_date(date: &str, after: bool) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let day = if after {
+        day.checked_add_days(Days::new(1))?
+    } else {
+        day
+    };
+    Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// A message the user started writing but did not send yet, as returned by `GET /drafts`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    pub conversation: Conversation,
+    pub text: String,
+    /// When the draft was last changed, on any device.
+    pub updated: DateTime<Utc>,
+}
+
+/// A service that delivers push notifications to phones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PushProvider {
+    /// An ntfy topic, with the url of the topic like `https://ntfy.sh/mytopic`.
+    Ntfy,
+    /// A Gotify server, with the url of the server and the token of an application on it.
+    Gotify,
+}
+
+impl PushProvider {
+    /// The name of the provider as it is stored.
+    pub fn key(self) -> &'static str {
+        match self {
+            PushProvider::Ntfy => "ntfy",
+            PushProvider::Gotify => "gotify",
+        }
+    }
+
+    /// The provider with that name, as returned by `PushProvider::key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "ntfy" => Some(PushProvider::Ntfy),
+            "gotify" => Some(PushProvider::Gotify),
+            _ => None,
+        }
+    }
+}
+
+/// Where the server pushes notifications for the user to, so they are told about new messages while no client is
+/// running. Sent to `PUT /push` and returned by `GET /push`. As clients read it too, fields it does not know are
+/// ignored in both directions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushSettings {
+    pub provider: PushProvider,
+    pub url: String,
+    /// The access token for ntfy topics that need one, or the application token for Gotify.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Whether to push messages that mention the user with `@` and their name.
+    pub mentions: bool,
+    /// Whether to push direct messages to the user.
+    pub direct_messages: bool,
+}
+
+/// The body of `PATCH /profile`. Only the fields that are given change, and `null` removes the display name or the
+/// avatar. An empty display name removes it too.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileUpdate {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    pub displayname: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    pub avatar: Option<Option<String>>,
+    /// Whether others are shown when the user last used the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_last_seen: Option<bool>,
+}
+
+impl ProfileUpdate {
+    /// The most characters a display name may have.
+    pub const MAX_DISPLAY_NAME: usize = 64;
+    /// The most characters a bio may have.
+    pub const MAX_BIO: usize = 1000;
+
+    /// Whether the update changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self == &ProfileUpdate::default()
+    }
+}
+
+/// Reads a field that is given, even as `null`, as `Some`, so missing fields can be told apart from `null` ones.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// The body of `POST /channels`, which creates a channel and joins it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateChannel {
+    pub name: String,
+    #[serde(default)]
+    pub topic: String,
+}
+
+/// The body of `PUT /channels/<id>/slowmode`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlowModeRequest {
+    /// How long members have to wait between their messages. 0 turns slow mode off.
+    pub seconds: u32,
+}
+
+/// The body of `PUT /channels/<id>/topic`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TopicRequest {
+    pub topic: String,
+}
+
+/// The body of `PUT /motd`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MotdRequest {
+    /// The new message of the day. Empty removes it.
+    pub text: String,
+}
+
+/// How a member of a channel is told about new messages in it. Push notifications only ever come for mentions in
+/// channels, so those are only stopped by muting the channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationLevel {
+    /// Every new message.
+    #[default]
+    All,
+    /// Only messages mentioning the user.
+    Mentions,
+    /// None at all.
+    Muted,
+}
+
+impl NotificationLevel {
+    /// The name of the level as it is stored.
+    pub fn key(self) -> &'static str {
+        match self {
+            NotificationLevel::All => "all",
+            NotificationLevel::Mentions => "mentions",
+            NotificationLevel::Muted => "muted",
+        }
+    }
+
+    /// The level with that name, as returned by `NotificationLevel::key`.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "all" => Some(NotificationLevel::All),
+            "mentions" => Some(NotificationLevel::Mentions),
+            "muted" => Some(NotificationLevel::Muted),
+            _ => None,
+        }
+    }
+}
+
+/// A channel the user gets fewer notifications for than every message. Listed by `GET /channels/notifications`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelNotifications {
+    pub channelid: i32,
+    pub level: NotificationLevel,
+}
+
+/// The body of `PUT /channels/<id>/notifications`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationRequest {
+    pub level: NotificationLevel,
+}
+
+/// What the user missed in a conversation since they last read it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationOverview {
+    pub conversation: Conversation,
+    /// The id of the newest message the user read, if they read any yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_read: Option<i32>,
+    /// How many messages other users sent after it.
+    pub unread: u32,
+    /// Whether any of the unread messages mention the user with `@` and their name.
+    pub mentioned: bool,
+}
+
+/// The state of a page of the conversations of the user, as returned by `GET /sync/overview`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Overview {
+    pub conversations: Vec<ConversationOverview>,
+    /// The cursor to ask for the following conversations with, or `None` if these are the last ones. Left out by
+    /// servers from before the overview was paged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+/// Everything a client needs to show a session, as returned by `GET /sync/initial`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InitialSync {
+    /// The logged in user.
+    pub user: User,
+    /// The first page of the conversations, as `GET /sync/overview` returns it.
+    pub conversations: Vec<ConversationSync>,
+    /// The cursor to ask `GET /sync/overview` for the following conversations with, or `None` if these are all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    /// The users who wrote the included messages, so their senders can be named. Servers from before the overview
+    /// was paged send all users.
+    pub users: Vec<User>,
+    pub limits: Limits,
+    /// The drafts of the user, empty on servers without `Feature::Drafts`.
+    #[serde(default)]
+    pub drafts: Vec<Draft>,
+}
+
+/// A conversation with its latest messages, newest first like `POST /messages` returns them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSync {
+    #[serde(flatten)]
+    pub overview: ConversationOverview,
+    pub messages: Vec<Message>,
+}
+
+/// Marks the messages of a conversation as read, up to and including the one with that id. Sent to `POST /read`, or
+/// `PUT /sync/read` on servers from before read receipts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadMarker {
+    #[serde(default)]
+    pub conversation: Conversation,
+    pub message_id: i32,
+}
+
+/// How far a user read a conversation, as listed by `GET /read`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadReceipt {
+    pub userid: i32,
+    /// The newest message the user read.
+    pub message_id: i32,
+}
+
+/// Where a message is forwarded to. Forwarding to a channel needs the user to be a member of it, like sending to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardTarget {
+    /// The main chat of the server.
+    #[default]
+    Chat,
+    /// The channel with that id.
+    Channel(i32),
+    /// The direct messages with the user with that id.
+    Direct(i32),
+}
+
+impl From<ForwardTarget> for Conversation {
+    fn from(target: ForwardTarget) -> Self {
+        match target {
+            ForwardTarget::Chat => Self::Chat,
+            ForwardTarget::Channel(channel) => Self::Channel(channel),
+            ForwardTarget::Direct(userid) => Self::Direct(userid),
+        }
+    }
+}
+
+/// The body of `POST /message/forward`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForwardRequest {
+    pub message_id: i32,
+    #[serde(default)]
+    pub target: ForwardTarget,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Describes the server, so clients can check they found the right one before logging in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+    /// A sentence or two about the server, if the operator wrote one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// A few lines of text art to show with the name, for terminal clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    /// The color to highlight the server with, like `#3b82f6`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<String>,
+    /// The version of the terms of service users have to accept to register, if the server has any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms_version: Option<String>,
+    /// Whether temporary guest accounts can be created.
+    #[serde(default)]
+    pub guests: bool,
+    /// Whether registering needs an invite code, which admins make with `POST /admin/invites`.
+    #[serde(default)]
+    pub invite_only: bool,
+    /// The oldest client version that works with the server, if the server requires one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_client_version: Option<String>,
+    /// Which features the server offers, keyed by `Feature::key`. `None` for servers from before features were
+    /// announced. Keys the client does not know are features newer than it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<BTreeMap<String, bool>>,
+    /// The version of the protocol the server speaks, as in `PROTOCOL_VERSION`. 0 for servers from before it was
+    /// told.
+    #[serde(default)]
+    pub protocol_version: u32,
+}
+
+impl ServerInfo {
+    /// Returns whether the server offers the feature. Servers that do not announce their features are assumed to
+    /// offer the ones that existed before features were announced.
+    pub fn supports(&self, feature: Feature) -> bool {
+        match &self.features {
+            Some(features) => features.get(feature.key()).copied().unwrap_or(false),
+            None => !feature.announced_only(),
+        }
+    }
+
+    /// Returns the red, green and blue parts of the accent color, if the server has one written like `#3b82f6`.
+    pub fn accent_rgb(&self) -> Option<(u8, u8, u8)> {
+        let hex = self.accent_color.as_deref()?.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let part = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+        Some((part(0..2)?, part(2..4)?, part(4..6)?))
+    }
+}
+
+/// Something a server may or may not offer, so clients only show what works with the server they are connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// `GET /saved`, `PUT /saved/<id>` and `DELETE /saved/<id>`.
+    SavedMessages,
+    /// `POST /message/forward`.
+    Forwarding,
+    /// Fetching the messages around a message with `GET /message/<id>`, which permalinks point to.
+    MessageContext,
+    /// `GET /sync/initial`.
+    InitialSync,
+    /// `POST /messages/stream`.
+    MessageStream,
+    /// `PUT /sync/read` and `GET /sync/overview`.
+    ReadMarkers,
+    Reactions,
+    /// `POST /attachments/<conversation>`, `GET /attachments/<id>` and `GET /message/<id>/attachments`.
+    Attachments,
+    /// `GET /channels`, `POST /channels` and the endpoints under `/channels/<id>`.
+    Channels,
+    /// The endpoints under `/direct/<userid>`.
+    DirectMessages,
+    /// End-to-end encrypted messages.
+    E2ee,
+    /// `GET /users` and `GET /saved` returning a `Page` instead of every item at once.
+    Pagination,
+    /// `POST /message` taking an `Idempotency-Key`, so sending can be retried.
+    IdempotencyKeys,
+    /// `MessageFilter::Latest` and the `Server-Time` header, so clients do not depend on their own clocks.
+    ServerClock,
+    /// `GET /drafts`, `PUT /drafts/<conversation>` and `DELETE /drafts/<conversation>`.
+    Drafts,
+    /// `GET /push`, `PUT /push` and `DELETE /push`.
+    PushNotifications,
+    /// `GET /messages/search`.
+    Search,
+    /// `DELETE /message/<id>`, the endpoints under `/admin/users/<id>` and the roles of users.
+    Moderation,
+    /// `GET /profile` and `PATCH /profile`, and display names in place of usernames.
+    Profiles,
+    /// `GET /message/<id>/context?around=`, which returns as many messages around the message as asked for.
+    ContextAround,
+    /// `GET /presence` and the presence changes on the event stream.
+    Presence,
+    /// `GET /auth/sessions`.
+    Sessions,
+    /// `POST /admin/impersonate/<user>` and `GET /admin/audit`.
+    Impersonation,
+    /// `GET /admin/addresses`, `POST /admin/addresses` and `DELETE /admin/addresses/<id>`.
+    AddressBans,
+    /// `PUT /channels/<id>/slowmode` and the `slow_mode` error.
+    SlowMode,
+    /// `POST /read`, `GET /read` and the `messages_read` system event, with read markers for every conversation.
+    ReadReceipts,
+    /// `GET /channels/notifications` and `PUT /channels/<id>/notifications`, to mute channels or only be told about mentions in them.
+    ChannelNotifications,
+    /// `GET /blocks`, `PUT /blocks/<userid>` and `DELETE /blocks/<userid>`, with the messages of blocked users left out for those who blocked them.
+    Blocking,
+    /// `GET /admin/users` lists the users sorted and filtered by role, presence and bans.
+    UserDirectory,
+    /// `POST /admin/users/<id>/kick` and `DELETE /admin/users/<id>/ban`, to log users out everywhere and lift bans, and the `account_banned` error.
+    Kicking,
+    /// Making invite codes, which servers that only accept invited users need to register.
+    Invites,
+    /// Bot accounts, which send an API key with each request instead of logging in.
+    Bots,
+    /// Posting new messages to urls registered by admins.
+    Webhooks,
+    /// `PUT /channels/<id>/topic` and the message of the day under `/motd`.
+    Topics,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 34] = [
+        Feature::SavedMessages,
+        Feature::Forwarding,
+        Feature::MessageContext,
+        Feature::InitialSync,
+        Feature::MessageStream,
+        Feature::ReadMarkers,
+        Feature::Reactions,
+        Feature::Attachments,
+        Feature::Channels,
+        Feature::DirectMessages,
+        Feature::E2ee,
+        Feature::Pagination,
+        Feature::IdempotencyKeys,
+        Feature::ServerClock,
+        Feature::Drafts,
+        Feature::PushNotifications,
+        Feature::Search,
+        Feature::Moderation,
+        Feature::Profiles,
+        Feature::ContextAround,
+        Feature::Presence,
+        Feature::Sessions,
+        Feature::Impersonation,
+        Feature::AddressBans,
+        Feature::SlowMode,
+        Feature::ReadReceipts,
+        Feature::ChannelNotifications,
+        Feature::Blocking,
+        Feature::UserDirectory,
+        Feature::Kicking,
+        Feature::Invites,
+        Feature::Bots,
+        Feature::Webhooks,
+        Feature::Topics,
+    ];
+
+    /// The name of the feature in `ServerInfo::features`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Feature::SavedMessages => "saved_messages",
+            Feature::Forwarding => "forwarding",
+            Feature::MessageContext => "message_context",
+            Feature::InitialSync => "initial_sync",
+            Feature::MessageStream => "message_stream",
+            Feature::ReadMarkers => "read_markers",
+            Feature::Reactions => "reactions",
+            Feature::Attachments => "attachments",
+            Feature::Channels => "channels",
+            Feature::DirectMessages => "direct_messages",
+            Feature::E2ee => "e2ee",
+            Feature::Pagination => "pagination",
+            Feature::IdempotencyKeys => "idempotency_keys",
+            Feature::ServerClock => "server_clock",
+            Feature::Drafts => "drafts",
+            Feature::PushNotifications => "push_notifications",
+            Feature::Search => "search",
+            Feature::Moderation => "moderation",
+            Feature::Profiles => "profiles",
+            Feature::ContextAround => "context_around",
+            Feature::Presence => "presence",
+            Feature::Sessions => "sessions",
+            Feature::Impersonation => "impersonation",
+            Feature::AddressBans => "address_bans",
+            Feature::SlowMode => "slow_mode",
+            Feature::ReadReceipts => "read_receipts",
+            Feature::ChannelNotifications => "channel_notifications",
+            Feature::Blocking => "blocking",
+            Feature::UserDirectory => "user_directory",
+            Feature::Kicking => "kicking",
+            Feature::Invites => "invites",
+            Feature::Bots => "bots",
+            Feature::Webhooks => "webhooks",
+            Feature::Topics => "topics",
+        }
+    }
+
+    /// Whether the feature is newer than announcing features, so servers that do not announce them lack it.
+    fn announced_only(self) -> bool {
+        matches!(
+            self,
+            Feature::Reactions
+                | Feature::Attachments
+                | Feature::Channels
+                | Feature::DirectMessages
+                | Feature::E2ee
+                | Feature::Pagination
+                | Feature::IdempotencyKeys
+                | Feature::ServerClock
+                | Feature::Drafts
+                | Feature::PushNotifications
+                | Feature::Search
+                | Feature::Moderation
+                | Feature::Profiles
+                | Feature::ContextAround
+                | Feature::Presence
+                | Feature::Sessions
+                | Feature::Impersonation
+                | Feature::AddressBans
+                | Feature::SlowMode
+                | Feature::ReadReceipts
+                | Feature::ChannelNotifications
+                | Feature::Blocking
+                | Feature::UserDirectory
+                | Feature::Kicking
+                | Feature::Invites
+                | Feature::Bots
+                | Feature::Webhooks
+                | Feature::Topics
+        )
+    }
+}
+
+/// The terms of service of a server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Terms {
+    pub version: String,
+    pub text: String,
+}
+
+/// The body returned by the server whenever a request fails.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_details(mut self, details: &str) -> Self {
+        self.details = Some(details.to_string());
+        self
+    }
+}
+
+/// Stable identifiers for the errors the server can return. Clients should match on these instead of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request body or headers could not be understood.
+    MalformedRequest,
+    /// The request is missing a valid login token.
+    NotAuthorized,
+    /// The username and password combination is not valid.
+    LoginFailed,
+    /// Registering needs an invite code, and the one given is not valid, has expired or was used already.
+    InviteInvalid,
+    /// The password is right, but a moderator banned the account.
+    AccountBanned,
+    /// The refresh token is not valid or has expired.
+    RefreshTokenInvalid,
+    /// The chosen username is already in use.
+    UsernameTaken,
+    /// A channel with the chosen name already exists.
+    ChannelNameTaken,
+    /// Only members of the channel can read and write in it.
+    NotAMember,
+    /// Registering needs the current version of the terms of service to be accepted.
+    TermsNotAccepted,
+    /// The server does not allow guest accounts.
+    GuestsDisabled,
+    /// Only guest accounts can be claimed.
+    NotAGuest,
+    /// Too many requests were sent in a short time.
+    RateLimited,
+    /// The user sent as many messages as their quota allows. The details hold when sending is possible again.
+    QuotaExceeded,
+    /// The channel is in slow mode and the user sent a message to it too recently. The details hold how many seconds
+    /// are left to wait.
+    SlowMode,
+    /// The user sent the same text to the conversation too often in a short time.
+    DuplicateMessage,
+    /// The requested resource does not exist.
+    NotFound,
+    /// An uploaded file is larger than the server accepts. The details hold the largest size accepted, in bytes.
+    FileTooLarge,
+    /// The server does not accept files of the type an uploaded file was recognized as. The details hold the type.
+    FileTypeNotAllowed,
+    /// The role of the user does not allow what was requested.
+    PermissionDenied,
+    /// The server does not accept requests, registrations or logins from the address of the client.
+    AddressBlocked,
+    /// The resource changed since the version the request gave in `If-Match`. The details hold the current version.
+    VersionConflict,
+    /// The server encountered an error whilst handling the request.
+    InternalError,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde::de::DeserializeOwned;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Serializes the value, checks it is sent as expected and that reading it back gives the same json.
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T, expected: Value) -> T {
+        let json = serde_json::to_value(value).unwrap();
+        assert_eq!(json, expected);
+        let read: T = serde_json::from_value(json).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), expected);
+        read
+    }
+
+    fn date() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap()
+    }
+
+    fn message() -> Message {
+        Message {
+            id: 7,
+            date: date(),
+            messagetext: "hello".into(),
+            userid: 2,
+            forwardedid: None,
+            forwardeduserid: None,
+            seq: 3,
+            channelid: Some(4),
+            recipientid: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn message_leaves_out_missing_optional_fields() {
+        let expected = json!({
+            "id": 7,
+            "date": "2024-05-01T12:30:00Z",
+            "messagetext": "hello",
+            "userid": 2,
+            "seq": 3,
+            "channelid": 4,
+        });
+        round_trip(&message(), expected);
+    }
+
+    #[test]
+    fn message_from_server_without_sequence_numbers() {
+        let json =
+            json!({"id": 1, "date": "2024-05-01T12:30:00Z", "messagetext": "hi", "userid": 2});
+        let message: Message = serde_json::from_value(json).unwrap();
+        assert_eq!(message.seq, 0);
+        assert_eq!(message.channelid, None);
+        assert!(message.attachments.is_empty());
+    }
+
+    #[test]
+    fn message_filter() {
+        round_trip(&MessageFilter::BeforeSeq(5), json!({"BeforeSeq": 5}));
+        round_trip(&MessageFilter::Latest(20), json!({"Latest": 20}));
+        round_trip(
+            &MessageFilter::After(date()),
+            json!({"After": "2024-05-01T12:30:00Z"}),
+        );
+    }
+
+    #[test]
+    fn conversation() {
+        round_trip(&Conversation::Chat, json!("chat"));
+        round_trip(&Conversation::Channel(3), json!({"channel": 3}));
+        round_trip(&Conversation::Direct(4), json!({"direct": 4}));
+    }
+
+    #[test]
+    fn credentials() {
+        let credentials = Credentials {
+            username: "alice".into(),
+            password: "secret".into(),
+            remember_me: true,
+            accepted_tos_version: Some("2".into()),
+            invite_code: None,
+        };
+        let expected = json!({
+            "username": "alice",
+            "password": "secret",
+            "remember_me": true,
+            "accepted_tos_version": "2",
+        });
+        round_trip(&credentials, expected);
+
+        let minimal: Credentials =
+            serde_json::from_value(json!({"username": "alice", "password": "secret"})).unwrap();
+        assert!(!minimal.remember_me);
+        assert_eq!(minimal.accepted_tos_version, None);
+        assert_eq!(minimal.invite_code, None);
+    }
+
+    #[test]
+    fn requests_refuse_unknown_fields() {
+        let credentials = json!({"username": "alice", "password": "secret", "remember": true});
+        assert!(serde_json::from_value::<Credentials>(credentials).is_err());
+        let marker = json!({"message_id": 3, "messageid": 3});
+        assert!(serde_json::from_value::<ReadMarker>(marker).is_err());
+        let profile = json!({"display_name": "Alice"});
+        assert!(serde_json::from_value::<ProfileUpdate>(profile).is_err());
+    }
+
+    #[test]
+    fn responses_ignore_unknown_fields() {
+        let json = json!({"token": "abc", "userid": 1, "expires": "never"});
+        let login: LoginResult = serde_json::from_value(json).unwrap();
+        assert_eq!(login.refresh_token, None);
+        let json = json!({"provider": "ntfy", "url": "https://ntfy.sh/x", "mentions": true, "direct_messages": false, "priority": 3});
+        assert!(serde_json::from_value::<PushSettings>(json).is_ok());
+    }
+
+    #[test]
+    fn login_result() {
+        let login = LoginResult {
+            token: "abc".into(),
+            refresh_token: None,
+            userid: 1,
+        };
+        round_trip(&login, json!({"token": "abc", "userid": 1}));
+    }
+
+    #[test]
+    fn read_marker_defaults_to_the_chat() {
+        let marker: ReadMarker = serde_json::from_value(json!({"message_id": 3})).unwrap();
+        assert_eq!(marker.conversation, Conversation::Chat);
+        let marker = ReadMarker {
+            conversation: Conversation::Direct(2),
+            message_id: 3,
+        };
+        round_trip(
+            &marker,
+            json!({"conversation": {"direct": 2}, "message_id": 3}),
+        );
+    }
+
+    #[test]
+    fn forward_request_defaults_to_the_chat() {
+        let request: ForwardRequest = serde_json::from_value(json!({"message_id": 3})).unwrap();
+        assert_eq!(request.target, ForwardTarget::Chat);
+        round_trip(&request, json!({"message_id": 3, "target": "chat"}));
+        let request = ForwardRequest {
+            message_id: 3,
+            target: ForwardTarget::Channel(5),
+        };
+        round_trip(&request, json!({"message_id": 3, "target": {"channel": 5}}));
+        assert_eq!(Conversation::from(request.target), Conversation::Channel(5));
+    }
+
+    #[test]
+    fn profile_update_tells_null_from_missing() {
+        let update: ProfileUpdate = serde_json::from_value(json!({"displayname": null})).unwrap();
+        assert_eq!(update.displayname, Some(None));
+        assert_eq!(update.avatar, None);
+        let update: ProfileUpdate = serde_json::from_value(json!({})).unwrap();
+        assert!(update.is_empty());
+        let update = ProfileUpdate {
+            displayname: Some(Some("Alice".into())),
+            show_last_seen: Some(false),
+            ..ProfileUpdate::default()
+        };
+        round_trip(
+            &update,
+            json!({"displayname": "Alice", "show_last_seen": false}),
+        );
+    }
+
+    #[test]
+    fn usernames_of_missing_users_are_null() {
+        let usernames: Usernames = HashMap::from([(1, Some("alice".into())), (2, None)]);
+        round_trip(&usernames, json!({"1": "alice", "2": null}));
+    }
+
+    #[test]
+    fn system_events_are_tagged_by_type() {
+        let event = SystemEvent {
+            date: date(),
+            kind: SystemEventKind::UserRenamed {
+                userid: 1,
+                old_name: "alice".into(),
+                new_name: "alicia".into(),
+            },
+        };
+        let expected = json!({
+            "date": "2024-05-01T12:30:00Z",
+            "kind": {"type": "user_renamed", "userid": 1, "old_name": "alice", "new_name": "alicia"},
+        });
+        round_trip(&event, expected);
+
+        let kind = SystemEventKind::MessagesRead {
+            userid: 1,
+            conversation: Conversation::Channel(3),
+            message_id: 9,
+        };
+        let expected = json!({"type": "messages_read", "userid": 1, "conversation": {"channel": 3}, "message_id": 9});
+        assert_eq!(round_trip(&kind, expected), kind);
+
+        let kind = SystemEventKind::DisplayNameChanged {
+            userid: 1,
+            username: "alice".into(),
+            displayname: None,
+        };
+        let expected = json!({"type": "display_name_changed", "userid": 1, "username": "alice", "displayname": null});
+        assert_eq!(round_trip(&kind, expected), kind);
+
+        let kind = SystemEventKind::TopicChanged {
+            channelid: Some(2),
+            topic: "News".into(),
+        };
+        let expected = json!({"type": "topic_changed", "channelid": 2, "topic": "News"});
+        assert_eq!(round_trip(&kind, expected), kind);
+
+        // Servers from before topics could be edited name no channel
+        let kind: SystemEventKind =
+            serde_json::from_value(json!({"type": "topic_changed", "topic": "News"})).unwrap();
+        assert_eq!(
+            kind,
+            SystemEventKind::TopicChanged {
+                channelid: None,
+                topic: "News".into()
+            }
+        );
+    }
+
+    #[test]
+    fn conversation_sync_flattens_the_overview() {
+        let sync = ConversationSync {
+            overview: ConversationOverview {
+                conversation: Conversation::Channel(4),
+                last_read: None,
+                unread: 2,
+                mentioned: false,
+            },
+            messages: vec![message()],
+        };
+        let expected = json!({
+            "conversation": {"channel": 4},
+            "unread": 2,
+            "mentioned": false,
+            "messages": [serde_json::to_value(message()).unwrap()],
+        });
+        round_trip(&sync, expected);
+    }
+
+    #[test]
+    fn server_info_from_old_servers() {
+        let info: ServerInfo =
+            serde_json::from_value(json!({"name": "chat", "version": "0.1.0"})).unwrap();
+        assert_eq!(info.protocol_version, 0);
+        assert!(!info.guests);
+        assert!(info.features.is_none());
+        assert!(info.supports(Feature::SavedMessages));
+        assert!(!info.supports(Feature::Webhooks));
+    }
+
+    #[test]
+    fn server_info() {
+        let info = ServerInfo {
+            name: "chat".into(),
+            version: "0.1.1".into(),
+            description: None,
+            banner: None,
+            accent_color: Some("#3b82f6".into()),
+            terms_version: None,
+            guests: true,
+            invite_only: false,
+            min_client_version: None,
+            features: Some(BTreeMap::from([
+                ("webhooks".into(), true),
+                ("e2ee".into(), false),
+            ])),
+            protocol_version: PROTOCOL_VERSION,
+        };
+        let expected = json!({
+            "name": "chat",
+            "version": "0.1.1",
+            "accent_color": "#3b82f6",
+            "guests": true,
+            "invite_only": false,
+            "features": {"e2ee": false, "webhooks": true},
+            "protocol_version": PROTOCOL_VERSION,
+        });
+        let read = round_trip(&info, expected);
+        assert!(read.supports(Feature::Webhooks));
+        assert!(!read.supports(Feature::E2ee));
+        assert!(!read.supports(Feature::Bots));
+    }
+
+    #[test]
+    fn api_error() {
+        let error = ApiError::new(ErrorCode::SlowMode, "Slow down.").with_details("12");
+        let expected = json!({"code": "slow_mode", "message": "Slow down.", "details": "12"});
+        round_trip(&error, expected);
+        let error = ApiError::new(ErrorCode::NotFound, "Gone.");
+        round_trip(&error, json!({"code": "not_found", "message": "Gone."}));
+    }
+
+    #[test]
+    fn error_codes_are_snake_case() {
+        round_trip(&ErrorCode::MalformedRequest, json!("malformed_request"));
+        round_trip(
+            &ErrorCode::RefreshTokenInvalid,
+            json!("refresh_token_invalid"),
+        );
+        round_trip(&ErrorCode::AddressBlocked, json!("address_blocked"));
+    }
+
+    #[test]
+    fn page_leaves_out_the_last_cursor() {
+        let page = Page {
+            items: vec![1, 2],
+            next: None,
+        };
+        round_trip(&page, json!({"items": [1, 2]}));
+        let page = Page {
+            items: vec![3],
+            next: Some("2".into()),
+        };
+        round_trip(&page, json!({"items": [3], "next": "2"}));
+    }
+
+    #[test]
+    fn issued_api_key_flattens_the_key_info() {
+        let issued = IssuedApiKey {
+            info: ApiKey {
+                id: 1,
+                userid: 5,
+                name: String::new(),
+                created: date(),
+            },
+            key: "secret".into(),
+        };
+        let expected =
+            json!({"id": 1, "userid": 5, "created": "2024-05-01T12:30:00Z", "key": "secret"});
+        round_trip(&issued, expected);
+    }
+
+    #[test]
+    fn webhook_payload_is_tagged_by_event() {
+        let payload = WebhookPayload::MessageCreated {
+            message: message(),
+            username: "alice".into(),
+        };
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event"], "message_created");
+        assert_eq!(json["username"], "alice");
+        assert_eq!(json["message"]["id"], 7);
+        round_trip(&payload, json);
+    }
+
+    #[test]
+    fn push_settings() {
+        let settings = PushSettings {
+            provider: PushProvider::Gotify,
+            url: "https://push.example.com".into(),
+            token: None,
+            mentions: true,
+            direct_messages: false,
+        };
+        let expected = json!({
+            "provider": "gotify",
+            "url": "https://push.example.com",
+            "mentions": true,
+            "direct_messages": false,
+        });
+        assert_eq!(round_trip(&settings, expected), settings);
+    }
+
+    #[test]
+    fn search_query_filters() {
+        let query = SearchQuery::parse("release from:alice notes in:general").unwrap();
+        let expected = SearchQuery {
+            words: "release notes".into(),
+            from: Some("alice".into()),
+            channel: Some("general".into()),
+            ..SearchQuery::default()
+        };
+        assert_eq!(query, expected);
+
+        // The last of a filter given twice counts
+        let query = SearchQuery::parse("from:alice from:bob").unwrap();
+        assert_eq!(query.from.as_deref(), Some("bob"));
+        assert_eq!(query.words, "");
+
+        // Filters without a value and words that only look like filters are searched for
+        let query = SearchQuery::parse("from: in: https://example.com").unwrap();
+        assert_eq!(query.words, "from: in: https://example.com");
+        assert_eq!((query.from, query.channel), (None, None));
+    }
+
+    #[test]
+    fn search_query_dates_and_attachments() {
+        let query =
+            SearchQuery::parse("after:2024-05-01 before:2024-05-31T12:00:00Z has:file").unwrap();
+        assert_eq!(
+            query.after,
+            Some(Utc.with_ymd_and_hms(2024, 5, 2, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            query.before,
+            Some(Utc.with_ymd_and_hms(2024, 5, 31, 12, 0, 0).unwrap())
+        );
+        assert!(query.has_attachment);
+        assert_eq!(query.words, "");
+
+        assert_eq!(
+            SearchQuery::parse("before:yesterday"),
+            Err(SearchSyntaxError::InvalidDate("yesterday".into()))
+        );
+        assert_eq!(
+            SearchQuery::parse("has:link"),
+            Err(SearchSyntaxError::UnknownHas("link".into()))
+        );
+    }
+
+    #[test]
+    fn search_query_quoted_phrases() {
+        // Quotes are kept with the words, and a filter in quotes is a word
+        let query = SearchQuery::parse("\"release notes\" from:alice").unwrap();
+        assert_eq!(query.words, "\"release notes\"");
+        assert_eq!(query.from.as_deref(), Some("alice"));
+
+        let query = SearchQuery::parse("\"from:alice\"").unwrap();
+        assert_eq!(query.words, "\"from:alice\"");
+        assert_eq!(query.from, None);
+    }
+
+    #[test]
+    fn empty_search_query() {
+        assert_eq!(SearchQuery::parse(""), Ok(SearchQuery::default()));
+        assert_eq!(SearchQuery::parse(" \t\n "), Ok(SearchQuery::default()));
+        // Spacing between words is not kept
+        assert_eq!(
+            SearchQuery::parse("  hello \t world ").unwrap().words,
+            "hello world"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Measures how much bandwidth compressing a page of 1000 messages saves and how long it takes, for both json and
+//! `MessagePack`.
+//!
+//! Run with ``cargo bench --bench compression``.
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use chat_core::models::Message;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+
+const MESSAGE_COUNT: i32 = 1000;
+const ITERATIONS: u32 = 50;
+
+fn main() {
+    let messages = sample_page();
+    let json = serde_json::to_vec(&messages).expect("messages can be serialized");
+    let msgpack = rmp_serde::to_vec_named(&messages).expect("messages can be serialized");
+
+    println!("Page of {MESSAGE_COUNT} messages, averaged over {ITERATIONS} runs, ratios relative to plain json\n");
+    println!(
+        "{:<10}{:<10}{:>12}{:>10}{:>12}",
+        "format", "encoding", "bytes", "ratio", "time"
+    );
+    for (format, page) in [("json", &json), ("msgpack", &msgpack)] {
+        report(format, "none", page, json.len(), <[u8]>::to_vec);
+        report(format, "gzip", page, json.len(), gzip);
+        report(format, "br", page, json.len(), brotli);
+        report(format, "zstd", page, json.len(), zstd);
+    }
+}
+
+/// Builds a page of messages resembling a normal conversation.
+fn sample_page() -> Vec<Message> {
+    let words = [
+        "hey",
+        "did",
+        "you",
+        "see",
+        "the",
+        "new",
+        "release",
+        "yesterday",
+        "I",
+        "think",
+        "it",
+        "fixed",
+        "the",
+        "login",
+        "issue",
+        "we",
+        "had",
+        "lol",
+        "yeah",
+        "works",
+        "for",
+        "me",
+        "now",
+    ];
+    let start: DateTime<Utc> = "2023-04-01T12:00:00Z".parse().expect("date is valid");
+
+    (0..MESSAGE_COUNT)
+        .map(|id| {
+            let length = 3 + (id * 7 % 12) as usize;
+            let messagetext = (0..length)
+                .map(|i| words[(id as usize * 13 + i * 5) % words.len()])
+                .collect::<Vec<_>>()
+                .join(" ");
+            Message {
+                id,
+                date: start + chrono::Duration::seconds(i64::from(id) * 37),
+                messagetext,
+                userid: id % 8 + 1,
+                forwardedid: None,
+                forwardeduserid: None,
+                seq: i64::from(id) + 1,
+                channelid: None,
+                recipientid: None,
+                attachments: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+fn report(
+    format: &str,
+    encoding: &str,
+    page: &[u8],
+    baseline: usize,
+    compress: fn(&[u8]) -> Vec<u8>,
+) {
+    let mut total = Duration::ZERO;
+    let mut size = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        size = compress(page).len();
+        total += start.elapsed();
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let ratio = size as f64 / baseline as f64 * 100.0;
+    println!(
+        "{format:<10}{encoding:<10}{size:>12}{ratio:>9.1}%{:>12?}",
+        total / ITERATIONS
+    );
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .expect("writing to a vec can not fail");
+    encoder.finish().expect("writing to a vec can not fail")
+}
+
+fn brotli(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+        encoder
+            .write_all(data)
+            .expect("writing to a vec can not fail");
+    }
+    output
+}
+
+fn zstd(data: &[u8]) -> Vec<u8> {
+    zstd::encode_all(data, 0).expect("reading from a slice can not fail")
+}
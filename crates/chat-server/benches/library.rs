@@ -0,0 +1,127 @@
+//! Measures the core library operations on seeded databases, so changes meant to speed them up can be checked and
+//! regressions noticed.
+//!
+//! Run with ``cargo bench --bench library``. The database is filled with fixtures and holds 10000 messages by
+//! default. Other sizes can be given in ``CHAT_BENCH_MESSAGES``, like
+//! ``CHAT_BENCH_MESSAGES=10000,1000000 cargo bench --bench library``.
+use std::path::Path;
+
+use chat_core::models::User;
+use chat_core::protocol::MessageFilter;
+use chat_server::config::Config;
+use chat_server::fixtures::{self, Fixtures};
+use chat_server::models::NewMessage;
+use chat_server::{
+    check_password, create_message, create_messages, establish_connection, get_messages, ChatApp,
+};
+use chrono::Utc;
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion};
+use diesel::SqliteConnection;
+
+const USERS: usize = 8;
+/// How many users are logged in while looking up tokens, as the lookup goes through all logins.
+const LOGINS: usize = 100;
+const BATCH: usize = 64;
+/// How many messages the database holds if ``CHAT_BENCH_MESSAGES`` does not say.
+const DEFAULT_MESSAGES: usize = 10_000;
+
+/// The sizes of the databases to measure on, in messages.
+fn sizes() -> Vec<usize> {
+    let sizes: Vec<usize> = std::env::var("CHAT_BENCH_MESSAGES")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|size| size.trim().parse().ok())
+        .collect();
+    if sizes.is_empty() {
+        vec![DEFAULT_MESSAGES]
+    } else {
+        sizes
+    }
+}
+
+fn library(c: &mut Criterion) {
+    for rows in sizes() {
+        let database =
+            std::env::temp_dir().join(format!("chat_app_bench_{}_{rows}.db", std::process::id()));
+        let fixtures = Fixtures {
+            users: USERS,
+            messages: rows,
+            ..Fixtures::default()
+        };
+        let _ = std::fs::remove_file(&database);
+        let mut conn = establish_connection(&database).expect("database can be created");
+        let users = fixtures::populate(&mut conn, &fixtures).expect("database can be seeded");
+
+        let mut group = c.benchmark_group(format!("{rows} messages"));
+        // Password checks take a while on purpose, so fewer samples keep the run short
+        group.sample_size(10);
+        run(&mut group, &mut conn, &database, &fixtures, &users);
+        group.finish();
+
+        drop(conn);
+        let _ = std::fs::remove_file(&database);
+    }
+}
+
+fn run(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    conn: &mut SqliteConnection,
+    database: &Path,
+    fixtures: &Fixtures,
+    users: &[User],
+) {
+    let password = fixtures.password.as_str();
+    group.bench_function("check_password", |b| {
+        b.iter(
+            || assert!(check_password(conn, &users[0].username, password).expect("user exists")),
+        );
+    });
+
+    let mut app = ChatApp::new(Config::default(), database).expect("app can be created");
+    let tokens: Vec<_> = (0..LOGINS)
+        .map(|login| {
+            let user = &users[login % users.len()];
+            app.login(&user.username, password).expect("user can login")
+        })
+        .collect();
+    // The newest login is the last one searched
+    let token = tokens.last().expect("users are logged in");
+    group.bench_function("token lookup", |b| {
+        b.iter(|| app.get_user_for_token(token).expect("token is valid"));
+    });
+
+    let middle = fixtures.start + (fixtures.end - fixtures.start) / 2;
+    let history = [
+        ("history, newest page", MessageFilter::Before(Utc::now())),
+        ("history, page in the middle", MessageFilter::Before(middle)),
+        ("history, oldest page", MessageFilter::After(fixtures.start)),
+    ];
+    for (name, filter) in history {
+        group.bench_function(name, |b| {
+            b.iter(|| get_messages(conn, &filter).expect("messages can be read"));
+        });
+    }
+
+    let author = users[0].id;
+    group.bench_function("insert message", |b| {
+        b.iter(|| create_message(conn, "a new message", author).expect("message can be inserted"));
+    });
+    let batch: Vec<NewMessage> = (0..BATCH)
+        .map(|index| NewMessage {
+            date: Utc::now(),
+            messagetext: format!("message number {index} of the batch"),
+            userid: author,
+            forwardedid: None,
+            forwardeduserid: None,
+            channelid: None,
+            recipientid: None,
+        })
+        .collect();
+    group.bench_function(format!("insert {BATCH} messages in a batch"), |b| {
+        b.iter(|| create_messages(conn, &batch).expect("messages can be inserted"));
+    });
+}
+
+criterion_group!(benches, library);
+criterion_main!(benches);
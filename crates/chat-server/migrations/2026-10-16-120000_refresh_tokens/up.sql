@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE refreshtokens (
+    id INTEGER NOT NULL PRIMARY KEY,
+    userid INTEGER NOT NULL,
+    hashedtoken TEXT NOT NULL,
+    validuntil TIMESTAMP NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
@@ -0,0 +1,24 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE messages_local (
+    id INTEGER NOT NULL PRIMARY KEY,
+    date TIMESTAMP NOT NULL,
+    messagetext TEXT NOT NULL,
+    userid INTEGER NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
+INSERT INTO messages_local (id, date, messagetext, userid)
+    SELECT id, strftime('%Y-%m-%d %H:%M:%f', date, 'localtime'), messagetext, userid FROM messages;
+DROP TABLE messages;
+ALTER TABLE messages_local RENAME TO messages;
+
+CREATE TABLE refreshtokens_local (
+    id INTEGER NOT NULL PRIMARY KEY,
+    userid INTEGER NOT NULL,
+    hashedtoken TEXT NOT NULL,
+    validuntil TIMESTAMP NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
+INSERT INTO refreshtokens_local (id, userid, hashedtoken, validuntil)
+    SELECT id, userid, hashedtoken, strftime('%Y-%m-%d %H:%M:%f', validuntil, 'localtime') FROM refreshtokens;
+DROP TABLE refreshtokens;
+ALTER TABLE refreshtokens_local RENAME TO refreshtokens;
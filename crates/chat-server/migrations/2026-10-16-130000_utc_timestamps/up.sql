@@ -0,0 +1,25 @@
+-- Timestamps used to be stored in the local time of the server. Store them in UTC instead, so clients can show
+-- them in their own time zone.
+CREATE TABLE messages_utc (
+    id INTEGER NOT NULL PRIMARY KEY,
+    date TIMESTAMPTZ NOT NULL,
+    messagetext TEXT NOT NULL,
+    userid INTEGER NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
+INSERT INTO messages_utc (id, date, messagetext, userid)
+    SELECT id, strftime('%Y-%m-%d %H:%M:%f+00:00', date, 'utc'), messagetext, userid FROM messages;
+DROP TABLE messages;
+ALTER TABLE messages_utc RENAME TO messages;
+
+CREATE TABLE refreshtokens_utc (
+    id INTEGER NOT NULL PRIMARY KEY,
+    userid INTEGER NOT NULL,
+    hashedtoken TEXT NOT NULL,
+    validuntil TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
+INSERT INTO refreshtokens_utc (id, userid, hashedtoken, validuntil)
+    SELECT id, userid, hashedtoken, strftime('%Y-%m-%d %H:%M:%f+00:00', validuntil, 'utc') FROM refreshtokens;
+DROP TABLE refreshtokens;
+ALTER TABLE refreshtokens_utc RENAME TO refreshtokens;
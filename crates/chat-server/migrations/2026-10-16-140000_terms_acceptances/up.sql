@@ -0,0 +1,8 @@
+-- Every version of the terms of service a user accepted, so it can be shown when they were accepted.
+CREATE TABLE termsacceptances (
+    id INTEGER NOT NULL PRIMARY KEY,
+    userid INTEGER NOT NULL,
+    version TEXT NOT NULL,
+    accepteddate TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
@@ -0,0 +1,6 @@
+-- Users created by `POST /auth/guest`, which can only be used until they expire.
+CREATE TABLE guests (
+    userid INTEGER NOT NULL PRIMARY KEY,
+    validuntil TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE messages DROP COLUMN forwardeduserid;
+ALTER TABLE messages DROP COLUMN forwardedid;
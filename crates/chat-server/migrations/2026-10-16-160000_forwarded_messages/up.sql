@@ -0,0 +1,3 @@
+-- Forwarded messages keep pointing to the original message and its author.
+ALTER TABLE messages ADD COLUMN forwardedid INTEGER REFERENCES messages(id);
+ALTER TABLE messages ADD COLUMN forwardeduserid INTEGER REFERENCES users(id);
@@ -0,0 +1,9 @@
+-- Messages users saved to find them again later. Each user can save a message once.
+CREATE TABLE savedmessages (
+    userid INTEGER NOT NULL,
+    messageid INTEGER NOT NULL,
+    saveddate TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY(userid, messageid),
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(messageid) REFERENCES messages(id)
+);
@@ -0,0 +1,8 @@
+-- The newest message each user read, so unread messages can be counted. There is only the one chat so far, so each
+-- user has a single marker.
+CREATE TABLE readmarkers (
+    userid INTEGER NOT NULL PRIMARY KEY,
+    messageid INTEGER NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(messageid) REFERENCES messages(id)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX messages_seq;
+ALTER TABLE messages DROP COLUMN seq;
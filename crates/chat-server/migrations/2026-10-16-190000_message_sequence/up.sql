@@ -0,0 +1,5 @@
+-- The position of each message in its conversation. Dates can collide, so history is ordered by this instead. The
+-- existing messages are numbered in the order they were inserted in.
+ALTER TABLE messages ADD COLUMN seq BIGINT NOT NULL DEFAULT 0;
+UPDATE messages SET seq = id;
+CREATE UNIQUE INDEX messages_seq ON messages(seq);
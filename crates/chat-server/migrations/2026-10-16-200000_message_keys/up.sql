@@ -0,0 +1,10 @@
+-- The idempotency keys clients sent messages with, so a retried request returns the message it already created
+-- instead of sending it again. Keys are only unique per user.
+CREATE TABLE messagekeys (
+    userid INTEGER NOT NULL,
+    idempotencykey TEXT NOT NULL,
+    messageid INTEGER NOT NULL,
+    PRIMARY KEY(userid, idempotencykey),
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(messageid) REFERENCES messages(id)
+);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX messages_recipient;
+DROP INDEX messages_channel;
+ALTER TABLE messages DROP COLUMN recipientid;
+ALTER TABLE messages DROP COLUMN channelid;
+DROP TABLE channelmembers;
+DROP TABLE channels;
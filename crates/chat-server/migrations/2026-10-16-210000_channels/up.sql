@@ -0,0 +1,21 @@
+-- Channels are conversations of their own, which users join to read and write in them. Messages without a channel
+-- or recipient are in the main chat of the server.
+CREATE TABLE channels (
+    id INTEGER NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    topic TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE channelmembers (
+    channelid INTEGER NOT NULL,
+    userid INTEGER NOT NULL,
+    PRIMARY KEY(channelid, userid),
+    FOREIGN KEY(channelid) REFERENCES channels(id),
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
+
+-- Direct messages have a recipient, only they and the sender can read them.
+ALTER TABLE messages ADD COLUMN channelid INTEGER REFERENCES channels(id);
+ALTER TABLE messages ADD COLUMN recipientid INTEGER REFERENCES users(id);
+CREATE INDEX messages_channel ON messages(channelid, seq);
+CREATE INDEX messages_recipient ON messages(recipientid, seq);
@@ -0,0 +1,10 @@
+-- The message each user is writing in each conversation, so it can be finished on another device. The conversation
+-- is stored as its key, like `chat`, `channel-3` or `direct-2`.
+CREATE TABLE drafts (
+    userid INTEGER NOT NULL,
+    conversation TEXT NOT NULL,
+    drafttext TEXT NOT NULL,
+    updateddate TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY(userid, conversation),
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
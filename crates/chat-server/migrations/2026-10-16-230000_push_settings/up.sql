@@ -0,0 +1,11 @@
+-- Where the server pushes notifications for each user to, so they are told about mentions and direct messages
+-- while no client is running. The provider is `ntfy` or `gotify`.
+CREATE TABLE pushsettings (
+    userid INTEGER PRIMARY KEY NOT NULL,
+    provider TEXT NOT NULL,
+    url TEXT NOT NULL,
+    token TEXT,
+    mentions BOOLEAN NOT NULL,
+    directmessages BOOLEAN NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
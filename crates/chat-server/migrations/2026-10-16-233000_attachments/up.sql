@@ -0,0 +1,13 @@
+-- Files sent along with messages. The files themselves are kept on disk, under their storage key, so the database
+-- stays small.
+CREATE TABLE attachments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    messageid INTEGER NOT NULL,
+    filename TEXT NOT NULL,
+    mimetype TEXT NOT NULL,
+    filesize BIGINT NOT NULL,
+    storagekey TEXT NOT NULL,
+    FOREIGN KEY(messageid) REFERENCES messages(id)
+);
+
+CREATE INDEX attachments_message ON attachments(messageid);
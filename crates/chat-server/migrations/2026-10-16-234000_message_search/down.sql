@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER messagesearch_update;
+DROP TRIGGER messagesearch_delete;
+DROP TRIGGER messagesearch_insert;
+DROP TABLE messagesearch;
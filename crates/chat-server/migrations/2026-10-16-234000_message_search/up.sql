@@ -0,0 +1,19 @@
+-- The words of every message, so messages can be searched. The text itself stays in the messages table, the
+-- triggers keep the index in step with it. Case and accents are ignored.
+CREATE VIRTUAL TABLE messagesearch USING fts5(
+    messagetext,
+    content = 'messages',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+INSERT INTO messagesearch(messagesearch) VALUES ('rebuild');
+CREATE TRIGGER messagesearch_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messagesearch(rowid, messagetext) VALUES (new.id, new.messagetext);
+END;
+CREATE TRIGGER messagesearch_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messagesearch(messagesearch, rowid, messagetext) VALUES ('delete', old.id, old.messagetext);
+END;
+CREATE TRIGGER messagesearch_update AFTER UPDATE OF messagetext ON messages BEGIN
+    INSERT INTO messagesearch(messagesearch, rowid, messagetext) VALUES ('delete', old.id, old.messagetext);
+    INSERT INTO messagesearch(rowid, messagetext) VALUES (new.id, new.messagetext);
+END;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER blobs_release;
+DROP TRIGGER blobs_reference;
+DROP TABLE blobs;
+ALTER TABLE attachments DROP COLUMN contenthash;
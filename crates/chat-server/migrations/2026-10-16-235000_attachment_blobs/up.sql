@@ -0,0 +1,19 @@
+-- The BLAKE2b-256 hash of each file, hex encoded, which new files are stored under. Files stored before have none.
+ALTER TABLE attachments ADD COLUMN contenthash TEXT;
+
+-- The files on disk with how many attachments use them. Uploading the same file again stores it only once, so the
+-- file can only be removed once no attachment uses it anymore. The triggers keep the counts up to date.
+CREATE TABLE blobs (
+    storagekey TEXT PRIMARY KEY NOT NULL,
+    filesize BIGINT NOT NULL,
+    refcount INTEGER NOT NULL
+);
+INSERT INTO blobs (storagekey, filesize, refcount)
+    SELECT storagekey, MAX(filesize), COUNT(*) FROM attachments GROUP BY storagekey;
+CREATE TRIGGER blobs_reference AFTER INSERT ON attachments BEGIN
+    INSERT INTO blobs (storagekey, filesize, refcount) VALUES (new.storagekey, new.filesize, 1)
+        ON CONFLICT (storagekey) DO UPDATE SET refcount = refcount + 1;
+END;
+CREATE TRIGGER blobs_release AFTER DELETE ON attachments BEGIN
+    UPDATE blobs SET refcount = refcount - 1 WHERE storagekey = old.storagekey;
+END;
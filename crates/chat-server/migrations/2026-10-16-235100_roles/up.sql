@@ -0,0 +1,3 @@
+-- What each user may do besides chatting, and whether a moderator banned them
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'moderator', 'admin'));
+ALTER TABLE users ADD COLUMN banned BOOLEAN NOT NULL DEFAULT 0;
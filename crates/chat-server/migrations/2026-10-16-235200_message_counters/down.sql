@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER messagecounters_insert;
+DROP TABLE messagecounters;
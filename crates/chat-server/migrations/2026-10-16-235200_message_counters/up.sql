@@ -0,0 +1,10 @@
+-- The highest id and sequence number given to a message, so those of deleted messages are never given out again and
+-- clients never mistake a new message for one they saw before. The trigger keeps them up to date.
+CREATE TABLE messagecounters (
+    lastid INTEGER PRIMARY KEY NOT NULL,
+    lastseq BIGINT NOT NULL
+);
+INSERT INTO messagecounters (lastid, lastseq) SELECT COALESCE(MAX(id), 0), COALESCE(MAX(seq), 0) FROM messages;
+CREATE TRIGGER messagecounters_insert AFTER INSERT ON messages BEGIN
+    UPDATE messagecounters SET lastid = MAX(lastid, new.id), lastseq = MAX(lastseq, new.seq);
+END;
@@ -0,0 +1,5 @@
+-- What users tell others about themselves. The display name is shown in place of the username, which stays what they
+-- log in and are mentioned with. The avatar is the address of an image.
+ALTER TABLE users ADD COLUMN displayname TEXT;
+ALTER TABLE users ADD COLUMN bio TEXT NOT NULL DEFAULT '';
+ALTER TABLE users ADD COLUMN avatar TEXT;
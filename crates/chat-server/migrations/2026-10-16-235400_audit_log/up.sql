@@ -0,0 +1,11 @@
+-- What admins and moderators did, like impersonating or banning users, so it can be looked into later
+CREATE TABLE auditlog (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    date TIMESTAMPTZ NOT NULL,
+    actorid INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('impersonate', 'delete_message', 'rename_user', 'ban_user', 'set_role')),
+    targetid INTEGER,
+    details TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY(actorid) REFERENCES users(id),
+    FOREIGN KEY(targetid) REFERENCES users(id)
+);
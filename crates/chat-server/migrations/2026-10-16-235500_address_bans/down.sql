@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE auditlog_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    date TIMESTAMPTZ NOT NULL,
+    actorid INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('impersonate', 'delete_message', 'rename_user', 'ban_user', 'set_role')),
+    targetid INTEGER,
+    details TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY(actorid) REFERENCES users(id),
+    FOREIGN KEY(targetid) REFERENCES users(id)
+);
+INSERT INTO auditlog_old (id, date, actorid, action, targetid, details)
+    SELECT id, date, actorid, action, targetid, details FROM auditlog
+    WHERE action NOT IN ('ban_address', 'unban_address');
+DROP TABLE auditlog;
+ALTER TABLE auditlog_old RENAME TO auditlog;
+
+DROP TABLE addressbans;
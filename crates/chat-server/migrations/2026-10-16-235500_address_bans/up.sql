@@ -0,0 +1,27 @@
+-- Ranges of addresses banned by admins while the server runs. Nobody may register or log in from them.
+CREATE TABLE addressbans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    network TEXT NOT NULL UNIQUE,
+    reason TEXT NOT NULL DEFAULT '',
+    date TIMESTAMPTZ NOT NULL,
+    createdby INTEGER NOT NULL,
+    FOREIGN KEY(createdby) REFERENCES users(id)
+);
+
+-- The check of a column can not be changed, so the audit log is copied into a table allowing the new actions
+CREATE TABLE auditlog_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    date TIMESTAMPTZ NOT NULL,
+    actorid INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK (action IN (
+        'impersonate', 'delete_message', 'rename_user', 'ban_user', 'set_role', 'ban_address', 'unban_address'
+    )),
+    targetid INTEGER,
+    details TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY(actorid) REFERENCES users(id),
+    FOREIGN KEY(targetid) REFERENCES users(id)
+);
+INSERT INTO auditlog_new (id, date, actorid, action, targetid, details)
+    SELECT id, date, actorid, action, targetid, details FROM auditlog;
+DROP TABLE auditlog;
+ALTER TABLE auditlog_new RENAME TO auditlog;
@@ -0,0 +1,2 @@
+-- Users have to wait this many seconds between their messages in the channel, 0 if they do not
+ALTER TABLE channels ADD COLUMN slowmode INTEGER NOT NULL DEFAULT 0;
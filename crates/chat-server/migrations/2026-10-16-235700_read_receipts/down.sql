@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE readmarkers_old (
+    userid INTEGER NOT NULL PRIMARY KEY,
+    messageid INTEGER NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(messageid) REFERENCES messages(id)
+);
+INSERT INTO readmarkers_old (userid, messageid)
+    SELECT userid, messageid FROM readmarkers WHERE conversation = 'chat';
+DROP TABLE readmarkers;
+ALTER TABLE readmarkers_old RENAME TO readmarkers;
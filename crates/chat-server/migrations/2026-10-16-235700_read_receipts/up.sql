@@ -0,0 +1,14 @@
+-- Read markers are kept for every conversation, so users can see how far the others read. The conversation is
+-- stored as its key, like for drafts. The markers of the main chat are kept.
+CREATE TABLE readmarkers_new (
+    userid INTEGER NOT NULL,
+    conversation TEXT NOT NULL,
+    messageid INTEGER NOT NULL,
+    PRIMARY KEY(userid, conversation),
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(messageid) REFERENCES messages(id)
+);
+INSERT INTO readmarkers_new (userid, conversation, messageid)
+    SELECT userid, 'chat', messageid FROM readmarkers;
+DROP TABLE readmarkers;
+ALTER TABLE readmarkers_new RENAME TO readmarkers;
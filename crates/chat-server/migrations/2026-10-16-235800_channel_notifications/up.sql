@@ -0,0 +1,2 @@
+-- How the member is told about new messages in the channel: all, mentions or muted
+ALTER TABLE channelmembers ADD COLUMN notifications TEXT NOT NULL DEFAULT 'all';
@@ -0,0 +1,8 @@
+-- Users whose messages the user does not want to see
+CREATE TABLE userblocks (
+    userid INTEGER NOT NULL,
+    blockedid INTEGER NOT NULL,
+    PRIMARY KEY(userid, blockedid),
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(blockedid) REFERENCES users(id)
+);
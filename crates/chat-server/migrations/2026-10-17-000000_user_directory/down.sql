@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX messages_user;
+DROP INDEX users_banned;
+DROP INDEX users_role;
@@ -0,0 +1,4 @@
+-- Lets admins list the users by role, by whether they are banned and by when they last sent a message
+CREATE INDEX users_role ON users(role);
+CREATE INDEX users_banned ON users(banned);
+CREATE INDEX messages_user ON messages(userid, date);
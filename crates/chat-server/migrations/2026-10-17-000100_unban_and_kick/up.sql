@@ -0,0 +1,18 @@
+-- The check of a column can not be changed, so the audit log is copied into a table allowing the new actions
+CREATE TABLE auditlog_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    date TIMESTAMPTZ NOT NULL,
+    actorid INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK (action IN (
+        'impersonate', 'delete_message', 'rename_user', 'ban_user', 'unban_user', 'kick_user', 'set_role',
+        'ban_address', 'unban_address'
+    )),
+    targetid INTEGER,
+    details TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY(actorid) REFERENCES users(id),
+    FOREIGN KEY(targetid) REFERENCES users(id)
+);
+INSERT INTO auditlog_new (id, date, actorid, action, targetid, details)
+    SELECT id, date, actorid, action, targetid, details FROM auditlog;
+DROP TABLE auditlog;
+ALTER TABLE auditlog_new RENAME TO auditlog;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_lastactive;
+ALTER TABLE users DROP COLUMN showlastseen;
+ALTER TABLE users DROP COLUMN lastactive;
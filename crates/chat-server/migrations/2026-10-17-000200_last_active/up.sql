@@ -0,0 +1,4 @@
+-- When each user last used the server, and whether others may see it in their profile
+ALTER TABLE users ADD COLUMN lastactive TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN showlastseen BOOLEAN NOT NULL DEFAULT 1;
+CREATE INDEX users_lastactive ON users(lastactive);
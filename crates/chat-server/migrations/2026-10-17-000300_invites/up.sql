@@ -0,0 +1,11 @@
+-- Codes admins hand out so people can register on servers where registering needs an invite. Each works once.
+CREATE TABLE invites (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    code TEXT NOT NULL UNIQUE,
+    createdby INTEGER NOT NULL,
+    created TIMESTAMPTZ NOT NULL,
+    expires TIMESTAMPTZ,
+    usedby INTEGER,
+    FOREIGN KEY(createdby) REFERENCES users(id),
+    FOREIGN KEY(usedby) REFERENCES users(id)
+);
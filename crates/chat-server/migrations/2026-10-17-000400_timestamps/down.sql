@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE channels DROP COLUMN updateddate;
+ALTER TABLE channels DROP COLUMN createddate;
+ALTER TABLE users DROP COLUMN updateddate;
+ALTER TABLE users DROP COLUMN createddate;
@@ -0,0 +1,5 @@
+-- When accounts and channels were made and last changed. Rows from before are left without, as nobody knows
+ALTER TABLE users ADD COLUMN createddate TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN updateddate TIMESTAMPTZ;
+ALTER TABLE channels ADD COLUMN createddate TIMESTAMPTZ;
+ALTER TABLE channels ADD COLUMN updateddate TIMESTAMPTZ;
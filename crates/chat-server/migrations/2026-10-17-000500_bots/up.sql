@@ -0,0 +1,19 @@
+-- Accounts for automation, made by admins. They have no password and log in with API keys instead.
+CREATE TABLE bots (
+    userid INTEGER NOT NULL PRIMARY KEY,
+    createdby INTEGER NOT NULL,
+    created TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id),
+    FOREIGN KEY(createdby) REFERENCES users(id)
+);
+
+-- The keys bots send in `Authorization: ApiKey <key>`. They do not expire, so only a hash of them is stored.
+CREATE TABLE apikeys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    userid INTEGER NOT NULL,
+    name TEXT NOT NULL DEFAULT '',
+    hashedkey TEXT NOT NULL,
+    created TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(userid) REFERENCES users(id)
+);
+CREATE INDEX apikeys_user ON apikeys(userid);
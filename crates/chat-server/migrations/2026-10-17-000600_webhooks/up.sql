@@ -0,0 +1,8 @@
+-- Urls admins registered, which the server posts every new message in the chat and the channels to.
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    url TEXT NOT NULL,
+    createdby INTEGER NOT NULL,
+    created TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(createdby) REFERENCES users(id)
+);
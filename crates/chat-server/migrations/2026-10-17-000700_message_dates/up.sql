@@ -0,0 +1,2 @@
+-- Speeds up paging through the history by date, which otherwise scans every message.
+CREATE INDEX messages_date ON messages(date);
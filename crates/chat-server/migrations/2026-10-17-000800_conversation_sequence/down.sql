@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER messagecounters_insert;
+DROP TABLE conversationcounters;
+-- Numbers repeat across conversations by now, so the messages are numbered in the order they were inserted in again
+UPDATE messages SET seq = id;
+ALTER TABLE messagecounters ADD COLUMN lastseq BIGINT NOT NULL DEFAULT 0;
+UPDATE messagecounters SET lastseq = lastid;
+CREATE TRIGGER messagecounters_insert AFTER INSERT ON messages BEGIN
+    UPDATE messagecounters SET lastid = MAX(lastid, new.id), lastseq = MAX(lastseq, new.seq);
+END;
+DROP INDEX messages_conversation_seq;
+ALTER TABLE messages DROP COLUMN conversation;
+CREATE UNIQUE INDEX messages_seq ON messages(seq);
@@ -0,0 +1,27 @@
+-- Messages are numbered within their conversation, so the numbers of one conversation have no gaps left by the
+-- others. Each message keeps the key of its conversation: "chat", "channel-<id>" or
+-- "direct-<lower user id>-<higher user id>", which is the same for both users. The existing messages keep their
+-- numbers, which are already unique and in order within each conversation.
+ALTER TABLE messages ADD COLUMN conversation TEXT NOT NULL DEFAULT 'chat';
+UPDATE messages SET conversation = CASE
+    WHEN channelid IS NOT NULL THEN 'channel-' || channelid
+    WHEN recipientid IS NOT NULL THEN 'direct-' || MIN(userid, recipientid) || '-' || MAX(userid, recipientid)
+    ELSE 'chat'
+END;
+DROP INDEX messages_seq;
+CREATE UNIQUE INDEX messages_conversation_seq ON messages(conversation, seq);
+-- The highest sequence number given to a message of each conversation, so those of deleted messages are never given
+-- out again. The trigger keeps them up to date, along with the highest id.
+CREATE TABLE conversationcounters (
+    conversation TEXT PRIMARY KEY NOT NULL,
+    lastseq BIGINT NOT NULL
+);
+INSERT INTO conversationcounters (conversation, lastseq)
+    SELECT conversation, MAX(seq) FROM messages GROUP BY conversation;
+DROP TRIGGER messagecounters_insert;
+ALTER TABLE messagecounters DROP COLUMN lastseq;
+CREATE TRIGGER messagecounters_insert AFTER INSERT ON messages BEGIN
+    UPDATE messagecounters SET lastid = MAX(lastid, new.id);
+    INSERT INTO conversationcounters (conversation, lastseq) VALUES (new.conversation, new.seq)
+        ON CONFLICT (conversation) DO UPDATE SET lastseq = MAX(lastseq, excluded.lastseq);
+END;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE channels DROP COLUMN version;
+ALTER TABLE users DROP COLUMN profileversion;
@@ -0,0 +1,3 @@
+-- Count the changes to profiles and channels, so an edit based on an outdated version can be refused
+ALTER TABLE users ADD COLUMN profileversion INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE channels ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
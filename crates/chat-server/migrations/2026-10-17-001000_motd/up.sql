@@ -0,0 +1,8 @@
+-- The message of the day, a single row which admins change and clients show everyone when they log in.
+CREATE TABLE motd (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    text TEXT NOT NULL,
+    updateddate TIMESTAMPTZ,
+    version INTEGER NOT NULL DEFAULT 1
+);
+INSERT INTO motd (id, text) VALUES (1, '');
@@ -2,6 +2,8 @@ use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
 };
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
 
 pub fn verify_password(password: &str, hashed_password: &str) -> bool {
     let argon2 = Argon2::default();
@@ -22,3 +24,9 @@ pub fn generate_hash(password: &str) -> String {
         .expect("error whilst hashung password")
         .to_string()
 }
+
+/// Hashes an API key with BLAKE2b-256, in hex. The keys are long random secrets, which a fast hash protects well
+/// enough, and they are checked on every request of a bot, which a password hash would slow down a lot.
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Blake2b::<U32>::digest(key.as_bytes()))
+}
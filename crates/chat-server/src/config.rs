@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Configuration for a `ChatApp` instance.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// The name of the server, shown to clients before they log in.
+    pub name: String,
+    pub branding: BrandingConfig,
+    /// The oldest client version that works with the server. Older clients ask their users to update.
+    pub min_client_version: Option<String>,
+    /// Path of the Sqlite database file, relative to the data directory. Gets created if it does not exist.
+    pub database: String,
+    /// Directory the files attached to messages are stored in, relative to the data directory. Gets created if it
+    /// does not exist.
+    pub attachments: String,
+    pub uploads: UploadConfig,
+    pub pool: PoolConfig,
+    pub session: SessionConfig,
+    /// The terms of service users have to accept to register. Registering needs no acceptance if not set.
+    pub terms: Option<TermsConfig>,
+    pub guests: GuestConfig,
+    pub invites: InviteConfig,
+    pub quotas: QuotaConfig,
+    pub spam: SpamConfig,
+    pub history: HistoryConfig,
+    pub digest: DigestConfig,
+    /// Further communities hosted by the same server, keyed by the name used in their paths.
+    pub tenants: HashMap<String, TenantConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            name: "Chat".into(),
+            branding: BrandingConfig::default(),
+            min_client_version: None,
+            database: "data.db".into(),
+            attachments: "attachments".into(),
+            uploads: UploadConfig::default(),
+            pool: PoolConfig::default(),
+            session: SessionConfig::default(),
+            terms: None,
+            guests: GuestConfig::default(),
+            invites: InviteConfig::default(),
+            quotas: QuotaConfig::default(),
+            spam: SpamConfig::default(),
+            history: HistoryConfig::default(),
+            digest: DigestConfig::default(),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the configuration of a tenant, taking anything it does not override from this configuration.
+    pub fn for_tenant(&self, key: &str, tenant: &TenantConfig) -> Config {
+        Config {
+            name: tenant.name.clone().unwrap_or_else(|| self.name.clone()),
+            branding: tenant
+                .branding
+                .clone()
+                .unwrap_or_else(|| self.branding.clone()),
+            // All tenants are served by the same server, so the same clients work with them
+            min_client_version: self.min_client_version.clone(),
+            database: tenant
+                .database
+                .clone()
+                .unwrap_or_else(|| format!("{key}.db")),
+            attachments: tenant
+                .attachments
+                .clone()
+                .unwrap_or_else(|| format!("{key}-attachments")),
+            uploads: tenant
+                .uploads
+                .clone()
+                .unwrap_or_else(|| self.uploads.clone()),
+            pool: tenant.pool.clone().unwrap_or_else(|| self.pool.clone()),
+            session: tenant
+                .session
+                .clone()
+                .unwrap_or_else(|| self.session.clone()),
+            terms: tenant.terms.clone().or_else(|| self.terms.clone()),
+            guests: tenant.guests.clone().unwrap_or_else(|| self.guests.clone()),
+            invites: tenant
+                .invites
+                .clone()
+                .unwrap_or_else(|| self.invites.clone()),
+            quotas: tenant.quotas.clone().unwrap_or_else(|| self.quotas.clone()),
+            spam: tenant.spam.clone().unwrap_or_else(|| self.spam.clone()),
+            history: tenant
+                .history
+                .clone()
+                .unwrap_or_else(|| self.history.clone()),
+            digest: tenant.digest.clone().unwrap_or_else(|| self.digest.clone()),
+            tenants: HashMap::new(),
+        }
+    }
+}
+
+/// A community hosted besides the main one, with its own users and messages. Anything not set is taken from the
+/// main configuration.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TenantConfig {
+    pub name: Option<String>,
+    pub branding: Option<BrandingConfig>,
+    /// Defaults to the key of the tenant, followed by `.db`.
+    pub database: Option<String>,
+    /// Defaults to the key of the tenant, followed by `-attachments`.
+    pub attachments: Option<String>,
+    pub uploads: Option<UploadConfig>,
+    pub pool: Option<PoolConfig>,
+    pub session: Option<SessionConfig>,
+    pub terms: Option<TermsConfig>,
+    pub guests: Option<GuestConfig>,
+    pub invites: Option<InviteConfig>,
+    pub quotas: Option<QuotaConfig>,
+    pub spam: Option<SpamConfig>,
+    pub history: Option<HistoryConfig>,
+    pub digest: Option<DigestConfig>,
+}
+
+/// How the server presents itself to clients, so users can tell servers apart.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BrandingConfig {
+    /// A sentence or two about the server, shown before logging in.
+    pub description: Option<String>,
+    /// A few lines of text art, shown above the description by terminal clients.
+    pub banner: Option<String>,
+    /// The color clients highlight the server with, like `#3b82f6`.
+    pub accent_color: Option<String>,
+}
+
+impl BrandingConfig {
+    /// Whether the accent color is written like `#3b82f6`, if there is one.
+    pub fn has_valid_accent_color(&self) -> bool {
+        self.accent_color.as_deref().is_none_or(|color| {
+            color.len() == 7
+                && color.starts_with('#')
+                && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+        })
+    }
+}
+
+/// Which files can be attached to messages. The type of a file is recognized from its content, the type the client
+/// claims for it is not trusted.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    /// The largest file accepted, in bytes. The `file` and `data-form` limits of Rocket have to allow it too.
+    pub max_size: u64,
+    /// The types of files accepted, like `image/png`, or `image/*` for every image. Files that are not recognized
+    /// are `application/octet-stream`. `*/*` accepts every file.
+    pub allowed_types: Vec<String>,
+    /// Whether rejected files are kept in the `quarantine` directory inside the attachments directory, so they can
+    /// be looked at later. They are deleted otherwise.
+    pub quarantine: bool,
+    /// Seconds between removing the files no attachment uses anymore, or 0 to leave that to `chat-admin gc`.
+    pub gc_interval: u64,
+}
+
+impl UploadConfig {
+    pub fn gc_interval(&self) -> Duration {
+        Duration::from_secs(self.gc_interval)
+    }
+
+    /// Whether files of the type are accepted.
+    pub fn allows(&self, mime: &str) -> bool {
+        let (kind, _) = mime.split_once('/').unwrap_or((mime, ""));
+        self.allowed_types.iter().any(|allowed| {
+            allowed.eq_ignore_ascii_case(mime)
+                || allowed == "*/*"
+                || allowed
+                    .strip_suffix("/*")
+                    .is_some_and(|allowed| allowed.eq_ignore_ascii_case(kind))
+        })
+    }
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 25 * 1024 * 1024, // 25 MiB
+            // Everything that is recognized, which leaves out executables and other unknown files
+            allowed_types: [
+                "image/png",
+                "image/jpeg",
+                "image/gif",
+                "image/webp",
+                "audio/*",
+                "video/*",
+                "application/pdf",
+                "application/zip",
+                "application/gzip",
+                "text/plain",
+            ]
+            .map(String::from)
+            .to_vec(),
+            quarantine: true,
+            gc_interval: 24 * 60 * 60, // A day
+        }
+    }
+}
+
+/// Tunes the pool of connections to the database.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// Most connections open at once. At least 1.
+    pub max_size: u32,
+    /// Connections kept open while nothing uses them. Keeps `max_size` connections open if not set.
+    pub min_idle: Option<u32>,
+    /// Seconds after which a connection is closed and replaced by a new one. 0 keeps connections open for good.
+    pub max_lifetime: u64,
+    /// Seconds to wait for a free connection before giving up on a request. At least 1.
+    pub connection_timeout: u64,
+}
+
+impl PoolConfig {
+    pub fn max_lifetime(&self) -> Option<Duration> {
+        (self.max_lifetime > 0).then(|| Duration::from_secs(self.max_lifetime))
+    }
+
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.connection_timeout.max(1))
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 10,
+            min_idle: None,
+            max_lifetime: 1800,     // 30 minutes
+            connection_timeout: 30, // 30 seconds
+        }
+    }
+}
+
+/// Where the terms of service are and which version they are in.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TermsConfig {
+    /// Users who accepted an older version have to accept the terms again when registering. Change it whenever
+    /// the terms change.
+    pub version: String,
+    /// Path of the text file holding the terms, relative to the config directory.
+    pub file: String,
+}
+
+/// Controls temporary guest accounts, which can be used without registering.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GuestConfig {
+    /// Whether guest accounts can be created with `POST /auth/guest`.
+    pub enabled: bool,
+    /// Seconds a guest account can be used for. Guests can not log in again afterwards.
+    pub lifetime: u64,
+    /// Seconds a guest has to wait between two messages.
+    pub message_interval: u64,
+}
+
+impl GuestConfig {
+    pub fn lifetime(&self) -> Duration {
+        Duration::from_secs(self.lifetime)
+    }
+
+    pub fn message_interval(&self) -> Duration {
+        Duration::from_secs(self.message_interval)
+    }
+}
+
+impl Default for GuestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lifetime: 3600,       // 1 hour
+            message_interval: 10, // 10 seconds
+        }
+    }
+}
+
+/// Controls whether registering needs an invite code, which admins make with `POST /admin/invites`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InviteConfig {
+    /// Whether registering, and claiming a guest account, needs an invite code. Each code works once.
+    pub required: bool,
+    /// Seconds an invite code can be used for after it was made, or 0 for codes that do not expire.
+    pub lifetime: u64,
+}
+
+impl InviteConfig {
+    pub fn lifetime(&self) -> Option<Duration> {
+        (self.lifetime > 0).then(|| Duration::from_secs(self.lifetime))
+    }
+}
+
+impl Default for InviteConfig {
+    fn default() -> Self {
+        Self {
+            required: false,
+            lifetime: 7 * 24 * 60 * 60, // A week
+        }
+    }
+}
+
+/// Limits how many messages each user can send, so misbehaving clients and bots can not fill the database. A
+/// limit of 0 turns that quota off.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    pub messages_per_minute: u32,
+    pub messages_per_day: u32,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            messages_per_minute: 30,
+            messages_per_day: 2000,
+        }
+    }
+}
+
+/// Stops users from sending the same text to a conversation over and over, like bots stuck in a loop or a paste sent
+/// twice by accident. Texts count as the same if they only differ in case, punctuation and spacing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpamConfig {
+    /// How many messages with the same text a user can send to a conversation within `window`. 0 turns the check
+    /// off.
+    pub max_copies: u32,
+    /// Seconds within which the copies are counted.
+    pub window: u64,
+    /// What happens to a message with one copy too many.
+    pub action: SpamAction,
+}
+
+impl SpamConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window)
+    }
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            max_copies: 2,
+            window: 60, // 1 minute
+            action: SpamAction::Reject,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpamAction {
+    /// The message is refused with `ErrorCode::DuplicateMessage`, so the user learns why it was not sent.
+    Reject,
+    /// The message is not written, but sending it succeeds as if it were the latest copy. Nobody sees it twice and
+    /// the sender is not bothered, which suits accidents better than bots.
+    Squash,
+}
+
+/// How many messages of the history are returned at a time.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Messages returned when the request does not ask for a number. At least 1.
+    pub page_size: u32,
+    /// The most messages a request can ask for. Requests asking for more get this many.
+    pub max_page_size: u32,
+}
+
+impl HistoryConfig {
+    /// The number of messages to return for a request that asked for that many, or for the default if it did not
+    /// ask.
+    pub fn page_size(&self, requested: Option<u32>) -> u32 {
+        let max = self.max_page_size.max(1);
+        requested.unwrap_or(self.page_size).clamp(1, max)
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            page_size: 20,
+            max_page_size: 200,
+        }
+    }
+}
+
+/// Where the weekly summary for the admins is posted. Nothing is posted unless both are set.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    /// The name of the channel the digest is posted to.
+    pub channel: Option<String>,
+    /// The username of the account posting the digest, like a bot. It has to be a member of the channel.
+    pub sender: Option<String>,
+}
+
+/// Controls how long login sessions stay valid.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Seconds a session stays valid without any activity. Every authenticated request extends it by that amount.
+    pub idle_timeout: u64,
+    /// Seconds after which a session expires regardless of activity.
+    pub max_lifetime: u64,
+    /// Seconds a refresh token issued for "remember me" logins stays valid.
+    pub refresh_lifetime: u64,
+    /// Seconds after which a login of an admin acting as another user expires regardless of activity.
+    pub impersonation_lifetime: u64,
+}
+
+impl SessionConfig {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout)
+    }
+
+    pub fn max_lifetime(&self) -> Duration {
+        Duration::from_secs(self.max_lifetime)
+    }
+
+    pub fn refresh_lifetime(&self) -> Duration {
+        Duration::from_secs(self.refresh_lifetime)
+    }
+
+    pub fn impersonation_lifetime(&self) -> Duration {
+        Duration::from_secs(self.impersonation_lifetime)
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: 1200,           // 20 minutes
+            max_lifetime: 43200,          // 12 hours
+            refresh_lifetime: 2_592_000,  // 30 days
+            impersonation_lifetime: 3600, // 1 hour
+        }
+    }
+}
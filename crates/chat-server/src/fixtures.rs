@@ -0,0 +1,206 @@
+//! Fills a database with plausible users and messages, for load testing, benchmarks and working on the ui against
+//! something that looks like a real conversation.
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use chat_core::models::User;
+use chat_core::schema;
+
+use crate::models::{NewAuthentication, NewMessage};
+use crate::{auth, create_messages, create_user, get_user_by_name, DbError};
+
+/// Messages are inserted this many at a time.
+const BATCH: usize = 10_000;
+
+const NAMES: [&str; 24] = [
+    "alice", "bob", "carol", "dave", "erin", "frank", "grace", "heidi", "ivan", "judy", "karl",
+    "laura", "mallory", "nina", "oscar", "peggy", "quinn", "rupert", "sybil", "trent", "ursula",
+    "victor", "wendy", "yusuf",
+];
+const REPLIES: [&str; 12] = [
+    "lol",
+    "yeah",
+    "sounds good",
+    "thanks!",
+    "brb",
+    "same",
+    "no idea",
+    "agreed",
+    "haha nice",
+    "ok",
+    "on it",
+    "makes sense",
+];
+const SUBJECTS: [&str; 10] = [
+    "I",
+    "we",
+    "the build",
+    "the new release",
+    "my laptop",
+    "the meeting",
+    "the server",
+    "it",
+    "nobody",
+    "the docs",
+];
+const VERBS: [&str; 10] = [
+    "broke", "fixed", "moved", "finished", "started", "checked", "missed", "updated", "forgot",
+    "liked",
+];
+const OBJECTS: [&str; 10] = [
+    "the login issue",
+    "the deploy",
+    "lunch",
+    "the tests",
+    "the whole thing",
+    "that pull request",
+    "the schedule",
+    "my notes",
+    "the coffee machine",
+    "yesterday's plan",
+];
+const ENDINGS: [&str; 8] = [
+    "",
+    "",
+    " again",
+    " yesterday",
+    " just now",
+    " somehow",
+    " for once",
+    " before the weekend",
+];
+
+/// What to generate.
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    pub users: usize,
+    pub messages: usize,
+    /// The messages are spread between these dates.
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Every generated user gets this password.
+    pub password: String,
+    /// The same seed gives the same users and messages. The dates of the messages depend on the time range as well.
+    pub seed: u64,
+}
+
+impl Default for Fixtures {
+    fn default() -> Self {
+        let end = Utc::now();
+        Self {
+            users: 20,
+            messages: 1000,
+            start: end - Duration::days(30),
+            end,
+            password: "password".into(),
+            seed: 0,
+        }
+    }
+}
+
+/// Creates the users and messages, returning the users. The users are named after common first names, with a number
+/// added once the names run out. A few users write most of the messages and some messages mention other users, like
+/// in a real conversation.
+///
+/// # Errors
+///
+/// This function will return an error if one of the names is already in use or writing to the database failed.
+pub fn populate(conn: &mut SqliteConnection, fixtures: &Fixtures) -> Result<Vec<User>, DbError> {
+    let mut rng = StdRng::seed_from_u64(fixtures.seed);
+    let users = conn.transaction(|conn| create_users(conn, fixtures.users, &fixtures.password))?;
+    if users.is_empty() {
+        return Ok(users);
+    }
+
+    let span = (fixtures.end - fixtures.start).num_milliseconds().max(0);
+    let mut dates: Vec<DateTime<Utc>> = (0..fixtures.messages)
+        .map(|_| fixtures.start + Duration::milliseconds(rng.gen_range(0..=span)))
+        .collect();
+    dates.sort();
+
+    for chunk in dates.chunks(BATCH) {
+        let batch: Vec<NewMessage> = chunk
+            .iter()
+            .map(|date| NewMessage {
+                date: *date,
+                messagetext: message_text(&mut rng, &users),
+                userid: pick_author(&mut rng, &users).id,
+                forwardedid: None,
+                forwardeduserid: None,
+                channelid: None,
+                recipientid: None,
+            })
+            .collect();
+        create_messages(conn, &batch)?;
+    }
+
+    Ok(users)
+}
+
+/// The name of the user with that index.
+fn username(index: usize) -> String {
+    let name = NAMES[index % NAMES.len()];
+    match index / NAMES.len() {
+        0 => name.to_string(),
+        round => format!("{name}{round}"),
+    }
+}
+
+fn create_users(
+    conn: &mut SqliteConnection,
+    count: usize,
+    password: &str,
+) -> Result<Vec<User>, DbError> {
+    // Hashing is slow on purpose, so all users share the same hash
+    let hash = auth::generate_hash(password);
+    (0..count)
+        .map(|index| {
+            let name = username(index);
+            create_user(conn, &name)?;
+            let user = get_user_by_name(conn, &name)?;
+            diesel::insert_into(schema::authentications::table)
+                .values(NewAuthentication {
+                    userid: user.id,
+                    hashedpassword: hash.clone(),
+                })
+                .execute(conn)?;
+            Ok(user)
+        })
+        .collect()
+}
+
+/// Picks who writes a message, favouring the first users, so a few of them write most messages.
+fn pick_author<'a>(rng: &mut StdRng, users: &'a [User]) -> &'a User {
+    let index = (rng.gen::<f64>().powi(2) * users.len() as f64) as usize;
+    &users[index.min(users.len() - 1)]
+}
+
+fn message_text(rng: &mut StdRng, users: &[User]) -> String {
+    let pick = |rng: &mut StdRng, words: &[&'static str]| {
+        *words.choose(rng).expect("word lists are not empty")
+    };
+    let mut text = if rng.gen_bool(0.3) {
+        pick(rng, &REPLIES).to_string()
+    } else {
+        let mut sentence = format!(
+            "{} {} {}{}",
+            pick(rng, &SUBJECTS),
+            pick(rng, &VERBS),
+            pick(rng, &OBJECTS),
+            pick(rng, &ENDINGS)
+        );
+        if rng.gen_bool(0.2) {
+            sentence.push_str(&format!(", {}", pick(rng, &REPLIES)));
+        }
+        sentence
+    };
+    if rng.gen_bool(0.07) {
+        let mentioned = users.choose(rng).expect("there are users");
+        text = format!("@{} {text}", mentioned.username);
+    }
+    text
+}
@@ -0,0 +1,113 @@
+//! Removes attachment files no attachment refers to anymore. Files are shared by every attachment with the same
+//! content, so they stay on disk when a message goes away and are only removed here once nothing uses them.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use diesel::sqlite::SqliteConnection;
+use thiserror::Error;
+
+use crate::{delete_unused_blobs, get_referenced_files, DbError};
+
+/// Files changed more recently than this are kept, as they may belong to uploads whose messages are not written yet.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// How many keys are looked up in the database at a time.
+const BATCH: usize = 500;
+
+/// What a collection removed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Collected {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Collected {
+    pub fn add(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GcError {
+    #[error("Could not look up which files are used: {0}")]
+    Database(#[from] DbError),
+    #[error("Could not read the attachment directory: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Removes the files in the attachment directory that no attachment refers to and that are older than the grace
+/// period, and returns what was removed.
+///
+/// # Errors
+///
+/// This function will return an error if the directory could not be read or the database could not be queried.
+/// Files that could not be removed are only logged.
+pub fn collect(conn: &mut SqliteConnection, directory: &Path) -> Result<Collected, GcError> {
+    let mut collected = Collected::default();
+    for key in find_unused(conn, directory)? {
+        if let Some(bytes) = remove_unused(directory, &key) {
+            collected.add(bytes);
+        }
+    }
+    Ok(collected)
+}
+
+/// Finds the keys of the files in the attachment directory that no attachment refers to, and forgets the stored
+/// files that are counted as unused. The quarantine and anything else that is not a stored file is left out.
+///
+/// # Errors
+///
+/// This function will return an error if the directory could not be read or the database could not be queried.
+pub fn find_unused(conn: &mut SqliteConnection, directory: &Path) -> Result<Vec<String>, GcError> {
+    delete_unused_blobs(conn)?;
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        // Stored files are named after random keys or hashes, both in hex
+        match entry.file_name().into_string() {
+            Ok(key) if !key.is_empty() && key.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                keys.push(key)
+            }
+            _ => {}
+        }
+    }
+
+    let mut unused = Vec::new();
+    for batch in keys.chunks(BATCH) {
+        let referenced = get_referenced_files(conn, batch)?;
+        unused.extend(
+            batch
+                .iter()
+                .filter(|key| !referenced.contains(key))
+                .cloned(),
+        );
+    }
+    Ok(unused)
+}
+
+/// Removes the unused file stored under the key, unless it changed within the grace period. Returns its size if it
+/// was removed.
+pub fn remove_unused(directory: &Path, key: &str) -> Option<u64> {
+    let path = directory.join(key);
+    let metadata = fs::metadata(&path).ok()?;
+    let changed = metadata.modified().ok()?;
+    let age = SystemTime::now()
+        .duration_since(changed)
+        .unwrap_or_default();
+    if age < GRACE_PERIOD {
+        return None;
+    }
+    match fs::remove_file(&path) {
+        Ok(()) => Some(metadata.len()),
+        Err(e) => {
+            println!("Could not remove unused attachment {key}:\n{e}");
+            None
+        }
+    }
+}
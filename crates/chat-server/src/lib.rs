@@ -0,0 +1,5171 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+use chat_core::models::{
+    AddressBan, ApiKey, Attachment, AuditAction, AuditEntry, Channel, Invite, Message, Motd,
+    Permission, Profile, Role, User, Webhook,
+};
+use chat_core::protocol::{
+    mentions, ChannelNotifications, Conversation, ConversationOverview, ConversationSync,
+    CreateChannel, Credentials, Draft, ForwardTarget, InitialSync, Limits, MessageContext,
+    MessageFilter, NotificationLevel, Overview, Page, PageRequest, ProfileUpdate, PushProvider,
+    PushSettings, Quota, QuotaPeriod, ReadMarker, ReadReceipt, SavedMessage, SearchFilter,
+    SearchQuery, SearchSyntaxError, Session, UserEntry, UserFilter, UserSort, WebhookPayload,
+};
+use chat_core::schema;
+use chat_core::{LoginToken, RefreshToken};
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::r2d2::ConnectionManager;
+use diesel::sql_types::{Bool, Text};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel::{prelude::*, r2d2::Pool};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use pool::{PoolMetrics, PoolStatus};
+use rand::Rng;
+use thiserror::Error;
+
+use crate::config::{Config, PoolConfig, SessionConfig, SpamAction};
+use crate::models::{
+    Authentication, DirectoryEntry, MessageRow, NewAddressBan, NewApiKey, NewAttachment,
+    NewAuditEntry, NewAuthentication, NewBot, NewChannel, NewChannelMember, NewDraft, NewGuest,
+    NewInvite, NewMessage, NewMessageKey, NewReadMarker, NewRefreshToken, NewSavedMessage,
+    NewTermsAcceptance, NewUser, NewUserBlock, NewWebhook, ProfileChanges, PushSettingsEntry,
+    RefreshTokenEntry,
+};
+use crate::network::{Network, NetworkError};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+/// The largest image that is its own thumbnail. The server does not scale images down, so larger ones get none.
+const THUMBNAIL_MAX_SIZE: i64 = 64 * 1024;
+
+/// How long after noting that a user was active the next request of theirs is noted again. Writing it on every
+/// request would keep the database busy for little gain.
+const ACTIVITY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+mod auth;
+pub mod config;
+pub mod fixtures;
+pub mod gc;
+pub mod models;
+pub mod network;
+pub mod pool;
+pub mod server;
+pub mod spam;
+
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error("Could not connect to database")]
+    ConnectionFailure,
+    #[error("Could not apply database migrations")]
+    MigrationFailure,
+    #[error("Could not insert user into database")]
+    UserCreationFailed,
+    #[error("A user with that name already exists")]
+    UsernameInUse,
+    #[error("Could not lookup user in database")]
+    UserFilterFailed,
+    #[error("Found multiple users with same username")]
+    UsernameCollisionDetected,
+    #[error("Could not find a user with that name")]
+    UserNotFound,
+    #[error("Could not find a message with that id")]
+    MessageNotFound,
+    #[error("A channel with that name already exists")]
+    ChannelNameInUse,
+    #[error("Could not find a channel with that id")]
+    ChannelNotFound,
+    #[error("Could not find an attachment with that id")]
+    AttachmentNotFound,
+    #[error("Database did not return item when inserting")]
+    NoReturnOnInsert,
+    #[error("The underlying database engine encountered an error")]
+    GenericError(#[from] diesel::result::Error),
+    #[error("Failed to create connection pool")]
+    PoolError(#[from] r2d2::Error),
+    #[error("No password set")]
+    NoPasswordSet,
+    #[error("The refresh token is invalid or has expired")]
+    RefreshTokenInvalid,
+    #[error("Could not find an address ban with that id")]
+    AddressBanNotFound,
+    #[error("The invite code is not valid, has expired or was used already")]
+    InviteInvalid,
+    #[error("Could not find an unused invite with that id")]
+    InviteNotFound,
+    #[error("Could not find a bot with that id")]
+    BotNotFound,
+    #[error("Could not find an API key of the bot with that id")]
+    ApiKeyNotFound,
+    #[error("The API key is not valid or was revoked")]
+    ApiKeyInvalid,
+    #[error("Could not find a webhook with that id")]
+    WebhookNotFound,
+}
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("A database operation caused an error")]
+    DatabaseError(#[from] DbError),
+    #[error("Failed to obtain a connection from the connection pool")]
+    PoolError(#[from] r2d2::Error),
+    #[error("Failed to login user")]
+    LoginFailed,
+    #[error("The user is banned")]
+    UserBanned,
+    #[error("The given token is invalid")]
+    TokenInvalid,
+    #[error("The current terms of service were not accepted")]
+    TermsNotAccepted,
+    #[error("Guest accounts are disabled")]
+    GuestsDisabled,
+    #[error("The account is not a guest account")]
+    NotAGuest,
+    #[error("Too many requests were sent in a short time")]
+    RateLimited,
+    #[error("The user sent as many messages per {period:?} as their quota allows")]
+    QuotaExceeded { period: QuotaPeriod, quota: Quota },
+    #[error("The channel is in slow mode and the user has to wait {remaining:?} before sending another message")]
+    SlowMode { remaining: Duration },
+    #[error("The user sent the same text too often in a short time")]
+    DuplicateMessage,
+    #[error("The page cursor is not valid")]
+    InvalidCursor,
+    #[error("The user is not a member of the channel")]
+    NotAMember,
+    #[error("Users can not block themselves")]
+    BlockSelf,
+    #[error("The push settings are not valid: {0}")]
+    InvalidPushSettings(&'static str),
+    #[error("The search has no words to search for")]
+    EmptySearch,
+    #[error("The search could not be read: {0}")]
+    InvalidSearch(#[from] SearchSyntaxError),
+    #[error("The role of the user does not allow this")]
+    PermissionDenied,
+    #[error("The profile is not valid: {0}")]
+    InvalidProfile(&'static str),
+    #[error("The address is banned")]
+    AddressBanned,
+    #[error("The range of addresses is not valid: {0}")]
+    InvalidNetwork(#[from] NetworkError),
+    #[error("The webhook url is not an http or https url")]
+    InvalidWebhookUrl,
+    #[error("The resource is at version {current}, not the one the change was based on")]
+    VersionConflict { current: i32 },
+}
+
+impl From<diesel::result::Error> for AppError {
+    fn from(error: diesel::result::Error) -> Self {
+        AppError::DatabaseError(DbError::GenericError(error))
+    }
+}
+
+/// A message waiting to be sent, along with who sends it.
+pub struct OutgoingMessage {
+    pub userid: i32,
+    pub text: String,
+    /// Where the message is sent, the main chat, a channel the user is a member of or another user.
+    pub conversation: Conversation,
+    /// The key the client sent the message with, so sending it again does not create a second message.
+    pub idempotency_key: Option<String>,
+    /// The files sent along with the message. They are stored already, only their rows are written with it.
+    pub attachments: Vec<StoredFile>,
+    /// The message this one is a copy of, if it is forwarded.
+    pub forwarded: Option<ForwardedFrom>,
+}
+
+/// The original of a forwarded message and its author. Copies of copies point to the first message.
+#[derive(Debug, Clone, Copy)]
+pub struct ForwardedFrom {
+    pub message_id: i32,
+    pub userid: i32,
+}
+
+/// A file uploaded with a message, stored in the attachment directory of the server under its key, which is the hash
+/// of its content.
+pub struct StoredFile {
+    pub name: String,
+    pub mime: String,
+    pub size: i64,
+    pub key: String,
+}
+
+/// A notification about a new message, to be pushed to the phone of a user.
+pub struct PushNotification {
+    pub userid: i32,
+    pub settings: PushSettings,
+    /// Who sent the message, and where if it was not sent to the user directly.
+    pub title: String,
+    pub text: String,
+}
+
+/// A new message, to be posted to a webhook.
+pub struct WebhookDelivery {
+    pub webhookid: i32,
+    pub url: String,
+    pub payload: WebhookPayload,
+}
+
+/// A message that was sent, or had been sent before with the same idempotency key.
+pub struct SentMessage {
+    pub message: Message,
+    /// Whether the message had been sent before, so nothing new was written.
+    pub repeated: bool,
+}
+
+/// What happened on the server within a period, summed up for the admins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Digest {
+    /// Accounts made, guests included. Accounts older than the server keeping track of it are not counted.
+    pub registrations: i64,
+    pub messages: i64,
+    /// Users who used the server, as far as it noted it.
+    pub active_users: i64,
+    /// Entries written to the audit log, like bans, kicks and role changes.
+    pub moderation_actions: i64,
+}
+
+/// The user doing something that needs a permission, along with their role. The role comes from when the user was
+/// authenticated, so checking the permission does not look them up again.
+#[derive(Debug, Clone, Copy)]
+pub struct Actor {
+    pub id: i32,
+    pub role: Role,
+}
+
+pub struct ChatApp {
+    db_connection: Pool<ConnectionManager<SqliteConnection>>,
+    pool_metrics: Arc<PoolMetrics>,
+    active_logins: Vec<ActiveLogin>,
+    config: Config,
+    /// When each guest last sent a message, as guests may only send one every so often.
+    last_guest_messages: HashMap<i32, SystemTime>,
+    /// How many event streams each user has open.
+    open_streams: HashMap<i32, usize>,
+    /// The users that were online when presence was last checked, to tell who came or went since.
+    online: HashSet<i32>,
+    /// When it was last noted that each user was active.
+    last_activity: HashMap<i32, SystemTime>,
+}
+
+impl ChatApp {
+    /// Create a new `ChatApp` instance using the Sqlite database at the given path.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if connecting to the database fails.
+    pub fn new(config: Config, database: &Path) -> Result<Self, AppError> {
+        let pool_metrics = Arc::new(PoolMetrics::default());
+        Ok(ChatApp {
+            db_connection: get_connection_pool(
+                &database.to_string_lossy(),
+                &config.pool,
+                &pool_metrics,
+            )?,
+            pool_metrics,
+            active_logins: Vec::new(),
+            config,
+            last_guest_messages: HashMap::new(),
+            open_streams: HashMap::new(),
+            online: HashSet::new(),
+            last_activity: HashMap::new(),
+        })
+    }
+
+    /// Returns how busy the pool of database connections is and how long getting a connection from it took so far.
+    pub fn pool_status(&self) -> PoolStatus {
+        self.pool_metrics
+            .status(self.db_connection.state(), self.db_connection.max_size())
+    }
+
+    /// Register a new user, returning it. If the server has terms of service, the version the user accepted must
+    /// be the current one, and the acceptance is stored along with the user. If registering needs an invite, the
+    /// code is used up by it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the current terms were not accepted, the invite code is not valid or
+    /// registering the user failed.
+    pub fn register(
+        &mut self,
+        username: &str,
+        password: &str,
+        accepted_terms: Option<&str>,
+        invite_code: Option<&str>,
+    ) -> Result<User, AppError> {
+        let terms = self.check_terms(accepted_terms)?;
+        let invite_required = self.config.invites.required;
+        let conn = &mut self.db_connection.get()?;
+        let user = conn.transaction(|conn| {
+            create_user(conn, username)?;
+            set_password(conn, username, password)?;
+            let user = get_user_by_name(conn, username)?;
+            if let Some(version) = terms {
+                accept_terms(conn, user.id, version)?;
+            }
+            if invite_required {
+                consume_invite(conn, invite_code.ok_or(DbError::InviteInvalid)?, user.id)?;
+            }
+            Ok::<_, DbError>(user)
+        })?;
+        Ok(user)
+    }
+
+    /// Creates a guest account with a generated name and logs in as it. The account and its login expire after
+    /// the configured lifetime, and guests can not log in again. Returns the guest, its login token and when it
+    /// expires.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if guest accounts are disabled, the current terms of service were not
+    /// accepted or the account could not be created.
+    pub fn create_guest(
+        &mut self,
+        accepted_terms: Option<&str>,
+    ) -> Result<(User, LoginToken, DateTime<Utc>), AppError> {
+        if !self.config.guests.enabled {
+            return Err(AppError::GuestsDisabled);
+        }
+        let terms = self.check_terms(accepted_terms)?;
+        let lifetime = self.config.guests.lifetime();
+
+        let conn = &mut self.db_connection.get()?;
+        let (user, valid_until) = conn.transaction(|conn| {
+            let (user, valid_until) = create_guest(conn, lifetime)?;
+            if let Some(version) = terms {
+                accept_terms(conn, user.id, version)?;
+            }
+            Ok::<_, DbError>((user, valid_until))
+        })?;
+
+        let mut active_login = ActiveLogin::new(&user.username, &self.config.session);
+        active_login.limit_lifetime(SystemTime::now() + lifetime);
+        let login_token = active_login.token.clone();
+        self.active_logins.push(active_login);
+
+        Ok((user, login_token, valid_until))
+    }
+
+    /// Turns the guest account logged in with that token into a full account with the given name and password.
+    /// The account keeps its id, its messages and its login, which no longer expires with the guest account.
+    /// Returns the account as it was before and after, so the new name can be announced.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token is not valid, the account is not a guest account, the name
+    /// is already in use, registering needs an invite and the code is not valid or the account could not be
+    /// updated. Nothing is changed in that case.
+    pub fn claim_guest_account(
+        &mut self,
+        login_token: &LoginToken,
+        credentials: &Credentials,
+    ) -> Result<(User, User), AppError> {
+        let guest = self.get_user_for_token(login_token)?;
+        let conn = &mut self.db_connection.get()?;
+        if !is_guest(conn, guest.id)? {
+            return Err(AppError::NotAGuest);
+        }
+
+        let user = conn.transaction(|conn| {
+            // Guests may keep their generated name
+            if credentials.username != guest.username {
+                change_username(conn, &guest.username, &credentials.username)?;
+            }
+            set_password(conn, &credentials.username, &credentials.password)?;
+            delete_guest(conn, guest.id)?;
+            // Claiming makes a full account, which needs an invite like registering does
+            if self.config.invites.required {
+                let code = credentials
+                    .invite_code
+                    .as_deref()
+                    .ok_or(DbError::InviteInvalid)?;
+                consume_invite(conn, code, guest.id)?;
+            }
+            get_user_by_name(conn, &credentials.username)
+        })?;
+
+        let expires_at = SystemTime::now() + self.config.session.max_lifetime();
+        for login in &mut self.active_logins {
+            if login.username == guest.username {
+                login.username = user.username.clone();
+                login.expires_at = expires_at;
+            }
+        }
+        self.last_guest_messages.remove(&guest.id);
+
+        Ok((guest, user))
+    }
+
+    /// Returns the version of the terms of service that has to be accepted, if the server has any.
+    fn check_terms(&self, accepted_terms: Option<&str>) -> Result<Option<&str>, AppError> {
+        let terms = self
+            .config
+            .terms
+            .as_ref()
+            .map(|terms| terms.version.as_str());
+        if terms.is_some() && accepted_terms != terms {
+            return Err(AppError::TermsNotAccepted);
+        }
+        Ok(terms)
+    }
+
+    /// Login as the user, returning a `LoginToken` for further operations.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `AppError::UserBanned` if the password is right but the user is banned, or an error
+    /// if the authentication failed.
+    pub fn login(&mut self, username: &str, password: &str) -> Result<LoginToken, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        if !check_password(conn, username, password)? {
+            return Err(AppError::LoginFailed);
+        }
+        // Only told after the password was right, so nobody learns who is banned by guessing names
+        if is_banned(conn, username)? {
+            return Err(AppError::UserBanned);
+        }
+        let active_login = ActiveLogin::new(username, &self.config.session);
+        let login_token = active_login.token.clone();
+
+        self.active_logins.push(active_login);
+
+        Ok(login_token)
+    }
+
+    /// Issues a long-lived refresh token for the user logged in with that token.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the login token is not valid, belongs to an admin acting as the user or
+    /// the refresh token could not be stored.
+    pub fn create_refresh_token(
+        &mut self,
+        login_token: &LoginToken,
+    ) -> Result<RefreshToken, AppError> {
+        let user = self.get_user_for_token(login_token)?;
+        if self
+            .find_login(login_token)
+            .is_some_and(|login| login.impersonator.is_some())
+        {
+            return Err(AppError::PermissionDenied);
+        }
+        let conn = &mut self.db_connection.get()?;
+        Ok(create_refresh_token(
+            conn,
+            user.id,
+            self.config.session.refresh_lifetime(),
+        )?)
+    }
+
+    /// Exchanges a refresh token for a new login and refresh token. The given refresh token can not be used again.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the refresh token is not valid or has expired.
+    pub fn refresh_login(
+        &mut self,
+        refresh_token: &RefreshToken,
+    ) -> Result<(LoginToken, RefreshToken), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let userid = consume_refresh_token(conn, refresh_token)?;
+        let user = get_user_by_id(conn, userid)?;
+        if is_banned(conn, &user.username)? {
+            return Err(DbError::RefreshTokenInvalid.into());
+        }
+        let refresh_token =
+            create_refresh_token(conn, user.id, self.config.session.refresh_lifetime())?;
+
+        let active_login = ActiveLogin::new(&user.username, &self.config.session);
+        let login_token = active_login.token.clone();
+        self.active_logins.push(active_login);
+
+        Ok((login_token, refresh_token))
+    }
+
+    /// Logout the user, invalidating the token.
+    pub fn logout(&mut self, login_token: &LoginToken) {
+        for (index, login) in self.active_logins.iter().enumerate() {
+            if login.token == *login_token {
+                self.active_logins.remove(index);
+                break;
+            }
+        }
+    }
+
+    /// Send a message as the user with that id. The user is expected to have been authenticated beforehand.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the messaged could not be sent.
+    pub fn send_message(&mut self, userid: i32, message: &str) -> Result<Message, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        self.check_sending(conn, userid)?;
+        Ok(create_message(conn, message, userid)?)
+    }
+
+    /// Sends a batch of messages, each as the user with the id it comes with and to the conversation it names. The
+    /// users are expected to have been authenticated beforehand. All messages are written in one transaction, which
+    /// is a lot faster than sending them one at a time. Each message is checked against the limits of its user on its
+    /// own, counting the messages before it in the batch, and the results are returned in the same order. A message
+    /// whose user already sent one with the same idempotency key is not sent again, its result is the message sent
+    /// back then.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no connection to the database could be made or the transaction failed.
+    /// Messages that could not be sent on their own, like to a channel the user is not a member of, get an error in
+    /// their result instead.
+    pub fn send_messages(
+        &mut self,
+        batch: &[OutgoingMessage],
+    ) -> Result<Vec<Result<SentMessage, AppError>>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        // Immediate, as every message in it looks up the last sequence number before it is written
+        let results = conn.immediate_transaction(|conn| {
+            let results = batch
+                .iter()
+                .map(|outgoing| {
+                    // In a savepoint of its own, so a message failing halfway leaves nothing of it behind, while the
+                    // others in the batch are still written
+                    conn.transaction(|conn| self.send_batched(conn, outgoing))
+                })
+                .collect();
+            Ok::<_, DbError>(results)
+        })?;
+        Ok(results)
+    }
+
+    /// Writes a message of a batch sent with `send_messages`, inside its transaction.
+    fn send_batched(
+        &mut self,
+        conn: &mut SqliteConnection,
+        outgoing: &OutgoingMessage,
+    ) -> Result<SentMessage, AppError> {
+        let key = outgoing.idempotency_key.as_deref();
+        if let Some(key) = key {
+            if let Some(message) = get_keyed_message(conn, outgoing.userid, key)? {
+                return Ok(SentMessage {
+                    message,
+                    repeated: true,
+                });
+            }
+        }
+        check_access(conn, outgoing.userid, outgoing.conversation)?;
+        if let Some(message) = self.check_duplicates(conn, outgoing)? {
+            return Ok(SentMessage {
+                message,
+                repeated: true,
+            });
+        }
+        check_slow_mode(conn, outgoing.userid, outgoing.conversation)?;
+        self.check_sending(conn, outgoing.userid)?;
+        let mut message = match outgoing.forwarded {
+            Some(original) => forward_message(
+                conn,
+                &outgoing.text,
+                outgoing.userid,
+                outgoing.conversation,
+                original,
+            )?,
+            None => create_conversation_message(
+                conn,
+                &outgoing.text,
+                outgoing.userid,
+                outgoing.conversation,
+            )?,
+        };
+        if let Some(key) = key {
+            store_message_key(conn, outgoing.userid, key, message.id)?;
+        }
+        if !outgoing.attachments.is_empty() {
+            create_attachments(conn, message.id, &outgoing.attachments)?;
+            message.attachments = get_attachments(conn, message.id)?;
+        }
+        Ok(SentMessage {
+            message,
+            repeated: false,
+        })
+    }
+
+    /// Makes a copy of a message for the user with that id to send to the target, through `send_messages` like any
+    /// other message. The copy keeps pointing to the original message and its author, also when forwarding a message
+    /// that was forwarded itself.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message does not exist or the user may not read it.
+    pub fn forward_message(
+        &mut self,
+        userid: i32,
+        message_id: i32,
+        target: ForwardTarget,
+        idempotency_key: Option<String>,
+    ) -> Result<OutgoingMessage, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let original = get_message(conn, message_id)?;
+        check_visible(conn, userid, &original)?;
+        Ok(OutgoingMessage {
+            userid,
+            text: original.messagetext,
+            conversation: Conversation::from(target),
+            idempotency_key,
+            attachments: Vec::new(),
+            forwarded: Some(ForwardedFrom {
+                message_id: original.forwardedid.unwrap_or(original.id),
+                userid: original.forwardeduserid.unwrap_or(original.userid),
+            }),
+        })
+    }
+
+    /// Checks whether the user may send a message now, as guests have to wait between messages and everyone has
+    /// a quota.
+    fn check_sending(&mut self, conn: &mut SqliteConnection, userid: i32) -> Result<(), AppError> {
+        if is_guest(conn, userid)? {
+            let now = SystemTime::now();
+            let interval = self.config.guests.message_interval();
+            if let Some(last) = self.last_guest_messages.get(&userid) {
+                if now.duration_since(*last).unwrap_or_default() < interval {
+                    return Err(AppError::RateLimited);
+                }
+            }
+            self.last_guest_messages.insert(userid, now);
+        }
+        for (period, quota) in self.message_quotas(conn, userid)? {
+            if quota.available_at.is_some() {
+                return Err(AppError::QuotaExceeded { period, quota });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether the user sent the text of the message to its conversation too often lately. Returns the latest
+    /// copy if the message is to be squashed into it instead of being sent.
+    fn check_duplicates(
+        &self,
+        conn: &mut SqliteConnection,
+        outgoing: &OutgoingMessage,
+    ) -> Result<Option<Message>, AppError> {
+        let spam = &self.config.spam;
+        if spam.max_copies == 0 {
+            return Ok(None);
+        }
+        let window = chrono::Duration::from_std(spam.window()).unwrap_or(chrono::Duration::zero());
+        let recent = get_recent_messages(
+            conn,
+            outgoing.userid,
+            outgoing.conversation,
+            Utc::now() - window,
+        )?;
+        let Some(latest) = spam::repeated(
+            &outgoing.text,
+            recent,
+            |message| message.messagetext.as_str(),
+            spam.max_copies,
+        ) else {
+            return Ok(None);
+        };
+        match spam.action {
+            SpamAction::Reject => Err(AppError::DuplicateMessage),
+            SpamAction::Squash => Ok(Some(latest)),
+        }
+    }
+
+    /// Gets how much of their quotas the user has used up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the messages of the user could not be counted.
+    pub fn get_limits(&mut self, userid: i32) -> Result<Limits, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let mut limits = Limits::default();
+        for (period, quota) in self.message_quotas(conn, userid)? {
+            match period {
+                QuotaPeriod::Minute => limits.messages_per_minute = Some(quota),
+                QuotaPeriod::Day => limits.messages_per_day = Some(quota),
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Counts the messages of the user against each message quota that is turned on.
+    fn message_quotas(
+        &self,
+        conn: &mut SqliteConnection,
+        userid: i32,
+    ) -> Result<Vec<(QuotaPeriod, Quota)>, DbError> {
+        let now = Utc::now();
+        let limits = [
+            (QuotaPeriod::Minute, self.config.quotas.messages_per_minute),
+            (QuotaPeriod::Day, self.config.quotas.messages_per_day),
+        ];
+        let mut quotas = Vec::new();
+        for (period, limit) in limits.into_iter().filter(|(_, limit)| *limit > 0) {
+            let since = now - period.duration();
+            let dates = message_dates_since(conn, userid, since, limit)?;
+            let used = u32::try_from(dates.len()).unwrap_or(u32::MAX);
+            // Once the oldest of the counted messages leaves the period, there is room for another one
+            let available_at = (used >= limit)
+                .then(|| dates.first().map(|date| *date + period.duration()))
+                .flatten();
+            quotas.push((
+                period,
+                Quota {
+                    limit,
+                    used,
+                    available_at,
+                },
+            ));
+        }
+        Ok(quotas)
+    }
+
+    /// Get the messages of the main chat to show the user with that id, leaving out those of the users they blocked.
+    /// The user is expected to have been authenticated beforehand. Up to `limit`
+    /// messages are returned, or the configured page size if not given, but never more than the configured maximum.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the messages could not be retrieved.
+    pub fn get_messages(
+        &mut self,
+        userid: i32,
+        filter: &MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, AppError> {
+        let limit = self.page_size(filter, limit);
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_conversation_page(
+            conn,
+            Conversation::Chat,
+            userid,
+            filter,
+            limit,
+        )?)
+    }
+
+    /// How many messages to return for the filter, if `limit` were asked for. `MessageFilter::Latest` asks for its
+    /// count unless a limit is given.
+    fn page_size(&self, filter: &MessageFilter, limit: Option<u32>) -> i64 {
+        let requested = match *filter {
+            MessageFilter::Latest(count) => limit.or(Some(count)),
+            _ => limit,
+        };
+        i64::from(self.config.history.page_size(requested))
+    }
+
+    /// Like `get_messages`, but with a custom page size, for reading through larger parts of the history.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the messages could not be retrieved.
+    pub fn get_message_page(
+        &mut self,
+        userid: i32,
+        filter: &MessageFilter,
+        limit: i64,
+    ) -> Result<Vec<Message>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_conversation_page(
+            conn,
+            Conversation::Chat,
+            userid,
+            filter,
+            limit,
+        )?)
+    }
+
+    /// Gets the message with that id, along with the messages sent before and after it in the same conversation, as
+    /// seen by the user with that id. `around` asks for that many messages on each side, up to the largest page the
+    /// server returns, and is a page if not given.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message does not exist, the user may not read it or the messages
+    /// could not be retrieved.
+    pub fn get_message_context(
+        &mut self,
+        userid: i32,
+        message_id: i32,
+        around: Option<u32>,
+    ) -> Result<MessageContext, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let mut message = get_message(conn, message_id)?;
+        check_visible(conn, userid, &message)?;
+        fill_attachments(conn, std::slice::from_mut(&mut message))?;
+        let conversation = Conversation::of(&message, userid);
+        let limit = i64::from(self.config.history.page_size(around));
+        let mut before = get_conversation_page(
+            conn,
+            conversation,
+            userid,
+            &MessageFilter::BeforeSeq(message.seq),
+            limit,
+        )?;
+        before.reverse();
+        let after = get_conversation_page(
+            conn,
+            conversation,
+            userid,
+            &MessageFilter::AfterSeq(message.seq),
+            limit,
+        )?;
+        Ok(MessageContext {
+            before,
+            message,
+            after,
+        })
+    }
+
+    /// Searches the messages the user with that id may read for the words of the query, a page at a time and the
+    /// newest first. Filters in the query, as read by `SearchQuery::parse`, take the place of those in `filter`.
+    /// Without words, every message passing the filters is found.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the search has neither words nor filters or can not be read, the
+    /// cursor is not valid, a user or channel named in the query does not exist, the user may not read the
+    /// conversation in the filter or the messages could not be searched.
+    pub fn search_messages(
+        &mut self,
+        userid: i32,
+        query: &str,
+        filter: &SearchFilter,
+        request: &PageRequest,
+    ) -> Result<Page<Message>, AppError> {
+        let query = SearchQuery::parse(query)?;
+        let before = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        let mut filter = *filter;
+        if let Some(name) = &query.from {
+            filter.from = Some(get_user_by_name(conn, name)?.id);
+        }
+        if let Some(name) = &query.channel {
+            filter.conversation = Some(Conversation::Channel(get_channel_by_name(conn, name)?.id));
+        }
+        filter.before = query.before.or(filter.before);
+        filter.after = query.after.or(filter.after);
+        filter.has_attachment |= query.has_attachment;
+        let narrowed = SearchFilter {
+            conversation: None,
+            ..filter
+        } != SearchFilter::default();
+        if query.words.is_empty() && !narrowed {
+            return Err(AppError::EmptySearch);
+        }
+        if let Some(conversation) = filter.conversation {
+            check_access(conn, userid, conversation)?;
+        }
+        let found = search_messages(
+            conn,
+            userid,
+            &query.words,
+            &filter,
+            before,
+            i64::from(limit) + 1,
+        )?;
+        Ok(paginate(found, limit, |message| message.id.to_string()))
+    }
+
+    /// Lists the files attached to the message, in the order they were uploaded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message does not exist, the user may not read it or the
+    /// attachments could not be retrieved.
+    pub fn get_attachments(
+        &mut self,
+        userid: i32,
+        message_id: i32,
+    ) -> Result<Vec<Attachment>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let message = get_message(conn, message_id)?;
+        check_visible(conn, userid, &message)?;
+        Ok(get_attachments(conn, message_id)?)
+    }
+
+    /// Gets the attachment with that id along with the key its file is stored under, if the user with that id may
+    /// read the message it is attached to.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the attachment does not exist or the user may not read its message.
+    pub fn get_attachment(
+        &mut self,
+        userid: i32,
+        attachment_id: i32,
+    ) -> Result<(Attachment, String), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let (attachment, key) = get_attachment(conn, attachment_id)?;
+        let message = get_message(conn, attachment.messageid)?;
+        // Attachments of messages the user can not see do not exist as far as they are concerned
+        check_visible(conn, userid, &message).map_err(|e| match e {
+            AppError::DatabaseError(DbError::MessageNotFound) => DbError::AttachmentNotFound.into(),
+            e => e,
+        })?;
+        Ok((attachment, key))
+    }
+
+    /// Gets the ones of the storage keys whose files are used by attachments. Files are shared by every attachment
+    /// with the same content, so they can only be removed once none uses them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the files could not be looked up.
+    pub fn get_referenced_files(&mut self, keys: &[String]) -> Result<Vec<String>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_referenced_files(conn, keys)?)
+    }
+
+    /// Finds the keys of the files in the attachment directory that no attachment refers to.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the directory could not be read or the files could not be looked up.
+    pub fn find_unused_files(&mut self, directory: &Path) -> Result<Vec<String>, gc::GcError> {
+        let conn = &mut self.db_connection.get().map_err(DbError::from)?;
+        gc::find_unused(conn, directory)
+    }
+
+    /// Saves the message for the user with that id, so they can find it again. Saving a message again does
+    /// nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message does not exist, the user may not read it or it could not
+    /// be saved.
+    pub fn save_message(&mut self, userid: i32, message_id: i32) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let message = get_message(conn, message_id)?;
+        check_visible(conn, userid, &message)?;
+        Ok(save_message(conn, userid, message_id)?)
+    }
+
+    /// Removes the message from the saved messages of the user with that id.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message could not be removed.
+    pub fn unsave_message(&mut self, userid: i32, message_id: i32) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(unsave_message(conn, userid, message_id)?)
+    }
+
+    /// Gets a page of the messages the user with that id saved, the most recently saved first.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the cursor is not valid or the messages could not be retrieved.
+    pub fn get_saved_messages(
+        &mut self,
+        userid: i32,
+        request: &PageRequest,
+    ) -> Result<Page<SavedMessage>, AppError> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(parse_saved_cursor(cursor).ok_or(AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        let saved = get_saved_messages(conn, userid, after, i64::from(limit) + 1)?;
+        Ok(paginate(saved, limit, |saved| {
+            format!(
+                "{}_{}",
+                saved.saved.timestamp_nanos_opt().unwrap_or_default(),
+                saved.message.id
+            )
+        }))
+    }
+
+    /// Gets a page of how many messages the user with that id has not read yet in each conversation they can read,
+    /// and whether they were mentioned in any of them. These are the main chat, the channels they joined and the
+    /// users they exchanged direct messages with, in that order, the channels and users by id.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the cursor is not valid, the user does not exist or the messages could
+    /// not be counted.
+    pub fn get_overview(
+        &mut self,
+        userid: i32,
+        request: &PageRequest,
+    ) -> Result<Overview, AppError> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(Conversation::from_key(cursor).ok_or(AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        // One more than asked for, to tell whether another page follows
+        let wanted = limit as usize + 1;
+        let conn = &mut self.db_connection.get()?;
+        let user = get_user_by_id(conn, userid)?;
+        let mut conversations = Vec::with_capacity(wanted);
+        if after.is_none() {
+            conversations.push(Conversation::Chat);
+        }
+        if !matches!(after, Some(Conversation::Direct(_))) {
+            let after = match after {
+                Some(Conversation::Channel(channel)) => Some(channel),
+                _ => None,
+            };
+            let left = (wanted - conversations.len()) as i64;
+            let channels = get_channel_page(conn, Some(userid), after, left)?;
+            conversations.extend(
+                channels
+                    .iter()
+                    .map(|channel| Conversation::Channel(channel.id)),
+            );
+        }
+        if conversations.len() < wanted {
+            let after = match after {
+                Some(Conversation::Direct(other)) => Some(other),
+                _ => None,
+            };
+            let left = (wanted - conversations.len()) as i64;
+            let partners = get_direct_partners(conn, userid, after, left)?;
+            conversations.extend(partners.into_iter().map(Conversation::Direct));
+        }
+        let page = paginate(conversations, limit, |conversation| conversation.key());
+        let conversations = page
+            .items
+            .into_iter()
+            .map(|conversation| {
+                let last_read = get_read_marker(conn, userid, conversation)?;
+                let (unread, mentioned) = count_unread(conn, &user, conversation, last_read)?;
+                Ok(ConversationOverview {
+                    conversation,
+                    last_read,
+                    unread,
+                    mentioned,
+                })
+            })
+            .collect::<Result<_, DbError>>()?;
+        Ok(Overview {
+            conversations,
+            next: page.next,
+        })
+    }
+
+    /// Gets everything a client needs to show a session for the user with that id at once, so it does not have
+    /// to ask for each part separately. Only the first page of the conversations is included, along with the users
+    /// who wrote the messages in them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user does not exist or any part could not be retrieved.
+    pub fn initial_sync(&mut self, userid: i32) -> Result<InitialSync, AppError> {
+        let user = self.get_user_by_id(userid)?;
+        let messages = self.get_messages(userid, &MessageFilter::Before(Utc::now()), None)?;
+        let overview = self.get_overview(userid, &PageRequest::default())?;
+        let conversations: Vec<ConversationSync> = overview
+            .conversations
+            .into_iter()
+            .map(|overview| {
+                let messages = match overview.conversation {
+                    Conversation::Chat => messages.clone(),
+                    conversation => {
+                        let filter = MessageFilter::Before(Utc::now());
+                        self.get_conversation_messages(userid, conversation, &filter, None)?
+                    }
+                };
+                Ok::<_, AppError>(ConversationSync { overview, messages })
+            })
+            .collect::<Result<_, _>>()?;
+        let mut authors: Vec<i32> = conversations
+            .iter()
+            .flat_map(|conversation| &conversation.messages)
+            .flat_map(|message| [Some(message.userid), message.forwardeduserid])
+            .flatten()
+            .collect();
+        authors.sort_unstable();
+        authors.dedup();
+        let users = {
+            let conn = &mut self.db_connection.get()?;
+            get_users_by_ids(conn, &authors)?
+        };
+        Ok(InitialSync {
+            user,
+            conversations,
+            next: overview.next,
+            users,
+            limits: self.get_limits(userid)?,
+            drafts: self.get_drafts(userid)?,
+        })
+    }
+
+    /// Gets the drafts of the user with that id, the most recently changed first.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the drafts could not be retrieved.
+    pub fn get_drafts(&mut self, userid: i32) -> Result<Vec<Draft>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_drafts(conn, userid)?)
+    }
+
+    /// Stores what the user with that id has written in the conversation so far, replacing their previous draft
+    /// there. Empty text removes the draft.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not send in the conversation or the draft could not be
+    /// stored.
+    pub fn save_draft(
+        &mut self,
+        userid: i32,
+        conversation: Conversation,
+        text: &str,
+    ) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        if text.is_empty() {
+            return Ok(delete_draft(conn, userid, conversation)?);
+        }
+        check_access(conn, userid, conversation)?;
+        Ok(save_draft(conn, userid, conversation, text)?)
+    }
+
+    /// Gets where notifications for the user with that id are pushed to, if they set that up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the settings could not be retrieved.
+    pub fn get_push_settings(&mut self, userid: i32) -> Result<Option<PushSettings>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_push_settings(conn, userid)?.and_then(push_settings))
+    }
+
+    /// Sets where notifications for the user with that id are pushed to, replacing what they set before.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the url is not an http or https url, Gotify is chosen without a
+    /// token or the settings could not be stored.
+    pub fn set_push_settings(
+        &mut self,
+        userid: i32,
+        settings: &PushSettings,
+    ) -> Result<(), AppError> {
+        match reqwest::Url::parse(&settings.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => {
+                return Err(AppError::InvalidPushSettings(
+                    "The url has to start with http:// or https://.",
+                ))
+            }
+        }
+        if settings.provider == PushProvider::Gotify
+            && settings.token.as_deref().is_none_or(str::is_empty)
+        {
+            return Err(AppError::InvalidPushSettings(
+                "Gotify needs the token of an application.",
+            ));
+        }
+        let conn = &mut self.db_connection.get()?;
+        let entry = PushSettingsEntry {
+            userid,
+            provider: settings.provider.key().to_string(),
+            url: settings.url.clone(),
+            token: settings.token.clone().filter(|token| !token.is_empty()),
+            mentions: settings.mentions,
+            directmessages: settings.direct_messages,
+        };
+        Ok(set_push_settings(conn, &entry)?)
+    }
+
+    /// Stops pushing notifications for the user with that id.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the settings could not be removed.
+    pub fn delete_push_settings(&mut self, userid: i32) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(delete_push_settings(conn, userid)?)
+    }
+
+    /// Works out whose phones are told about the message: the recipient of a direct message, and the users it
+    /// mentions that may read it. Only users who asked for that kind of notification are included, never the
+    /// sender.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sender, the channel or the push settings could not be retrieved.
+    pub fn push_notifications(
+        &mut self,
+        message: &Message,
+    ) -> Result<Vec<PushNotification>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let sender = get_user_by_id(conn, message.userid)?.username;
+        let title = match (message.channelid, message.recipientid) {
+            (Some(channel), _) => format!("{sender} in #{}", get_channel(conn, channel)?.name),
+            (None, Some(_)) => sender,
+            (None, None) => format!("{sender} in {}", self.config.name),
+        };
+        let mut notifications = Vec::new();
+        for (entry, username) in get_push_recipients(conn, message.userid)? {
+            let wanted = match (message.channelid, message.recipientid) {
+                _ if has_blocked(conn, entry.userid, message.userid)? => false,
+                (None, Some(recipient)) => entry.directmessages && entry.userid == recipient,
+                (Some(channel), _) => {
+                    entry.mentions
+                        && mentions(&message.messagetext, &username)
+                        && get_member_notifications(conn, channel, entry.userid)?
+                            .is_some_and(|level| level != NotificationLevel::Muted)
+                }
+                (None, None) => entry.mentions && mentions(&message.messagetext, &username),
+            };
+            let userid = entry.userid;
+            if let Some(settings) = push_settings(entry).filter(|_| wanted) {
+                notifications.push(PushNotification {
+                    userid,
+                    settings,
+                    title: title.clone(),
+                    text: message.messagetext.clone(),
+                });
+            }
+        }
+        Ok(notifications)
+    }
+
+    /// Works out which webhooks are told about the message, along with what they are sent. Direct messages are
+    /// never posted to any.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sender or the webhooks could not be retrieved.
+    pub fn webhook_deliveries(
+        &mut self,
+        message: &Message,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        if message.recipientid.is_some() {
+            return Ok(Vec::new());
+        }
+        let conn = &mut self.db_connection.get()?;
+        let webhooks = get_webhooks(conn)?;
+        if webhooks.is_empty() {
+            return Ok(Vec::new());
+        }
+        let payload = WebhookPayload::MessageCreated {
+            message: message.clone(),
+            username: get_user_by_id(conn, message.userid)?.username,
+        };
+        Ok(webhooks
+            .into_iter()
+            .map(|webhook| WebhookDelivery {
+                webhookid: webhook.id,
+                url: webhook.url,
+                payload: payload.clone(),
+            })
+            .collect())
+    }
+
+    /// Marks the messages of the conversation as read by the user with that id, up to the given message. Returns
+    /// whether the marker moved, as marking an older message as read does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not read the conversation, the message is not part of it
+    /// or the marker could not be stored.
+    pub fn mark_read(&mut self, userid: i32, marker: &ReadMarker) -> Result<bool, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_access(conn, userid, marker.conversation)?;
+        let message = get_message(conn, marker.message_id)?;
+        if Conversation::of(&message, userid) != marker.conversation {
+            return Err(DbError::MessageNotFound.into());
+        }
+        Ok(set_read_marker(
+            conn,
+            userid,
+            marker.conversation,
+            marker.message_id,
+        )?)
+    }
+
+    /// Lists how far the users read the conversation, as seen by the user with that id. Direct messages only list
+    /// the two users writing them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not read the conversation or the markers could not be
+    /// loaded.
+    pub fn get_read_receipts(
+        &mut self,
+        userid: i32,
+        conversation: Conversation,
+    ) -> Result<Vec<ReadReceipt>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_access(conn, userid, conversation)?;
+        let markers = match conversation {
+            Conversation::Chat | Conversation::Channel(_) => {
+                get_read_markers(conn, &conversation.key())?
+            }
+            Conversation::Direct(other) => {
+                let mut markers = Vec::new();
+                if let Some(read) = get_read_marker(conn, userid, conversation)? {
+                    markers.push((userid, read));
+                }
+                // The other user keeps their marker under the conversation with this user
+                if let Some(read) = get_read_marker(conn, other, Conversation::Direct(userid))? {
+                    markers.push((other, read));
+                }
+                markers
+            }
+        };
+        Ok(markers
+            .into_iter()
+            .map(|(reader, message_id)| ReadReceipt {
+                userid: reader,
+                message_id,
+            })
+            .collect())
+    }
+
+    /// Whether the viewer may see that the reader read the conversation, which is named as the reader sees it.
+    /// Returns the conversation as the viewer sees it if so.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel membership could not be looked up.
+    pub fn receipt_for(
+        &mut self,
+        viewer: i32,
+        reader: i32,
+        conversation: Conversation,
+    ) -> Result<Option<Conversation>, AppError> {
+        match conversation {
+            Conversation::Chat => Ok(Some(conversation)),
+            Conversation::Channel(channel) => {
+                let conn = &mut self.db_connection.get()?;
+                Ok(is_channel_member(conn, channel, viewer)?.then_some(conversation))
+            }
+            Conversation::Direct(other) if viewer == reader => {
+                Ok(Some(Conversation::Direct(other)))
+            }
+            Conversation::Direct(other) if viewer == other => {
+                Ok(Some(Conversation::Direct(reader)))
+            }
+            Conversation::Direct(_) => Ok(None),
+        }
+    }
+
+    /// Creates a channel, with the user with that id as its first member.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a channel with that name already exists or it could not be created.
+    pub fn create_channel(
+        &mut self,
+        userid: i32,
+        channel: &CreateChannel,
+    ) -> Result<Channel, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let created = conn.transaction(|conn| {
+            let created = create_channel(conn, &channel.name, &channel.topic)?;
+            join_channel(conn, created.id, userid)?;
+            Ok::<_, DbError>(created)
+        })?;
+        Ok(created)
+    }
+
+    /// Adds the user with that id to the members of the channel. Joining a channel again does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel does not exist or the membership could not be stored.
+    pub fn join_channel(&mut self, userid: i32, channel_id: i32) -> Result<Channel, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let channel = get_channel(conn, channel_id)?;
+        join_channel(conn, channel_id, userid)?;
+        Ok(channel)
+    }
+
+    /// Removes the user with that id from the members of the channel. Leaving a channel that one is not a member of
+    /// does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the membership could not be removed.
+    pub fn leave_channel(&mut self, userid: i32, channel_id: i32) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(leave_channel(conn, channel_id, userid)?)
+    }
+
+    /// Makes the members of the channel wait that many seconds between their messages, or lets them send as often
+    /// as their quotas allow again with 0. Needs a role allowed to set slow mode.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not set slow mode, the channel does not exist, is not at
+    /// the expected version or could not be changed.
+    pub fn set_slow_mode(
+        &mut self,
+        actor: Actor,
+        channel_id: i32,
+        expected: Option<i32>,
+        seconds: u32,
+    ) -> Result<Channel, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::SetSlowMode)?;
+        check_version(expected, get_channel(conn, channel_id)?.version)?;
+        let seconds = i32::try_from(seconds).unwrap_or(i32::MAX);
+        Ok(set_channel_slowmode(conn, channel_id, seconds)?)
+    }
+
+    /// Changes the topic of the channel. Needs a role allowed to set topics.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not set topics, the channel does not exist, is not at the
+    /// expected version or could not be changed.
+    pub fn set_channel_topic(
+        &mut self,
+        actor: Actor,
+        channel_id: i32,
+        expected: Option<i32>,
+        topic: &str,
+    ) -> Result<Channel, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::SetTopics)?;
+        check_version(expected, get_channel(conn, channel_id)?.version)?;
+        Ok(set_channel_topic(conn, channel_id, topic)?)
+    }
+
+    /// Gets the message of the day.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message of the day could not be retrieved.
+    pub fn get_motd(&mut self) -> Result<Motd, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_motd(conn)?)
+    }
+
+    /// Changes the message of the day, or removes it with an empty text. Needs a role allowed to set it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not set the message of the day, it is not at the expected
+    /// version or could not be changed.
+    pub fn set_motd(
+        &mut self,
+        actor: Actor,
+        expected: Option<i32>,
+        text: &str,
+    ) -> Result<Motd, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::SetMotd)?;
+        check_version(expected, get_motd(conn)?.version)?;
+        Ok(set_motd(conn, text)?)
+    }
+
+    /// Sets how the user with that id is told about new messages in the channel. Only members of the channel can.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel does not exist, the user is not a member of it or the
+    /// setting could not be stored.
+    pub fn set_channel_notifications(
+        &mut self,
+        userid: i32,
+        channel_id: i32,
+        level: NotificationLevel,
+    ) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        get_channel(conn, channel_id)?;
+        if set_member_notifications(conn, channel_id, userid, level)? {
+            Ok(())
+        } else {
+            Err(AppError::NotAMember)
+        }
+    }
+
+    /// Lists the channels the user with that id gets fewer notifications for than every message.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the memberships could not be loaded.
+    pub fn get_channel_notifications(
+        &mut self,
+        userid: i32,
+    ) -> Result<Vec<ChannelNotifications>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_notification_overrides(conn, userid)?)
+    }
+
+    /// Gets a page of the channels ordered by id, or only of the ones the user with that id is a member of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the cursor is not valid or the channels could not be retrieved.
+    pub fn get_channel_page(
+        &mut self,
+        userid: i32,
+        joined: bool,
+        request: &PageRequest,
+    ) -> Result<Page<Channel>, AppError> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        let member = joined.then_some(userid);
+        let channels = get_channel_page(conn, member, after, i64::from(limit) + 1)?;
+        Ok(paginate(channels, limit, |channel| channel.id.to_string()))
+    }
+
+    /// Gets the messages of the channel, like `get_messages` does for the main chat.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel does not exist, the user with that id is not a member of it
+    /// or the messages could not be retrieved.
+    pub fn get_channel_messages(
+        &mut self,
+        userid: i32,
+        channel_id: i32,
+        filter: &MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, AppError> {
+        self.get_conversation_messages(userid, Conversation::Channel(channel_id), filter, limit)
+    }
+
+    /// Gets the direct messages between the user with that id and the other user, like `get_messages` does for the
+    /// main chat.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the other user does not exist or the messages could not be retrieved.
+    pub fn get_direct_messages(
+        &mut self,
+        userid: i32,
+        other: i32,
+        filter: &MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, AppError> {
+        self.get_conversation_messages(userid, Conversation::Direct(other), filter, limit)
+    }
+
+    /// Gets the messages of any conversation the user with that id may read.
+    fn get_conversation_messages(
+        &mut self,
+        userid: i32,
+        conversation: Conversation,
+        filter: &MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, AppError> {
+        let limit = self.page_size(filter, limit);
+        let conn = &mut self.db_connection.get()?;
+        check_access(conn, userid, conversation)?;
+        Ok(get_conversation_page(
+            conn,
+            conversation,
+            userid,
+            filter,
+            limit,
+        )?)
+    }
+
+    /// Returns true if the user with that id may read the message, so it can be passed on to them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel membership could not be looked up.
+    pub fn can_read(&mut self, userid: i32, message: &Message) -> Result<bool, AppError> {
+        let Some(channel) = message.channelid else {
+            return Ok(message
+                .recipientid
+                .is_none_or(|recipient| recipient == userid || message.userid == userid));
+        };
+        let conn = &mut self.db_connection.get()?;
+        Ok(is_channel_member(conn, channel, userid)?)
+    }
+
+    /// Returns true if the message is passed on to the user with that id: they may read it and did not block its
+    /// sender.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel membership or the blocked users could not be looked up.
+    pub fn shows(&mut self, userid: i32, message: &Message) -> Result<bool, AppError> {
+        if !self.can_read(userid, message)? {
+            return Ok(false);
+        }
+        let conn = &mut self.db_connection.get()?;
+        Ok(!has_blocked(conn, userid, message.userid)?)
+    }
+
+    /// Blocks the user with the id `blocked` for the user with the id `userid`, so their messages are no longer shown
+    /// to them. Blocking someone twice does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the users are the same, the one to block does not exist or the block
+    /// could not be stored.
+    pub fn block_user(&mut self, userid: i32, blocked: i32) -> Result<(), AppError> {
+        if userid == blocked {
+            return Err(AppError::BlockSelf);
+        }
+        let conn = &mut self.db_connection.get()?;
+        match get_user_by_id(conn, blocked) {
+            Ok(_) => Ok(block_user(conn, userid, blocked)?),
+            Err(DbError::GenericError(diesel::result::Error::NotFound)) => {
+                Err(DbError::UserNotFound.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Shows the messages of the user with the id `blocked` to the user with the id `userid` again. Unblocking someone
+    /// who is not blocked does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the block could not be removed.
+    pub fn unblock_user(&mut self, userid: i32, blocked: i32) -> Result<(), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(unblock_user(conn, userid, blocked)?)
+    }
+
+    /// Gets a page of the users the user with that id blocked, ordered by id.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the cursor is not valid or the blocked users could not be loaded.
+    pub fn get_blocked_users(
+        &mut self,
+        userid: i32,
+        request: &PageRequest,
+    ) -> Result<Page<User>, AppError> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        let users = get_blocked_users(conn, userid, after, i64::from(limit) + 1)?;
+        Ok(paginate(users, limit, |user| user.id.to_string()))
+    }
+
+    /// Gets the user with that id.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user does not exist.
+    pub fn get_user_by_id(&mut self, id: i32) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_user_by_id(conn, id)?)
+    }
+
+    /// Gets the profile of the user with that id, as the user with the id `viewer` sees it. When the user was last
+    /// seen is left out for others if the user keeps it to themselves.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user does not exist.
+    pub fn get_profile(&mut self, viewer: i32, id: i32) -> Result<Profile, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let mut profile = get_profile(conn, id)?;
+        if !profile.show_last_seen && viewer != id {
+            profile.last_seen = None;
+        }
+        Ok(profile)
+    }
+
+    /// Changes the fields of the profile of the user with that id that the update gives, and returns the profile.
+    /// Display names and bios are trimmed, and display names that are empty afterwards are removed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the display name or the bio is too long, the display name holds
+    /// control characters, the avatar is not an http or https url, the profile is not at the expected version or
+    /// could not be changed.
+    pub fn update_profile(
+        &mut self,
+        userid: i32,
+        expected: Option<i32>,
+        update: &ProfileUpdate,
+    ) -> Result<Profile, AppError> {
+        let displayname = update.displayname.as_ref().map(|name| {
+            name.as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+        });
+        if let Some(Some(name)) = displayname {
+            if name.chars().count() > ProfileUpdate::MAX_DISPLAY_NAME {
+                return Err(AppError::InvalidProfile("The display name is too long."));
+            }
+            if name.chars().any(char::is_control) {
+                return Err(AppError::InvalidProfile(
+                    "The display name can not hold control characters.",
+                ));
+            }
+        }
+        let bio = update.bio.as_deref().map(str::trim);
+        if bio.is_some_and(|bio| bio.chars().count() > ProfileUpdate::MAX_BIO) {
+            return Err(AppError::InvalidProfile("The bio is too long."));
+        }
+        if let Some(Some(avatar)) = &update.avatar {
+            match reqwest::Url::parse(avatar) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => {
+                    return Err(AppError::InvalidProfile(
+                        "The avatar has to start with http:// or https://.",
+                    ))
+                }
+            }
+        }
+        let conn = &mut self.db_connection.get()?;
+        check_version(expected, get_profile(conn, userid)?.version)?;
+        let changes = ProfileChanges {
+            displayname,
+            bio,
+            avatar: update.avatar.as_ref().map(Option::as_deref),
+            showlastseen: update.show_last_seen,
+        };
+        Ok(update_profile(conn, userid, &changes)?)
+    }
+
+    /// Gets all registered users.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the users could not be retrieved.
+    pub fn get_all_users(&mut self) -> Result<Vec<User>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_all_users(conn)?)
+    }
+
+    /// Gets a page of the registered users, ordered by id.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the cursor is not valid or the users could not be retrieved.
+    pub fn get_user_page(&mut self, request: &PageRequest) -> Result<Page<User>, AppError> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        let users = get_user_page(conn, after, i64::from(limit) + 1)?;
+        Ok(paginate(users, limit, |user| user.id.to_string()))
+    }
+
+    /// Gets a page of the users matching the filter in the given order, with whether they are banned, online, when
+    /// they were last active and when their account was made and changed. Only roles that may list users can do this.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not list users, the cursor is not valid or the users could
+    /// not be retrieved.
+    pub fn get_user_directory(
+        &mut self,
+        actor: Actor,
+        sort: UserSort,
+        filter: &UserFilter,
+        request: &PageRequest,
+    ) -> Result<Page<UserEntry>, AppError> {
+        // Other orders than by id can not continue after an item, so the cursor is how many were listed already
+        let offset: i64 = match &request.cursor {
+            Some(cursor) => cursor
+                .parse()
+                .ok()
+                .filter(|offset| *offset >= 0)
+                .ok_or(AppError::InvalidCursor)?,
+            None => 0,
+        };
+        let limit = request.limit();
+        let online = self.online_users()?;
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ListUsers)?;
+        let entries =
+            get_user_directory(conn, sort, filter, &online, offset, i64::from(limit) + 1)?
+                .into_iter()
+                .map(|entry| UserEntry {
+                    online: online.binary_search(&entry.user.id).is_ok(),
+                    user: entry.user,
+                    banned: entry.banned,
+                    last_active: entry.lastactive,
+                    created: entry.createddate,
+                    updated: entry.updateddate,
+                })
+                .collect();
+        let next = offset + i64::from(limit);
+        Ok(paginate(entries, limit, |_| next.to_string()))
+    }
+
+    /// Deletes the message, along with its attachments, as the user with that id. Users may delete their own
+    /// messages, the ones of others need a role that may delete messages. Returns the deleted message.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message does not exist or the user may not read it, the user may
+    /// not delete it or it could not be deleted.
+    pub fn delete_message(&mut self, actor: Actor, message_id: i32) -> Result<Message, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let message = get_message(conn, message_id)?;
+        check_visible(conn, actor.id, &message)?;
+        if message.userid == actor.id {
+            delete_message(conn, message_id)?;
+            return Ok(message);
+        }
+        check_permission(actor, Permission::DeleteMessages)?;
+        conn.transaction(|conn| {
+            delete_message(conn, message_id)?;
+            let details = message.id.to_string();
+            record_audit(
+                conn,
+                actor.id,
+                AuditAction::DeleteMessage,
+                Some(message.userid),
+                &details,
+            )
+        })?;
+        Ok(message)
+    }
+
+    /// Renames the user with the id `target` as the actor, who needs a role that may rename users and outranks the
+    /// one of the target. Their logins stay valid. Returns the user before and after.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either user does not exist, the user may not rename the target, the
+    /// name is already in use or the user could not be renamed.
+    pub fn rename_user(
+        &mut self,
+        actor: Actor,
+        target: i32,
+        name: &str,
+    ) -> Result<(User, User), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let old = check_authority(conn, actor, target, Permission::RenameUsers)?;
+        let renamed = conn.transaction(|conn| {
+            change_username(conn, &old.username, name)?;
+            record_audit(conn, actor.id, AuditAction::RenameUser, Some(target), name)?;
+            get_user_by_id(conn, target)
+        })?;
+        for login in &mut self.active_logins {
+            if login.username == old.username {
+                login.username = renamed.username.clone();
+            }
+        }
+        Ok((old, renamed))
+    }
+
+    /// Bans the user with the id `target` as the actor, who needs a role that may ban users and outranks the one of
+    /// the target. The target is logged out everywhere and can not log in again. Returns the banned user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either user does not exist, the user may not ban the target or the
+    /// target could not be banned.
+    pub fn ban_user(&mut self, actor: Actor, target: i32) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let user = check_authority(conn, actor, target, Permission::BanUsers)?;
+        conn.transaction(|conn| {
+            ban_user(conn, target)?;
+            record_audit(conn, actor.id, AuditAction::BanUser, Some(target), "")
+        })?;
+        self.active_logins
+            .retain(|login| login.username != user.username);
+        Ok(user)
+    }
+
+    /// Lifts the ban of the user with the id `target` as the actor, who needs a role that may
+    /// ban users and outranks the one of the target. The target can log in again. Returns the user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either user does not exist, the user may not unban the target or the
+    /// ban could not be lifted.
+    pub fn unban_user(&mut self, actor: Actor, target: i32) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let user = check_authority(conn, actor, target, Permission::BanUsers)?;
+        conn.transaction(|conn| {
+            unban_user(conn, target)?;
+            record_audit(conn, actor.id, AuditAction::UnbanUser, Some(target), "")
+        })?;
+        Ok(user)
+    }
+
+    /// Logs the user with the id `target` out everywhere as the actor, who needs a role that may
+    /// ban users and outranks the one of the target. Unlike a ban, the target can log in again right away. Returns
+    /// the kicked user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either user does not exist, the user may not kick the target or the
+    /// refresh tokens of the target could not be revoked.
+    pub fn kick_user(&mut self, actor: Actor, target: i32) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let user = check_authority(conn, actor, target, Permission::BanUsers)?;
+        conn.transaction(|conn| {
+            revoke_refresh_tokens(conn, target)?;
+            record_audit(conn, actor.id, AuditAction::KickUser, Some(target), "")
+        })?;
+        self.active_logins
+            .retain(|login| login.username != user.username);
+        Ok(user)
+    }
+
+    /// Gives the user with the id `target` the role as the actor, who needs a role that may
+    /// assign roles. Nobody can change their own role, so the last admin can not lock everyone out.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either user does not exist, the user may not assign roles or the role
+    /// could not be changed.
+    pub fn set_role(&mut self, actor: Actor, target: i32, role: Role) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        if actor.id == target {
+            return Err(AppError::PermissionDenied);
+        }
+        check_permission(actor, Permission::AssignRoles)?;
+        conn.transaction(|conn| {
+            set_role(conn, target, role)?;
+            record_audit(
+                conn,
+                actor.id,
+                AuditAction::SetRole,
+                Some(target),
+                role.key(),
+            )
+        })?;
+        Ok(get_user_by_id(conn, target)?)
+    }
+
+    /// Logs the actor in as the user with the id `target`, to help them or look into a problem.
+    /// Needs a role that may impersonate users and outranks the one of the target. The login is recorded in the
+    /// audit log with the reason, shown to the target in their sessions and expires after the configured time.
+    /// Returns the target, the login token and when it expires.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either user does not exist, the user may not impersonate the target or
+    /// the login could not be recorded.
+    pub fn impersonate(
+        &mut self,
+        actor: Actor,
+        target: i32,
+        reason: &str,
+    ) -> Result<(User, LoginToken, DateTime<Utc>), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let user = check_authority(conn, actor, target, Permission::Impersonate)?;
+        record_audit(
+            conn,
+            actor.id,
+            AuditAction::Impersonate,
+            Some(target),
+            reason,
+        )?;
+
+        let mut active_login = ActiveLogin::new(&user.username, &self.config.session);
+        active_login
+            .limit_lifetime(SystemTime::now() + self.config.session.impersonation_lifetime());
+        active_login.impersonator = Some(actor.id);
+        let login_token = active_login.token.clone();
+        let expires_at = active_login.expires_at.into();
+        self.active_logins.push(active_login);
+
+        Ok((user, login_token, expires_at))
+    }
+
+    /// Lists the logins of the user logged in with that token, the oldest first, including those of admins acting as
+    /// the user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token is not in use or the admins could not be looked up.
+    pub fn get_sessions(&mut self, login_token: &LoginToken) -> Result<Vec<Session>, AppError> {
+        let Some(username) = self.get_username_for_token(login_token) else {
+            return Err(AppError::TokenInvalid);
+        };
+        let conn = &mut self.db_connection.get()?;
+        let mut sessions = Vec::new();
+        for login in self
+            .active_logins
+            .iter()
+            .filter(|login| login.username == username)
+        {
+            let impersonated_by = match login.impersonator {
+                Some(admin) => Some(get_user_by_id(conn, admin)?.username),
+                None => None,
+            };
+            sessions.push(Session {
+                created: login.created.into(),
+                valid_until: login.valid_until.into(),
+                expires_at: login.expires_at.into(),
+                current: login.token == *login_token,
+                impersonated_by,
+            });
+        }
+        sessions.sort_by_key(|session| session.created);
+        Ok(sessions)
+    }
+
+    /// Gets a page of the audit log, the newest entries first, as the user with that id, who needs a role that may
+    /// view it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not view the audit log, the cursor is not valid or the
+    /// entries could not be retrieved.
+    pub fn get_audit_log(
+        &mut self,
+        actor: Actor,
+        request: &PageRequest,
+    ) -> Result<Page<AuditEntry>, AppError> {
+        let before = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ViewAuditLog)?;
+        let entries = get_audit_page(conn, before, i64::from(limit) + 1)?;
+        Ok(paginate(entries, limit, |entry| entry.id.to_string()))
+    }
+
+    /// Returns the id of the channel the digest is posted to and the id of the user posting it, as configured.
+    /// `None` if no digest is configured.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel or the user does not exist.
+    pub fn get_digest_target(&mut self) -> Result<Option<(i32, i32)>, AppError> {
+        let (Some(channel), Some(sender)) =
+            (&self.config.digest.channel, &self.config.digest.sender)
+        else {
+            return Ok(None);
+        };
+        let conn = &mut self.db_connection.get()?;
+        let channel = get_channel_by_name(conn, channel)?;
+        let sender = get_user_by_name(conn, sender)?;
+        Ok(Some((channel.id, sender.id)))
+    }
+
+    /// Counts what happened on the server since the date.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the counts could not be retrieved.
+    pub fn get_digest(&mut self, since: DateTime<Utc>) -> Result<Digest, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        Ok(count_digest(conn, since)?)
+    }
+
+    /// Refuses addresses in a range an admin banned, so nobody registers or logs in from them. Requests whose address
+    /// is not known are let through.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `AppError::AddressBanned` if the address is banned, or an error if the bans could
+    /// not be retrieved.
+    pub fn check_address(&mut self, address: Option<IpAddr>) -> Result<(), AppError> {
+        let Some(address) = address else {
+            return Ok(());
+        };
+        let conn = &mut self.db_connection.get()?;
+        let banned = get_address_bans(conn)?
+            .iter()
+            .filter_map(|ban| ban.network.parse::<Network>().ok())
+            .any(|network| network.contains(address));
+        if banned {
+            Err(AppError::AddressBanned)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Gets a page of the banned ranges of addresses, the oldest first, as the user with that id, who needs a role
+    /// that may ban them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not ban addresses, the cursor is not valid or the bans
+    /// could not be retrieved.
+    pub fn get_address_bans(
+        &mut self,
+        actor: Actor,
+        request: &PageRequest,
+    ) -> Result<Page<AddressBan>, AppError> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::BanAddresses)?;
+        let bans = get_address_ban_page(conn, after, i64::from(limit) + 1)?;
+        Ok(paginate(bans, limit, |ban| ban.id.to_string()))
+    }
+
+    /// Bans a range of addresses, in CIDR notation, as the user with that id, who needs a role that may do so.
+    /// Nobody can register or log in from the range afterwards, but those logged in already stay so. Banning a
+    /// range again returns the ban it already has.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not ban addresses, the range is not valid or the ban
+    /// could not be stored.
+    pub fn ban_address(
+        &mut self,
+        actor: Actor,
+        network: &str,
+        reason: &str,
+    ) -> Result<AddressBan, AppError> {
+        let network = network.parse::<Network>()?.to_string();
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::BanAddresses)?;
+        Ok(conn.transaction(|conn| {
+            if insert_address_ban(conn, actor.id, &network, reason)? {
+                record_audit(conn, actor.id, AuditAction::BanAddress, None, &network)?;
+            }
+            get_address_ban_by_network(conn, &network)
+        })?)
+    }
+
+    /// Lifts the ban with that id as the user with that id, who needs a role that may ban addresses. Returns the ban
+    /// that was lifted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not ban addresses, the ban does not exist or could not be
+    /// removed.
+    pub fn unban_address(&mut self, actor: Actor, ban_id: i32) -> Result<AddressBan, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::BanAddresses)?;
+        Ok(conn.transaction(|conn| {
+            let ban = delete_address_ban(conn, ban_id)?;
+            record_audit(
+                conn,
+                actor.id,
+                AuditAction::UnbanAddress,
+                None,
+                &ban.network,
+            )?;
+            Ok::<_, DbError>(ban)
+        })?)
+    }
+
+    /// Gets a page of the invite codes, the newest first, as the user with that id, who needs a role that may make
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not make invites, the cursor is not valid or the invites
+    /// could not be retrieved.
+    pub fn get_invites(
+        &mut self,
+        actor: Actor,
+        request: &PageRequest,
+    ) -> Result<Page<Invite>, AppError> {
+        let before = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::CreateInvites)?;
+        let invites = get_invites(conn, before, i64::from(limit) + 1)?;
+        Ok(paginate(invites, limit, |invite| invite.id.to_string()))
+    }
+
+    /// Makes a new invite code as the user with that id, who needs a role that may do so. It expires after the
+    /// configured lifetime.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not make invites or the invite could not be stored.
+    pub fn create_invite(&mut self, actor: Actor) -> Result<Invite, AppError> {
+        let expires = self
+            .config
+            .invites
+            .lifetime()
+            .and_then(|lifetime| chrono::Duration::from_std(lifetime).ok())
+            .map(|lifetime| Utc::now() + lifetime);
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::CreateInvites)?;
+        Ok(create_invite(conn, actor.id, expires)?)
+    }
+
+    /// Withdraws the unused invite with that id as the user with that id, who needs a role that may make invites.
+    /// Returns the invite.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not make invites, there is no unused invite with that id
+    /// or it could not be removed.
+    pub fn delete_invite(&mut self, actor: Actor, invite_id: i32) -> Result<Invite, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::CreateInvites)?;
+        Ok(delete_invite(conn, invite_id)?)
+    }
+
+    /// Gets a page of the bot accounts, the newest first, as the user with that id, who needs a role that may
+    /// manage them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage bots, the cursor is not valid or the bots could
+    /// not be retrieved.
+    pub fn get_bots(
+        &mut self,
+        actor: Actor,
+        request: &PageRequest,
+    ) -> Result<Page<User>, AppError> {
+        let before = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageBots)?;
+        let bots = get_bots(conn, before, i64::from(limit) + 1)?;
+        Ok(paginate(bots, limit, |bot| bot.id.to_string()))
+    }
+
+    /// Makes a bot account with that name as the user with that id, who needs a role that may do so. Bots have no
+    /// password, they log in with the API keys made for them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage bots, the name is already in use or the
+    /// account could not be created.
+    pub fn create_bot(&mut self, actor: Actor, username: &str) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageBots)?;
+        Ok(create_bot(conn, username, actor.id)?)
+    }
+
+    /// Lists the API keys of the bot with that id, the newest first, as the user with that id, who needs a role
+    /// that may manage bots.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage bots, there is no bot with that id or the
+    /// keys could not be retrieved.
+    pub fn get_api_keys(&mut self, actor: Actor, bot_id: i32) -> Result<Vec<ApiKey>, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageBots)?;
+        if !is_bot(conn, bot_id)? {
+            return Err(DbError::BotNotFound.into());
+        }
+        Ok(get_api_keys(conn, bot_id)?)
+    }
+
+    /// Makes an API key for the bot with that id as the user with that id, who needs a role that may manage bots.
+    /// Returns the key along with what is stored about it, as it can not be looked up again.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage bots, there is no bot with that id or the
+    /// key could not be stored.
+    pub fn create_api_key(
+        &mut self,
+        actor: Actor,
+        bot_id: i32,
+        name: &str,
+    ) -> Result<(ApiKey, String), AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageBots)?;
+        if !is_bot(conn, bot_id)? {
+            return Err(DbError::BotNotFound.into());
+        }
+        Ok(create_api_key(conn, bot_id, name)?)
+    }
+
+    /// Revokes the API key with that id of the bot with that id as the user with that id, who needs a role that may
+    /// manage bots. Requests with the key are refused from then on. Returns the key.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage bots, the bot has no key with that id or it
+    /// could not be removed.
+    pub fn revoke_api_key(
+        &mut self,
+        actor: Actor,
+        bot_id: i32,
+        key_id: i32,
+    ) -> Result<ApiKey, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageBots)?;
+        Ok(revoke_api_key(conn, bot_id, key_id)?)
+    }
+
+    /// Validates the API key, returning the bot it belongs to. Unlike login tokens, keys are not held in memory,
+    /// so they keep working after the server restarts.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key is not valid, was revoked or the bot is banned.
+    pub fn authenticate_api_key(&mut self, key: &str) -> Result<User, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        let userid = check_api_key(conn, key)?;
+        let user = get_user_by_id(conn, userid)?;
+        if is_banned(conn, &user.username)? {
+            return Err(AppError::UserBanned);
+        }
+        self.note_activity(user.id);
+        Ok(user)
+    }
+
+    /// Gets a page of the webhooks, the newest first, as the user with that id, who needs a role that may manage
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage webhooks, the cursor is not valid or the
+    /// webhooks could not be retrieved.
+    pub fn get_webhooks(
+        &mut self,
+        actor: Actor,
+        request: &PageRequest,
+    ) -> Result<Page<Webhook>, AppError> {
+        let before = match &request.cursor {
+            Some(cursor) => Some(cursor.parse().map_err(|_| AppError::InvalidCursor)?),
+            None => None,
+        };
+        let limit = request.limit();
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageWebhooks)?;
+        let webhooks = get_webhook_page(conn, before, i64::from(limit) + 1)?;
+        Ok(paginate(webhooks, limit, |webhook| webhook.id.to_string()))
+    }
+
+    /// Registers the url as a webhook as the user with that id, who needs a role that may do so. Every new message
+    /// in the chat and the channels is posted to it from then on.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage webhooks, the url is not an http or https url
+    /// or the webhook could not be stored.
+    pub fn create_webhook(&mut self, actor: Actor, url: &str) -> Result<Webhook, AppError> {
+        let url = url.trim();
+        if !reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return Err(AppError::InvalidWebhookUrl);
+        }
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageWebhooks)?;
+        Ok(create_webhook(conn, url, actor.id)?)
+    }
+
+    /// Removes the webhook with that id as the user with that id, who needs a role that may manage webhooks.
+    /// Messages that are still being posted to it are posted anyway. Returns the webhook.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user may not manage webhooks, there is no webhook with that id or
+    /// it could not be removed.
+    pub fn delete_webhook(&mut self, actor: Actor, webhook_id: i32) -> Result<Webhook, AppError> {
+        let conn = &mut self.db_connection.get()?;
+        check_permission(actor, Permission::ManageWebhooks)?;
+        Ok(delete_webhook(conn, webhook_id)?)
+    }
+
+    /// Gets the user that is logged in with that token.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token is not in use.
+    pub fn get_user_for_token(&mut self, login_token: &LoginToken) -> Result<User, AppError> {
+        let Some(username) = self.get_username_for_token(login_token) else {
+            return Err(AppError::TokenInvalid);
+        };
+        let conn = &mut self.db_connection.get()?;
+        Ok(get_user_by_name(conn, &username)?)
+    }
+
+    /// Validates the token and marks the session as active, returning the user it belongs to.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token is not in use or has expired.
+    pub fn authenticate(&mut self, login_token: &LoginToken) -> Result<User, AppError> {
+        self.touch_session(login_token)?;
+        let user = self.get_user_for_token(login_token)?;
+        // Admins acting as a user do not make them active
+        if self
+            .find_login(login_token)
+            .is_some_and(|login| login.impersonator.is_none())
+        {
+            self.note_activity(user.id);
+        }
+        Ok(user)
+    }
+
+    /// Stores that the user is active now, unless that was stored less than `ACTIVITY_INTERVAL` ago. Failures are
+    /// left for the next request.
+    fn note_activity(&mut self, userid: i32) {
+        let noted_recently = self
+            .last_activity
+            .get(&userid)
+            .and_then(|noted| noted.elapsed().ok())
+            .is_some_and(|elapsed| elapsed < ACTIVITY_INTERVAL);
+        if noted_recently {
+            return;
+        }
+        let Ok(mut conn) = self.db_connection.get() else {
+            return;
+        };
+        if set_last_active(&mut conn, userid, Utc::now()).is_ok() {
+            self.last_activity.insert(userid, SystemTime::now());
+        }
+    }
+
+    /// Marks the session as active, extending its idle timeout. The session can not be extended beyond its maximum lifetime.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token is not in use or has expired.
+    pub fn touch_session(&mut self, login_token: &LoginToken) -> Result<(), AppError> {
+        self.prune_expired_logins();
+        let idle_timeout = self.config.session.idle_timeout();
+        let Some(login) = self
+            .active_logins
+            .iter_mut()
+            .find(|login| login.token == *login_token)
+        else {
+            return Err(AppError::TokenInvalid);
+        };
+
+        login.touch(idle_timeout);
+        Ok(())
+    }
+
+    /// Returns the ids of the users that are online, ordered by id. Users are online while they hold a valid login
+    /// token or have an event stream open.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the users could not be looked up.
+    pub fn online_users(&mut self) -> Result<Vec<i32>, AppError> {
+        self.prune_expired_logins();
+        // Admins acting as a user do not make them online
+        let names: Vec<&str> = self
+            .active_logins
+            .iter()
+            .filter(|login| login.impersonator.is_none())
+            .map(|login| login.username.as_str())
+            .collect();
+        let conn = &mut self.db_connection.get()?;
+        let mut online = get_user_ids(conn, &names)?;
+        online.extend(self.open_streams.keys());
+        online.sort_unstable();
+        online.dedup();
+        Ok(online)
+    }
+
+    /// Notes that the user opened an event stream, so they stay online while it is open.
+    pub fn open_stream(&mut self, userid: i32) {
+        *self.open_streams.entry(userid).or_default() += 1;
+    }
+
+    /// Notes that one of the event streams of the user was closed.
+    pub fn close_stream(&mut self, userid: i32) {
+        if let Some(count) = self.open_streams.get_mut(&userid) {
+            *count -= 1;
+            if *count == 0 {
+                self.open_streams.remove(&userid);
+            }
+        }
+    }
+
+    /// Returns the users that came online or went offline since this was last called, ordered by id, along with
+    /// whether they are online now. Users that were deleted meanwhile are left out.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the users could not be looked up. The changes are reported on the next
+    /// call then.
+    pub fn presence_changes(&mut self) -> Result<Vec<(User, bool)>, AppError> {
+        let online: HashSet<i32> = self.online_users()?.into_iter().collect();
+        let mut changes: Vec<(i32, bool)> = online
+            .difference(&self.online)
+            .map(|userid| (*userid, true))
+            .chain(
+                self.online
+                    .difference(&online)
+                    .map(|userid| (*userid, false)),
+            )
+            .collect();
+        changes.sort_unstable();
+        let conn = &mut self.db_connection.get()?;
+        let mut users = Vec::with_capacity(changes.len());
+        for (userid, now_online) in changes {
+            match get_user_by_id(conn, userid) {
+                Ok(user) => users.push((user, now_online)),
+                Err(DbError::GenericError(diesel::result::Error::NotFound)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.online = online;
+        Ok(users)
+    }
+
+    fn get_username_for_token(&mut self, login_token: &LoginToken) -> Option<String> {
+        self.find_login(login_token)
+            .map(|login| login.username.clone())
+    }
+
+    fn find_login(&mut self, login_token: &LoginToken) -> Option<&ActiveLogin> {
+        self.prune_expired_logins();
+        self.active_logins
+            .iter()
+            .find(|login| login.token == *login_token)
+    }
+
+    /// Removes all logins that have expired.
+    fn prune_expired_logins(&mut self) {
+        let now = SystemTime::now();
+        self.active_logins.retain(|login| !login.is_expired(now));
+    }
+}
+
+struct ActiveLogin {
+    username: String,
+    token: LoginToken,
+    created: SystemTime,
+    /// The admin acting as the user with this login, if it is not the user's own.
+    impersonator: Option<i32>,
+    /// The session expires at this point, unless it gets extended by activity.
+    valid_until: SystemTime,
+    /// The session can not be extended past this point.
+    expires_at: SystemTime,
+}
+
+impl ActiveLogin {
+    pub fn new(username: &str, config: &SessionConfig) -> Self {
+        let username = username.into();
+        let token = LoginToken(generate_secret(7));
+
+        let now = SystemTime::now();
+        let expires_at = now + config.max_lifetime();
+        let valid_until = (now + config.idle_timeout()).min(expires_at);
+
+        ActiveLogin {
+            username,
+            token,
+            created: now,
+            impersonator: None,
+            valid_until,
+            expires_at,
+        }
+    }
+
+    /// Makes sure the session ends at the given point at the latest.
+    fn limit_lifetime(&mut self, until: SystemTime) {
+        self.expires_at = self.expires_at.min(until);
+        self.valid_until = self.valid_until.min(until);
+    }
+
+    /// Extends the session by the idle timeout, capped at the maximum lifetime.
+    fn touch(&mut self, idle_timeout: Duration) {
+        self.valid_until = (SystemTime::now() + idle_timeout).min(self.expires_at);
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.valid_until < now || self.expires_at < now
+    }
+}
+
+/// Checks whether a resource at the current version may be changed by a request that expects the given one. Requests
+/// that expect none change it whatever the version.
+fn check_version(expected: Option<i32>, current: i32) -> Result<(), AppError> {
+    match expected {
+        Some(expected) if expected != current => Err(AppError::VersionConflict { current }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks whether the role of the user grants the permission.
+fn check_permission(actor: Actor, permission: Permission) -> Result<(), AppError> {
+    if actor.role.can(permission) {
+        Ok(())
+    } else {
+        Err(AppError::PermissionDenied)
+    }
+}
+
+/// Checks whether the user may act on the target with the permission, which also needs a role outranking the one of
+/// the target. Returns the target.
+fn check_authority(
+    conn: &mut SqliteConnection,
+    actor: Actor,
+    target: i32,
+    permission: Permission,
+) -> Result<User, AppError> {
+    let target = match get_user_by_id(conn, target) {
+        Ok(target) => target,
+        Err(DbError::GenericError(diesel::result::Error::NotFound)) => {
+            return Err(DbError::UserNotFound.into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if actor.role.can(permission) && actor.role.outranks(target.role) && actor.id != target.id {
+        Ok(target)
+    } else {
+        Err(AppError::PermissionDenied)
+    }
+}
+
+/// Checks whether the user may read and write in the conversation. Every user may write to any other user, but
+/// channels are only open to their members.
+fn check_access(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    conversation: Conversation,
+) -> Result<(), AppError> {
+    match conversation {
+        Conversation::Chat => Ok(()),
+        Conversation::Channel(channel) => {
+            get_channel(conn, channel)?;
+            if is_channel_member(conn, channel, userid)? {
+                Ok(())
+            } else {
+                Err(AppError::NotAMember)
+            }
+        }
+        Conversation::Direct(other) => match get_user_by_id(conn, other) {
+            Ok(_) => Ok(()),
+            Err(DbError::GenericError(diesel::result::Error::NotFound)) => {
+                Err(DbError::UserNotFound.into())
+            }
+            Err(e) => Err(e.into()),
+        },
+    }
+}
+
+/// Checks whether the user waited long enough since their last message in the conversation, if it is a channel in
+/// slow mode. Those allowed to set slow mode never have to wait.
+fn check_slow_mode(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    conversation: Conversation,
+) -> Result<(), AppError> {
+    let Conversation::Channel(channel) = conversation else {
+        return Ok(());
+    };
+    let interval = get_channel(conn, channel)?.slowmode;
+    if interval <= 0
+        || get_user_by_id(conn, userid)?
+            .role
+            .can(Permission::SetSlowMode)
+    {
+        return Ok(());
+    }
+    let Some(last) = last_channel_message_date(conn, channel, userid)? else {
+        return Ok(());
+    };
+    let available_at = last + chrono::Duration::seconds(i64::from(interval));
+    match (available_at - Utc::now()).to_std() {
+        Ok(remaining) if !remaining.is_zero() => Err(AppError::SlowMode { remaining }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks whether the user may read the message. Direct messages between other users are treated as if they did not
+/// exist, so their ids give nothing away.
+fn check_visible(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    message: &Message,
+) -> Result<(), AppError> {
+    match (message.channelid, message.recipientid) {
+        (Some(channel), _) => check_access(conn, userid, Conversation::Channel(channel)),
+        (None, Some(recipient)) if recipient != userid && message.userid != userid => {
+            Err(DbError::MessageNotFound.into())
+        }
+        (None, _) => Ok(()),
+    }
+}
+
+/// Generates a random base64 encoded string from the given amount of bytes.
+fn generate_secret(bytes: usize) -> String {
+    let mut rng = rand::thread_rng();
+    let data: Vec<u8> = (0..bytes).map(|_| rng.gen()).collect();
+    base64::engine::general_purpose::STANDARD_NO_PAD.encode(data)
+}
+
+/// Create a new user.
+///
+/// # Errors
+///
+/// This function will return an error if the cration of the user failed.
+pub fn create_user(conn: &mut SqliteConnection, name: &str) -> Result<(), DbError> {
+    if get_user_by_name(conn, name).is_ok() {
+        return Err(DbError::UsernameInUse);
+    }
+
+    let now = Utc::now();
+    let new_user = NewUser {
+        username: name,
+        createddate: now,
+        updateddate: now,
+    };
+
+    match diesel::insert_into(schema::users::table)
+        .values(&new_user)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(DbError::UserCreationFailed)?,
+    }
+}
+
+/// Get a specific user from the database.
+///
+/// # Errors
+///
+/// This function will return an error if no user with that name could be found.
+pub fn get_user_by_name(conn: &mut SqliteConnection, name: &str) -> Result<User, DbError> {
+    use chat_core::schema::users::dsl::{username, users};
+
+    let Ok(mut found_users) = users
+        .filter(username.eq(name))
+        .select(User::as_select())
+        .load(conn)
+    else {
+        return Err(DbError::UserFilterFailed)?;
+    };
+
+    if found_users.len() > 1 {
+        Err(DbError::UsernameCollisionDetected)?
+    } else {
+        let Some(user) = found_users.pop() else {
+            return Err(DbError::UserNotFound);
+        };
+
+        Ok(user)
+    }
+}
+
+/// Gets the user with that id.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist.
+pub fn get_user_by_id(conn: &mut SqliteConnection, id: i32) -> Result<User, DbError> {
+    use chat_core::schema::users::dsl::{id as user_id, users};
+
+    Ok(users
+        .filter(user_id.eq(id))
+        .select(User::as_select())
+        .first(conn)?)
+}
+
+/// Returns the ids of the users with those names. Names nobody has are skipped.
+///
+/// # Errors
+///
+/// This function will return an error if the users could not be retrieved.
+pub fn get_user_ids(conn: &mut SqliteConnection, names: &[&str]) -> Result<Vec<i32>, DbError> {
+    use chat_core::schema::users::dsl::{id, username, users};
+
+    Ok(users.filter(username.eq_any(names)).select(id).load(conn)?)
+}
+
+/// Change the name of a user.
+///
+/// # Errors
+///
+/// This function will return an error if no user with that name could be found or it would cause a collision.
+pub fn change_username(
+    conn: &mut SqliteConnection,
+    current_username: &str,
+    new_username: &str,
+) -> Result<(), DbError> {
+    use chat_core::schema::users::dsl::{updateddate, username, users};
+
+    if get_user_by_name(conn, new_username).is_ok() {
+        return Err(DbError::UsernameInUse);
+    }
+
+    let user_to_update = users.filter(username.eq(current_username));
+    let rows_affected = diesel::update(user_to_update)
+        .set((username.eq(new_username), updateddate.eq(Utc::now())))
+        .execute(conn)?;
+
+    if rows_affected == 0 {
+        return Err(DbError::UserNotFound);
+    }
+
+    Ok(())
+}
+
+/// Gets the profile of the user with that id.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist.
+pub fn get_profile(conn: &mut SqliteConnection, id: i32) -> Result<Profile, DbError> {
+    use chat_core::schema::users::dsl::{id as user_id, users};
+
+    users
+        .filter(user_id.eq(id))
+        .select(Profile::as_select())
+        .first(conn)
+        .optional()?
+        .ok_or(DbError::UserNotFound)
+}
+
+/// Changes the fields of the profile the changes give, and returns the profile.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist or the profile could not be changed.
+pub fn update_profile(
+    conn: &mut SqliteConnection,
+    id: i32,
+    changes: &ProfileChanges,
+) -> Result<Profile, DbError> {
+    use chat_core::schema::users::dsl::{id as user_id, profileversion, updateddate, users};
+
+    // Diesel refuses updates that change nothing
+    if changes.displayname.is_some()
+        || changes.bio.is_some()
+        || changes.avatar.is_some()
+        || changes.showlastseen.is_some()
+    {
+        diesel::update(users.filter(user_id.eq(id)))
+            .set((
+                changes,
+                updateddate.eq(Utc::now()),
+                profileversion.eq(profileversion + 1),
+            ))
+            .execute(conn)?;
+    }
+    get_profile(conn, id)
+}
+
+/// Stores when the user was last active.
+///
+/// # Errors
+///
+/// This function will return an error if the date could not be stored.
+pub fn set_last_active(
+    conn: &mut SqliteConnection,
+    id: i32,
+    date: DateTime<Utc>,
+) -> Result<(), DbError> {
+    use chat_core::schema::users::dsl::{id as user_id, lastactive, users};
+
+    diesel::update(users.filter(user_id.eq(id)))
+        .set(lastactive.eq(date))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Delete a user.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn delete_user(conn: &mut SqliteConnection, name: &str) -> Result<(), DbError> {
+    use chat_core::schema::users::dsl::{username, users};
+
+    let user_to_delete = users.filter(username.eq(name));
+    let affected_rows = diesel::delete(user_to_delete).execute(conn)?;
+
+    if affected_rows == 0 {
+        return Err(DbError::UserNotFound);
+    }
+
+    Ok(())
+}
+
+/// Gives the user the role.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist or the role could not be changed.
+pub fn set_role(conn: &mut SqliteConnection, id: i32, new_role: Role) -> Result<(), DbError> {
+    use chat_core::schema::users::dsl::{id as user_id, role, updateddate, users};
+
+    let rows_affected = diesel::update(users.filter(user_id.eq(id)))
+        .set((role.eq(new_role.key()), updateddate.eq(Utc::now())))
+        .execute(conn)?;
+    if rows_affected == 0 {
+        return Err(DbError::UserNotFound);
+    }
+    Ok(())
+}
+
+/// Bans the user, who can not log in anymore. Their refresh tokens are revoked.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist or could not be banned.
+pub fn ban_user(conn: &mut SqliteConnection, id: i32) -> Result<(), DbError> {
+    use chat_core::schema::users::dsl::{banned, id as user_id, updateddate, users};
+
+    conn.transaction(|conn| {
+        let rows_affected = diesel::update(users.filter(user_id.eq(id)))
+            .set((banned.eq(true), updateddate.eq(Utc::now())))
+            .execute(conn)?;
+        if rows_affected == 0 {
+            return Err(DbError::UserNotFound);
+        }
+        revoke_refresh_tokens(conn, id)
+    })
+}
+
+/// Lifts the ban of the user, who can log in again.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist or the ban could not be lifted.
+pub fn unban_user(conn: &mut SqliteConnection, id: i32) -> Result<(), DbError> {
+    use chat_core::schema::users::dsl::{banned, id as user_id, updateddate, users};
+
+    let rows_affected = diesel::update(users.filter(user_id.eq(id)))
+        .set((banned.eq(false), updateddate.eq(Utc::now())))
+        .execute(conn)?;
+    if rows_affected == 0 {
+        return Err(DbError::UserNotFound);
+    }
+    Ok(())
+}
+
+/// Revokes every refresh token of the user, so none of their logins can be renewed.
+///
+/// # Errors
+///
+/// This function will return an error if the tokens could not be revoked.
+pub fn revoke_refresh_tokens(conn: &mut SqliteConnection, id: i32) -> Result<(), DbError> {
+    use chat_core::schema::refreshtokens::dsl::{refreshtokens, userid};
+
+    diesel::delete(refreshtokens.filter(userid.eq(id))).execute(conn)?;
+    Ok(())
+}
+
+/// Returns whether the user with that name is banned.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist.
+pub fn is_banned(conn: &mut SqliteConnection, name: &str) -> Result<bool, DbError> {
+    use chat_core::schema::users::dsl::{banned, username, users};
+
+    users
+        .filter(username.eq(name))
+        .select(banned)
+        .first(conn)
+        .optional()?
+        .ok_or(DbError::UserNotFound)
+}
+
+/// Returns all users.
+///
+/// # Errors
+///
+/// This function will return an error if reading all entries from the user table fails.
+pub fn get_all_users(conn: &mut SqliteConnection) -> Result<Vec<User>, DbError> {
+    Ok(schema::users::dsl::users
+        .select(User::as_select())
+        .load(conn)?)
+}
+
+/// Returns up to `limit` users with an id above `after`, ordered by id.
+///
+/// # Errors
+///
+/// This function will return an error if the users could not be retrieved.
+pub fn get_user_page(
+    conn: &mut SqliteConnection,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<Vec<User>, DbError> {
+    use schema::users::dsl::{id, users};
+
+    Ok(users
+        .filter(id.gt(after.unwrap_or(i32::MIN)))
+        .order(id.asc())
+        .limit(limit)
+        .select(User::as_select())
+        .load(conn)?)
+}
+
+/// Returns the users with those ids, ordered by id.
+///
+/// # Errors
+///
+/// This function will return an error if the users could not be retrieved.
+pub fn get_users_by_ids(conn: &mut SqliteConnection, ids: &[i32]) -> Result<Vec<User>, DbError> {
+    use schema::users::dsl::{id, users};
+
+    Ok(users
+        .filter(id.eq_any(ids))
+        .order(id.asc())
+        .select(User::as_select())
+        .load(conn)?)
+}
+
+/// Returns up to `limit` users matching the filter in the given order, skipping the first `offset`, with whether
+/// they are banned, when they were last active and when their account was made and changed. `online` holds the ids
+/// of the users online, sorted, for filtering by it.
+///
+/// # Errors
+///
+/// This function will return an error if the users could not be retrieved.
+pub fn get_user_directory(
+    conn: &mut SqliteConnection,
+    sort: UserSort,
+    filter: &UserFilter,
+    online: &[i32],
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<DirectoryEntry>, DbError> {
+    use schema::users::dsl::{banned, id, lastactive, role, username, users};
+
+    let mut query = users.select(DirectoryEntry::as_select()).into_boxed();
+    if let Some(filter_role) = filter.role {
+        query = query.filter(role.eq(filter_role.key()));
+    }
+    if let Some(filter_banned) = filter.banned {
+        query = query.filter(banned.eq(filter_banned));
+    }
+    match filter.online {
+        Some(true) => query = query.filter(id.eq_any(online)),
+        Some(false) => query = query.filter(diesel::dsl::not(id.eq_any(online))),
+        None => {}
+    }
+    // Ties are broken by id, so pages do not overlap
+    query = match sort {
+        UserSort::Name => query.order((username.asc(), id.asc())),
+        UserSort::Joined => query.order(id.desc()),
+        // Users the server does not know to have been active have no date, which SQLite sorts last when descending
+        UserSort::Active => query.order((lastactive.desc(), id.desc())),
+    };
+    Ok(query.offset(offset).limit(limit).load(conn)?)
+}
+
+/// Records what an admin or moderator did in the audit log.
+///
+/// # Errors
+///
+/// This function will return an error if the entry could not be stored.
+pub fn record_audit(
+    conn: &mut SqliteConnection,
+    actorid: i32,
+    action: AuditAction,
+    targetid: Option<i32>,
+    details: &str,
+) -> Result<(), DbError> {
+    let entry = NewAuditEntry {
+        date: Utc::now(),
+        actorid,
+        action: action.key(),
+        targetid,
+        details,
+    };
+    diesel::insert_into(schema::auditlog::table)
+        .values(&entry)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Returns up to `limit` entries of the audit log with an id below `before`, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the entries could not be retrieved.
+pub fn get_audit_page(
+    conn: &mut SqliteConnection,
+    before: Option<i32>,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, DbError> {
+    use schema::auditlog::dsl::{auditlog, id};
+
+    Ok(auditlog
+        .filter(id.lt(before.unwrap_or(i32::MAX)))
+        .order(id.desc())
+        .limit(limit)
+        .select(AuditEntry::as_select())
+        .load(conn)?)
+}
+
+/// Counts the accounts made, the messages sent, the users active and the moderation actions taken since the date.
+///
+/// # Errors
+///
+/// This function will return an error if the counts could not be retrieved.
+pub fn count_digest(conn: &mut SqliteConnection, since: DateTime<Utc>) -> Result<Digest, DbError> {
+    use schema::{auditlog, messages, users};
+
+    Ok(Digest {
+        registrations: users::table
+            .filter(users::createddate.ge(since))
+            .count()
+            .get_result(conn)?,
+        messages: messages::table
+            .filter(messages::date.ge(since))
+            .count()
+            .get_result(conn)?,
+        active_users: users::table
+            .filter(users::lastactive.ge(since))
+            .count()
+            .get_result(conn)?,
+        moderation_actions: auditlog::table
+            .filter(auditlog::date.ge(since))
+            .count()
+            .get_result(conn)?,
+    })
+}
+
+/// Returns every banned range of addresses, the oldest first.
+///
+/// # Errors
+///
+/// This function will return an error if the bans could not be retrieved.
+pub fn get_address_bans(conn: &mut SqliteConnection) -> Result<Vec<AddressBan>, DbError> {
+    use schema::addressbans::dsl::{addressbans, id};
+
+    Ok(addressbans
+        .order(id.asc())
+        .select(AddressBan::as_select())
+        .load(conn)?)
+}
+
+/// Returns up to `limit` banned ranges of addresses with an id above `after`, the oldest first.
+///
+/// # Errors
+///
+/// This function will return an error if the bans could not be retrieved.
+pub fn get_address_ban_page(
+    conn: &mut SqliteConnection,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<Vec<AddressBan>, DbError> {
+    use schema::addressbans::dsl::{addressbans, id};
+
+    Ok(addressbans
+        .filter(id.gt(after.unwrap_or(i32::MIN)))
+        .order(id.asc())
+        .limit(limit)
+        .select(AddressBan::as_select())
+        .load(conn)?)
+}
+
+fn get_address_ban_by_network(
+    conn: &mut SqliteConnection,
+    range: &str,
+) -> Result<AddressBan, DbError> {
+    use schema::addressbans::dsl::{addressbans, network};
+
+    Ok(addressbans
+        .filter(network.eq(range))
+        .select(AddressBan::as_select())
+        .first(conn)?)
+}
+
+/// Stores a ban of the range of addresses, unless it is banned already. Returns whether it was stored.
+///
+/// # Errors
+///
+/// This function will return an error if the ban could not be stored.
+pub fn insert_address_ban(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    network: &str,
+    reason: &str,
+) -> Result<bool, DbError> {
+    let ban = NewAddressBan {
+        network,
+        reason,
+        date: Utc::now(),
+        createdby: userid,
+    };
+    let inserted = diesel::insert_into(schema::addressbans::table)
+        .values(&ban)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(inserted > 0)
+}
+
+/// Removes the ban with that id, returning it.
+///
+/// # Errors
+///
+/// This function will return `DbError::AddressBanNotFound` if there is no such ban, or an error if it could not be
+/// removed.
+pub fn delete_address_ban(conn: &mut SqliteConnection, ban_id: i32) -> Result<AddressBan, DbError> {
+    use schema::addressbans::dsl::{addressbans, id};
+
+    let ban = match addressbans
+        .find(ban_id)
+        .select(AddressBan::as_select())
+        .first(conn)
+    {
+        Ok(ban) => ban,
+        Err(diesel::result::Error::NotFound) => return Err(DbError::AddressBanNotFound),
+        Err(e) => return Err(e.into()),
+    };
+    diesel::delete(addressbans.filter(id.eq(ban_id))).execute(conn)?;
+    Ok(ban)
+}
+
+/// Returns up to `limit` invites with an id below `before`, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the invites could not be retrieved.
+pub fn get_invites(
+    conn: &mut SqliteConnection,
+    before: Option<i32>,
+    limit: i64,
+) -> Result<Vec<Invite>, DbError> {
+    use schema::invites::dsl::{id, invites};
+
+    Ok(invites
+        .filter(id.lt(before.unwrap_or(i32::MAX)))
+        .order(id.desc())
+        .limit(limit)
+        .select(Invite::as_select())
+        .load(conn)?)
+}
+
+/// Stores a new invite with a random code, made by the user with that id, and returns it.
+///
+/// # Errors
+///
+/// This function will return an error if the invite could not be stored.
+pub fn create_invite(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    expires: Option<DateTime<Utc>>,
+) -> Result<Invite, DbError> {
+    let code = generate_invite_code();
+    let invite = NewInvite {
+        code: &code,
+        createdby: userid,
+        created: Utc::now(),
+        expires,
+    };
+    diesel::insert_into(schema::invites::table)
+        .values(&invite)
+        .returning(Invite::as_returning())
+        .get_result(conn)
+        .optional()?
+        .ok_or(DbError::NoReturnOnInsert)
+}
+
+/// Uses up the invite with that code for the user with that id, who registered with it.
+///
+/// # Errors
+///
+/// This function will return `DbError::InviteInvalid` if there is no such code or it expired or was used already,
+/// or an error if it could not be used up.
+pub fn consume_invite(
+    conn: &mut SqliteConnection,
+    invite_code: &str,
+    userid: i32,
+) -> Result<(), DbError> {
+    use schema::invites::dsl::{code, expires, invites, usedby};
+
+    let rows_affected = diesel::update(
+        invites
+            .filter(code.eq(invite_code.trim()))
+            .filter(usedby.is_null())
+            .filter(expires.is_null().or(expires.gt(Utc::now()))),
+    )
+    .set(usedby.eq(userid))
+    .execute(conn)?;
+    if rows_affected == 0 {
+        return Err(DbError::InviteInvalid);
+    }
+    Ok(())
+}
+
+/// Removes the unused invite with that id, returning it. Used ones are kept, to tell who invited whom.
+///
+/// # Errors
+///
+/// This function will return `DbError::InviteNotFound` if there is no such unused invite, or an error if it could
+/// not be removed.
+pub fn delete_invite(conn: &mut SqliteConnection, invite_id: i32) -> Result<Invite, DbError> {
+    use schema::invites::dsl::{id, invites, usedby};
+
+    let unused = invites.filter(id.eq(invite_id)).filter(usedby.is_null());
+    let invite = match unused.select(Invite::as_select()).first(conn) {
+        Ok(invite) => invite,
+        Err(diesel::result::Error::NotFound) => return Err(DbError::InviteNotFound),
+        Err(e) => return Err(e.into()),
+    };
+    diesel::delete(unused).execute(conn)?;
+    Ok(invite)
+}
+
+/// Generates a code for an invite, made of letters and digits that can not be mistaken for one another, so it can
+/// be read out or typed off a screen.
+fn generate_invite_code() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut rng = rand::thread_rng();
+    (0..12)
+        .map(|_| char::from(ALPHABET[rng.gen_range(0..ALPHABET.len())]))
+        .collect()
+}
+
+/// Whether the user with that id is a bot account.
+///
+/// # Errors
+///
+/// This function will return an error if the database could not be queried.
+pub fn is_bot(conn: &mut SqliteConnection, id: i32) -> Result<bool, DbError> {
+    use chat_core::schema::bots::dsl::{bots, userid};
+
+    let count: i64 = bots.filter(userid.eq(id)).count().get_result(conn)?;
+    Ok(count > 0)
+}
+
+/// Returns up to `limit` bot accounts with an id below `before`, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the bots could not be retrieved.
+pub fn get_bots(
+    conn: &mut SqliteConnection,
+    before: Option<i32>,
+    limit: i64,
+) -> Result<Vec<User>, DbError> {
+    use schema::bots::dsl::{bots, userid};
+    use schema::users::dsl::{id, users};
+
+    Ok(users
+        .filter(id.eq_any(bots.select(userid)))
+        .filter(id.lt(before.unwrap_or(i32::MAX)))
+        .order(id.desc())
+        .limit(limit)
+        .select(User::as_select())
+        .load(conn)?)
+}
+
+/// Creates a bot account with that name, made by the user with that id, and returns it.
+///
+/// # Errors
+///
+/// This function will return an error if the name is already in use or the account could not be created.
+pub fn create_bot(
+    conn: &mut SqliteConnection,
+    name: &str,
+    createdby: i32,
+) -> Result<User, DbError> {
+    conn.transaction(|conn| {
+        create_user(conn, name)?;
+        let user = get_user_by_name(conn, name)?;
+        let bot = NewBot {
+            userid: user.id,
+            createdby,
+            created: Utc::now(),
+        };
+        diesel::insert_into(schema::bots::table)
+            .values(&bot)
+            .execute(conn)?;
+        Ok(user)
+    })
+}
+
+/// Returns the API keys of the bot with that id, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the keys could not be retrieved.
+pub fn get_api_keys(conn: &mut SqliteConnection, bot_id: i32) -> Result<Vec<ApiKey>, DbError> {
+    use schema::apikeys::dsl::{apikeys, id, userid};
+
+    Ok(apikeys
+        .filter(userid.eq(bot_id))
+        .order(id.desc())
+        .select(ApiKey::as_select())
+        .load(conn)?)
+}
+
+/// Stores a new API key for the bot with that id and returns it along with the key itself, which is made of the id
+/// of the key and a random secret. Only a hash of the key is stored.
+///
+/// # Errors
+///
+/// This function will return an error if the key could not be stored.
+pub fn create_api_key(
+    conn: &mut SqliteConnection,
+    bot_id: i32,
+    name: &str,
+) -> Result<(ApiKey, String), DbError> {
+    let secret = generate_secret(32);
+    let new_key = NewApiKey {
+        userid: bot_id,
+        name: name.trim(),
+        hashedkey: auth::hash_key(&secret),
+        created: Utc::now(),
+    };
+    let api_key = diesel::insert_into(schema::apikeys::table)
+        .values(&new_key)
+        .returning(ApiKey::as_returning())
+        .get_result(conn)
+        .optional()?
+        .ok_or(DbError::NoReturnOnInsert)?;
+    let key = format!("{}.{secret}", api_key.id);
+    Ok((api_key, key))
+}
+
+/// Removes the API key with that id of the bot with that id, returning it.
+///
+/// # Errors
+///
+/// This function will return `DbError::ApiKeyNotFound` if the bot has no such key, or an error if it could not be
+/// removed.
+pub fn revoke_api_key(
+    conn: &mut SqliteConnection,
+    bot_id: i32,
+    key_id: i32,
+) -> Result<ApiKey, DbError> {
+    use schema::apikeys::dsl::{apikeys, id, userid};
+
+    let key = apikeys.filter(id.eq(key_id)).filter(userid.eq(bot_id));
+    let api_key = match key.select(ApiKey::as_select()).first(conn) {
+        Ok(api_key) => api_key,
+        Err(diesel::result::Error::NotFound) => return Err(DbError::ApiKeyNotFound),
+        Err(e) => return Err(e.into()),
+    };
+    diesel::delete(key).execute(conn)?;
+    Ok(api_key)
+}
+
+/// Checks the API key, returning the id of the bot it belongs to.
+///
+/// # Errors
+///
+/// This function will return `DbError::ApiKeyInvalid` if the key is malformed, does not exist or does not match,
+/// or an error if it could not be looked up.
+pub fn check_api_key(conn: &mut SqliteConnection, key: &str) -> Result<i32, DbError> {
+    use schema::apikeys::dsl::{apikeys, hashedkey, id, userid};
+
+    let Some((key_id, secret)) = key.split_once('.') else {
+        return Err(DbError::ApiKeyInvalid);
+    };
+    let Ok(key_id) = key_id.parse::<i32>() else {
+        return Err(DbError::ApiKeyInvalid);
+    };
+    let Some((bot_id, hashed)) = apikeys
+        .filter(id.eq(key_id))
+        .select((userid, hashedkey))
+        .first::<(i32, String)>(conn)
+        .optional()?
+    else {
+        return Err(DbError::ApiKeyInvalid);
+    };
+    if auth::hash_key(secret) != hashed {
+        return Err(DbError::ApiKeyInvalid);
+    }
+    Ok(bot_id)
+}
+
+/// Returns every webhook, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the webhooks could not be retrieved.
+pub fn get_webhooks(conn: &mut SqliteConnection) -> Result<Vec<Webhook>, DbError> {
+    use schema::webhooks::dsl::{id, webhooks};
+
+    Ok(webhooks
+        .order(id.desc())
+        .select(Webhook::as_select())
+        .load(conn)?)
+}
+
+/// Returns up to `limit` webhooks with an id below `before`, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the webhooks could not be retrieved.
+pub fn get_webhook_page(
+    conn: &mut SqliteConnection,
+    before: Option<i32>,
+    limit: i64,
+) -> Result<Vec<Webhook>, DbError> {
+    use schema::webhooks::dsl::{id, webhooks};
+
+    Ok(webhooks
+        .filter(id.lt(before.unwrap_or(i32::MAX)))
+        .order(id.desc())
+        .limit(limit)
+        .select(Webhook::as_select())
+        .load(conn)?)
+}
+
+/// Stores the url as a new webhook, registered by the user with that id, and returns it.
+///
+/// # Errors
+///
+/// This function will return an error if the webhook could not be stored.
+pub fn create_webhook(
+    conn: &mut SqliteConnection,
+    url: &str,
+    createdby: i32,
+) -> Result<Webhook, DbError> {
+    let webhook = NewWebhook {
+        url,
+        createdby,
+        created: Utc::now(),
+    };
+    diesel::insert_into(schema::webhooks::table)
+        .values(&webhook)
+        .returning(Webhook::as_returning())
+        .get_result(conn)
+        .optional()?
+        .ok_or(DbError::NoReturnOnInsert)
+}
+
+/// Removes the webhook with that id, returning it.
+///
+/// # Errors
+///
+/// This function will return `DbError::WebhookNotFound` if there is no such webhook, or an error if it could not
+/// be removed.
+pub fn delete_webhook(conn: &mut SqliteConnection, webhook_id: i32) -> Result<Webhook, DbError> {
+    use schema::webhooks::dsl::{id, webhooks};
+
+    let webhook = match webhooks
+        .filter(id.eq(webhook_id))
+        .select(Webhook::as_select())
+        .first(conn)
+    {
+        Ok(webhook) => webhook,
+        Err(diesel::result::Error::NotFound) => return Err(DbError::WebhookNotFound),
+        Err(e) => return Err(e.into()),
+    };
+    diesel::delete(webhooks.filter(id.eq(webhook_id))).execute(conn)?;
+    Ok(webhook)
+}
+
+/// Turns the items of a page, fetched with one more than `limit` to tell whether another page follows, into a
+/// `Page`. The cursor of the next page is made from its last item.
+fn paginate<T>(mut items: Vec<T>, limit: u32, cursor: impl Fn(&T) -> String) -> Page<T> {
+    let limit = limit as usize;
+    let next = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(cursor)
+    } else {
+        None
+    };
+    Page { items, next }
+}
+
+/// Sets the password for the given user.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist or the password could not be set.
+pub fn set_password(
+    conn: &mut SqliteConnection,
+    username: &str,
+    password: &str,
+) -> Result<(), DbError> {
+    use schema::authentications::dsl::{authentications, hashedpassword, userid};
+    let hash = auth::generate_hash(password);
+    let user = get_user_by_name(conn, username)?;
+    let user_auth_data = authentications.filter(userid.eq(user.id));
+    let auth_exists = user_auth_data.first::<Authentication>(conn).is_ok();
+
+    if auth_exists {
+        diesel::update(user_auth_data)
+            .set(hashedpassword.eq(hash))
+            .execute(conn)?;
+    } else {
+        let auth_data = NewAuthentication {
+            userid: user.id,
+            hashedpassword: hash,
+        };
+        diesel::insert_into(authentications)
+            .values(auth_data)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Checks if the given username and password are valid.
+///
+/// # Errors
+///
+/// This function will return an error if the user does not exist or no password is set.
+pub fn check_password(
+    conn: &mut SqliteConnection,
+    username: &str,
+    password: &str,
+) -> Result<bool, DbError> {
+    use schema::authentications::dsl::{authentications, userid};
+    let user = get_user_by_name(conn, username)?;
+    let Ok(auth_data) = authentications
+        .filter(userid.eq(user.id))
+        .first::<Authentication>(conn)
+    else {
+        return Err(DbError::NoPasswordSet);
+    };
+
+    Ok(auth::verify_password(password, &auth_data.hashedpassword))
+}
+
+/// Creates a new refresh token for the user. Only a hash of the token gets stored.
+///
+/// # Errors
+///
+/// This function will return an error if the token could not be stored.
+pub fn create_refresh_token(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    valid_for: Duration,
+) -> Result<RefreshToken, DbError> {
+    let secret = generate_secret(32);
+    let valid_for = chrono::Duration::from_std(valid_for).unwrap_or(chrono::Duration::zero());
+    let new_token = NewRefreshToken {
+        userid,
+        hashedtoken: auth::generate_hash(&secret),
+        validuntil: Utc::now() + valid_for,
+    };
+    let entry: RefreshTokenEntry = diesel::insert_into(schema::refreshtokens::table)
+        .values(new_token)
+        .get_result(conn)?;
+
+    Ok(RefreshToken(format!("{}.{secret}", entry.id)))
+}
+
+/// Creates a guest account with a generated name, which can be used for the given time.
+///
+/// # Errors
+///
+/// This function will return an error if no free name was found or the guest could not be stored.
+pub fn create_guest(
+    conn: &mut SqliteConnection,
+    lifetime: Duration,
+) -> Result<(User, DateTime<Utc>), DbError> {
+    let mut rng = rand::thread_rng();
+    // The names are random, so a few tries are enough to find a free one unless there are a lot of guests
+    let name = (0..10)
+        .map(|_| format!("guest-{:06}", rng.gen_range(0..1_000_000)))
+        .find(|name| get_user_by_name(conn, name).is_err())
+        .ok_or(DbError::UsernameInUse)?;
+    create_user(conn, &name)?;
+    let user = get_user_by_name(conn, &name)?;
+
+    let lifetime = chrono::Duration::from_std(lifetime).unwrap_or(chrono::Duration::zero());
+    let guest = NewGuest {
+        userid: user.id,
+        validuntil: Utc::now() + lifetime,
+    };
+    diesel::insert_into(schema::guests::table)
+        .values(&guest)
+        .execute(conn)?;
+    Ok((user, guest.validuntil))
+}
+
+/// Turns the guest account into a regular account, which does not expire.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn delete_guest(conn: &mut SqliteConnection, id: i32) -> Result<(), DbError> {
+    use chat_core::schema::guests::dsl::{guests, userid};
+
+    diesel::delete(guests.filter(userid.eq(id))).execute(conn)?;
+    Ok(())
+}
+
+/// Returns true if the user is a guest account.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn is_guest(conn: &mut SqliteConnection, id: i32) -> Result<bool, DbError> {
+    use chat_core::schema::guests::dsl::{guests, userid};
+
+    let count: i64 = guests.filter(userid.eq(id)).count().get_result(conn)?;
+    Ok(count > 0)
+}
+
+/// Records that the user accepted the given version of the terms of service, at the current time.
+///
+/// # Errors
+///
+/// This function will return an error if the acceptance could not be stored.
+pub fn accept_terms(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    version: &str,
+) -> Result<(), DbError> {
+    let acceptance = NewTermsAcceptance {
+        userid,
+        version,
+        accepteddate: Utc::now(),
+    };
+    diesel::insert_into(schema::termsacceptances::table)
+        .values(acceptance)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Consumes the refresh token, returning the id of the user it belongs to. Each refresh token can only be used once.
+///
+/// # Errors
+///
+/// This function will return an error if the token does not exist, does not match or has expired.
+pub fn consume_refresh_token(
+    conn: &mut SqliteConnection,
+    refresh_token: &RefreshToken,
+) -> Result<i32, DbError> {
+    use schema::refreshtokens::dsl::{id, refreshtokens};
+
+    let Some((token_id, secret)) = refresh_token.0.split_once('.') else {
+        return Err(DbError::RefreshTokenInvalid);
+    };
+    let Ok(token_id) = token_id.parse::<i32>() else {
+        return Err(DbError::RefreshTokenInvalid);
+    };
+    let Some(entry) = refreshtokens
+        .filter(id.eq(token_id))
+        .first::<RefreshTokenEntry>(conn)
+        .optional()?
+    else {
+        return Err(DbError::RefreshTokenInvalid);
+    };
+
+    if !auth::verify_password(secret, &entry.hashedtoken) {
+        return Err(DbError::RefreshTokenInvalid);
+    }
+
+    diesel::delete(refreshtokens.filter(id.eq(token_id))).execute(conn)?;
+
+    if entry.validuntil < Utc::now() {
+        return Err(DbError::RefreshTokenInvalid);
+    }
+
+    Ok(entry.userid)
+}
+
+/// Creates a new message.
+///
+/// # Errors
+///
+/// This function will return an error if inserting the message into the database fails.
+pub fn create_message(
+    conn: &mut SqliteConnection,
+    message: &str,
+    userid: i32,
+) -> Result<Message, DbError> {
+    create_conversation_message(conn, message, userid, Conversation::Chat)
+}
+
+/// Creates a new message in the conversation. The conversation is not checked, that is up to the caller.
+///
+/// # Errors
+///
+/// This function will return an error if inserting the message into the database fails.
+pub fn create_conversation_message(
+    conn: &mut SqliteConnection,
+    message: &str,
+    userid: i32,
+    conversation: Conversation,
+) -> Result<Message, DbError> {
+    let (channelid, recipientid) = conversation_columns(conversation);
+    let new_message = NewMessage {
+        date: Utc::now(),
+        messagetext: message.into(),
+        userid,
+        forwardedid: None,
+        forwardeduserid: None,
+        channelid,
+        recipientid,
+    };
+    insert_message(conn, &new_message)
+}
+
+/// Gets the message the user sent with that idempotency key, if they sent one.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_keyed_message(
+    conn: &mut SqliteConnection,
+    id: i32,
+    key: &str,
+) -> Result<Option<Message>, DbError> {
+    use chat_core::schema::messagekeys::dsl::{idempotencykey, messagekeys, userid};
+    use chat_core::schema::messages;
+
+    Ok(messagekeys
+        .inner_join(messages::table)
+        .filter(userid.eq(id))
+        .filter(idempotencykey.eq(key))
+        .select(MessageRow::as_select())
+        .first(conn)
+        .optional()?
+        .map(Message::from))
+}
+
+/// Remembers that the user sent the message with that idempotency key.
+///
+/// # Errors
+///
+/// This function will return an error if the key could not be stored, like when the user already used it.
+pub fn store_message_key(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    key: &str,
+    messageid: i32,
+) -> Result<(), DbError> {
+    let entry = NewMessageKey {
+        userid,
+        idempotencykey: key,
+        messageid,
+    };
+    diesel::insert_into(schema::messagekeys::table)
+        .values(entry)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The channel and the recipient columns of a message sent in the conversation.
+fn conversation_columns(conversation: Conversation) -> (Option<i32>, Option<i32>) {
+    match conversation {
+        Conversation::Chat => (None, None),
+        Conversation::Channel(channel) => (Some(channel), None),
+        Conversation::Direct(recipient) => (None, Some(recipient)),
+    }
+}
+
+/// Creates a copy of a message in the conversation, sent by the given user. The copy points to the original message
+/// and its author.
+///
+/// # Errors
+///
+/// This function will return an error if inserting the message into the database fails.
+pub fn forward_message(
+    conn: &mut SqliteConnection,
+    message: &str,
+    userid: i32,
+    conversation: Conversation,
+    original: ForwardedFrom,
+) -> Result<Message, DbError> {
+    let (channelid, recipientid) = conversation_columns(conversation);
+    let new_message = NewMessage {
+        date: Utc::now(),
+        messagetext: message.into(),
+        userid,
+        forwardedid: Some(original.message_id),
+        forwardeduserid: Some(original.userid),
+        channelid,
+        recipientid,
+    };
+    insert_message(conn, &new_message)
+}
+
+/// Creates all the messages in one transaction, which is a lot faster than creating them one at a time. Returns them
+/// in the same order. Either all messages are created or none.
+///
+/// # Errors
+///
+/// This function will return an error if inserting any of the messages into the database fails.
+pub fn create_messages(
+    conn: &mut SqliteConnection,
+    new_messages: &[NewMessage],
+) -> Result<Vec<Message>, DbError> {
+    conn.immediate_transaction(|conn| {
+        new_messages
+            .iter()
+            .map(|new_message| insert_message(conn, new_message))
+            .collect()
+    })
+}
+
+/// Inserts the message with the next id and the next sequence number of its conversation, which were never given to
+/// a message before, not even to deleted ones. Looking the numbers up and inserting happen in one transaction, and
+/// the unique indexes on the numbers reject the insert should another connection take them meanwhile.
+fn insert_message(
+    conn: &mut SqliteConnection,
+    new_message: &NewMessage,
+) -> Result<Message, DbError> {
+    use schema::conversationcounters::dsl::{conversationcounters, lastseq};
+    use schema::messagecounters::dsl::{lastid, messagecounters};
+    use schema::messages::dsl::{conversation, id, messages, seq};
+
+    let key = conversation_key(new_message);
+    conn.transaction(|conn| {
+        let last_id: i32 = messagecounters.select(lastid).first(conn)?;
+        let last_seq: i64 = conversationcounters
+            .filter(schema::conversationcounters::conversation.eq(&key))
+            .select(lastseq)
+            .first(conn)
+            .optional()?
+            .unwrap_or(0);
+        let mut result: Vec<MessageRow> = diesel::insert_into(messages)
+            .values((
+                new_message,
+                id.eq(last_id + 1),
+                seq.eq(last_seq + 1),
+                conversation.eq(&key),
+            ))
+            .returning(MessageRow::as_returning())
+            .get_results(conn)?;
+
+        if let Some(message) = result.pop() {
+            Ok(message.into())
+        } else {
+            Err(DbError::NoReturnOnInsert)
+        }
+    })
+}
+
+/// The key the conversation of the message is numbered under, which is the same for both users of direct messages.
+fn conversation_key(message: &NewMessage) -> String {
+    match (message.channelid, message.recipientid) {
+        (Some(channel), _) => format!("channel-{channel}"),
+        (None, Some(recipient)) => {
+            format!(
+                "direct-{}-{}",
+                message.userid.min(recipient),
+                message.userid.max(recipient)
+            )
+        }
+        (None, None) => "chat".to_string(),
+    }
+}
+
+/// Adds the stored files to the message.
+///
+/// # Errors
+///
+/// This function will return an error if inserting any of the attachments fails.
+pub fn create_attachments(
+    conn: &mut SqliteConnection,
+    messageid: i32,
+    files: &[StoredFile],
+) -> Result<(), DbError> {
+    let entries: Vec<NewAttachment> = files
+        .iter()
+        .map(|file| NewAttachment {
+            messageid,
+            filename: &file.name,
+            mimetype: &file.mime,
+            filesize: file.size,
+            storagekey: &file.key,
+            contenthash: Some(&file.key),
+        })
+        .collect();
+    diesel::insert_into(schema::attachments::table)
+        .values(&entries)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Gets the files attached to the message, in the order they were added.
+///
+/// # Errors
+///
+/// This function will return an error if the attachments could not be retrieved.
+pub fn get_attachments(
+    conn: &mut SqliteConnection,
+    message_id: i32,
+) -> Result<Vec<Attachment>, DbError> {
+    use chat_core::schema::attachments::dsl::{
+        attachments, contenthash, filename, filesize, id, messageid, mimetype,
+    };
+
+    let rows: Vec<AttachmentRow> = attachments
+        .filter(messageid.eq(message_id))
+        .order(id)
+        .select((id, messageid, filename, mimetype, filesize, contenthash))
+        .load(conn)?;
+    Ok(rows.into_iter().map(attachment).collect())
+}
+
+/// Fills in the attachments of the messages, with one query for all of them.
+///
+/// # Errors
+///
+/// This function will return an error if the attachments could not be retrieved.
+pub fn fill_attachments(
+    conn: &mut SqliteConnection,
+    messages: &mut [Message],
+) -> Result<(), DbError> {
+    use chat_core::schema::attachments::dsl::{
+        attachments, contenthash, filename, filesize, id, messageid, mimetype,
+    };
+
+    let ids: Vec<i32> = messages.iter().map(|message| message.id).collect();
+    let rows: Vec<AttachmentRow> = attachments
+        .filter(messageid.eq_any(ids))
+        .order(id)
+        .select((id, messageid, filename, mimetype, filesize, contenthash))
+        .load(conn)?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let mut by_message: HashMap<i32, Vec<Attachment>> = HashMap::new();
+    for row in rows {
+        by_message.entry(row.1).or_default().push(attachment(row));
+    }
+    for message in messages {
+        message.attachments = by_message.remove(&message.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// The id, message id, name, type, size and hash of an attachment, as stored.
+type AttachmentRow = (i32, i32, String, String, i64, Option<String>);
+
+/// The attachment as sent to clients.
+fn attachment((id, messageid, name, mime, size, hash): AttachmentRow) -> Attachment {
+    let thumbnail = (mime.starts_with("image/") && size <= THUMBNAIL_MAX_SIZE)
+        .then(|| format!("/attachments/{id}"));
+    Attachment {
+        id,
+        messageid,
+        name,
+        mime,
+        size,
+        thumbnail,
+        hash,
+    }
+}
+
+/// Gets the attachment with that id along with the key its file is stored under.
+///
+/// # Errors
+///
+/// This function will return an error if the attachment does not exist.
+pub fn get_attachment(
+    conn: &mut SqliteConnection,
+    attachment_id: i32,
+) -> Result<(Attachment, String), DbError> {
+    use chat_core::schema::attachments::dsl::{
+        attachments, contenthash, filename, filesize, id, messageid, mimetype, storagekey,
+    };
+
+    let (row, key): (AttachmentRow, String) = attachments
+        .filter(id.eq(attachment_id))
+        .select((
+            (id, messageid, filename, mimetype, filesize, contenthash),
+            storagekey,
+        ))
+        .first(conn)
+        .optional()?
+        .ok_or(DbError::AttachmentNotFound)?;
+    Ok((attachment(row), key))
+}
+
+/// Gets the ones of the storage keys whose files are used by at least one attachment.
+///
+/// # Errors
+///
+/// This function will return an error if the files could not be looked up.
+pub fn get_referenced_files(
+    conn: &mut SqliteConnection,
+    keys: &[String],
+) -> Result<Vec<String>, DbError> {
+    use chat_core::schema::blobs::dsl::{blobs, refcount, storagekey};
+
+    Ok(blobs
+        .filter(storagekey.eq_any(keys))
+        .filter(refcount.gt(0))
+        .select(storagekey)
+        .load(conn)?)
+}
+
+/// Forgets the stored files no attachment uses anymore, returning how many there were.
+///
+/// # Errors
+///
+/// This function will return an error if the files could not be deleted.
+pub fn delete_unused_blobs(conn: &mut SqliteConnection) -> Result<usize, DbError> {
+    use chat_core::schema::blobs::dsl::{blobs, refcount};
+
+    Ok(diesel::delete(blobs.filter(refcount.le(0))).execute(conn)?)
+}
+
+/// Gets the message with that id.
+///
+/// # Errors
+///
+/// This function will return an error if the message does not exist.
+pub fn get_message(conn: &mut SqliteConnection, message_id: i32) -> Result<Message, DbError> {
+    use chat_core::schema::messages::dsl::{id, messages};
+
+    messages
+        .filter(id.eq(message_id))
+        .select(MessageRow::as_select())
+        .first(conn)
+        .optional()?
+        .map(Message::from)
+        .ok_or(DbError::MessageNotFound)
+}
+
+/// Deletes the message together with its attachments, the bookmarks of it and the idempotency key it was sent with.
+/// Files no other attachment uses are left for the garbage collection.
+///
+/// # Errors
+///
+/// This function will return an error if the message does not exist or could not be deleted.
+pub fn delete_message(conn: &mut SqliteConnection, message_id: i32) -> Result<(), DbError> {
+    use chat_core::schema::{attachments, messagekeys, messages, savedmessages};
+
+    conn.transaction(|conn| {
+        diesel::delete(attachments::table.filter(attachments::messageid.eq(message_id)))
+            .execute(conn)?;
+        diesel::delete(savedmessages::table.filter(savedmessages::messageid.eq(message_id)))
+            .execute(conn)?;
+        diesel::delete(messagekeys::table.filter(messagekeys::messageid.eq(message_id)))
+            .execute(conn)?;
+        let rows_affected =
+            diesel::delete(messages::table.filter(messages::id.eq(message_id))).execute(conn)?;
+        if rows_affected == 0 {
+            return Err(DbError::MessageNotFound);
+        }
+        Ok(())
+    })
+}
+
+/// Gets the dates of the latest messages the user sent after the given point, but no more than `limit`. The dates are
+/// sorted from oldest to newest.
+///
+/// # Errors
+///
+/// This function will return an error if the messages could not be retrieved.
+pub fn message_dates_since(
+    conn: &mut SqliteConnection,
+    id: i32,
+    since: DateTime<Utc>,
+    limit: u32,
+) -> Result<Vec<DateTime<Utc>>, DbError> {
+    use chat_core::schema::messages::dsl::{date, messages, userid};
+
+    let mut dates: Vec<DateTime<Utc>> = messages
+        .filter(userid.eq(id))
+        .filter(date.gt(since))
+        .order(date.desc())
+        .limit(i64::from(limit))
+        .select(date)
+        .load(conn)?;
+    dates.reverse();
+    Ok(dates)
+}
+
+/// Saves the message for the user, at the current time. Does nothing if the user already saved it.
+///
+/// # Errors
+///
+/// This function will return an error if the message could not be saved.
+pub fn save_message(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    messageid: i32,
+) -> Result<(), DbError> {
+    let saved = NewSavedMessage {
+        userid,
+        messageid,
+        saveddate: Utc::now(),
+    };
+    diesel::insert_or_ignore_into(schema::savedmessages::table)
+        .values(saved)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Removes the message from the saved messages of the user. Does nothing if the user did not save it.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn unsave_message(
+    conn: &mut SqliteConnection,
+    id: i32,
+    message_id: i32,
+) -> Result<(), DbError> {
+    use chat_core::schema::savedmessages::dsl::{messageid, savedmessages, userid};
+
+    diesel::delete(
+        savedmessages
+            .filter(userid.eq(id))
+            .filter(messageid.eq(message_id)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Gets the drafts of the user, the most recently changed first. Drafts stored under a conversation key this
+/// version does not know are skipped.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn get_drafts(conn: &mut SqliteConnection, id: i32) -> Result<Vec<Draft>, DbError> {
+    use chat_core::schema::drafts::dsl::{conversation, drafts, drafttext, updateddate, userid};
+
+    let rows: Vec<(String, String, DateTime<Utc>)> = drafts
+        .filter(userid.eq(id))
+        .order(updateddate.desc())
+        .select((conversation, drafttext, updateddate))
+        .load(conn)?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(key, text, updated)| {
+            Some(Draft {
+                conversation: Conversation::from_key(&key)?,
+                text,
+                updated,
+            })
+        })
+        .collect())
+}
+
+/// Stores the draft of the user in the conversation at the current time, replacing the one they had there.
+///
+/// # Errors
+///
+/// This function will return an error if the draft could not be stored.
+pub fn save_draft(
+    conn: &mut SqliteConnection,
+    userid: i32,
+    conversation: Conversation,
+    text: &str,
+) -> Result<(), DbError> {
+    let key = conversation.key();
+    let draft = NewDraft {
+        userid,
+        conversation: &key,
+        drafttext: text,
+        updateddate: Utc::now(),
+    };
+    diesel::insert_into(schema::drafts::table)
+        .values(&draft)
+        .on_conflict((schema::drafts::userid, schema::drafts::conversation))
+        .do_update()
+        .set(&draft)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Removes the draft of the user in the conversation. Does nothing if they have none there.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn delete_draft(
+    conn: &mut SqliteConnection,
+    id: i32,
+    key: Conversation,
+) -> Result<(), DbError> {
+    use chat_core::schema::drafts::dsl::{conversation, drafts, userid};
+
+    diesel::delete(
+        drafts
+            .filter(userid.eq(id))
+            .filter(conversation.eq(key.key())),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Gets where notifications for the user are pushed to, if they set that up.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn get_push_settings(
+    conn: &mut SqliteConnection,
+    id: i32,
+) -> Result<Option<PushSettingsEntry>, DbError> {
+    use chat_core::schema::pushsettings::dsl::{pushsettings, userid};
+
+    Ok(pushsettings.filter(userid.eq(id)).first(conn).optional()?)
+}
+
+/// Stores where notifications for the user are pushed to, replacing what was stored before.
+///
+/// # Errors
+///
+/// This function will return an error if the settings could not be stored.
+pub fn set_push_settings(
+    conn: &mut SqliteConnection,
+    entry: &PushSettingsEntry,
+) -> Result<(), DbError> {
+    diesel::insert_into(schema::pushsettings::table)
+        .values(entry)
+        .on_conflict(schema::pushsettings::userid)
+        .do_update()
+        .set(entry)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Removes the push settings of the user. Does nothing if they have none.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn delete_push_settings(conn: &mut SqliteConnection, id: i32) -> Result<(), DbError> {
+    use chat_core::schema::pushsettings::dsl::{pushsettings, userid};
+
+    diesel::delete(pushsettings.filter(userid.eq(id))).execute(conn)?;
+    Ok(())
+}
+
+/// Gets the push settings of every user besides the sender, along with their names, which mentions are looked for.
+///
+/// # Errors
+///
+/// This function will return an error if the operation fails.
+pub fn get_push_recipients(
+    conn: &mut SqliteConnection,
+    sender: i32,
+) -> Result<Vec<(PushSettingsEntry, String)>, DbError> {
+    use chat_core::schema::{pushsettings, users};
+
+    Ok(pushsettings::table
+        .inner_join(users::table)
+        .filter(pushsettings::userid.ne(sender))
+        .select((pushsettings::all_columns, users::username))
+        .load(conn)?)
+}
+
+/// The push settings as sent to clients. `None` if they name a provider this version does not know.
+fn push_settings(entry: PushSettingsEntry) -> Option<PushSettings> {
+    Some(PushSettings {
+        provider: PushProvider::from_key(&entry.provider)?,
+        url: entry.url,
+        token: entry.token,
+        mentions: entry.mentions,
+        direct_messages: entry.directmessages,
+    })
+}
+
+/// Gets up to `limit` messages the user saved, the most recently saved first. If `after` is given, only the ones
+/// saved before that date and message id are returned.
+///
+/// # Errors
+///
+/// This function will return an error if the messages could not be retrieved.
+pub fn get_saved_messages(
+    conn: &mut SqliteConnection,
+    id: i32,
+    after: Option<(DateTime<Utc>, i32)>,
+    limit: i64,
+) -> Result<Vec<SavedMessage>, DbError> {
+    use chat_core::schema::messages;
+    use chat_core::schema::savedmessages::dsl::{messageid, saveddate, savedmessages, userid};
+
+    let mut query = savedmessages
+        .inner_join(messages::table)
+        .filter(userid.eq(id))
+        .order((saveddate.desc(), messageid.desc()))
+        .select((saveddate, MessageRow::as_select()))
+        .limit(limit)
+        .into_boxed();
+    if let Some((date, message)) = after {
+        query = query.filter(
+            saveddate
+                .lt(date)
+                .or(saveddate.eq(date).and(messageid.lt(message))),
+        );
+    }
+    let (dates, mut saved): (Vec<DateTime<Utc>>, Vec<Message>) = query
+        .load::<(DateTime<Utc>, MessageRow)>(conn)?
+        .into_iter()
+        .map(|(date, row)| (date, row.into()))
+        .unzip();
+    fill_attachments(conn, &mut saved)?;
+    Ok(dates
+        .into_iter()
+        .zip(saved)
+        .map(|(saved, message)| SavedMessage { saved, message })
+        .collect())
+}
+
+/// Reads a cursor into the saved messages, made of the nanosecond timestamp the last message was saved at and its
+/// id.
+fn parse_saved_cursor(cursor: &str) -> Option<(DateTime<Utc>, i32)> {
+    let (nanos, message) = cursor.split_once('_')?;
+    Some((
+        DateTime::from_timestamp_nanos(nanos.parse().ok()?),
+        message.parse().ok()?,
+    ))
+}
+
+/// Creates a channel with that name and topic.
+///
+/// # Errors
+///
+/// This function will return an error if a channel with that name already exists or it could not be created.
+pub fn create_channel(
+    conn: &mut SqliteConnection,
+    name: &str,
+    topic: &str,
+) -> Result<Channel, DbError> {
+    use schema::channels::dsl::{channels, name as channel_name};
+
+    if channels
+        .filter(channel_name.eq(name))
+        .count()
+        .get_result::<i64>(conn)?
+        > 0
+    {
+        return Err(DbError::ChannelNameInUse);
+    }
+    let now = Utc::now();
+    let new_channel = NewChannel {
+        name,
+        topic,
+        createddate: now,
+        updateddate: now,
+    };
+    Ok(diesel::insert_into(channels)
+        .values(new_channel)
+        .returning(Channel::as_returning())
+        .get_result(conn)?)
+}
+
+/// Gets the channel with that id.
+///
+/// # Errors
+///
+/// This function will return an error if the channel does not exist.
+pub fn get_channel(conn: &mut SqliteConnection, channel_id: i32) -> Result<Channel, DbError> {
+    use schema::channels::dsl::{channels, id};
+
+    channels
+        .filter(id.eq(channel_id))
+        .select(Channel::as_select())
+        .first(conn)
+        .optional()?
+        .ok_or(DbError::ChannelNotFound)
+}
+
+/// Gets the channel with that name.
+///
+/// # Errors
+///
+/// This function will return an error if the channel does not exist.
+pub fn get_channel_by_name(
+    conn: &mut SqliteConnection,
+    channel_name: &str,
+) -> Result<Channel, DbError> {
+    use schema::channels::dsl::{channels, name};
+
+    channels
+        .filter(name.eq(channel_name))
+        .select(Channel::as_select())
+        .first(conn)
+        .optional()?
+        .ok_or(DbError::ChannelNotFound)
+}
+
+/// Returns up to `limit` channels with an id above `after`, ordered by id. If `member` is given, only the channels
+/// that user is a member of are returned.
+///
+/// # Errors
+///
+/// This function will return an error if the channels could not be retrieved.
+pub fn get_channel_page(
+    conn: &mut SqliteConnection,
+    member: Option<i32>,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<Vec<Channel>, DbError> {
+    use schema::channelmembers::dsl::{channelid, channelmembers, userid};
+    use schema::channels::dsl::{channels, id};
+
+    let mut query = channels
+        .filter(id.gt(after.unwrap_or(i32::MIN)))
+        .order(id.asc())
+        .limit(limit)
+        .select(Channel::as_select())
+        .into_boxed();
+    if let Some(member) = member {
+        query = query.filter(id.eq_any(channelmembers.filter(userid.eq(member)).select(channelid)));
+    }
+    Ok(query.load(conn)?)
+}
+
+/// Adds the user to the members of the channel. Does nothing if they already are one.
+///
+/// # Errors
+///
+/// This function will return an error if the membership could not be stored.
+pub fn join_channel(
+    conn: &mut SqliteConnection,
+    channelid: i32,
+    userid: i32,
+) -> Result<(), DbError> {
+    diesel::insert_or_ignore_into(schema::channelmembers::table)
+        .values(NewChannelMember { channelid, userid })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Removes the user from the members of the channel. Does nothing if they are not one.
+///
+/// # Errors
+///
+/// This function will return an error if the membership could not be removed.
+pub fn leave_channel(conn: &mut SqliteConnection, channel_id: i32, id: i32) -> Result<(), DbError> {
+    use schema::channelmembers::dsl::{channelid, channelmembers, userid};
+
+    diesel::delete(
+        channelmembers
+            .filter(channelid.eq(channel_id))
+            .filter(userid.eq(id)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Gets how the member is told about new messages in the channel. `None` if they are not a member of it.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_member_notifications(
+    conn: &mut SqliteConnection,
+    channel_id: i32,
+    id: i32,
+) -> Result<Option<NotificationLevel>, DbError> {
+    use schema::channelmembers::dsl::{channelid, channelmembers, notifications, userid};
+
+    let level: Option<String> = channelmembers
+        .filter(channelid.eq(channel_id))
+        .filter(userid.eq(id))
+        .select(notifications)
+        .first(conn)
+        .optional()?;
+    // Levels this version does not know are treated as the default
+    Ok(level.map(|level| NotificationLevel::from_key(&level).unwrap_or_default()))
+}
+
+/// Sets how the member is told about new messages in the channel. Returns false if they are not a member of it.
+///
+/// # Errors
+///
+/// This function will return an error if the setting could not be stored.
+pub fn set_member_notifications(
+    conn: &mut SqliteConnection,
+    channel_id: i32,
+    id: i32,
+    level: NotificationLevel,
+) -> Result<bool, DbError> {
+    use schema::channelmembers::dsl::{channelid, channelmembers, notifications, userid};
+
+    let changed = diesel::update(
+        channelmembers
+            .filter(channelid.eq(channel_id))
+            .filter(userid.eq(id)),
+    )
+    .set(notifications.eq(level.key()))
+    .execute(conn)?;
+    Ok(changed > 0)
+}
+
+/// Gets the channels the user is a member of and gets fewer notifications for than every message.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_notification_overrides(
+    conn: &mut SqliteConnection,
+    id: i32,
+) -> Result<Vec<ChannelNotifications>, DbError> {
+    use schema::channelmembers::dsl::{channelid, channelmembers, notifications, userid};
+
+    let overrides: Vec<(i32, String)> = channelmembers
+        .filter(userid.eq(id))
+        .filter(notifications.ne(NotificationLevel::All.key()))
+        .select((channelid, notifications))
+        .order_by(channelid)
+        .load(conn)?;
+    Ok(overrides
+        .into_iter()
+        .filter_map(|(channel, level)| {
+            Some(ChannelNotifications {
+                channelid: channel,
+                level: NotificationLevel::from_key(&level)?,
+            })
+        })
+        .collect())
+}
+
+/// Sets how many seconds the members of the channel have to wait between their messages. Returns the changed channel.
+///
+/// # Errors
+///
+/// This function will return an error if the channel does not exist or could not be changed.
+pub fn set_channel_slowmode(
+    conn: &mut SqliteConnection,
+    channel_id: i32,
+    seconds: i32,
+) -> Result<Channel, DbError> {
+    use schema::channels::dsl::{channels, id, slowmode, updateddate, version};
+
+    diesel::update(channels.filter(id.eq(channel_id)))
+        .set((
+            slowmode.eq(seconds),
+            updateddate.eq(Utc::now()),
+            version.eq(version + 1),
+        ))
+        .returning(Channel::as_returning())
+        .get_result(conn)
+        .optional()?
+        .ok_or(DbError::ChannelNotFound)
+}
+
+/// Sets the topic of the channel. Returns the changed channel.
+///
+/// # Errors
+///
+/// This function will return an error if the channel does not exist or could not be changed.
+pub fn set_channel_topic(
+    conn: &mut SqliteConnection,
+    channel_id: i32,
+    new_topic: &str,
+) -> Result<Channel, DbError> {
+    use schema::channels::dsl::{channels, id, topic, updateddate, version};
+
+    diesel::update(channels.filter(id.eq(channel_id)))
+        .set((
+            topic.eq(new_topic),
+            updateddate.eq(Utc::now()),
+            version.eq(version + 1),
+        ))
+        .returning(Channel::as_returning())
+        .get_result(conn)
+        .optional()?
+        .ok_or(DbError::ChannelNotFound)
+}
+
+/// Gets the message of the day.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_motd(conn: &mut SqliteConnection) -> Result<Motd, DbError> {
+    Ok(schema::motd::table.select(Motd::as_select()).first(conn)?)
+}
+
+/// Sets the message of the day. Returns the changed one.
+///
+/// # Errors
+///
+/// This function will return an error if it could not be changed.
+pub fn set_motd(conn: &mut SqliteConnection, new_text: &str) -> Result<Motd, DbError> {
+    use schema::motd::dsl::{motd, text, updateddate, version};
+
+    Ok(diesel::update(motd)
+        .set((
+            text.eq(new_text),
+            updateddate.eq(Utc::now()),
+            version.eq(version + 1),
+        ))
+        .returning(Motd::as_returning())
+        .get_result(conn)?)
+}
+
+/// Gets the date of the latest message the user sent to the channel, if they sent any.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn last_channel_message_date(
+    conn: &mut SqliteConnection,
+    channel_id: i32,
+    id: i32,
+) -> Result<Option<DateTime<Utc>>, DbError> {
+    use schema::messages::dsl::{channelid, date, messages, userid};
+
+    Ok(messages
+        .filter(channelid.eq(channel_id))
+        .filter(userid.eq(id))
+        .order(date.desc())
+        .select(date)
+        .first(conn)
+        .optional()?)
+}
+
+/// Returns true if the user is a member of the channel.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn is_channel_member(
+    conn: &mut SqliteConnection,
+    channel_id: i32,
+    id: i32,
+) -> Result<bool, DbError> {
+    use schema::channelmembers::dsl::{channelid, channelmembers, userid};
+
+    let count: i64 = channelmembers
+        .filter(channelid.eq(channel_id))
+        .filter(userid.eq(id))
+        .count()
+        .get_result(conn)?;
+    Ok(count > 0)
+}
+
+/// Returns true if the user blocked the other one.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn has_blocked(conn: &mut SqliteConnection, id: i32, other: i32) -> Result<bool, DbError> {
+    use schema::userblocks::dsl::{blockedid, userblocks, userid};
+
+    let count: i64 = userblocks
+        .filter(userid.eq(id))
+        .filter(blockedid.eq(other))
+        .count()
+        .get_result(conn)?;
+    Ok(count > 0)
+}
+
+/// Blocks the other user for the user. Does nothing if they already did.
+///
+/// # Errors
+///
+/// This function will return an error if the block could not be stored.
+pub fn block_user(conn: &mut SqliteConnection, userid: i32, blockedid: i32) -> Result<(), DbError> {
+    diesel::insert_or_ignore_into(schema::userblocks::table)
+        .values(NewUserBlock { userid, blockedid })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Removes the block of the other user by the user. Does nothing if there is none.
+///
+/// # Errors
+///
+/// This function will return an error if the block could not be removed.
+pub fn unblock_user(conn: &mut SqliteConnection, id: i32, other: i32) -> Result<(), DbError> {
+    use schema::userblocks::dsl::{blockedid, userblocks, userid};
+
+    diesel::delete(userblocks.filter(userid.eq(id)).filter(blockedid.eq(other))).execute(conn)?;
+    Ok(())
+}
+
+/// Gets up to `limit` of the users the user blocked with an id above `after`, ordered by id.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_blocked_users(
+    conn: &mut SqliteConnection,
+    id: i32,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<Vec<User>, DbError> {
+    use schema::{userblocks, users};
+
+    Ok(userblocks::table
+        .inner_join(users::table.on(users::id.eq(userblocks::blockedid)))
+        .filter(userblocks::userid.eq(id))
+        .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+        .select(User::as_select())
+        .order_by(users::id)
+        .limit(limit)
+        .load(conn)?)
+}
+
+/// Gets the id of the newest message the user read in the conversation, if they read any yet.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_read_marker(
+    conn: &mut SqliteConnection,
+    id: i32,
+    conversation: Conversation,
+) -> Result<Option<i32>, DbError> {
+    use chat_core::schema::readmarkers::dsl::{
+        conversation as key, messageid, readmarkers, userid,
+    };
+
+    Ok(readmarkers
+        .filter(userid.eq(id))
+        .filter(key.eq(conversation.key()))
+        .select(messageid)
+        .first(conn)
+        .optional()?)
+}
+
+/// Gets the users who read the conversation with that key, each with the id of the newest message they read.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_read_markers(
+    conn: &mut SqliteConnection,
+    conversation: &str,
+) -> Result<Vec<(i32, i32)>, DbError> {
+    use chat_core::schema::readmarkers::dsl::{
+        conversation as key, messageid, readmarkers, userid,
+    };
+
+    Ok(readmarkers
+        .filter(key.eq(conversation))
+        .select((userid, messageid))
+        .order_by(userid)
+        .load(conn)?)
+}
+
+/// Records that the user read the messages of the conversation up to the one with that id. The marker only moves
+/// forward, so marking an older message as read does nothing. Returns whether it moved.
+///
+/// # Errors
+///
+/// This function will return an error if the marker could not be stored.
+pub fn set_read_marker(
+    conn: &mut SqliteConnection,
+    id: i32,
+    conversation: Conversation,
+    message_id: i32,
+) -> Result<bool, DbError> {
+    conn.transaction(|conn| {
+        if get_read_marker(conn, id, conversation)?.is_some_and(|read| read >= message_id) {
+            return Ok(false);
+        }
+        let key = conversation.key();
+        let marker = NewReadMarker {
+            userid: id,
+            conversation: &key,
+            messageid: message_id,
+        };
+        diesel::replace_into(schema::readmarkers::table)
+            .values(marker)
+            .execute(conn)?;
+        Ok(true)
+    })
+}
+
+/// Counts the messages other users sent to the conversation after the given one, or all of them if there is none.
+/// Also returns whether any of them mention the user. Direct messages are the ones between the user and the other
+/// user.
+///
+/// # Errors
+///
+/// This function will return an error if the messages could not be counted.
+pub fn count_unread(
+    conn: &mut SqliteConnection,
+    user: &User,
+    conversation: Conversation,
+    after: Option<i32>,
+) -> Result<(u32, bool), DbError> {
+    use chat_core::schema::messages::dsl::{id, messages, messagetext, userid};
+
+    // Boxed queries are used up when run, so both lookups build their own
+    let unread = || {
+        in_conversation(messages.into_boxed(), conversation, user.id)
+            .filter(userid.ne(user.id))
+            .filter(id.gt(after.unwrap_or(0)))
+    };
+    let count: i64 = unread().count().get_result(conn)?;
+
+    // The database narrows it down, but only `mentions` knows where names end
+    let name = user
+        .username
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    let candidates: Vec<String> = unread()
+        .filter(messagetext.like(format!("%@{name}%")).escape('\\'))
+        .select(messagetext)
+        .load(conn)?;
+    let mentioned = candidates.iter().any(|text| mentions(text, &user.username));
+
+    Ok((u32::try_from(count).unwrap_or(u32::MAX), mentioned))
+}
+
+/// Gets the ids of up to `limit` users above `after` the user with that id sent direct messages to or got direct
+/// messages from, ordered by id.
+///
+/// # Errors
+///
+/// This function will return an error if the lookup failed.
+pub fn get_direct_partners(
+    conn: &mut SqliteConnection,
+    id: i32,
+    after: Option<i32>,
+    limit: i64,
+) -> Result<Vec<i32>, DbError> {
+    use chat_core::schema::messages::dsl::{channelid, messages, recipientid, userid};
+
+    let after = after.unwrap_or(i32::MIN);
+    let direct = messages.filter(channelid.is_null());
+    // The first ones of both together are among the first ones of each
+    let sent_to: Vec<Option<i32>> = direct
+        .filter(userid.eq(id))
+        .filter(recipientid.gt(after))
+        .select(recipientid)
+        .distinct()
+        .order(recipientid.asc())
+        .limit(limit)
+        .load(conn)?;
+    let received_from: Vec<i32> = direct
+        .filter(recipientid.eq(id))
+        .filter(userid.gt(after))
+        .select(userid)
+        .distinct()
+        .order(userid.asc())
+        .limit(limit)
+        .load(conn)?;
+    let mut partners: Vec<i32> = sent_to.into_iter().flatten().chain(received_from).collect();
+    partners.sort_unstable();
+    partners.dedup();
+    partners.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+    Ok(partners)
+}
+
+/// Get messages of the main chat written before or after the given date, lmited to the 20 closest to it at a time.
+///
+/// # Errors
+///
+/// This function will return an error if the messages cannot be retrieved.
+pub fn get_messages(
+    conn: &mut SqliteConnection,
+    filter: &MessageFilter,
+) -> Result<Vec<Message>, DbError> {
+    get_message_page(conn, filter, 20)
+}
+
+/// Get up to `limit` messages of the main chat written before or after the given date or sequence number, or the
+/// latest ones, the ones closest to it first. Either way they are ordered by sequence number, so messages sent at
+/// the same time keep their order.
+///
+/// # Errors
+///
+/// This function will return an error if the messages cannot be retrieved.
+pub fn get_message_page(
+    conn: &mut SqliteConnection,
+    filter: &MessageFilter,
+    limit: i64,
+) -> Result<Vec<Message>, DbError> {
+    get_conversation_page(conn, Conversation::Chat, 0, filter, limit)
+}
+
+/// Like `get_message_page`, but for any conversation. Direct messages are the ones between the viewer and the other
+/// user, and messages of users the viewer blocked are left out. Whether the viewer may read the conversation is not
+/// checked, that is up to the caller.
+///
+/// # Errors
+///
+/// This function will return an error if the messages cannot be retrieved.
+pub fn get_conversation_page(
+    conn: &mut SqliteConnection,
+    conversation: Conversation,
+    viewer: i32,
+    filter: &MessageFilter,
+    limit: i64,
+) -> Result<Vec<Message>, DbError> {
+    use schema::messages::dsl::{date, messages, seq, userid};
+    use schema::userblocks;
+
+    let blocked = userblocks::table
+        .filter(userblocks::userid.eq(viewer))
+        .select(userblocks::blockedid);
+    let query = in_conversation(messages.limit(limit).into_boxed(), conversation, viewer)
+        .filter(diesel::dsl::not(userid.eq_any(blocked)));
+
+    let query = match *filter {
+        MessageFilter::Before(before) => query.filter(date.lt(before)).order_by(seq.desc()),
+        MessageFilter::BeforeSeq(before) => query.filter(seq.lt(before)).order_by(seq.desc()),
+        // Ascending, so paging forward does not skip the messages right after the date
+        MessageFilter::After(after) => query.filter(date.gt(after)).order_by(seq.asc()),
+        MessageFilter::AfterSeq(after) => query.filter(seq.gt(after)).order_by(seq.asc()),
+        MessageFilter::Latest(count) => query
+            .order_by(seq.desc())
+            .limit(limit.min(i64::from(count))),
+    };
+
+    let mut page = load_messages(query, conn)?;
+    fill_attachments(conn, &mut page)?;
+    Ok(page)
+}
+
+/// Gets the messages the user sent to the conversation after the given point, the newest first.
+///
+/// # Errors
+///
+/// This function will return an error if the messages could not be retrieved.
+pub fn get_recent_messages(
+    conn: &mut SqliteConnection,
+    id: i32,
+    conversation: Conversation,
+    since: DateTime<Utc>,
+) -> Result<Vec<Message>, DbError> {
+    use schema::messages::dsl::{date, messages, seq, userid};
+
+    let query = messages
+        .filter(userid.eq(id))
+        .filter(date.gt(since))
+        .into_boxed();
+    load_messages(
+        in_conversation(query, conversation, id).order_by(seq.desc()),
+        conn,
+    )
+}
+
+/// Loads the messages the query finds, without their attachments.
+fn load_messages(
+    query: schema::messages::BoxedQuery<'_, Sqlite>,
+    conn: &mut SqliteConnection,
+) -> Result<Vec<Message>, DbError> {
+    let rows: Vec<MessageRow> = query.select(MessageRow::as_select()).load(conn)?;
+    Ok(rows.into_iter().map(Message::from).collect())
+}
+
+/// Narrows the query down to the messages of the conversation, as seen by the viewer.
+fn in_conversation(
+    query: schema::messages::BoxedQuery<'_, Sqlite>,
+    conversation: Conversation,
+    viewer: i32,
+) -> schema::messages::BoxedQuery<'_, Sqlite> {
+    use schema::messages::dsl::{channelid, recipientid, userid};
+    match conversation {
+        Conversation::Chat => query
+            .filter(channelid.is_null())
+            .filter(recipientid.is_null()),
+        Conversation::Channel(channel) => query.filter(channelid.eq(channel)),
+        Conversation::Direct(other) => query.filter(channelid.is_null()).filter(
+            userid
+                .eq(viewer)
+                .and(recipientid.eq(other))
+                .or(userid.eq(other).and(recipientid.eq(viewer))),
+        ),
+    }
+}
+
+/// Find up to `limit` messages whose text contains every word of the query, or words starting with them, the newest
+/// first. A query without words finds every message passing the filter. Without a conversation in the filter, every
+/// message the viewer may read is searched. `before` skips the messages from that id on, to page through the
+/// results, as sequence numbers only order the messages within one conversation.
+///
+/// # Errors
+///
+/// This function will return an error if the messages cannot be searched.
+pub fn search_messages(
+    conn: &mut SqliteConnection,
+    viewer: i32,
+    query: &str,
+    filter: &SearchFilter,
+    before: Option<i32>,
+    limit: i64,
+) -> Result<Vec<Message>, DbError> {
+    use schema::messages::dsl::{channelid, date, id, messages, recipientid, userid};
+    use schema::{attachments, channelmembers, messagesearch};
+
+    let mut search = messages.order_by(id.desc()).limit(limit).into_boxed();
+    if let Some(pattern) = search_pattern(query) {
+        let matching = messagesearch::table
+            .filter(sql::<Bool>("messagesearch MATCH ").bind::<Text, _>(pattern))
+            .select(messagesearch::rowid);
+        search = search.filter(id.eq_any(matching));
+    }
+    let mut search = match filter.conversation {
+        Some(conversation) => in_conversation(search, conversation, viewer),
+        None => {
+            let joined = channelmembers::table
+                .filter(channelmembers::userid.eq(viewer))
+                .select(channelmembers::channelid.nullable());
+            search.filter(
+                channelid.eq_any(joined).or(channelid.is_null().and(
+                    recipientid
+                        .is_null()
+                        .or(userid.eq(viewer))
+                        .or(recipientid.eq(viewer)),
+                )),
+            )
+        }
+    };
+    if let Some(author) = filter.from {
+        search = search.filter(userid.eq(author));
+    }
+    if let Some(sent_before) = filter.before {
+        search = search.filter(date.lt(sent_before));
+    }
+    if let Some(sent_after) = filter.after {
+        search = search.filter(date.ge(sent_after));
+    }
+    if filter.has_attachment {
+        search = search.filter(id.eq_any(attachments::table.select(attachments::messageid)));
+    }
+    if let Some(before) = before {
+        search = search.filter(id.lt(before));
+    }
+
+    let mut found = load_messages(search, conn)?;
+    fill_attachments(conn, &mut found)?;
+    Ok(found)
+}
+
+/// Turns what the user searched for into an FTS5 query for messages with every word, or a word starting with it.
+/// Each word is quoted, so the query syntax of FTS5 is not available to users and their searches can not fail to
+/// parse. Returns `None` if there is nothing to search for.
+fn search_pattern(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Establish a connection to the database at the given path.
+///
+/// # Errors
+///
+/// This function will return an error if a connection could not be established or the database schema is not valid.
+pub fn establish_connection(database: &Path) -> Result<SqliteConnection, DbError> {
+    let mut connection = SqliteConnection::establish(&database.to_string_lossy())
+        .or(Err(DbError::UsernameCollisionDetected))?;
+    connection
+        .run_pending_migrations(MIGRATIONS)
+        .or(Err(DbError::MigrationFailure))?;
+
+    Ok(connection)
+}
+
+/// Create a connection pool to interact with the database at the given path, configured as given and reporting its
+/// checkouts to the metrics.
+///
+/// # Errors
+///
+/// This function will return an error if a connection pool could not be created.
+pub fn get_connection_pool(
+    url: &str,
+    config: &PoolConfig,
+    metrics: &Arc<PoolMetrics>,
+) -> Result<Pool<ConnectionManager<SqliteConnection>>, DbError> {
+    let manager = ConnectionManager::<SqliteConnection>::new(url);
+    let max_size = config.max_size.max(1);
+    let Ok(pool) = Pool::builder()
+        .test_on_check_out(true)
+        .max_size(max_size)
+        .min_idle(config.min_idle.map(|min_idle| min_idle.min(max_size)))
+        .max_lifetime(config.max_lifetime())
+        .connection_timeout(config.connection_timeout())
+        .event_handler(metrics.handler())
+        .build(manager)
+    else {
+        return Err(DbError::ConnectionFailure);
+    };
+
+    // Waits for a connection, as the pool may not keep any open while idle
+    let mut conn = pool.get()?;
+    conn.run_pending_migrations(MIGRATIONS)
+        .or(Err(DbError::MigrationFailure))?;
+
+    Ok(pool)
+}
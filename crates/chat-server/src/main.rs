@@ -0,0 +1,49 @@
+use std::{env, panic, path::Path, process};
+
+use chat_core::{crash, paths::Paths};
+use rocket::figment::providers::{Env, Format, Toml};
+
+#[rocket::launch]
+fn rocket() -> _ {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let paths = match Paths::resolve(&args).and_then(|paths| paths.create().map(|()| paths)) {
+        Ok(paths) => paths,
+        Err(e) => {
+            println!("{e}");
+            process::exit(1)
+        }
+    };
+
+    // Rocket answers requests whose handler panicked with a 500 and carries on, so panics are only logged, together
+    // with a crash report to look into them later
+    let data = paths.data.clone();
+    panic::set_hook(Box::new(move |info| {
+        let description = crash::describe("server", info);
+        eprintln!("{description}");
+        match crash::write_report(&data, "server", &description) {
+            Ok(path) => eprintln!("A crash report was written to {}.", path.display()),
+            Err(e) => eprintln!("Could not write a crash report: {e}"),
+        }
+    }));
+
+    // Rocket only looks for its configuration in the working directory, so the config directory is checked too
+    let mut figment = rocket::Config::figment();
+    let file = paths.config.join("Rocket.toml");
+    if env::var_os("ROCKET_CONFIG").is_none() && !Path::new("Rocket.toml").exists() && file.exists()
+    {
+        // The environment is merged again, so it still takes precedence over the file
+        figment = figment
+            .merge(Toml::file(file).nested())
+            .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE"]).global());
+    }
+
+    if Path::new("data.db").exists() && !paths.data.join("data.db").exists() {
+        println!(
+            "There is a data.db in the working directory, but the database is now kept in {}. Move it there or \
+             start the server with --data-dir . to keep using it.",
+            paths.data.display()
+        );
+    }
+
+    chat_server::server::rocket(figment, &paths)
+}
@@ -0,0 +1,262 @@
+//! The rows of the tables only the server reads and writes. The models sent to clients are in `chat_core::models`.
+use chat_core::models::{Message, User};
+use chat_core::schema::{
+    addressbans, apikeys, attachments, auditlog, authentications, bots, channelmembers, channels,
+    drafts, guests, invites, messagekeys, messages, pushsettings, readmarkers, refreshtokens,
+    savedmessages, termsacceptances, userblocks, users, webhooks,
+};
+use chrono::{DateTime, Utc};
+use diesel::{AsChangeset, Insertable, Queryable, Selectable};
+
+#[derive(Debug, Queryable)]
+pub struct Authentication {
+    pub id: i32,
+    pub userid: i32,
+    pub hashedpassword: String,
+}
+
+#[derive(Debug, Queryable)]
+pub struct RefreshTokenEntry {
+    pub id: i32,
+    pub userid: i32,
+    pub hashedtoken: String,
+    pub validuntil: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = users)]
+pub struct NewUser<'a> {
+    pub username: &'a str,
+    pub createddate: DateTime<Utc>,
+    pub updateddate: DateTime<Utc>,
+}
+
+/// A user with what admins and moderators see of them in the user directory.
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = users)]
+pub struct DirectoryEntry {
+    #[diesel(embed)]
+    pub user: User,
+    pub banned: bool,
+    pub lastactive: Option<DateTime<Utc>>,
+    pub createddate: Option<DateTime<Utc>>,
+    pub updateddate: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = authentications)]
+pub struct NewAuthentication {
+    pub userid: i32,
+    pub hashedpassword: String,
+}
+
+/// A row of the `messages` table, which is turned into a `Message` before it is sent to clients.
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = messages)]
+pub struct MessageRow {
+    pub id: i32,
+    pub date: DateTime<Utc>,
+    pub messagetext: String,
+    pub userid: i32,
+    pub forwardedid: Option<i32>,
+    pub forwardeduserid: Option<i32>,
+    pub seq: i64,
+    pub channelid: Option<i32>,
+    pub recipientid: Option<i32>,
+}
+
+impl From<MessageRow> for Message {
+    fn from(row: MessageRow) -> Self {
+        Self {
+            id: row.id,
+            date: row.date,
+            messagetext: row.messagetext,
+            userid: row.userid,
+            forwardedid: row.forwardedid,
+            forwardeduserid: row.forwardeduserid,
+            seq: row.seq,
+            channelid: row.channelid,
+            recipientid: row.recipientid,
+            // Attachments are not in the table, the server fills them in after loading
+            attachments: Vec::new(),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = messages)]
+pub struct NewMessage {
+    pub date: DateTime<Utc>,
+    pub messagetext: String,
+    pub userid: i32,
+    pub forwardedid: Option<i32>,
+    pub forwardeduserid: Option<i32>,
+    pub channelid: Option<i32>,
+    pub recipientid: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = channels)]
+pub struct NewChannel<'a> {
+    pub name: &'a str,
+    pub topic: &'a str,
+    pub createddate: DateTime<Utc>,
+    pub updateddate: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = channelmembers)]
+pub struct NewChannelMember {
+    pub channelid: i32,
+    pub userid: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = messagekeys)]
+pub struct NewMessageKey<'a> {
+    pub userid: i32,
+    pub idempotencykey: &'a str,
+    pub messageid: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = refreshtokens)]
+pub struct NewRefreshToken {
+    pub userid: i32,
+    pub hashedtoken: String,
+    pub validuntil: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = addressbans)]
+pub struct NewAddressBan<'a> {
+    pub network: &'a str,
+    pub reason: &'a str,
+    pub date: DateTime<Utc>,
+    pub createdby: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = invites)]
+pub struct NewInvite<'a> {
+    pub code: &'a str,
+    pub createdby: i32,
+    pub created: DateTime<Utc>,
+    pub expires: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = bots)]
+pub struct NewBot {
+    pub userid: i32,
+    pub createdby: i32,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = apikeys)]
+pub struct NewApiKey<'a> {
+    pub userid: i32,
+    pub name: &'a str,
+    pub hashedkey: String,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = webhooks)]
+pub struct NewWebhook<'a> {
+    pub url: &'a str,
+    pub createdby: i32,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = auditlog)]
+pub struct NewAuditEntry<'a> {
+    pub date: DateTime<Utc>,
+    pub actorid: i32,
+    pub action: &'a str,
+    pub targetid: Option<i32>,
+    pub details: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = guests)]
+pub struct NewGuest {
+    pub userid: i32,
+    pub validuntil: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = readmarkers)]
+pub struct NewReadMarker<'a> {
+    pub userid: i32,
+    pub conversation: &'a str,
+    pub messageid: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = userblocks)]
+pub struct NewUserBlock {
+    pub userid: i32,
+    pub blockedid: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = savedmessages)]
+pub struct NewSavedMessage {
+    pub userid: i32,
+    pub messageid: i32,
+    pub saveddate: DateTime<Utc>,
+}
+
+/// A file attached to a message. `storagekey` is the name of the file in the attachment directory.
+#[derive(Insertable)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment<'a> {
+    pub messageid: i32,
+    pub filename: &'a str,
+    pub mimetype: &'a str,
+    pub filesize: i64,
+    pub storagekey: &'a str,
+    pub contenthash: Option<&'a str>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = drafts)]
+pub struct NewDraft<'a> {
+    pub userid: i32,
+    pub conversation: &'a str,
+    pub drafttext: &'a str,
+    pub updateddate: DateTime<Utc>,
+}
+
+/// Where notifications for a user are pushed to. `provider` is the key of a `PushProvider`.
+#[derive(Debug, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = pushsettings, treat_none_as_null = true)]
+pub struct PushSettingsEntry {
+    pub userid: i32,
+    pub provider: String,
+    pub url: String,
+    pub token: Option<String>,
+    pub mentions: bool,
+    pub directmessages: bool,
+}
+
+/// The fields of a profile to change. Fields that are `None` stay as they are, `Some(None)` removes the value.
+#[derive(AsChangeset)]
+#[diesel(table_name = users)]
+pub struct ProfileChanges<'a> {
+    pub displayname: Option<Option<&'a str>>,
+    pub bio: Option<&'a str>,
+    pub avatar: Option<Option<&'a str>>,
+    pub showlastseen: Option<bool>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = termsacceptances)]
+pub struct NewTermsAcceptance<'a> {
+    pub userid: i32,
+    pub version: &'a str,
+    pub accepteddate: DateTime<Utc>,
+}
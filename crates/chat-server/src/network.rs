@@ -0,0 +1,191 @@
+//! Ranges of addresses, which the server can be configured to accept or refuse requests from and admins can ban.
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A range of addresses in CIDR notation, like `192.0.2.0/24` or `2001:db8::/32`. A single address is a range of
+/// just that address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Network {
+    /// The first address of the range, with every bit after the prefix cleared.
+    address: IpAddr,
+    prefix: u8,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum NetworkError {
+    #[error("{0:?} is not an address")]
+    InvalidAddress(String),
+    #[error("{0:?} is not a prefix length for the address")]
+    InvalidPrefix(String),
+}
+
+impl Network {
+    /// Whether the address is in the range. IPv4 addresses mapped to IPv6, as dual-stack sockets report them, are
+    /// matched against IPv4 ranges.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        let address = match address {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+            v4 @ IpAddr::V4(_) => v4,
+        };
+        match (self.address, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => mask_v4(address, self.prefix) == network,
+            (IpAddr::V6(network), IpAddr::V6(address)) => mask_v6(address, self.prefix) == network,
+            _ => false,
+        }
+    }
+}
+
+/// Whether the address is in any of the ranges.
+pub fn any_contains(networks: &[Network], address: IpAddr) -> bool {
+    networks.iter().any(|network| network.contains(address))
+}
+
+fn mask_v4(address: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+    Ipv4Addr::from(u32::from(address) & mask)
+}
+
+fn mask_v6(address: Ipv6Addr, prefix: u8) -> Ipv6Addr {
+    let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+    Ipv6Addr::from(u128::from(address) & mask)
+}
+
+impl FromStr for Network {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let address: IpAddr = address
+            .parse()
+            .map_err(|_| NetworkError::InvalidAddress(address.to_string()))?;
+        let max = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| NetworkError::InvalidPrefix(prefix.to_string()))?,
+            None => max,
+        };
+        let address = match address {
+            IpAddr::V4(v4) => IpAddr::V4(mask_v4(v4, prefix)),
+            IpAddr::V6(v6) => IpAddr::V6(mask_v6(v6, prefix)),
+        };
+        Ok(Self { address, prefix })
+    }
+}
+
+impl TryFrom<String> for Network {
+    type Error = NetworkError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Network> for String {
+    fn from(network: Network) -> Self {
+        network.to_string()
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::{any_contains, Network, NetworkError};
+
+    fn network(s: &str) -> Network {
+        s.parse().unwrap()
+    }
+
+    fn address(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parsing_clears_the_host_bits() {
+        assert_eq!(network("192.0.2.77/24").to_string(), "192.0.2.0/24");
+        assert_eq!(network("2001:db8::1/32").to_string(), "2001:db8::/32");
+        assert_eq!(network(" 192.0.2.1 ").to_string(), "192.0.2.1/32");
+        assert_eq!(network("2001:db8::1").to_string(), "2001:db8::1/128");
+    }
+
+    #[test]
+    fn invalid_networks_are_refused() {
+        assert_eq!(
+            "192.0.2.0/33".parse::<Network>(),
+            Err(NetworkError::InvalidPrefix("33".into()))
+        );
+        assert_eq!(
+            "2001:db8::/129".parse::<Network>(),
+            Err(NetworkError::InvalidPrefix("129".into()))
+        );
+        assert_eq!(
+            "192.0.2.0/".parse::<Network>(),
+            Err(NetworkError::InvalidPrefix(String::new()))
+        );
+        assert_eq!(
+            "example.com/24".parse::<Network>(),
+            Err(NetworkError::InvalidAddress("example.com".into()))
+        );
+    }
+
+    #[test]
+    fn zero_prefix_matches_every_address_of_its_family() {
+        assert!(network("0.0.0.0/0").contains(address("203.0.113.9")));
+        assert!(network("10.1.2.3/0").contains(address("255.255.255.255")));
+        assert!(network("::/0").contains(address("2001:db8::1")));
+        assert!(!network("0.0.0.0/0").contains(address("2001:db8::1")));
+        assert!(!network("::/0").contains(address("203.0.113.9")));
+    }
+
+    #[test]
+    fn full_prefix_matches_only_that_address() {
+        assert!(network("192.0.2.1/32").contains(address("192.0.2.1")));
+        assert!(!network("192.0.2.1/32").contains(address("192.0.2.2")));
+        assert!(network("2001:db8::1/128").contains(address("2001:db8::1")));
+        assert!(!network("2001:db8::1/128").contains(address("2001:db8::2")));
+    }
+
+    #[test]
+    fn prefix_boundaries() {
+        let range = network("192.0.2.0/25");
+        assert!(range.contains(address("192.0.2.0")));
+        assert!(range.contains(address("192.0.2.127")));
+        assert!(!range.contains(address("192.0.2.128")));
+        assert!(!range.contains(address("192.0.1.255")));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_match_ipv4_ranges() {
+        assert!(network("192.0.2.0/24").contains(address("::ffff:192.0.2.1")));
+        assert!(!network("192.0.2.0/24").contains(address("::ffff:198.51.100.1")));
+        // Only mapped addresses are, the deprecated compatible ones are not
+        assert!(!network("192.0.2.0/24").contains(address("::192.0.2.1")));
+        assert!(!network("2001:db8::/32").contains(address("::ffff:192.0.2.1")));
+    }
+
+    #[test]
+    fn any_of_several_ranges() {
+        let ranges = [network("10.0.0.0/8"), network("2001:db8::/32")];
+        assert!(any_contains(&ranges, address("10.20.30.40")));
+        assert!(any_contains(&ranges, address("2001:db8:1::1")));
+        assert!(!any_contains(&ranges, address("192.0.2.1")));
+        assert!(!any_contains(&[], address("10.0.0.1")));
+    }
+}
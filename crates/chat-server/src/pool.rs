@@ -0,0 +1,75 @@
+//! Statistics about the pool of database connections, so operators can tell whether it is sized right.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use r2d2::event::{CheckoutEvent, TimeoutEvent};
+use r2d2::{HandleEvent, State};
+use serde::Serialize;
+
+/// A snapshot of the connection pool.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStatus {
+    /// Most connections the pool opens.
+    pub max_size: u32,
+    /// Connections currently open.
+    pub connections: u32,
+    /// Open connections nothing is using right now.
+    pub idle: u32,
+    /// Open connections currently checked out.
+    pub in_use: u32,
+    /// Connections handed out since the pool was created.
+    pub checkouts: u64,
+    /// Requests for a connection that gave up, as none became free in time.
+    pub checkout_failures: u64,
+    /// Time spent waiting for connections, summed over all checkouts.
+    pub total_wait: Duration,
+    /// The longest a single checkout had to wait.
+    pub max_wait: Duration,
+}
+
+/// Counts checkouts and how long they took. Gets handed the events of the pool it is attached to.
+#[derive(Debug, Default)]
+pub struct PoolMetrics {
+    checkouts: AtomicU64,
+    checkout_failures: AtomicU64,
+    total_wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+}
+
+impl PoolMetrics {
+    /// Creates an event handler feeding these metrics, to be attached to a pool.
+    pub fn handler(self: &Arc<Self>) -> Box<dyn HandleEvent> {
+        Box::new(MetricsHandler(Arc::clone(self)))
+    }
+
+    /// Combines the counters with the current state of the pool.
+    pub fn status(&self, state: State, max_size: u32) -> PoolStatus {
+        PoolStatus {
+            max_size,
+            connections: state.connections,
+            idle: state.idle_connections,
+            in_use: state.connections - state.idle_connections,
+            checkouts: self.checkouts.load(Ordering::Relaxed),
+            checkout_failures: self.checkout_failures.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.total_wait_nanos.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MetricsHandler(Arc<PoolMetrics>);
+
+impl HandleEvent for MetricsHandler {
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        let wait = u64::try_from(event.duration().as_nanos()).unwrap_or(u64::MAX);
+        self.0.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.0.total_wait_nanos.fetch_add(wait, Ordering::Relaxed);
+        self.0.max_wait_nanos.fetch_max(wait, Ordering::Relaxed);
+    }
+
+    fn handle_timeout(&self, _event: TimeoutEvent) {
+        self.0.checkout_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use chat_core::models::Attachment;
+use rand::Rng;
+use rocket::fs::TempFile;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, Responder};
+use rocket::tokio::fs::{self, File};
+use rocket::tokio::io::AsyncReadExt;
+use rocket::{Request, Response};
+use thiserror::Error;
+
+use super::etag::not_modified;
+use super::sniffing::{sniff, SNIFF_LENGTH};
+use crate::config::UploadConfig;
+use crate::gc::{self, Collected};
+use crate::StoredFile;
+
+/// The media type of uploads that did not say what they are.
+const UNKNOWN_TYPE: &str = "application/octet-stream";
+
+/// The directory inside the attachments directory rejected files are moved to.
+const QUARANTINE: &str = "quarantine";
+
+/// Why an uploaded file was not stored.
+#[derive(Debug, Error)]
+pub enum UploadError {
+    #[error("The file is larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("Files of type {0} are not accepted")]
+    TypeNotAllowed(&'static str),
+    #[error("The file could not be stored: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The directory the files attached to messages are kept in. Each file is stored once under the hash of its
+/// content, so the names users upload files with never end up in a path and files sent several times take up space
+/// only once.
+pub struct AttachmentStore {
+    directory: PathBuf,
+    config: UploadConfig,
+    /// How many uploads stored each file and did not send or discard it yet. Their attachments might not be written
+    /// yet, so these files are never removed.
+    in_use: Mutex<HashMap<String, usize>>,
+}
+
+impl AttachmentStore {
+    pub fn new(directory: PathBuf, config: UploadConfig) -> Self {
+        Self {
+            directory,
+            config,
+            in_use: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Moves the uploaded file into the directory under the hash of its content, if the configuration accepts it.
+    /// Its type is recognized from its content. Rejected files are quarantined. The file stays in use until it is
+    /// released or discarded.
+    pub async fn store(&self, file: &mut TempFile<'_>) -> Result<StoredFile, UploadError> {
+        // Kept under a random key until its content is known
+        let incoming = new_key();
+        let name = file_name(file);
+        let size = file.len();
+        let path = self.directory.join(&incoming);
+        file.move_copy_to(&path).await?;
+        let (hash, start) = match read_content(&path).await {
+            Ok(content) => content,
+            Err(e) => {
+                self.remove(&[incoming]).await;
+                return Err(e.into());
+            }
+        };
+        let mime = sniff(&start);
+
+        let rejection = if size > self.config.max_size {
+            UploadError::TooLarge(self.config.max_size)
+        } else if !self.config.allows(mime) {
+            UploadError::TypeNotAllowed(mime)
+        } else {
+            if let Err(e) = self.keep(&path, &hash) {
+                self.remove(&[incoming]).await;
+                return Err(e.into());
+            }
+            return Ok(StoredFile {
+                name,
+                mime: mime.into(),
+                size: i64::try_from(size).unwrap_or(i64::MAX),
+                key: hash,
+            });
+        };
+        let key = incoming;
+        let declared = file
+            .content_type()
+            .map_or_else(|| UNKNOWN_TYPE.to_string(), ToString::to_string);
+        let kept = if self.quarantine(&key).await {
+            format!("Moved it to the quarantine as {key}")
+        } else {
+            "Deleted it".to_string()
+        };
+        println!("Rejected the upload {name:?}, sent as {declared} and recognized as {mime}: {rejection}. {kept}.");
+        Err(rejection)
+    }
+
+    /// Moves the file to its key and marks it as used. A file with the same content stored before is replaced by the
+    /// identical copy.
+    fn keep(&self, path: &Path, key: &str) -> io::Result<()> {
+        let mut in_use = self
+            .in_use
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        std::fs::rename(path, self.directory.join(key))?;
+        *in_use.entry(key.to_string()).or_default() += 1;
+        Ok(())
+    }
+
+    /// Lets go of files stored by an upload whose message was written.
+    pub fn release(&self, keys: &[String]) {
+        let mut in_use = self
+            .in_use
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for key in keys {
+            release(&mut in_use, key);
+        }
+    }
+
+    /// Lets go of files stored by an upload whose message was not written, and removes the ones no other upload
+    /// uses. `referenced` are the keys attachments refer to, which are kept as well.
+    pub fn discard(&self, keys: &[String], referenced: &[String]) {
+        // Held whilst removing, so no upload of the same content can store the file in between
+        let mut in_use = self
+            .in_use
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        for key in keys {
+            if release(&mut in_use, key) && !referenced.contains(key) {
+                if let Err(e) = std::fs::remove_file(self.directory.join(key)) {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        println!("Could not remove attachment {key}:\n{e}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// The directory the files are kept in.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// How often files that are not used anymore are removed. Zero if they are not.
+    pub fn gc_interval(&self) -> std::time::Duration {
+        self.config.gc_interval()
+    }
+
+    /// Removes the files stored under the keys, which no attachment refers to, unless an upload is using them.
+    pub fn remove_unused(&self, keys: &[String]) -> Collected {
+        // Held whilst removing, so no upload of the same content can store the file in between
+        let in_use = self
+            .in_use
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut collected = Collected::default();
+        for key in keys {
+            if in_use.contains_key(key) {
+                continue;
+            }
+            if let Some(bytes) = gc::remove_unused(&self.directory, key) {
+                collected.add(bytes);
+            }
+        }
+        collected
+    }
+
+    /// Moves the file stored under the key into the quarantine, or deletes it if rejected files are not kept.
+    /// Returns whether it was quarantined.
+    async fn quarantine(&self, key: &str) -> bool {
+        if self.config.quarantine {
+            let quarantine = self.directory.join(QUARANTINE);
+            let moved = match fs::create_dir_all(&quarantine).await {
+                Ok(()) => fs::rename(self.directory.join(key), quarantine.join(key)).await,
+                Err(e) => Err(e),
+            };
+            match moved {
+                Ok(()) => return true,
+                Err(e) => println!("Could not quarantine attachment {key}:\n{e}"),
+            }
+        }
+        self.remove(&[key.to_string()]).await;
+        false
+    }
+
+    /// Removes the files stored under the keys. Files that are gone already are skipped.
+    async fn remove(&self, keys: &[String]) {
+        for key in keys {
+            if let Err(e) = fs::remove_file(self.directory.join(key)).await {
+                if e.kind() != io::ErrorKind::NotFound {
+                    println!("Could not remove attachment {key}:\n{e}");
+                }
+            }
+        }
+    }
+
+    /// Opens the file stored under the key.
+    pub async fn open(&self, key: &str) -> io::Result<File> {
+        File::open(self.directory.join(key)).await
+    }
+}
+
+/// Counts one use of the file less. Returns whether it is not used anymore.
+fn release(in_use: &mut HashMap<String, usize>, key: &str) -> bool {
+    match in_use.get_mut(key) {
+        Some(count) if *count > 1 => {
+            *count -= 1;
+            false
+        }
+        _ => {
+            in_use.remove(key);
+            true
+        }
+    }
+}
+
+/// Reads the whole file, returning the BLAKE2b-256 hash of its content in hex and its first bytes, which are enough
+/// to recognize its type.
+async fn read_content(path: &Path) -> io::Result<(String, Vec<u8>)> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Blake2b::<U32>::new();
+    let mut start = Vec::with_capacity(SNIFF_LENGTH);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        let chunk = &buffer[..read];
+        let missing = SNIFF_LENGTH.saturating_sub(start.len()).min(read);
+        start.extend_from_slice(&chunk[..missing]);
+        hasher.update(chunk);
+    }
+    Ok((format!("{:x}", hasher.finalize()), start))
+}
+
+/// Generates a key to store a file under, which is safe to use as a file name.
+fn new_key() -> String {
+    let mut rng = rand::thread_rng();
+    (0..16)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
+/// The name the file was uploaded with, without the directories some clients send along.
+fn file_name(file: &TempFile<'_>) -> String {
+    let raw = file
+        .raw_name()
+        .map(|name| name.dangerous_unsafe_unsanitized_raw().as_str())
+        .unwrap_or_default();
+    let name: String = raw
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "file".into(),
+        name => name.into(),
+    }
+}
+
+/// An attachment sent to the client as a download, with the name and type it was uploaded with. Its hash is the
+/// `ETag`, so clients holding the file already get a `304 Not Modified` instead.
+pub struct Download {
+    pub file: File,
+    pub attachment: Attachment,
+}
+
+impl<'r> Responder<'r, 'static> for Download {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        if let Some(hash) = &self.attachment.hash {
+            let etag = format!("\"{hash}\"");
+            if not_modified(request, &etag) {
+                return response
+                    .status(Status::NotModified)
+                    .header(Header::new("ETag", etag))
+                    .ok();
+            }
+            response.header(Header::new("ETag", etag));
+        }
+        let content_type =
+            ContentType::parse_flexible(&self.attachment.mime).unwrap_or(ContentType::Binary);
+        response
+            .header(content_type)
+            .header(Header::new(
+                "Content-Disposition",
+                content_disposition(&self.attachment.name),
+            ))
+            // Browsers must not guess the type of a file someone else uploaded
+            .header(Header::new("X-Content-Type-Options", "nosniff"))
+            .sized_body(None, self.file)
+            .ok()
+    }
+}
+
+/// Builds a `Content-Disposition` header that makes the file a download under its name. Clients that do not
+/// understand the encoded name get one with everything but plain ASCII replaced.
+fn content_disposition(name: &str) -> String {
+    let fallback: String = name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = name
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
@@ -0,0 +1,45 @@
+//! Reads json request bodies, and tells clients what was wrong with the ones that could not be read.
+use std::ops::Deref;
+
+use rocket::data::{self, Data, FromData, Outcome};
+use rocket::serde::json::{self, Json};
+use rocket::Request;
+use serde::Deserialize;
+
+/// A json request body, read like `Json`. If it can not be read, why is remembered, so the catcher can send it
+/// along with `malformed_request`. The request types refuse fields they do not know, so a typo or a field of a
+/// newer protocol is reported instead of silently ignored.
+pub struct JsonBody<T>(pub T);
+
+/// Why the body of the request could not be read, remembered for the catchers. Empty if it was read.
+pub struct BodyError(pub Option<String>);
+
+impl<T> JsonBody<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: Deserialize<'r>> FromData<'r> for JsonBody<T> {
+    type Error = json::Error<'r>;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        match Json::<T>::from_data(req, data).await {
+            Outcome::Success(body) => Outcome::Success(Self(body.into_inner())),
+            Outcome::Failure((status, error)) => {
+                req.local_cache(|| BodyError(Some(error.to_string())));
+                Outcome::Failure((status, error))
+            }
+            Outcome::Forward(data) => Outcome::Forward(data),
+        }
+    }
+}
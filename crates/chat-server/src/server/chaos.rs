@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use rand::Rng;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::tokio::io::{AsyncRead, ReadBuf};
+use rocket::tokio::time::{sleep, Sleep};
+use rocket::{Data, Request, Response};
+use serde::Deserialize;
+
+/// What can go wrong with the requests to a route.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ChaosRule {
+    /// Responses are held back for a random time up to this many milliseconds.
+    pub delay: u64,
+    /// Share of responses, between 0 and 1, that are replaced by an error. The request is still handled, like when
+    /// a proxy fails after the server did its work.
+    pub error_rate: f64,
+    /// Status code of the errors.
+    pub error_status: u16,
+    /// Event streams are cut after a random time up to this many seconds.
+    pub drop_after: Option<u64>,
+}
+
+impl Default for ChaosRule {
+    fn default() -> Self {
+        Self {
+            delay: 0,
+            error_rate: 0.0,
+            error_status: 503,
+            drop_after: None,
+        }
+    }
+}
+
+/// Makes the server misbehave on purpose, so the resilience of clients can be tested. Only available when the
+/// server is built with the `chaos` feature.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    /// Applies to requests no route below matches.
+    #[serde(flatten)]
+    pub default: ChaosRule,
+    /// Rules for the requests whose path starts with the key. The longest matching key wins.
+    pub routes: HashMap<String, ChaosRule>,
+}
+
+impl ChaosConfig {
+    fn rule(&self, path: &str) -> &ChaosRule {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(&self.default, |(_, rule)| rule)
+    }
+}
+
+/// Injects the delays, errors and dropped event streams described by the configuration.
+pub struct Chaos {
+    config: ChaosConfig,
+}
+
+impl Chaos {
+    pub fn new(config: ChaosConfig) -> Self {
+        println!("Chaos mode is on, the server will misbehave on purpose.");
+        Self { config }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Chaos {
+    fn info(&self) -> Info {
+        Info {
+            name: "Chaos",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let rule = self.config.rule(req.uri().path().as_str());
+        if rule.delay > 0 {
+            let delay = rand::thread_rng().gen_range(0..=rule.delay);
+            sleep(Duration::from_millis(delay)).await;
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let rule = self.config.rule(request.uri().path().as_str());
+        if rand::thread_rng().gen_bool(rule.error_rate.clamp(0.0, 1.0)) {
+            let status = Status::from_code(rule.error_status).unwrap_or(Status::ServiceUnavailable);
+            *response = Response::build().status(status).finalize();
+            return;
+        }
+        if let Some(drop_after) = rule.drop_after {
+            if response.content_type() == Some(ContentType::EventStream) {
+                let lifetime = rand::thread_rng().gen_range(0..=drop_after * 1000);
+                let body = response.body_mut().take();
+                response.set_streamed_body(Cutoff {
+                    inner: Box::pin(body),
+                    deadline: Box::pin(sleep(Duration::from_millis(lifetime))),
+                });
+            }
+        }
+    }
+}
+
+/// A body that ends once the deadline passed, cutting off whatever was still to come.
+struct Cutoff<R> {
+    inner: Pin<Box<R>>,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl<R: AsyncRead> AsyncRead for Cutoff<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.deadline.as_mut().poll(cx).is_ready() {
+            // Reading nothing marks the end of the body
+            return Poll::Ready(Ok(()));
+        }
+        self.inner.as_mut().poll_read(cx, buf)
+    }
+}
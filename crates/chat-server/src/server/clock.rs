@@ -0,0 +1,25 @@
+//! Tells clients the time of the server, so they do not have to rely on their own clocks.
+use chat_core::protocol::SERVER_TIME;
+use chrono::{SecondsFormat, Utc};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Request, Response};
+
+/// Adds the current time of the server to every response, in the `Server-Time` header. The `Date` header only has
+/// whole seconds, which is not enough to tell apart messages sent right after each other.
+pub struct ServerTime;
+
+#[rocket::async_trait]
+impl Fairing for ServerTime {
+    fn info(&self) -> Info {
+        Info {
+            name: "Server time",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        response.set_header(Header::new(SERVER_TIME, now));
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{Cursor, Write};
+
+use flate2::write::GzEncoder;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header};
+use rocket::{Request, Response};
+use serde::Deserialize;
+
+/// Configuration for compressing response bodies.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// Bodies smaller than this many bytes are sent as is, as compressing them is not worth it.
+    pub min_size: usize,
+    pub gzip: bool,
+    pub brotli: bool,
+    pub zstd: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size: 1024,
+            gzip: true,
+            brotli: true,
+            zstd: true,
+        }
+    }
+}
+
+/// The encodings responses can be compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Zstd,
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Zstd => "zstd",
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Compresses response bodies with zstd, brotli or gzip, depending on what the client accepts.
+pub struct Compression {
+    config: CompressionConfig,
+}
+
+impl Compression {
+    pub fn new(config: CompressionConfig) -> Self {
+        Self { config }
+    }
+
+    /// Picks the encoding to use for the given `Accept-Encoding` header. Zstd is preferred, as it compresses nearly as
+    /// well as brotli in a fraction of the time, then brotli and then gzip.
+    fn pick_encoding(&self, accept_encoding: &str) -> Option<Encoding> {
+        let accepted: Vec<&str> = accept_encoding
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let name = parts.next()?.trim();
+                let rejected = parts.any(|param| {
+                    param
+                        .trim()
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        == Some(0.0)
+                });
+                (!rejected).then_some(name)
+            })
+            .collect();
+
+        if self.config.zstd && accepted.contains(&"zstd") {
+            Some(Encoding::Zstd)
+        } else if self.config.brotli && accepted.contains(&"br") {
+            Some(Encoding::Brotli)
+        } else if self.config.gzip && accepted.contains(&"gzip") {
+            Some(Encoding::Gzip)
+        } else {
+            None
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !self.config.enabled || response.headers().contains("Content-Encoding") {
+            return;
+        }
+        // Event streams are sent piece by piece, so they can not be compressed as a whole
+        if response.content_type() == Some(ContentType::EventStream) {
+            return;
+        }
+        let Some(accept_encoding) = request.headers().get_one("Accept-Encoding") else {
+            return;
+        };
+        let Some(encoding) = self.pick_encoding(accept_encoding) else {
+            return;
+        };
+        if response
+            .body()
+            .preset_size()
+            .is_none_or(|size| size < self.config.min_size)
+        {
+            return;
+        }
+
+        let Ok(body) = response.body_mut().to_bytes().await else {
+            return;
+        };
+        let Ok(compressed) = compress(&body, encoding) else {
+            // Compressing failed, so fall back to sending the original body
+            response.set_sized_body(body.len(), Cursor::new(body));
+            return;
+        };
+
+        response.set_header(Header::new("Content-Encoding", encoding.name()));
+        // Keep what the response already varies on, like the format picked from `Accept`
+        let vary = match response.headers().get_one("Vary") {
+            Some(vary) => format!("{vary}, Accept-Encoding"),
+            None => "Accept-Encoding".to_string(),
+        };
+        response.set_header(Header::new("Vary", vary));
+        response.set_sized_body(compressed.len(), Cursor::new(compressed));
+    }
+}
+
+/// Compresses the data using the given encoding.
+fn compress(data: &[u8], encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Zstd => zstd::encode_all(data, 0),
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        Encoding::Brotli => {
+            let mut output = Vec::new();
+            {
+                // Quality 5 gives most of the size reduction at a fraction of the cost of the maximum of 11
+                let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                encoder.write_all(data)?;
+            }
+            Ok(output)
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Posts a summary of the past week into a channel for the admins, as configured under `chat.digest`.
+use std::sync::Arc;
+
+use chat_core::protocol::Conversation;
+use chrono::{DateTime, Datelike, Days, Duration, NaiveTime, Utc};
+use rocket::futures::lock::Mutex;
+use rocket::tokio::time;
+
+use super::ingestion::IngestionSender;
+use crate::{ChatApp, Digest, OutgoingMessage};
+
+/// The time of day the digest is posted on Mondays, in UTC.
+const POSTED_AT: NaiveTime = match NaiveTime::from_hms_opt(8, 0, 0) {
+    Some(time) => time,
+    None => NaiveTime::MIN,
+};
+
+/// Starts posting the digest every Monday, covering the week before. Whether and where it is posted is looked up
+/// each time.
+pub fn start(app: Arc<Mutex<ChatApp>>, ingestion: IngestionSender) {
+    rocket::tokio::spawn(async move {
+        loop {
+            let next = next_run(Utc::now());
+            time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+            post(&app, &ingestion, next).await;
+        }
+    });
+}
+
+/// The first Monday at `POSTED_AT` after the time.
+fn next_run(now: DateTime<Utc>) -> DateTime<Utc> {
+    let monday = now.date_naive() - Days::new(u64::from(now.weekday().num_days_from_monday()));
+    let run = monday.and_time(POSTED_AT).and_utc();
+    if run > now {
+        run
+    } else {
+        run + Duration::weeks(1)
+    }
+}
+
+/// Posts the digest of the week up to the date. Failures are only logged, the next week tries again.
+async fn post(app: &Mutex<ChatApp>, ingestion: &IngestionSender, until: DateTime<Utc>) {
+    let since = until - Duration::weeks(1);
+    let (channel, sender, digest) = {
+        let mut app = app.lock().await;
+        let (channel, sender) = match app.get_digest_target() {
+            Ok(Some(target)) => target,
+            Ok(None) => return,
+            Err(e) => {
+                println!("Could not find the channel or the sender of the digest:\n{e}");
+                return;
+            }
+        };
+        match app.get_digest(since) {
+            Ok(digest) => (channel, sender, digest),
+            Err(e) => {
+                println!("Could not count what happened for the digest:\n{e}");
+                return;
+            }
+        }
+    };
+    let message = OutgoingMessage {
+        userid: sender,
+        text: text(&digest, since, until),
+        conversation: Conversation::Channel(channel),
+        // Posting the digest of the same week again does nothing
+        idempotency_key: Some(format!("digest-{}", until.date_naive())),
+        attachments: Vec::new(),
+        forwarded: None,
+    };
+    match ingestion.submit(message).await {
+        Some(Ok(_)) => {}
+        Some(Err(e)) => println!("Could not post the digest:\n{e}"),
+        None => println!("Could not post the digest, as messages are not written anymore."),
+    }
+}
+
+fn text(digest: &Digest, since: DateTime<Utc>, until: DateTime<Utc>) -> String {
+    format!(
+        "Digest of the week from {} to {}:\n\
+         - {} accounts were made\n\
+         - {} messages were sent\n\
+         - {} users were active\n\
+         - {} moderation actions were taken",
+        since.date_naive(),
+        until.date_naive(),
+        digest.registrations,
+        digest.messages,
+        digest.active_users,
+        digest.moderation_actions,
+    )
+}
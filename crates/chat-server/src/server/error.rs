@@ -0,0 +1,139 @@
+use chat_core::protocol::{ApiError, ErrorCode};
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{catch, Request};
+
+use super::body::BodyError;
+
+/// A failed request, responded to with a `ApiError` as json body.
+#[derive(Debug)]
+pub struct ErrorResponse {
+    status: Status,
+    error: ApiError,
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: &str) -> Self {
+        ApiError::new(code, message).into()
+    }
+
+    /// Used when something went wrong on our end. The actual error is not exposed to the client.
+    pub fn internal() -> Self {
+        Self::new(
+            ErrorCode::InternalError,
+            "The server encountered an error whilst handling the request.",
+        )
+    }
+
+    #[must_use]
+    pub fn with_details(mut self, details: &str) -> Self {
+        self.error.details = Some(details.to_string());
+        self
+    }
+}
+
+impl From<ApiError> for ErrorResponse {
+    fn from(error: ApiError) -> Self {
+        let status = match error.code {
+            ErrorCode::MalformedRequest => Status::BadRequest,
+            ErrorCode::NotAuthorized | ErrorCode::LoginFailed | ErrorCode::RefreshTokenInvalid => {
+                Status::Unauthorized
+            }
+            ErrorCode::UsernameTaken | ErrorCode::ChannelNameTaken => Status::Conflict,
+            ErrorCode::TermsNotAccepted
+            | ErrorCode::GuestsDisabled
+            | ErrorCode::NotAGuest
+            | ErrorCode::NotAMember
+            | ErrorCode::PermissionDenied
+            | ErrorCode::AccountBanned
+            | ErrorCode::InviteInvalid
+            | ErrorCode::AddressBlocked => Status::Forbidden,
+            ErrorCode::RateLimited
+            | ErrorCode::QuotaExceeded
+            | ErrorCode::SlowMode
+            | ErrorCode::DuplicateMessage => Status::TooManyRequests,
+            ErrorCode::NotFound => Status::NotFound,
+            ErrorCode::VersionConflict => Status::PreconditionFailed,
+            ErrorCode::FileTooLarge => Status::PayloadTooLarge,
+            ErrorCode::FileTypeNotAllowed => Status::UnsupportedMediaType,
+            ErrorCode::InternalError => Status::InternalServerError,
+        };
+
+        Self { status, error }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ErrorResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        (self.status, Json(self.error)).respond_to(request)
+    }
+}
+
+#[catch(400)]
+pub fn bad_request(request: &Request) -> ErrorResponse {
+    with_body_error(
+        ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The request could not be understood by the server.",
+        ),
+        request,
+    )
+}
+
+#[catch(401)]
+pub fn unauthorized() -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorCode::NotAuthorized,
+        "The login token is missing, not valid or has expired. Login again and try again.",
+    )
+}
+
+#[catch(404)]
+pub fn not_found(request: &Request) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorCode::NotFound,
+        "The requested resource does not exist.",
+    )
+    .with_details(request.uri().path().as_str())
+}
+
+#[catch(422)]
+pub fn unprocessable_entity(request: &Request) -> ErrorResponse {
+    with_body_error(
+        ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The request body is missing fields, has fields of the wrong type or fields the server does not know.",
+        ),
+        request,
+    )
+}
+
+/// Tells what was wrong with the body of the request in the details, like which field the server does not know.
+fn with_body_error(response: ErrorResponse, request: &Request) -> ErrorResponse {
+    match request.local_cache(|| BodyError(None)) {
+        BodyError(Some(error)) => response.with_details(error),
+        BodyError(None) => response,
+    }
+}
+
+/// Only registered for attachments, where the limits of Rocket turn away files that are too large before they
+/// reach the route.
+#[catch(413)]
+pub fn file_too_large(request: &Request) -> ErrorResponse {
+    let response = ErrorResponse::new(
+        ErrorCode::FileTooLarge,
+        "The file is larger than the server accepts.",
+    );
+    match request.limits().get("file") {
+        Some(limit) => response.with_details(&limit.as_u64().to_string()),
+        None => response,
+    }
+}
+
+#[catch(default)]
+pub fn default(status: Status, _request: &Request) -> ErrorResponse {
+    let mut response = ErrorResponse::internal();
+    response.status = status;
+    response
+}
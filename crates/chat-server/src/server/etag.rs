@@ -0,0 +1,70 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+use serde::Serialize;
+
+/// A json response carrying an `ETag`. If the client already has the current version, as indicated by
+/// `If-None-Match`, a `304 Not Modified` without a body is sent instead.
+pub struct Tagged<T>(pub T);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Tagged<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_string(&self.0).map_err(|_| Status::InternalServerError)?;
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        // Weak, since the body might still get compressed on the way out
+        let etag = format!("W/\"{:x}\"", hasher.finish());
+
+        let not_modified = not_modified(request, &etag);
+
+        let mut response = Response::build();
+        response.header(Header::new("ETag", etag));
+        if not_modified {
+            response.status(Status::NotModified);
+        } else {
+            response
+                .header(ContentType::JSON)
+                .sized_body(body.len(), std::io::Cursor::new(body));
+        }
+
+        response.ok()
+    }
+}
+
+/// Whether the `If-None-Match` header of the request matches the `ETag`. Tags are compared weakly, as is done for
+/// `If-None-Match`.
+pub fn not_modified(request: &Request<'_>, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    request
+        .headers()
+        .get("If-None-Match")
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// The version of a resource a change is based on, from the `If-Match` header of the request, like `"3"`. Without
+/// the header, or with `*`, the change applies whatever the version. Other tags are rejected as malformed.
+pub struct IfMatch(pub Option<i32>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("If-Match").map(str::trim) {
+            None | Some("*") => Outcome::Success(IfMatch(None)),
+            Some(tag) => match tag
+                .strip_prefix('"')
+                .and_then(|tag| tag.strip_suffix('"'))
+                .and_then(|version| version.parse().ok())
+            {
+                Some(version) => Outcome::Success(IfMatch(Some(version))),
+                None => Outcome::Failure((Status::BadRequest, ())),
+            },
+        }
+    }
+}
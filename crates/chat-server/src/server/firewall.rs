@@ -0,0 +1,123 @@
+//! Refuses requests from addresses the configuration does not allow, before any route sees them.
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+use chat_core::protocol::ErrorCode;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::Responder;
+use rocket::{Data, Request, Response};
+use serde::Deserialize;
+
+use super::error::ErrorResponse;
+use crate::network::{any_contains, Network};
+
+/// No route is mounted here, so blocked requests are not handled by any.
+const BLOCKED_PATH: &str = "/blocked";
+
+/// Which addresses requests are accepted from.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FirewallConfig {
+    /// Only requests from these ranges are accepted. Requests from anywhere are if it is empty.
+    pub allow: Vec<Network>,
+    /// Requests from these ranges are refused, even if they are allowed above.
+    pub deny: Vec<Network>,
+    /// The reverse proxies in front of the server. Only for requests coming from them is the address in Rocket's
+    /// `ip_header` taken as the one of the client, as anyone else could put any address in it.
+    pub proxies: Vec<Network>,
+}
+
+/// The address the request came from, as far as the firewall trusts it. Routes and the rate limiter use it instead
+/// of Rocket's `client_ip`, which believes the `ip_header` of every request.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientAddress(pub Option<IpAddr>);
+
+impl ClientAddress {
+    /// The address the firewall found for the request, or the one of the connection if it did not look at it.
+    pub fn of(request: &Request<'_>) -> Option<IpAddr> {
+        request
+            .local_cache(|| ClientAddress(request.remote().map(|remote| remote.ip())))
+            .0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientAddress {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientAddress(ClientAddress::of(req)))
+    }
+}
+
+/// Refuses the requests the configuration does not allow with `ErrorCode::AddressBlocked`. Bans made by admins while
+/// the server runs only keep people from registering and logging in, so they are checked by the routes doing that.
+pub struct Firewall {
+    config: FirewallConfig,
+}
+
+impl Firewall {
+    pub fn new(config: FirewallConfig) -> Self {
+        Self { config }
+    }
+
+    /// The address of the connection, or the one the proxy it came through names.
+    fn address(&self, request: &Request<'_>) -> Option<IpAddr> {
+        let remote = request.remote()?.ip();
+        if any_contains(&self.config.proxies, remote) {
+            request.real_ip().or(Some(remote))
+        } else {
+            Some(remote)
+        }
+    }
+
+    fn allows(&self, address: Option<IpAddr>) -> bool {
+        let Some(address) = address else {
+            // Nothing is known about where the request came from, which is only fine if it could be from anywhere
+            return self.config.allow.is_empty();
+        };
+        (self.config.allow.is_empty() || any_contains(&self.config.allow, address))
+            && !any_contains(&self.config.deny, address)
+    }
+}
+
+/// Whether the request was refused, remembered from `on_request` for `on_response`.
+struct Blocked(bool);
+
+#[rocket::async_trait]
+impl Fairing for Firewall {
+    fn info(&self) -> Info {
+        Info {
+            name: "Firewall",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let address = self.address(req);
+        req.local_cache(|| ClientAddress(address));
+        if self.allows(address) {
+            return;
+        }
+        req.local_cache(|| Blocked(true));
+        if let Ok(uri) = Origin::parse(BLOCKED_PATH) {
+            req.set_uri(uri);
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Blocked(blocked) = request.local_cache(|| Blocked(false));
+        if !blocked {
+            return;
+        }
+        let error = ErrorResponse::new(
+            ErrorCode::AddressBlocked,
+            "The server does not accept requests from your address.",
+        );
+        if let Ok(blocked) = error.respond_to(request) {
+            *response = blocked;
+        }
+    }
+}
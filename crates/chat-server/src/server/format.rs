@@ -0,0 +1,34 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, Responder};
+use rocket::{Request, Response};
+use serde::Serialize;
+
+/// A response sent as `MessagePack` if the client asks for it in `Accept`, and as json otherwise. `MessagePack` is
+/// smaller and faster to parse, which adds up for responses carrying a lot of history.
+pub struct Negotiated<T>(pub T);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Negotiated<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let wants_msgpack = request
+            .headers()
+            .get("Accept")
+            .flat_map(|value| value.split(','))
+            .filter_map(|entry| entry.split(';').next())
+            .any(|media_type| media_type.trim() == "application/msgpack");
+
+        let (content_type, body) = if wants_msgpack {
+            // Named, so fields are looked up by name like in json and optional fields can be left out
+            let body = rmp_serde::to_vec_named(&self.0).map_err(|_| Status::InternalServerError)?;
+            (ContentType::MsgPack, body)
+        } else {
+            let body = serde_json::to_vec(&self.0).map_err(|_| Status::InternalServerError)?;
+            (ContentType::JSON, body)
+        };
+
+        Response::build()
+            .header(content_type)
+            .header(Header::new("Vary", "Accept"))
+            .sized_body(body.len(), std::io::Cursor::new(body))
+            .ok()
+    }
+}
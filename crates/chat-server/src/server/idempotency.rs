@@ -0,0 +1,26 @@
+use chat_core::protocol::IDEMPOTENCY_KEY;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+/// The longest idempotency key accepted, in bytes. Clients generate far shorter ones.
+const MAX_LENGTH: usize = 128;
+
+/// The `Idempotency-Key` header of a request, if it has one. Requests with an empty or overly long key are
+/// rejected as malformed.
+pub struct IdempotencyKey(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one(IDEMPOTENCY_KEY) {
+            None => Outcome::Success(IdempotencyKey(None)),
+            Some(key) if key.is_empty() || key.len() > MAX_LENGTH => {
+                Outcome::Failure((Status::BadRequest, ()))
+            }
+            Some(key) => Outcome::Success(IdempotencyKey(Some(key.to_string()))),
+        }
+    }
+}
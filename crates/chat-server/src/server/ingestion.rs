@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+
+use chat_core::models::Message;
+use rocket::futures::lock::Mutex;
+use rocket::tokio::sync::broadcast::Sender;
+use rocket::tokio::sync::{mpsc, oneshot};
+
+use super::ServerEvent;
+use crate::{AppError, ChatApp, OutgoingMessage, SentMessage};
+
+/// How many messages can wait in the queue before senders have to wait for room.
+const QUEUE_SIZE: usize = 1024;
+/// The most messages written to the database in one transaction.
+const MAX_BATCH: usize = 64;
+
+/// A message waiting to be written, along with where to report the outcome.
+struct Submission {
+    message: OutgoingMessage,
+    reply: oneshot::Sender<Result<SentMessage, AppError>>,
+}
+
+/// Writes incoming messages to the database in batches. Messages arriving while a batch is written are collected
+/// and written together in the next one, so bursts need far fewer transactions, while a single message is written
+/// right away.
+pub struct IngestionQueue {
+    tx: mpsc::Sender<Submission>,
+    /// Taken by the worker once it starts.
+    rx: StdMutex<Option<mpsc::Receiver<Submission>>>,
+}
+
+impl IngestionQueue {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        Self {
+            tx,
+            rx: StdMutex::new(Some(rx)),
+        }
+    }
+
+    /// Queues the message and waits until it was written. Returns `None` if the queue is not running.
+    pub async fn submit(&self, message: OutgoingMessage) -> Option<Result<SentMessage, AppError>> {
+        submit(&self.tx, message).await
+    }
+
+    /// Returns a sender for tasks that queue messages of their own.
+    pub fn sender(&self) -> IngestionSender {
+        IngestionSender(self.tx.clone())
+    }
+
+    /// Starts writing the queued messages of the app, announcing each one that was sent and passing it on for push
+    /// notifications and webhooks once its batch was committed. Messages that were sent before with the same idempotency key were
+    /// announced back then. Does nothing if the queue was started already.
+    pub fn start(
+        &self,
+        app: Arc<Mutex<ChatApp>>,
+        broadcast: Sender<ServerEvent>,
+        push: mpsc::Sender<Message>,
+        webhooks: mpsc::Sender<Message>,
+    ) {
+        let Some(mut rx) = self
+            .rx
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+        rocket::tokio::spawn(async move {
+            let mut submissions = Vec::with_capacity(MAX_BATCH);
+            while rx.recv_many(&mut submissions, MAX_BATCH).await > 0 {
+                let (batch, replies): (Vec<_>, Vec<_>) = submissions
+                    .drain(..)
+                    .map(|submission| (submission.message, submission.reply))
+                    .unzip();
+                let results = app.lock().await.send_messages(&batch);
+                // If the whole batch failed, dropping the replies tells their senders
+                let Ok(results) = results else {
+                    continue;
+                };
+                for (reply, result) in replies.into_iter().zip(results) {
+                    match &result {
+                        Ok(sent) if !sent.repeated => {
+                            let _ = broadcast.send(ServerEvent::Message(sent.message.clone()));
+                            let _ = push.send(sent.message.clone()).await;
+                            let _ = webhooks.send(sent.message.clone()).await;
+                        }
+                        _ => {}
+                    }
+                    let _ = reply.send(result);
+                }
+            }
+        });
+    }
+}
+
+/// Queues messages like `IngestionQueue::submit`, for tasks that do not hold the queue.
+#[derive(Clone)]
+pub struct IngestionSender(mpsc::Sender<Submission>);
+
+impl IngestionSender {
+    /// Queues the message and waits until it was written. Returns `None` if the queue is not running.
+    pub async fn submit(&self, message: OutgoingMessage) -> Option<Result<SentMessage, AppError>> {
+        submit(&self.0, message).await
+    }
+}
+
+async fn submit(
+    tx: &mpsc::Sender<Submission>,
+    message: OutgoingMessage,
+) -> Option<Result<SentMessage, AppError>> {
+    let (reply, outcome) = oneshot::channel();
+    let submission = Submission { message, reply };
+    tx.send(submission).await.ok()?;
+    outcome.await.ok()
+}
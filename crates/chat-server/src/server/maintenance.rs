@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use rocket::futures::lock::Mutex;
+use rocket::tokio::time::{self, MissedTickBehavior};
+
+use super::attachments::AttachmentStore;
+use crate::ChatApp;
+
+/// Starts removing the attachment files of the app that no attachment refers to anymore, every so often as
+/// configured. Does nothing if that is turned off.
+pub fn start(app: Arc<Mutex<ChatApp>>, attachments: Arc<AttachmentStore>) {
+    let interval = attachments.gc_interval();
+    if interval.is_zero() {
+        return;
+    }
+    rocket::tokio::spawn(async move {
+        let mut timer = time::interval_at(time::Instant::now() + interval, interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            timer.tick().await;
+            collect_garbage(&app, &attachments).await;
+        }
+    });
+}
+
+/// Removes the unused files once. Failures are only logged, the next run tries again.
+async fn collect_garbage(app: &Mutex<ChatApp>, attachments: &AttachmentStore) {
+    let unused = match app.lock().await.find_unused_files(attachments.directory()) {
+        Ok(unused) => unused,
+        Err(e) => {
+            println!("Could not look for unused attachments:\n{e}");
+            return;
+        }
+    };
+    let collected = attachments.remove_unused(&unused);
+    if collected.files > 0 {
+        println!(
+            "Removed {} unused attachment files, which freed {} bytes.",
+            collected.files, collected.bytes
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt::Write;
+
+use crate::pool::PoolStatus;
+
+/// Writes the pool status in the Prometheus text format.
+pub fn render(pool: &PoolStatus) -> String {
+    let metrics: [(&str, &str, &str, f64); 8] = [
+        (
+            "chat_db_pool_max_size",
+            "gauge",
+            "Most connections the pool opens.",
+            f64::from(pool.max_size),
+        ),
+        (
+            "chat_db_pool_connections",
+            "gauge",
+            "Connections currently open.",
+            f64::from(pool.connections),
+        ),
+        (
+            "chat_db_pool_idle",
+            "gauge",
+            "Open connections nothing is using.",
+            f64::from(pool.idle),
+        ),
+        (
+            "chat_db_pool_in_use",
+            "gauge",
+            "Open connections currently checked out.",
+            f64::from(pool.in_use),
+        ),
+        (
+            "chat_db_pool_checkouts_total",
+            "counter",
+            "Connections handed out.",
+            pool.checkouts as f64,
+        ),
+        (
+            "chat_db_pool_checkout_failures_total",
+            "counter",
+            "Requests for a connection that timed out.",
+            pool.checkout_failures as f64,
+        ),
+        (
+            "chat_db_pool_wait_seconds_total",
+            "counter",
+            "Time spent waiting for connections.",
+            pool.total_wait.as_secs_f64(),
+        ),
+        (
+            "chat_db_pool_max_wait_seconds",
+            "gauge",
+            "The longest a single checkout had to wait.",
+            pool.max_wait.as_secs_f64(),
+        ),
+    ];
+
+    let mut output = String::new();
+    for (name, kind, help, value) in metrics {
+        // Writing to a string can not fail
+        let _ = writeln!(
+            output,
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+        );
+    }
+    output
+}
@@ -0,0 +1,2172 @@
+//! The HTTP server, exposing a `ChatApp` to clients.
+#![allow(clippy::let_unit_value)]
+#![allow(clippy::no_effect_underscore_binding)]
+// Deriving `FromForm` allows a lint newer compilers removed
+#![allow(renamed_and_removed_lints)]
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::{Actor, AppError, ChatApp, DbError, OutgoingMessage, SentMessage, StoredFile};
+use attachments::{Download, UploadError};
+use body::JsonBody;
+use chat_core::models::{
+    AddressBan, ApiKey, Attachment, AuditEntry, Channel, Invite, Message, Motd, Profile, Role,
+    User, Webhook,
+};
+use chat_core::paths::Paths;
+use chat_core::protocol::{
+    search_date, AddressBanRequest, ChannelNotifications, Conversation, CreateApiKey, CreateBot,
+    CreateChannel, Credentials, Draft, ErrorCode, ForwardRequest, GuestLogin, GuestRequest,
+    ImpersonationLogin, ImpersonationRequest, InitialSync, IssuedApiKey, Limits, LoginResult,
+    MessageContext, MessageFilter, MotdRequest, NotificationRequest, Overview, Page, PageRequest,
+    Presence, ProfileUpdate, PushSettings, QuotaPeriod, ReadMarker, ReadReceipt, RefreshRequest,
+    SavedMessage, SearchFilter, ServerInfo, Session, SlowModeRequest, SystemEvent, SystemEventKind,
+    Terms, TopicRequest, UserEntry, UserFilter, UserSort, Usernames, WebhookRequest, SYSTEM_EVENT,
+};
+use chat_core::{LoginToken, RefreshToken};
+use chrono::Utc;
+use compression::{Compression, CompressionConfig};
+use error::ErrorResponse;
+use etag::{IfMatch, Tagged};
+use firewall::{ClientAddress, Firewall, FirewallConfig};
+use format::Negotiated;
+use idempotency::IdempotencyKey;
+use ratelimit::{RateLimitConfig, RateLimiter};
+use rocket::fairing::AdHoc;
+use rocket::figment::Figment;
+use rocket::form::{Form, FromForm};
+use rocket::fs::TempFile;
+use rocket::futures::lock::Mutex;
+use rocket::http::ContentType;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::stream::{Event, EventStream, TextStream};
+use rocket::serde::json::Json;
+use rocket::tokio::sync::broadcast::{self, Receiver, Sender};
+use rocket::{catchers, delete, get, patch, post, put, routes, Build, Request, Rocket};
+use serde::de::DeserializeOwned;
+use tenant::{Tenant, TenantRouting, Tenants};
+
+mod attachments;
+mod body;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod clock;
+mod compression;
+mod digest;
+mod error;
+mod etag;
+mod firewall;
+mod format;
+mod idempotency;
+mod ingestion;
+mod maintenance;
+mod metrics;
+mod presence;
+mod push;
+mod ratelimit;
+mod sniffing;
+mod tenant;
+mod version;
+mod webhooks;
+
+/// What is sent to clients over the event stream.
+#[derive(Clone)]
+enum ServerEvent {
+    Message(Message),
+    System(SystemEvent),
+    /// The reader read the conversation, named as they see it, up to the message. Only passed on to those who may
+    /// see it, as a `messages_read` system event.
+    Read {
+        reader: i32,
+        conversation: Conversation,
+        message_id: i32,
+    },
+}
+
+struct MessageBroadcast {
+    tx: Sender<ServerEvent>,
+    rx: Receiver<ServerEvent>,
+}
+
+impl MessageBroadcast {
+    fn new() -> Self {
+        let (tx, rx) = broadcast::channel(16);
+        Self { tx, rx }
+    }
+}
+
+/// Builds the server from the given configuration. The chat server itself is configured under the `chat` key.
+/// Relative paths in it are resolved against the given directories.
+///
+/// Exits the process if the configuration is not valid or the database could not be opened.
+pub fn rocket(figment: Figment, paths: &Paths) -> Rocket<Build> {
+    let rocket = rocket::custom(figment);
+    let config: Config = read_config(rocket.figment(), "chat");
+    let compression: CompressionConfig = read_config(rocket.figment(), "chat.compression");
+    let firewall: FirewallConfig = read_config(rocket.figment(), "chat.firewall");
+    let rate_limits: RateLimitConfig = read_config(rocket.figment(), "chat.rate_limits");
+    let others = config
+        .tenants
+        .iter()
+        .map(|(key, overrides)| {
+            if !tenant::is_valid_key(key) {
+                println!("The tenant key {key:?} may only contain letters, digits, - and _");
+                std::process::exit(1)
+            }
+            let tenant = Tenant::new(config.for_tenant(key, overrides), paths);
+            (key.clone(), tenant)
+        })
+        .collect();
+    let tenants = Tenants {
+        default: Tenant::new(config, paths),
+        others,
+    };
+    // Attached first, so blocked requests do not reach anything else
+    let rocket = rocket
+        .attach(Firewall::new(firewall))
+        .manage(tenants)
+        .attach(TenantRouting)
+        // After the routing, so requests to every tenant are limited by the same paths
+        .attach(RateLimiter::new(rate_limits));
+    // Attached before compression, so its errors replace responses before they get compressed
+    #[cfg(feature = "chaos")]
+    let rocket = {
+        let chaos: chaos::ChaosConfig = read_config(rocket.figment(), "chat.chaos");
+        rocket.attach(chaos::Chaos::new(chaos))
+    };
+    rocket
+        .attach(AdHoc::on_liftoff("Message ingestion", |rocket| {
+            Box::pin(async move {
+                if let Some(tenants) = rocket.state::<Tenants>() {
+                    tenants.all().for_each(Tenant::start);
+                }
+            })
+        }))
+        .attach(Compression::new(compression))
+        .attach(clock::ServerTime)
+        .attach(version::ProtocolVersion)
+        .mount(
+            "/auth",
+            routes![login, logout, refresh, guest, claim, limits, sessions],
+        )
+        .mount("/sync", routes![initial, overview, mark_read])
+        .mount(
+            "/admin",
+            routes![
+                rename_user,
+                ban_user,
+                unban_user,
+                kick_user,
+                set_role,
+                impersonate,
+                audit_log,
+                user_directory,
+                get_address_bans,
+                ban_address,
+                unban_address,
+                get_invites,
+                create_invite,
+                delete_invite,
+                get_bots,
+                create_bot,
+                get_api_keys,
+                create_api_key,
+                revoke_api_key,
+                get_webhooks,
+                create_webhook,
+                delete_webhook
+            ],
+        )
+        .mount(
+            "/",
+            routes![
+                send_message,
+                forward_message,
+                get_message,
+                get_message_context,
+                delete_message,
+                send_attachments,
+                download_attachment,
+                get_attachments,
+                get_saved,
+                save_message,
+                unsave_message,
+                get_drafts,
+                save_draft,
+                delete_draft,
+                get_push_settings,
+                set_push_settings,
+                delete_push_settings,
+                get_own_profile,
+                get_profile,
+                update_profile,
+                get_messages,
+                stream_messages,
+                search_messages,
+                get_user,
+                get_users,
+                get_presence,
+                get_blocks,
+                block_user,
+                unblock_user,
+                get_channels,
+                create_channel,
+                join_channel,
+                leave_channel,
+                set_slow_mode,
+                set_channel_topic,
+                get_motd,
+                set_motd,
+                get_channel_notifications,
+                set_channel_notifications,
+                mark_conversation_read,
+                get_read_receipts,
+                send_channel_message,
+                get_channel_messages,
+                send_direct_message,
+                get_direct_messages,
+                register,
+                events,
+                info,
+                terms,
+                pool_metrics
+            ],
+        )
+        .register(
+            "/",
+            catchers![
+                error::bad_request,
+                error::unauthorized,
+                error::not_found,
+                error::unprocessable_entity,
+                error::default
+            ],
+        )
+        .register("/attachments", catchers![error::file_too_large])
+}
+
+/// Returns the chat of the default tenant of a server built by [`rocket`], for embedding the server in a client
+/// that wants to answer its own requests without going through HTTP. The server shares it, so changes made through
+/// it are seen by both.
+pub fn default_app(rocket: &Rocket<Build>) -> Option<Arc<Mutex<ChatApp>>> {
+    rocket
+        .state::<Tenants>()
+        .map(|tenants| Arc::clone(&tenants.default.app))
+}
+
+/// Reads a section of the configuration, falling back to the defaults if it is not present.
+fn read_config<T: DeserializeOwned + Default>(figment: &Figment, key: &str) -> T {
+    match figment.extract_inner::<T>(key) {
+        Ok(config) => config,
+        Err(e) if e.missing() => T::default(),
+        Err(e) => {
+            println!("Could not read configuration:\n{e}");
+            std::process::exit(1)
+        }
+    }
+}
+
+#[post("/register", data = "<credentials>")]
+async fn register(
+    tenant: &Tenant,
+    address: ClientAddress,
+    credentials: JsonBody<Credentials>,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    check_address(&mut app, address.0)?;
+    match app.register(
+        &credentials.username,
+        &credentials.password,
+        credentials.accepted_tos_version.as_deref(),
+        credentials.invite_code.as_deref(),
+    ) {
+        Ok(user) => {
+            let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+                date: Utc::now(),
+                kind: SystemEventKind::UserJoined {
+                    userid: user.id,
+                    username: user.username,
+                },
+            }));
+            Ok(())
+        }
+        Err(AppError::DatabaseError(DbError::UsernameInUse)) => Err(ErrorResponse::new(
+            ErrorCode::UsernameTaken,
+            "Username is already taken.",
+        )),
+        Err(AppError::TermsNotAccepted) => Err(ErrorResponse::new(
+            ErrorCode::TermsNotAccepted,
+            "The current terms of service have to be accepted to register.",
+        )),
+        Err(AppError::DatabaseError(DbError::InviteInvalid)) => Err(invite_invalid()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+fn invite_invalid() -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorCode::InviteInvalid,
+        "The server only accepts new accounts with a valid invite code.",
+    )
+}
+
+#[post("/login", data = "<login_form>")]
+async fn login(
+    tenant: &Tenant,
+    address: ClientAddress,
+    login_form: JsonBody<Credentials>,
+) -> Result<Json<LoginResult>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    check_address(&mut app, address.0)?;
+    let token = match app.login(&login_form.username, &login_form.password) {
+        Ok(token) => token,
+        Err(AppError::UserBanned) => {
+            return Err(ErrorResponse::new(
+                ErrorCode::AccountBanned,
+                "This account is banned from the server.",
+            ))
+        }
+        Err(_) => {
+            return Err(ErrorResponse::new(
+                ErrorCode::LoginFailed,
+                "Authentication Failure. Check your credentials or try again later.",
+            ))
+        }
+    };
+    let user = app
+        .get_user_for_token(&token)
+        .map_err(|_| ErrorResponse::internal())?;
+    presence::announce(&mut app, &tenant.broadcast.tx);
+    let refresh_token = if login_form.remember_me {
+        app.create_refresh_token(&token).ok().map(|token| token.0)
+    } else {
+        None
+    };
+    Ok(Json(LoginResult {
+        token: token.0,
+        refresh_token,
+        userid: user.id,
+    }))
+}
+
+#[post("/guest", data = "<request>")]
+async fn guest(
+    tenant: &Tenant,
+    address: ClientAddress,
+    request: Option<JsonBody<GuestRequest>>,
+) -> Result<Json<GuestLogin>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    check_address(&mut app, address.0)?;
+    let request = request.map(JsonBody::into_inner).unwrap_or_default();
+    match app.create_guest(request.accepted_tos_version.as_deref()) {
+        Ok((user, token, valid_until)) => {
+            let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+                date: Utc::now(),
+                kind: SystemEventKind::UserJoined {
+                    userid: user.id,
+                    username: user.username.clone(),
+                },
+            }));
+            presence::announce(&mut app, &tenant.broadcast.tx);
+            Ok(Json(GuestLogin {
+                token: token.0,
+                userid: user.id,
+                username: user.username,
+                valid_until,
+            }))
+        }
+        Err(AppError::GuestsDisabled) => Err(ErrorResponse::new(
+            ErrorCode::GuestsDisabled,
+            "This server does not allow guest accounts.",
+        )),
+        Err(AppError::TermsNotAccepted) => Err(ErrorResponse::new(
+            ErrorCode::TermsNotAccepted,
+            "The current terms of service have to be accepted to join.",
+        )),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Turns a guest account into a full account, keeping its messages and login.
+#[post("/claim", data = "<credentials>")]
+async fn claim(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    credentials: JsonBody<Credentials>,
+) -> Result<Json<LoginResult>, ErrorResponse> {
+    // Bots send an API key instead of logging in, and are never guests
+    let Some(token) = &user.token else {
+        return Err(not_a_guest());
+    };
+    let mut app = tenant.app.lock().await;
+    let (guest, claimed) = match app.claim_guest_account(token, &credentials) {
+        Ok(accounts) => accounts,
+        Err(AppError::NotAGuest) => return Err(not_a_guest()),
+        Err(AppError::DatabaseError(DbError::UsernameInUse)) => {
+            return Err(ErrorResponse::new(
+                ErrorCode::UsernameTaken,
+                "Username is already taken.",
+            ))
+        }
+        Err(AppError::DatabaseError(DbError::InviteInvalid)) => return Err(invite_invalid()),
+        Err(_) => return Err(ErrorResponse::internal()),
+    };
+
+    if guest.username != claimed.username {
+        let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+            date: Utc::now(),
+            kind: SystemEventKind::UserRenamed {
+                userid: claimed.id,
+                old_name: guest.username,
+                new_name: claimed.username.clone(),
+            },
+        }));
+    }
+    let refresh_token = if credentials.remember_me {
+        app.create_refresh_token(token).ok().map(|token| token.0)
+    } else {
+        None
+    };
+    Ok(Json(LoginResult {
+        token: token.0.clone(),
+        refresh_token,
+        userid: claimed.id,
+    }))
+}
+
+fn not_a_guest() -> ErrorResponse {
+    ErrorResponse::new(ErrorCode::NotAGuest, "Only guest accounts can be claimed.")
+}
+
+#[post("/refresh", data = "<refresh_request>")]
+async fn refresh(
+    tenant: &Tenant,
+    address: ClientAddress,
+    refresh_request: JsonBody<RefreshRequest>,
+) -> Result<Json<LoginResult>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    check_address(&mut app, address.0)?;
+    let refresh_token = RefreshToken(refresh_request.refresh_token.clone());
+    let Ok((token, refresh_token)) = app.refresh_login(&refresh_token) else {
+        return Err(ErrorResponse::new(
+            ErrorCode::RefreshTokenInvalid,
+            "The refresh token is invalid or has expired. Login again.",
+        ));
+    };
+    let user = app
+        .get_user_for_token(&token)
+        .map_err(|_| ErrorResponse::internal())?;
+    presence::announce(&mut app, &tenant.broadcast.tx);
+    Ok(Json(LoginResult {
+        token: token.0,
+        refresh_token: Some(refresh_token.0),
+        userid: user.id,
+    }))
+}
+
+/// Refuses registrations and logins from addresses banned by an admin.
+fn check_address(app: &mut ChatApp, address: Option<IpAddr>) -> Result<(), ErrorResponse> {
+    match app.check_address(address) {
+        Ok(()) => Ok(()),
+        Err(AppError::AddressBanned) => Err(ErrorResponse::new(
+            ErrorCode::AddressBlocked,
+            "Your address is banned from registering and logging in.",
+        )),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+#[get("/logout")]
+async fn logout(tenant: &Tenant, user: &AuthenticatedUser) {
+    let mut app = tenant.app.lock().await;
+    // API keys stay valid until an admin revokes them
+    if let Some(token) = &user.token {
+        app.logout(token);
+    }
+    presence::announce(&mut app, &tenant.broadcast.tx);
+}
+
+/// Lists the logins of the user, including those of admins acting as them.
+#[get("/sessions")]
+async fn sessions(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Json<Vec<Session>>, ErrorResponse> {
+    // Bots have API keys instead of logins
+    let Some(token) = &user.token else {
+        return Ok(Json(Vec::new()));
+    };
+    let mut app = tenant.app.lock().await;
+    match app.get_sessions(token) {
+        Ok(sessions) => Ok(Json(sessions)),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Tells users how much of their quotas they have used up.
+#[get("/limits")]
+async fn limits(tenant: &Tenant, user: &AuthenticatedUser) -> Result<Json<Limits>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_limits(user.id) {
+        Ok(limits) => Ok(Json(limits)),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Sends a message. A message sent again with the same `Idempotency-Key` is not sent twice, so clients can safely
+/// retry when they did not get a response.
+#[post("/message", data = "<message>")]
+async fn send_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    key: IdempotencyKey,
+    message: String,
+) -> Result<(), ErrorResponse> {
+    send(tenant, user, key, message, Conversation::Chat, Vec::new()).await
+}
+
+/// Queues a message with the stored files attached to the conversation and waits until it was written.
+async fn send(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    key: IdempotencyKey,
+    message: String,
+    conversation: Conversation,
+    attachments: Vec<StoredFile>,
+) -> Result<(), ErrorResponse> {
+    let keys: Vec<String> = attachments.iter().map(|file| file.key.clone()).collect();
+    let outgoing = OutgoingMessage {
+        userid: user.id,
+        text: message,
+        conversation,
+        idempotency_key: key.0,
+        attachments,
+        forwarded: None,
+    };
+    // Goes through the queue, which announces the message once it was written
+    let outcome = tenant.ingestion.submit(outgoing).await;
+    if matches!(
+        outcome,
+        Some(Ok(SentMessage {
+            repeated: false,
+            ..
+        }))
+    ) {
+        tenant.attachments.release(&keys);
+    } else {
+        // The message that was not sent or had been sent before does not refer to the files
+        discard_files(tenant, &keys).await;
+    }
+    match outcome {
+        Some(Ok(_)) => Ok(()),
+        Some(Err(e)) => Err(sending_error(e)),
+        None => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Sends a copy of a message, which keeps pointing to the original and its author. It is checked and written like
+/// any other message sent to the target.
+#[post("/message/forward", data = "<request>")]
+async fn forward_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    key: IdempotencyKey,
+    request: JsonBody<ForwardRequest>,
+) -> Result<(), ErrorResponse> {
+    // Not holding the app while queueing, as the queue needs it to write the message
+    let forwarded = {
+        let mut app = tenant.app.lock().await;
+        app.forward_message(user.id, request.message_id, request.target, key.0)
+    };
+    let outgoing = match forwarded {
+        Ok(outgoing) => outgoing,
+        Err(AppError::DatabaseError(DbError::MessageNotFound)) => {
+            return Err(ErrorResponse::new(
+                ErrorCode::NotFound,
+                "The message to forward does not exist.",
+            ))
+        }
+        Err(e) => return Err(conversation_error(e)),
+    };
+    match tenant.ingestion.submit(outgoing).await {
+        Some(Ok(_)) => Ok(()),
+        Some(Err(e)) => Err(sending_error(e)),
+        None => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Turns the errors of sending a message into a response.
+fn sending_error(error: AppError) -> ErrorResponse {
+    match error {
+        AppError::RateLimited => ErrorResponse::new(
+            ErrorCode::RateLimited,
+            "Guests have to wait a moment between messages.",
+        ),
+        AppError::QuotaExceeded { period, quota } => {
+            let period = match period {
+                QuotaPeriod::Minute => "minute",
+                QuotaPeriod::Day => "day",
+            };
+            let message = format!(
+                "You can send {} messages per {period}. Wait a moment before sending more.",
+                quota.limit
+            );
+            let response = ErrorResponse::new(ErrorCode::QuotaExceeded, &message);
+            match quota.available_at {
+                Some(date) => response.with_details(&date.to_rfc3339()),
+                None => response,
+            }
+        }
+        AppError::SlowMode { remaining } => {
+            // Rounded up, so waiting that long is always enough
+            let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+            let message =
+                format!("The channel is in slow mode. Wait {seconds} seconds before sending more.");
+            ErrorResponse::new(ErrorCode::SlowMode, &message).with_details(&seconds.to_string())
+        }
+        AppError::DuplicateMessage => ErrorResponse::new(
+            ErrorCode::DuplicateMessage,
+            "You sent this message several times already. Wait a moment before sending it again.",
+        ),
+        e => conversation_error(e),
+    }
+}
+
+/// Turns the errors of reaching a conversation the user may not read or that does not exist into a response.
+fn conversation_error(error: AppError) -> ErrorResponse {
+    match error {
+        AppError::NotAMember => ErrorResponse::new(
+            ErrorCode::NotAMember,
+            "Only members of the channel can read and write in it.",
+        ),
+        AppError::DatabaseError(DbError::ChannelNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The channel does not exist.")
+        }
+        AppError::DatabaseError(DbError::UserNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The user does not exist.")
+        }
+        _ => ErrorResponse::internal(),
+    }
+}
+
+/// Returns a message with the messages around it. Clients use the path as the permalink of the message.
+#[get("/message/<id>")]
+async fn get_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<MessageContext>, ErrorResponse> {
+    message_context(tenant, user, id, None).await
+}
+
+/// Returns a message with `around` messages sent before and after it, so clients can show it within its
+/// conversation.
+#[get("/message/<id>/context?<around>")]
+async fn get_message_context(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    around: Option<u32>,
+) -> Result<Json<MessageContext>, ErrorResponse> {
+    message_context(tenant, user, id, around).await
+}
+
+async fn message_context(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    around: Option<u32>,
+) -> Result<Json<MessageContext>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_message_context(user.id, id, around) {
+        Ok(context) => Ok(Json(context)),
+        Err(AppError::DatabaseError(DbError::MessageNotFound)) => Err(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "The message does not exist.",
+        )),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+/// A message with files attached, sent as `multipart/form-data`. Each file is a `file` field, the text of the
+/// message is the optional `text` field.
+#[derive(FromForm)]
+struct Upload<'r> {
+    text: Option<String>,
+    file: Vec<TempFile<'r>>,
+}
+
+/// Sends a message with files attached to the conversation. Which files are accepted is up to the upload
+/// configuration, and how large they may be also to the `file` and `data-form` limits of Rocket. One rejected file
+/// rejects the whole message.
+#[post("/attachments/<conversation>", data = "<upload>")]
+async fn send_attachments(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    key: IdempotencyKey,
+    conversation: &str,
+    mut upload: Form<Upload<'_>>,
+) -> Result<(), ErrorResponse> {
+    let conversation = Conversation::from_key(conversation).ok_or_else(unknown_conversation)?;
+    if upload.file.is_empty() {
+        return Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "Attach at least one file.",
+        ));
+    }
+    let mut stored = Vec::with_capacity(upload.file.len());
+    for file in &mut upload.file {
+        match tenant.attachments.store(file).await {
+            Ok(file) => stored.push(file),
+            Err(e) => {
+                let keys: Vec<String> = stored.into_iter().map(|file| file.key).collect();
+                discard_files(tenant, &keys).await;
+                return Err(upload_error(e));
+            }
+        }
+    }
+    let text = upload.text.take().unwrap_or_default();
+    send(tenant, user, key, text, conversation, stored).await
+}
+
+/// Removes the stored files no attachment refers to, as other messages may have the same files attached.
+async fn discard_files(tenant: &Tenant, keys: &[String]) {
+    if keys.is_empty() {
+        return;
+    }
+    // Held until the files are removed, so no message referring to them can be written in between
+    let mut app = tenant.app.lock().await;
+    let referenced = match app.get_referenced_files(keys) {
+        Ok(referenced) => referenced,
+        Err(e) => {
+            println!("Could not look up whether attachments are referenced, keeping them:\n{e}");
+            keys.to_vec()
+        }
+    };
+    tenant.attachments.discard(keys, &referenced);
+}
+
+fn upload_error(error: UploadError) -> ErrorResponse {
+    match error {
+        UploadError::TooLarge(max) => ErrorResponse::new(
+            ErrorCode::FileTooLarge,
+            "The file is larger than the server accepts.",
+        )
+        .with_details(&max.to_string()),
+        UploadError::TypeNotAllowed(mime) => ErrorResponse::new(
+            ErrorCode::FileTypeNotAllowed,
+            &format!("The server does not accept files of type {mime}."),
+        )
+        .with_details(mime),
+        UploadError::Io(e) => {
+            println!("Could not store an attachment:\n{e}");
+            ErrorResponse::internal()
+        }
+    }
+}
+
+/// Returns the file of an attachment, named and typed the way it was uploaded.
+#[get("/attachments/<id>")]
+async fn download_attachment(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Download, ErrorResponse> {
+    let found = tenant.app.lock().await.get_attachment(user.id, id);
+    let (attachment, key) = found.map_err(|e| match e {
+        AppError::DatabaseError(DbError::AttachmentNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The attachment does not exist.")
+        }
+        e => conversation_error(e),
+    })?;
+    match tenant.attachments.open(&key).await {
+        Ok(file) => Ok(Download { file, attachment }),
+        Err(e) => {
+            println!("Could not open attachment {id}:\n{e}");
+            Err(ErrorResponse::internal())
+        }
+    }
+}
+
+/// Lists the files attached to a message.
+#[get("/message/<id>/attachments")]
+async fn get_attachments(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<Vec<Attachment>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_attachments(user.id, id) {
+        Ok(attachments) => Ok(Json(attachments)),
+        Err(AppError::DatabaseError(DbError::MessageNotFound)) => Err(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "The message does not exist.",
+        )),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+/// Lists the messages the user saved, the most recently saved first, a page at a time.
+#[get("/saved?<limit>&<cursor>")]
+async fn get_saved(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<SavedMessage>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_saved_messages(user.id, &PageRequest { limit, cursor }) {
+        Ok(saved) => Ok(Json(saved)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// The response to a page cursor that does not belong to the collection or could not be read.
+fn invalid_cursor() -> ErrorResponse {
+    ErrorResponse::new(ErrorCode::MalformedRequest, "The page cursor is not valid.")
+}
+
+#[put("/saved/<id>")]
+async fn save_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.save_message(user.id, id) {
+        Ok(()) => Ok(()),
+        Err(AppError::DatabaseError(DbError::MessageNotFound)) => Err(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "The message to save does not exist.",
+        )),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+#[delete("/saved/<id>")]
+async fn unsave_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.unsave_message(user.id, id) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Lists the drafts of the user, so a message started on one device can be finished on another.
+#[get("/drafts")]
+async fn get_drafts(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Json<Vec<Draft>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_drafts(user.id) {
+        Ok(drafts) => Ok(Json(drafts)),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Replaces the draft of the user in the conversation with the text in the body. Clients send it a little after
+/// the user stops typing rather than on every key.
+#[put("/drafts/<conversation>", data = "<text>")]
+async fn save_draft(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    conversation: &str,
+    text: String,
+) -> Result<(), ErrorResponse> {
+    let conversation = Conversation::from_key(conversation).ok_or_else(unknown_conversation)?;
+    let mut app = tenant.app.lock().await;
+    app.save_draft(user.id, conversation, &text)
+        .map_err(conversation_error)
+}
+
+#[delete("/drafts/<conversation>")]
+async fn delete_draft(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    conversation: &str,
+) -> Result<(), ErrorResponse> {
+    let conversation = Conversation::from_key(conversation).ok_or_else(unknown_conversation)?;
+    let mut app = tenant.app.lock().await;
+    app.save_draft(user.id, conversation, "")
+        .map_err(conversation_error)
+}
+
+/// Returns where notifications for the user are pushed to. Responds with 404 if they did not set that up.
+#[get("/push")]
+async fn get_push_settings(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Json<PushSettings>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_push_settings(user.id) {
+        Ok(Some(settings)) => Ok(Json(settings)),
+        Ok(None) => Err(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "No push notifications are set up.",
+        )),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Pushes notifications about mentions and direct messages to the ntfy topic or Gotify server in the body, from
+/// now on.
+#[put("/push", data = "<settings>")]
+async fn set_push_settings(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    settings: JsonBody<PushSettings>,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.set_push_settings(user.id, &settings) {
+        Ok(()) => Ok(()),
+        Err(AppError::InvalidPushSettings(reason)) => {
+            Err(ErrorResponse::new(ErrorCode::MalformedRequest, reason))
+        }
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+#[delete("/push")]
+async fn delete_push_settings(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.delete_push_settings(user.id) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Returns the profile of the user.
+#[get("/profile")]
+async fn get_own_profile(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Tagged<Profile>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_profile(user.id, user.id)
+        .map(Tagged)
+        .map_err(|_| ErrorResponse::internal())
+}
+
+/// Returns the profile of the user with that id.
+#[get("/profile/<id>")]
+async fn get_profile(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Tagged<Profile>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_profile(user.id, id)
+        .map(Tagged)
+        .map_err(conversation_error)
+}
+
+/// Changes the fields of the profile of the user that the body gives, and returns the profile. Everyone is told
+/// when the display name changes. With `If-Match`, nothing changes if the profile is not at that version anymore.
+#[patch("/profile", data = "<update>")]
+async fn update_profile(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    version: IfMatch,
+    update: JsonBody<ProfileUpdate>,
+) -> Result<Json<Profile>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let old = app
+        .get_profile(user.id, user.id)
+        .map_err(|_| ErrorResponse::internal())?;
+    let profile = match app.update_profile(user.id, version.0, &update) {
+        Ok(profile) => profile,
+        Err(AppError::InvalidProfile(reason)) => {
+            return Err(ErrorResponse::new(ErrorCode::MalformedRequest, reason))
+        }
+        Err(AppError::VersionConflict { current }) => return Err(version_conflict(current)),
+        Err(_) => return Err(ErrorResponse::internal()),
+    };
+    if profile.displayname != old.displayname {
+        let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+            date: Utc::now(),
+            kind: SystemEventKind::DisplayNameChanged {
+                userid: profile.id,
+                username: profile.username.clone(),
+                displayname: profile.displayname.clone(),
+            },
+        }));
+    }
+    Ok(Json(profile))
+}
+
+/// The response to a conversation in a path that is not `chat`, `channel-<id>` or `direct-<userid>`.
+fn unknown_conversation() -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorCode::MalformedRequest,
+        "The conversation is not valid.",
+    )
+}
+
+/// Gives clients everything they need to show a session in one response, which saves round trips when
+/// connecting over slow links. Sent as `MessagePack` to clients that ask for it.
+#[get("/initial")]
+async fn initial(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Negotiated<InitialSync>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.initial_sync(user.id) {
+        Ok(sync) => Ok(Negotiated(sync)),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Tells clients how many messages the user has not read yet in each conversation, so they can show it right
+/// away without fetching the messages, a page of conversations at a time.
+#[get("/overview?<limit>&<cursor>")]
+async fn overview(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Overview>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_overview(user.id, &PageRequest { limit, cursor }) {
+        Ok(overview) => Ok(Json(overview)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Kept for clients from before read receipts, which only mark the main chat as read.
+#[put("/read", data = "<marker>")]
+async fn mark_read(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    marker: JsonBody<ReadMarker>,
+) -> Result<(), ErrorResponse> {
+    store_read_marker(tenant, user, &marker).await
+}
+
+/// Moves the read marker of the user in the conversation forward and tells the others who can see it.
+#[post("/read", data = "<marker>")]
+async fn mark_conversation_read(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    marker: JsonBody<ReadMarker>,
+) -> Result<(), ErrorResponse> {
+    store_read_marker(tenant, user, &marker).await
+}
+
+async fn store_read_marker(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    marker: &ReadMarker,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.mark_read(user.id, marker) {
+        Ok(moved) => {
+            if moved {
+                let _ = tenant.broadcast.tx.send(ServerEvent::Read {
+                    reader: user.id,
+                    conversation: marker.conversation,
+                    message_id: marker.message_id,
+                });
+            }
+            Ok(())
+        }
+        Err(AppError::DatabaseError(DbError::MessageNotFound)) => Err(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "The message to mark as read does not exist in the conversation.",
+        )),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+/// Lists how far the users read the conversation, the main chat if none is given.
+#[get("/read?<conversation>")]
+async fn get_read_receipts(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    conversation: Option<&str>,
+) -> Result<Json<Vec<ReadReceipt>>, ErrorResponse> {
+    let conversation = match conversation {
+        Some(key) => Conversation::from_key(key).ok_or_else(unknown_conversation)?,
+        None => Conversation::Chat,
+    };
+    let mut app = tenant.app.lock().await;
+    app.get_read_receipts(user.id, conversation)
+        .map(Json)
+        .map_err(conversation_error)
+}
+
+/// Returns a page of the main chat. `limit` asks for that many messages instead of the configured page size, up to
+/// the configured maximum.
+#[post("/messages?<limit>", data = "<filter>")]
+async fn get_messages(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    filter: JsonBody<MessageFilter>,
+) -> Result<Json<Vec<Message>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_messages(user.id, &filter, limit) {
+        Ok(messages) => Ok(Json(messages)),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// How many messages the history stream reads from the database at a time.
+const STREAM_PAGE: i64 = 500;
+
+/// Streams every message before or after the date as newline-delimited JSON, one message per line and in the same
+/// order as `POST /messages`, leaving out the users the user blocked like it does. With `MessageFilter::Latest`, the
+/// stream ends once that many messages were sent. Meant for exports and large syncs, so clients can handle the
+/// messages as they arrive instead of waiting for one huge array. The stream ends early if the database fails.
+#[post("/messages/stream", data = "<filter>")]
+async fn stream_messages<'a>(
+    tenant: &'a Tenant,
+    user: &AuthenticatedUser,
+    filter: JsonBody<MessageFilter>,
+) -> (ContentType, TextStream![String + 'a]) {
+    let mut filter = filter.into_inner();
+    let mut remaining = match filter {
+        MessageFilter::Latest(count) => i64::from(count),
+        _ => i64::MAX,
+    };
+    let userid = user.id;
+    let stream = TextStream! {
+        while remaining > 0 {
+            // The lock is only held for one page at a time, so other requests are not held up
+            let page_size = STREAM_PAGE.min(remaining);
+            let Ok(page) = tenant.app.lock().await.get_message_page(userid, &filter, page_size) else {
+                return;
+            };
+            remaining -= i64::try_from(page.len()).unwrap_or(i64::MAX);
+            let Some(last) = page.last() else {
+                return;
+            };
+            // Continuing by sequence number, so messages sent at the same time as the last one are not skipped
+            filter = if filter.is_before() {
+                MessageFilter::BeforeSeq(last.seq)
+            } else {
+                MessageFilter::AfterSeq(last.seq)
+            };
+            let mut lines = String::new();
+            for message in &page {
+                if let Ok(line) = serde_json::to_string(message) {
+                    lines.push_str(&line);
+                    lines.push('\n');
+                }
+            }
+            yield lines;
+        }
+    };
+    (ContentType::new("application", "x-ndjson"), stream)
+}
+
+/// Searches the messages the user can read for the words in `q`, the newest first, a page at a time.
+/// `conversation`, named like for drafts, only searches that conversation and `from` only messages of the user
+/// with that id. `before` and `after` take days or RFC 3339 times, and `has=attachment` only finds messages with
+/// files. `q` may hold the same filters, written like `SearchQuery::parse` reads them, which take their place.
+#[get("/messages/search?<q>&<conversation>&<from>&<before>&<after>&<has>&<limit>&<cursor>")]
+#[allow(clippy::too_many_arguments)]
+async fn search_messages(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    q: &str,
+    conversation: Option<&str>,
+    from: Option<i32>,
+    before: Option<&str>,
+    after: Option<&str>,
+    has: Option<&str>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<Message>>, ErrorResponse> {
+    let conversation = match conversation {
+        Some(key) => Some(Conversation::from_key(key).ok_or_else(unknown_conversation)?),
+        None => None,
+    };
+    let date = |date: Option<&str>, after: bool| match date {
+        Some(date) => search_date(date, after).map(Some).ok_or_else(|| {
+            ErrorResponse::new(ErrorCode::MalformedRequest, "The date is not valid.")
+        }),
+        None => Ok(None),
+    };
+    let has_attachment = match has {
+        Some("attachment") => true,
+        Some(_) => {
+            return Err(ErrorResponse::new(
+                ErrorCode::MalformedRequest,
+                "Only has=attachment is known.",
+            ))
+        }
+        None => false,
+    };
+    let filter = SearchFilter {
+        conversation,
+        from,
+        before: date(before, false)?,
+        after: date(after, true)?,
+        has_attachment,
+    };
+    let mut app = tenant.app.lock().await;
+    match app.search_messages(user.id, q, &filter, &PageRequest { limit, cursor }) {
+        Ok(found) => Ok(Json(found)),
+        Err(AppError::EmptySearch) => Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The search has no words to search for.",
+        )),
+        Err(AppError::InvalidSearch(e)) => Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            &format!("The search could not be read: {e}."),
+        )),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+#[post("/user", data = "<ids>")]
+async fn get_user(tenant: &Tenant, ids: JsonBody<Vec<i32>>) -> Json<Usernames> {
+    let mut app = tenant.app.lock().await;
+    let names = ids
+        .iter()
+        .map(|id| {
+            let username = app.get_user_by_id(*id).ok().map(|user| user.username);
+            (*id, username)
+        })
+        .collect();
+    Json(names)
+}
+
+/// Lists the registered users by id, a page at a time.
+#[get("/users?<limit>&<cursor>")]
+async fn get_users(
+    tenant: &Tenant,
+    _user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Tagged<Page<User>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_user_page(&PageRequest { limit, cursor }) {
+        Ok(users) => Ok(Tagged(users)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Lists the users the user blocked by id, a page at a time.
+#[get("/blocks?<limit>&<cursor>")]
+async fn get_blocks(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<User>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_blocked_users(user.id, &PageRequest { limit, cursor }) {
+        Ok(users) => Ok(Json(users)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Blocks the user with that id, so their messages are no longer shown to the user making the request.
+#[put("/blocks/<userid>")]
+async fn block_user(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    userid: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.block_user(user.id, userid) {
+        Ok(()) => Ok(()),
+        Err(AppError::BlockSelf) => Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "You can not block yourself.",
+        )),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+/// Shows the messages of the user with that id again.
+#[delete("/blocks/<userid>")]
+async fn unblock_user(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    userid: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.unblock_user(user.id, userid) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Deletes a message. Authors may delete their own messages, moderators and admins those of anyone.
+#[delete("/message/<id>")]
+async fn delete_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let message = app
+        .delete_message(user.actor(), id)
+        .map_err(moderation_error)?;
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::MessageDeleted {
+            messageid: message.id,
+        },
+    }));
+    Ok(())
+}
+
+/// Renames a user to the name in the body. Needs a role that may rename users and outranks the one of the user.
+#[put("/users/<id>/name", data = "<name>")]
+async fn rename_user(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    name: String,
+) -> Result<(), ErrorResponse> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The new name can not be empty.",
+        ));
+    }
+    let mut app = tenant.app.lock().await;
+    let (old, renamed) = app
+        .rename_user(user.actor(), id, name)
+        .map_err(moderation_error)?;
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::UserRenamed {
+            userid: renamed.id,
+            old_name: old.username,
+            new_name: renamed.username,
+        },
+    }));
+    Ok(())
+}
+
+/// Bans a user, logging them out everywhere. Needs a role that may ban users and outranks the one of the user.
+#[post("/users/<id>/ban")]
+async fn ban_user(tenant: &Tenant, user: &AuthenticatedUser, id: i32) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let banned = app.ban_user(user.actor(), id).map_err(moderation_error)?;
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::UserBanned {
+            userid: banned.id,
+            username: banned.username,
+        },
+    }));
+    presence::announce(&mut app, &tenant.broadcast.tx);
+    Ok(())
+}
+
+/// Lifts the ban of a user, who can log in again. Needs a role that may ban users and outranks the one of the user.
+#[delete("/users/<id>/ban")]
+async fn unban_user(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let unbanned = app.unban_user(user.actor(), id).map_err(moderation_error)?;
+    println!("{} lifted the ban of {}", user.username, unbanned.username);
+    Ok(())
+}
+
+/// Logs a user out everywhere without banning them. Needs a role that may ban users and outranks the one of the
+/// user.
+#[post("/users/<id>/kick")]
+async fn kick_user(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.kick_user(user.actor(), id).map_err(moderation_error)?;
+    presence::announce(&mut app, &tenant.broadcast.tx);
+    Ok(())
+}
+
+/// Gives a user the role in the body. Only admins may assign roles, and not to themselves.
+#[put("/users/<id>/role", data = "<role>")]
+async fn set_role(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    role: JsonBody<Role>,
+) -> Result<Json<User>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.set_role(user.actor(), id, *role)
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Logs in as a user, to help them or look into a problem. Only admins may do so, giving the reason in the body.
+/// The login is recorded in the audit log, shown to the user in their sessions and expires after a while.
+#[post("/impersonate/<id>", data = "<request>")]
+async fn impersonate(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    request: JsonBody<ImpersonationRequest>,
+) -> Result<Json<ImpersonationLogin>, ErrorResponse> {
+    let reason = request.reason.trim();
+    if reason.is_empty() {
+        return Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "A reason has to be given.",
+        ));
+    }
+    let mut app = tenant.app.lock().await;
+    let (target, token, valid_until) = app
+        .impersonate(user.actor(), id, reason)
+        .map_err(moderation_error)?;
+    println!(
+        "{} is acting as {}: {reason}",
+        user.username, target.username
+    );
+    Ok(Json(ImpersonationLogin {
+        token: token.0,
+        userid: target.id,
+        username: target.username,
+        valid_until,
+    }))
+}
+
+/// Lists what admins and moderators did, the newest first, a page at a time. Only admins may see it.
+#[get("/audit?<limit>&<cursor>")]
+async fn audit_log(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<AuditEntry>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_audit_log(user.actor(), &PageRequest { limit, cursor }) {
+        Ok(entries) => Ok(Json(entries)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(e) => Err(moderation_error(e)),
+    }
+}
+
+/// Lists the users in the order of `sort`, by name if it is left out, a page at a time. `role`, `online` and
+/// `banned` only list the users matching them. Only moderators and admins may see it.
+#[get("/users?<sort>&<role>&<online>&<banned>&<limit>&<cursor>")]
+#[allow(clippy::too_many_arguments)]
+async fn user_directory(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    sort: Option<&str>,
+    role: Option<&str>,
+    online: Option<bool>,
+    banned: Option<bool>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<UserEntry>>, ErrorResponse> {
+    let sort = match sort {
+        Some(key) => UserSort::from_key(key).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorCode::MalformedRequest,
+                "Users can only be sorted by name, joined or active.",
+            )
+        })?,
+        None => UserSort::default(),
+    };
+    let role = match role {
+        Some(key) => Some(Role::from_key(key).ok_or_else(|| {
+            ErrorResponse::new(ErrorCode::MalformedRequest, "The role does not exist.")
+        })?),
+        None => None,
+    };
+    let filter = UserFilter {
+        role,
+        online,
+        banned,
+    };
+    let mut app = tenant.app.lock().await;
+    match app.get_user_directory(user.actor(), sort, &filter, &PageRequest { limit, cursor }) {
+        Ok(users) => Ok(Json(users)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(e) => Err(moderation_error(e)),
+    }
+}
+
+/// Lists the banned ranges of addresses, the oldest first., a page at a time. Only admins may see them.
+#[get("/addresses?<limit>&<cursor>")]
+async fn get_address_bans(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<AddressBan>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_address_bans(user.actor(), &PageRequest { limit, cursor })
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Bans the range of addresses in the body from registering and logging in. Only admins may do so.
+#[post("/addresses", data = "<request>")]
+async fn ban_address(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    request: JsonBody<AddressBanRequest>,
+) -> Result<Json<AddressBan>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let ban = app
+        .ban_address(user.actor(), &request.network, request.reason.trim())
+        .map_err(moderation_error)?;
+    println!("{} banned the addresses {}", user.username, ban.network);
+    Ok(Json(ban))
+}
+
+/// Lifts a ban of a range of addresses. Only admins may do so.
+#[delete("/addresses/<id>")]
+async fn unban_address(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<AddressBan>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.unban_address(user.actor(), id)
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Lists the invite codes, the newest first., a page at a time. Only admins may do so.
+#[get("/invites?<limit>&<cursor>")]
+async fn get_invites(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<Invite>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_invites(user.actor(), &PageRequest { limit, cursor })
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Makes a new invite code, which lets one person register. Only admins may do so.
+#[post("/invites")]
+async fn create_invite(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Json<Invite>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let invite = app.create_invite(user.actor()).map_err(moderation_error)?;
+    println!("{} made an invite code", user.username);
+    Ok(Json(invite))
+}
+
+/// Withdraws an invite code that was not used yet. Only admins may do so.
+#[delete("/invites/<id>")]
+async fn delete_invite(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<Invite>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.delete_invite(user.actor(), id)
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Lists the bot accounts, the newest first., a page at a time. Only admins may do so.
+#[get("/bots?<limit>&<cursor>")]
+async fn get_bots(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<User>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_bots(user.actor(), &PageRequest { limit, cursor })
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Makes a bot account, which logs in with the API keys made for it. Only admins may do so.
+#[post("/bots", data = "<request>")]
+async fn create_bot(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    request: JsonBody<CreateBot>,
+) -> Result<Json<User>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let bot = app
+        .create_bot(user.actor(), &request.username)
+        .map_err(moderation_error)?;
+    println!("{} made the bot {}", user.username, bot.username);
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::UserJoined {
+            userid: bot.id,
+            username: bot.username.clone(),
+        },
+    }));
+    Ok(Json(bot))
+}
+
+/// Lists the API keys of a bot, the newest first. Only admins may do so.
+#[get("/bots/<id>/keys")]
+async fn get_api_keys(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<Vec<ApiKey>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_api_keys(user.actor(), id)
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Makes an API key for a bot. The key is only in this response. Only admins may do so.
+#[post("/bots/<id>/keys", data = "<request>")]
+async fn create_api_key(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    request: JsonBody<CreateApiKey>,
+) -> Result<Json<IssuedApiKey>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let (info, key) = app
+        .create_api_key(user.actor(), id, &request.name)
+        .map_err(moderation_error)?;
+    println!("{} made an API key for the bot {id}", user.username);
+    Ok(Json(IssuedApiKey { info, key }))
+}
+
+/// Revokes an API key of a bot, which is refused from then on. Only admins may do so.
+#[delete("/bots/<id>/keys/<key>")]
+async fn revoke_api_key(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    key: i32,
+) -> Result<Json<ApiKey>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let revoked = app
+        .revoke_api_key(user.actor(), id, key)
+        .map_err(moderation_error)?;
+    println!("{} revoked an API key of the bot {id}", user.username);
+    Ok(Json(revoked))
+}
+
+/// Lists the webhooks new messages are posted to, the newest first., a page at a time. Only admins may do so.
+#[get("/webhooks?<limit>&<cursor>")]
+async fn get_webhooks(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+) -> Result<Json<Page<Webhook>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_webhooks(user.actor(), &PageRequest { limit, cursor })
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+/// Registers a webhook, which every new message in the chat and the channels is posted to. Only admins may do so.
+#[post("/webhooks", data = "<request>")]
+async fn create_webhook(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    request: JsonBody<WebhookRequest>,
+) -> Result<Json<Webhook>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let webhook = app
+        .create_webhook(user.actor(), &request.url)
+        .map_err(moderation_error)?;
+    println!("{} registered the webhook {}", user.username, webhook.id);
+    Ok(Json(webhook))
+}
+
+/// Removes a webhook, so no more messages are posted to it. Only admins may do so.
+#[delete("/webhooks/<id>")]
+async fn delete_webhook(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<Webhook>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.delete_webhook(user.actor(), id)
+        .map(Json)
+        .map_err(moderation_error)
+}
+
+fn moderation_error(error: AppError) -> ErrorResponse {
+    match error {
+        AppError::PermissionDenied => ErrorResponse::new(
+            ErrorCode::PermissionDenied,
+            "Your role does not allow this.",
+        ),
+        AppError::DatabaseError(DbError::MessageNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The message does not exist.")
+        }
+        AppError::DatabaseError(DbError::AddressBanNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The ban does not exist.")
+        }
+        AppError::DatabaseError(DbError::InviteNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The invite does not exist.")
+        }
+        AppError::DatabaseError(DbError::BotNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The bot does not exist.")
+        }
+        AppError::DatabaseError(DbError::ApiKeyNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The API key does not exist.")
+        }
+        AppError::DatabaseError(DbError::WebhookNotFound) => {
+            ErrorResponse::new(ErrorCode::NotFound, "The webhook does not exist.")
+        }
+        AppError::InvalidCursor => invalid_cursor(),
+        AppError::InvalidWebhookUrl => ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The webhook url has to be an http or https url.",
+        ),
+        AppError::InvalidNetwork(e) => ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The range of addresses is not valid.",
+        )
+        .with_details(&e.to_string()),
+        AppError::DatabaseError(DbError::UsernameInUse) => {
+            ErrorResponse::new(ErrorCode::UsernameTaken, "Username is already taken.")
+        }
+        AppError::VersionConflict { current } => version_conflict(current),
+        e => conversation_error(e),
+    }
+}
+
+/// The response to a change based on an outdated version of what it changes.
+fn version_conflict(current: i32) -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorCode::VersionConflict,
+        "Someone else changed this meanwhile. Reload it and try again.",
+    )
+    .with_details(&current.to_string())
+}
+
+/// Lists the channels by id, a page at a time. With `joined`, only the ones the user is a member of.
+#[get("/channels?<limit>&<cursor>&<joined>")]
+async fn get_channels(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    joined: bool,
+) -> Result<Tagged<Page<Channel>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_channel_page(user.id, joined, &PageRequest { limit, cursor }) {
+        Ok(channels) => Ok(Tagged(channels)),
+        Err(AppError::InvalidCursor) => Err(invalid_cursor()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Creates a channel, which the user joins right away.
+#[post("/channels", data = "<request>")]
+async fn create_channel(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    request: JsonBody<CreateChannel>,
+) -> Result<Json<Channel>, ErrorResponse> {
+    let mut request = request.into_inner();
+    request.name = request.name.trim().to_string();
+    if request.name.is_empty() {
+        return Err(ErrorResponse::new(
+            ErrorCode::MalformedRequest,
+            "The channel needs a name.",
+        ));
+    }
+    let mut app = tenant.app.lock().await;
+    match app.create_channel(user.id, &request) {
+        Ok(channel) => Ok(Json(channel)),
+        Err(AppError::DatabaseError(DbError::ChannelNameInUse)) => Err(ErrorResponse::new(
+            ErrorCode::ChannelNameTaken,
+            "A channel with that name already exists.",
+        )),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+#[put("/channels/<id>/members")]
+async fn join_channel(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<Json<Channel>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.join_channel(user.id, id) {
+        Ok(channel) => Ok(Json(channel)),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+#[delete("/channels/<id>/members")]
+async fn leave_channel(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.leave_channel(user.id, id) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Makes the members of the channel wait the seconds in the body between their messages, 0 to let them send freely
+/// again. Only moderators and admins may do so. With `If-Match`, nothing changes if the channel is not at that
+/// version anymore.
+#[put("/channels/<id>/slowmode", data = "<request>")]
+async fn set_slow_mode(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    version: IfMatch,
+    request: JsonBody<SlowModeRequest>,
+) -> Result<Json<Channel>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let channel = app
+        .set_slow_mode(user.actor(), id, version.0, request.seconds)
+        .map_err(moderation_error)?;
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::SlowModeChanged {
+            channelid: channel.id,
+            seconds: request.seconds,
+        },
+    }));
+    Ok(Json(channel))
+}
+
+/// Changes the topic of the channel to the one in the body and tells everyone. Only moderators and admins may do
+/// so. With `If-Match`, nothing changes if the channel is not at that version anymore.
+#[put("/channels/<id>/topic", data = "<request>")]
+async fn set_channel_topic(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    version: IfMatch,
+    request: JsonBody<TopicRequest>,
+) -> Result<Json<Channel>, ErrorResponse> {
+    let topic = request.topic.trim();
+    let mut app = tenant.app.lock().await;
+    let channel = app
+        .set_channel_topic(user.actor(), id, version.0, topic)
+        .map_err(moderation_error)?;
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::TopicChanged {
+            channelid: Some(channel.id),
+            topic: channel.topic.clone(),
+        },
+    }));
+    Ok(Json(channel))
+}
+
+/// Returns the message of the day, which is empty if there is none.
+#[get("/motd")]
+async fn get_motd(
+    tenant: &Tenant,
+    _user: &AuthenticatedUser,
+) -> Result<Tagged<Motd>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.get_motd()
+        .map(Tagged)
+        .map_err(|_| ErrorResponse::internal())
+}
+
+/// Changes the message of the day to the text in the body, or removes it with an empty one, and tells everyone.
+/// Only admins may do so. With `If-Match`, nothing changes if it is not at that version anymore.
+#[put("/motd", data = "<request>")]
+async fn set_motd(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    version: IfMatch,
+    request: JsonBody<MotdRequest>,
+) -> Result<Json<Motd>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    let motd = app
+        .set_motd(user.actor(), version.0, request.text.trim())
+        .map_err(moderation_error)?;
+    println!("{} changed the message of the day", user.username);
+    let _ = tenant.broadcast.tx.send(ServerEvent::System(SystemEvent {
+        date: Utc::now(),
+        kind: SystemEventKind::MotdChanged {
+            text: motd.text.clone(),
+        },
+    }));
+    Ok(Json(motd))
+}
+
+/// Lists the channels the user gets fewer notifications for than every message.
+#[get("/channels/notifications")]
+async fn get_channel_notifications(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+) -> Result<Json<Vec<ChannelNotifications>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_channel_notifications(user.id) {
+        Ok(overrides) => Ok(Json(overrides)),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Sets how the user is told about new messages in a channel they are a member of.
+#[put("/channels/<id>/notifications", data = "<request>")]
+async fn set_channel_notifications(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    request: JsonBody<NotificationRequest>,
+) -> Result<(), ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    app.set_channel_notifications(user.id, id, request.level)
+        .map_err(conversation_error)
+}
+
+/// Sends a message to a channel the user is a member of, like `POST /message` does to the main chat.
+#[post("/channels/<id>/message", data = "<message>")]
+async fn send_channel_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    key: IdempotencyKey,
+    id: i32,
+    message: String,
+) -> Result<(), ErrorResponse> {
+    send(
+        tenant,
+        user,
+        key,
+        message,
+        Conversation::Channel(id),
+        Vec::new(),
+    )
+    .await
+}
+
+/// Returns the messages of a channel the user is a member of, like `POST /messages` does for the main chat.
+#[post("/channels/<id>/messages?<limit>", data = "<filter>")]
+async fn get_channel_messages(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    id: i32,
+    limit: Option<u32>,
+    filter: JsonBody<MessageFilter>,
+) -> Result<Json<Vec<Message>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_channel_messages(user.id, id, &filter, limit) {
+        Ok(messages) => Ok(Json(messages)),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+/// Sends a message only the user with that id and the sender can read.
+#[post("/direct/<userid>/message", data = "<message>")]
+async fn send_direct_message(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    key: IdempotencyKey,
+    userid: i32,
+    message: String,
+) -> Result<(), ErrorResponse> {
+    send(
+        tenant,
+        user,
+        key,
+        message,
+        Conversation::Direct(userid),
+        Vec::new(),
+    )
+    .await
+}
+
+/// Returns the direct messages between the user and the user with that id, like `POST /messages` does for the main
+/// chat.
+#[post("/direct/<userid>/messages?<limit>", data = "<filter>")]
+async fn get_direct_messages(
+    tenant: &Tenant,
+    user: &AuthenticatedUser,
+    userid: i32,
+    limit: Option<u32>,
+    filter: JsonBody<MessageFilter>,
+) -> Result<Json<Vec<Message>>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.get_direct_messages(user.id, userid, &filter, limit) {
+        Ok(messages) => Ok(Json(messages)),
+        Err(e) => Err(conversation_error(e)),
+    }
+}
+
+/// Tells clients which server they reached. Does not need a login, so it can be checked before logging in.
+#[get("/info")]
+fn info(tenant: &Tenant) -> Json<ServerInfo> {
+    Json(tenant.info.clone())
+}
+
+/// Returns the terms of service users have to accept to register.
+#[get("/terms")]
+fn terms(tenant: &Tenant) -> Result<Json<Terms>, ErrorResponse> {
+    tenant.terms.clone().map(Json).ok_or_else(|| {
+        ErrorResponse::new(ErrorCode::NotFound, "The server has no terms of service.")
+    })
+}
+
+/// Reports how the database connection pool of the tenant is doing, in the Prometheus text format. Does not need a
+/// login, so scrapers can read it. Block it in front of the server if it should not be public.
+#[get("/metrics")]
+async fn pool_metrics(tenant: &Tenant) -> (ContentType, String) {
+    let status = tenant.app.lock().await.pool_status();
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        metrics::render(&status),
+    )
+}
+
+/// Lists the users that are online.
+#[get("/presence")]
+async fn get_presence(
+    tenant: &Tenant,
+    _user: &AuthenticatedUser,
+) -> Result<Json<Presence>, ErrorResponse> {
+    let mut app = tenant.app.lock().await;
+    match app.online_users() {
+        Ok(online) => Ok(Json(Presence { online })),
+        Err(_) => Err(ErrorResponse::internal()),
+    }
+}
+
+/// Streams new messages and system events. Channel and direct messages only go to the users who may read them.
+#[get("/events")]
+async fn events<'a>(user: &AuthenticatedUser, tenant: &'a Tenant) -> EventStream![Event + 'a] {
+    let userid = user.id;
+    let mut rx = tenant.broadcast.rx.resubscribe();
+    EventStream! {
+        // Dropped along with the stream once the client goes away
+        let _open = presence::OpenStream::new(&tenant.app, &tenant.broadcast.tx, userid).await;
+        loop {
+            let event = rx.recv().await;
+            match event {
+                Ok(ServerEvent::Message(message)) => {
+                    if tenant.app.lock().await.shows(userid, &message).unwrap_or(false) {
+                        yield Event::json(&message);
+                    }
+                },
+                Ok(ServerEvent::System(event)) => {yield Event::json(&event).event(SYSTEM_EVENT)},
+                Ok(ServerEvent::Read { reader, conversation, message_id }) => {
+                    let seen = tenant.app.lock().await.receipt_for(userid, reader, conversation);
+                    if let Ok(Some(conversation)) = seen {
+                        let event = SystemEvent {
+                            date: Utc::now(),
+                            kind: SystemEventKind::MessagesRead { userid: reader, conversation, message_id },
+                        };
+                        yield Event::json(&event).event(SYSTEM_EVENT);
+                    }
+                },
+                Err(_) => return ,
+            };
+        }
+    }
+}
+
+/// The user making the request, resolved once per request by the request guard.
+struct AuthenticatedUser {
+    id: i32,
+    username: String,
+    /// The role of the user when the request was made, so permission checks do not look it up again.
+    role: Role,
+    /// The login the request was made with. Bots send an API key instead, so they have none.
+    token: Option<LoginToken>,
+}
+
+impl AuthenticatedUser {
+    /// The user as the one acting on something that needs a permission.
+    fn actor(&self) -> Actor {
+        Actor {
+            id: self.id,
+            role: self.role,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ApiKeyError {
+    Missing,
+    Invalid,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r AuthenticatedUser {
+    type Error = ApiKeyError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // Cache the result, so the token only gets resolved once, no matter how often the guard is used
+        let result = req
+            .local_cache_async(async { authenticate_request(req).await })
+            .await;
+
+        match result {
+            Ok(user) => Outcome::Success(user),
+            Err((status, error)) => Outcome::Failure((*status, *error)),
+        }
+    }
+}
+
+async fn authenticate_request(
+    req: &Request<'_>,
+) -> Result<AuthenticatedUser, (Status, ApiKeyError)> {
+    let Outcome::Success(tenant) = req.guard::<&Tenant>().await else {
+        return Err((Status::NotFound, ApiKeyError::Invalid));
+    };
+
+    let Some(header) = req.headers().get_one("Authorization") else {
+        return Err((Status::Unauthorized, ApiKeyError::Missing));
+    };
+
+    // Bots send a key that does not expire, so they do not have to log in and refresh their login
+    if let Some(key) = header.strip_prefix("ApiKey ") {
+        let mut app = tenant.app.lock().await;
+        let Ok(user) = app.authenticate_api_key(key) else {
+            return Err((Status::Unauthorized, ApiKeyError::Invalid));
+        };
+        return Ok(AuthenticatedUser {
+            id: user.id,
+            username: user.username,
+            role: user.role,
+            token: None,
+        });
+    }
+
+    let Some(token) = header.strip_prefix("Bearer ") else {
+        return Err((Status::Unauthorized, ApiKeyError::Invalid));
+    };
+
+    let login_token = LoginToken(token.to_string());
+    let mut app = tenant.app.lock().await;
+    let Ok(user) = app.authenticate(&login_token) else {
+        return Err((Status::Unauthorized, ApiKeyError::Invalid));
+    };
+
+    Ok(AuthenticatedUser {
+        id: user.id,
+        username: user.username,
+        role: user.role,
+        token: Some(login_token),
+    })
+}
@@ -0,0 +1,87 @@
+//! Tracks who is online and tells the clients when someone comes online or goes offline.
+use std::sync::Arc;
+use std::time::Duration;
+
+use chat_core::protocol::{SystemEvent, SystemEventKind};
+use chrono::Utc;
+use rocket::futures::lock::Mutex;
+use rocket::tokio::runtime::Handle;
+use rocket::tokio::sync::broadcast::Sender;
+use rocket::tokio::time::{self, MissedTickBehavior};
+
+use super::ServerEvent;
+use crate::ChatApp;
+
+/// How often to look for logins that expired, as they run out without a request telling the server.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Starts announcing the users whose logins expired as offline.
+pub fn start(app: Arc<Mutex<ChatApp>>, broadcast: &Sender<ServerEvent>) {
+    let broadcast = broadcast.clone();
+    rocket::tokio::spawn(async move {
+        let mut timer = time::interval(EXPIRY_CHECK_INTERVAL);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            timer.tick().await;
+            announce(&mut *app.lock().await, &broadcast);
+        }
+    });
+}
+
+/// Tells the clients who came online or went offline since the last time. Failures are left for the next time.
+pub fn announce(app: &mut ChatApp, broadcast: &Sender<ServerEvent>) {
+    let Ok(changes) = app.presence_changes() else {
+        return;
+    };
+    for (user, online) in changes {
+        let _ = broadcast.send(ServerEvent::System(SystemEvent {
+            date: Utc::now(),
+            kind: SystemEventKind::PresenceChanged {
+                userid: user.id,
+                username: user.username,
+                online,
+            },
+        }));
+    }
+}
+
+/// Keeps its user online while an event stream of theirs is open.
+pub struct OpenStream {
+    app: Arc<Mutex<ChatApp>>,
+    broadcast: Sender<ServerEvent>,
+    userid: i32,
+}
+
+impl OpenStream {
+    pub async fn new(
+        app: &Arc<Mutex<ChatApp>>,
+        broadcast: &Sender<ServerEvent>,
+        userid: i32,
+    ) -> Self {
+        let mut locked = app.lock().await;
+        locked.open_stream(userid);
+        announce(&mut locked, broadcast);
+        drop(locked);
+        Self {
+            app: Arc::clone(app),
+            broadcast: broadcast.clone(),
+            userid,
+        }
+    }
+}
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        let app = Arc::clone(&self.app);
+        let broadcast = self.broadcast.clone();
+        let userid = self.userid;
+        // Streams still open when the server shuts down are not announced, as nobody is left to tell
+        if let Ok(runtime) = Handle::try_current() {
+            runtime.spawn(async move {
+                let mut app = app.lock().await;
+                app.close_stream(userid);
+                announce(&mut app, &broadcast);
+            });
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Pushes notifications about new messages to ntfy or Gotify, so users are told about them while no client runs.
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+use std::time::Duration;
+
+use chat_core::models::Message;
+use chat_core::protocol::PushProvider;
+use rocket::futures::lock::Mutex;
+use rocket::tokio::sync::mpsc;
+use serde_json::json;
+
+use crate::{ChatApp, PushNotification};
+
+/// How long to wait for a push endpoint before giving up on a notification.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// The priority of notifications sent to Gotify, which shows them as a popup from 4 on.
+const GOTIFY_PRIORITY: u8 = 5;
+
+/// How many messages can wait for their notifications before the ingestion worker has to wait for room.
+const QUEUE_SIZE: usize = 1024;
+
+/// The messages sent, waiting for notifications about them to be pushed. Unlike the broadcast to the event streams,
+/// which skips what a slow receiver missed, every message queued here is looked at.
+pub struct PushQueue {
+    tx: mpsc::Sender<Message>,
+    /// Taken by the pusher once it starts.
+    rx: StdMutex<Option<mpsc::Receiver<Message>>>,
+}
+
+impl PushQueue {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        Self {
+            tx,
+            rx: StdMutex::new(Some(rx)),
+        }
+    }
+
+    /// A handle to queue messages with, for the ingestion worker.
+    pub fn sender(&self) -> mpsc::Sender<Message> {
+        self.tx.clone()
+    }
+
+    /// Starts pushing notifications about the queued messages, to the users of the app who set up push settings.
+    /// Does nothing if the pusher was started already.
+    pub fn start(&self, app: Arc<Mutex<ChatApp>>) {
+        let Some(mut rx) = self
+            .rx
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+        let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                println!("Could not set up push notifications:\n{e}");
+                return;
+            }
+        };
+        rocket::tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let Ok(notifications) = app.lock().await.push_notifications(&message) else {
+                    continue;
+                };
+                for notification in notifications {
+                    // Sent on their own, so a slow endpoint does not hold up the others
+                    rocket::tokio::spawn(push(client.clone(), notification));
+                }
+            }
+        });
+    }
+}
+
+/// Sends the notification to the endpoint the user set up. Failures are only logged, as the message itself was
+/// sent fine.
+async fn push(client: reqwest::Client, notification: PushNotification) {
+    let settings = &notification.settings;
+    let request = match settings.provider {
+        // Passed in the query, as headers can not hold names outside of ASCII
+        PushProvider::Ntfy => {
+            let request = client
+                .post(&settings.url)
+                .query(&[("title", &notification.title)])
+                .body(notification.text.clone());
+            match &settings.token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+        PushProvider::Gotify => client
+            .post(format!("{}/message", settings.url.trim_end_matches('/')))
+            .header(
+                "X-Gotify-Key",
+                settings.token.as_deref().unwrap_or_default(),
+            )
+            .json(&json!({
+                "title": notification.title,
+                "message": notification.text,
+                "priority": GOTIFY_PRIORITY,
+            })),
+    };
+    if let Err(e) = request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+    {
+        println!(
+            "Could not push a notification to user {}: {e}",
+            notification.userid
+        );
+    }
+}
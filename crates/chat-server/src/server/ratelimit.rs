@@ -0,0 +1,210 @@
+//! Limits how often a login or an address can send messages, log in and register, so a single misbehaving client
+//! can not flood the database or the event stream of everyone else.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use chat_core::protocol::ErrorCode;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::{Header, Method};
+use rocket::request::Outcome;
+use rocket::response::Responder;
+use rocket::{Data, Request, Response};
+use serde::Deserialize;
+
+use super::error::ErrorResponse;
+use super::firewall::ClientAddress;
+use super::AuthenticatedUser;
+
+/// No route is mounted here, so limited requests are not handled by any.
+const LIMITED_PATH: &str = "/rate-limited";
+
+/// Counters are only swept once there are this many, as looking at every one on each request would be slow.
+const SWEEP_THRESHOLD: usize = 4096;
+
+/// How many requests are accepted within each period. A limit of 0 turns it off. Read once at startup.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Seconds the requests are counted over.
+    pub period: u64,
+    /// Messages a single login can send within the period, to any conversation and with or without files.
+    pub messages_per_token: u32,
+    /// Messages that can be sent from a single address within the period, by all logins together.
+    pub messages_per_address: u32,
+    /// Logins, refreshed logins, registrations and guest accounts that can be asked for from a single address within
+    /// the period.
+    pub auth_per_address: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            period: 60,
+            messages_per_token: 60,
+            messages_per_address: 120,
+            auth_per_address: 10,
+        }
+    }
+}
+
+/// The kinds of requests that are limited, each counted on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Action {
+    Message,
+    Auth,
+}
+
+impl Action {
+    /// The kind of the request, if it is limited. Paths of other tenants were already stripped of their prefix.
+    fn of(request: &Request<'_>) -> Option<Self> {
+        if request.method() != Method::Post {
+            return None;
+        }
+        let path = request.uri().path().as_str();
+        match path {
+            "/auth/login" | "/auth/guest" | "/auth/refresh" | "/register" => Some(Self::Auth),
+            "/message" | "/message/forward" => Some(Self::Message),
+            _ if path.starts_with("/attachments/") => Some(Self::Message),
+            _ if (path.starts_with("/channels/") || path.starts_with("/direct/"))
+                && path.ends_with("/message") =>
+            {
+                Some(Self::Message)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Who the requests are counted for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Requester {
+    Token(String),
+    /// A bot, which has no login token but counts like one.
+    Bot(i32),
+    Address(IpAddr),
+}
+
+/// The requests counted since the start of the current period.
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+/// Refuses the requests over the configured limits with `ErrorCode::RateLimited`. The response tells how many
+/// seconds are left until the period ends, in its details and in `Retry-After`.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    windows: Mutex<HashMap<(Action, Requester), Window>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn period(&self) -> Duration {
+        Duration::from_secs(self.config.period.max(1))
+    }
+
+    /// Counts the request for its login and its address. Returns how long to wait if either is over its limit.
+    async fn check(&self, request: &Request<'_>) -> Option<Duration> {
+        let action = Action::of(request)?;
+        let address = ClientAddress::of(request);
+        let mut requesters = Vec::new();
+        match action {
+            Action::Message => {
+                // Only logins the server knows are counted, so made up tokens do not each get a counter of their own
+                if let Outcome::Success(user) = request.guard::<&AuthenticatedUser>().await {
+                    // The API key of a bot counts like a login token
+                    let requester = match &user.token {
+                        Some(token) => Requester::Token(token.0.clone()),
+                        None => Requester::Bot(user.id),
+                    };
+                    requesters.push((requester, self.config.messages_per_token));
+                }
+                if let Some(address) = address {
+                    requesters.push((
+                        Requester::Address(address),
+                        self.config.messages_per_address,
+                    ));
+                }
+            }
+            Action::Auth => {
+                if let Some(address) = address {
+                    requesters.push((Requester::Address(address), self.config.auth_per_address));
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let period = self.period();
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        if windows.len() >= SWEEP_THRESHOLD {
+            windows.retain(|_, window| now.duration_since(window.start) < period);
+        }
+        let mut wait = None;
+        for (requester, limit) in requesters.into_iter().filter(|(_, limit)| *limit > 0) {
+            let window = windows.entry((action, requester)).or_insert(Window {
+                start: now,
+                count: 0,
+            });
+            if now.duration_since(window.start) >= period {
+                window.start = now;
+                window.count = 0;
+            }
+            // Refused requests count too, so clients that keep trying do not get through any sooner
+            window.count = window.count.saturating_add(1);
+            if window.count > limit {
+                let left = period.saturating_sub(now.duration_since(window.start));
+                wait = wait.max(Some(left));
+            }
+        }
+        wait
+    }
+}
+
+/// How long the request has to wait, remembered from `on_request` for `on_response`. `None` if it was accepted.
+struct Limited(Option<Duration>);
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limiter",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let Some(wait) = self.check(req).await else {
+            return;
+        };
+        req.local_cache(|| Limited(Some(wait)));
+        if let Ok(uri) = Origin::parse(LIMITED_PATH) {
+            req.set_uri(uri);
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Limited(Some(wait)) = request.local_cache(|| Limited(None)) else {
+            return;
+        };
+        // Rounded up, so waiting that long is always enough
+        let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        let error = ErrorResponse::new(
+            ErrorCode::RateLimited,
+            "Too many requests. Wait a moment before trying again.",
+        )
+        .with_details(&seconds.to_string());
+        if let Ok(mut limited) = error.respond_to(request) {
+            limited.set_header(Header::new("Retry-After", seconds.to_string()));
+            *response = limited;
+        }
+    }
+}
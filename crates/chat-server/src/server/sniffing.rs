@@ -0,0 +1,129 @@
+//! Recognizing the type of uploaded files from their first bytes, as the type clients claim can not be trusted.
+
+/// How many bytes from the start of a file are looked at.
+pub const SNIFF_LENGTH: usize = 512;
+
+/// Files starting with these bytes, at that offset, are of that type.
+const SIGNATURES: [(usize, &[u8], &str); 13] = [
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (4, b"ftyp", "video/mp4"),
+    (0, b"\x1a\x45\xdf\xa3", "video/webm"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"PK\x05\x06", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+];
+
+/// The type of a file starting with those bytes. Text is recognized as `text/plain`, whatever format it is in, so
+/// browsers never render uploaded markup. Anything else is `application/octet-stream`.
+pub fn sniff(start: &[u8]) -> &'static str {
+    let at = |offset: usize, magic: &[u8]| start.get(offset..offset + magic.len()) == Some(magic);
+    if let Some((_, _, mime)) = SIGNATURES
+        .iter()
+        .find(|(offset, magic, _)| at(*offset, magic))
+    {
+        return mime;
+    }
+    // RIFF containers have their type after the size
+    if at(0, b"RIFF") {
+        if at(8, b"WEBP") {
+            return "image/webp";
+        }
+        if at(8, b"WAVE") {
+            return "audio/wav";
+        }
+    }
+    // MPEG audio without tags starts right with a frame header
+    if start.len() >= 2 && start[0] == 0xff && start[1] & 0xe0 == 0xe0 && start[1] & 0x06 != 0 {
+        return "audio/mpeg";
+    }
+    if is_text(start) {
+        return "text/plain";
+    }
+    "application/octet-stream"
+}
+
+/// Whether the bytes are UTF-8 text, which may be cut off in the middle of a character at the end.
+fn is_text(start: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(start) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    valid
+        && !start
+            .iter()
+            .any(|&b| b.is_ascii_control() && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sniff, SIGNATURES};
+
+    /// The magic followed by some bytes that are not text, as the rest of a real file would be.
+    fn file(offset: usize, magic: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; offset];
+        bytes.extend_from_slice(magic);
+        bytes.extend_from_slice(&[0x00, 0x01, 0x80, 0xfe]);
+        bytes
+    }
+
+    #[test]
+    fn every_signature_is_recognized() {
+        for (offset, magic, mime) in SIGNATURES {
+            assert_eq!(sniff(&file(offset, magic)), mime, "{magic:?}");
+        }
+        assert_eq!(
+            sniff(&file(0, b"RIFF\x24\x00\x00\x00WEBPVP8 ")),
+            "image/webp"
+        );
+        assert_eq!(
+            sniff(&file(0, b"RIFF\x24\x00\x00\x00WAVEfmt ")),
+            "audio/wav"
+        );
+        assert_eq!(
+            sniff(&file(0, b"RIFF\x24\x00\x00\x00AVI LIST")),
+            "application/octet-stream"
+        );
+        assert_eq!(sniff(&file(0, &[0xff, 0xfb, 0x90, 0x64])), "audio/mpeg");
+    }
+
+    #[test]
+    fn the_claimed_type_does_not_matter() {
+        // Only the content is looked at, so markup uploaded as cat.png is served as text and a program uploaded
+        // as cat.jpg as bytes
+        assert_eq!(
+            sniff(b"<html><script>alert(1)</script></html>"),
+            "text/plain"
+        );
+        assert_eq!(sniff(&file(0, b"MZ\x90\x00")), "application/octet-stream");
+        assert_eq!(sniff(b"%PDF-1.7\n"), "application/pdf");
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(sniff(b"Hello\tworld\r\n"), "text/plain");
+        assert_eq!(sniff("Grüße 🎉".as_bytes()), "text/plain");
+        assert_eq!(sniff(b"\x1b[31mred\x1b[0m"), "text/plain");
+        assert_eq!(sniff(b"nul\x00byte"), "application/octet-stream");
+        assert_eq!(sniff(b"\xc3\x28 invalid"), "application/octet-stream");
+    }
+
+    #[test]
+    fn truncated_input() {
+        // Shorter than the magic, or cut off before the type of a container
+        assert_eq!(sniff(b"\x89PN"), "application/octet-stream");
+        assert_eq!(sniff(b"\x00\x00\x00\x18fty"), "application/octet-stream");
+        assert_eq!(sniff(b"GIF8"), "text/plain");
+        assert_eq!(sniff(b"RIFF"), "text/plain");
+        assert_eq!(sniff(&[0xff]), "application/octet-stream");
+        assert_eq!(sniff(b""), "text/plain");
+        // Text cut off in the middle of a character is still text
+        assert_eq!(sniff(&"Grüße".as_bytes()[..3]), "text/plain");
+    }
+}
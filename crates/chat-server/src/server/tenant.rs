@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+
+use chat_core::paths::Paths;
+use chat_core::protocol::{Feature, ServerInfo, Terms, PROTOCOL_VERSION};
+use chat_core::Version;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::futures::lock::Mutex;
+use rocket::http::uri::Origin;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request};
+
+use super::attachments::AttachmentStore;
+use super::ingestion::IngestionQueue;
+use super::push::PushQueue;
+use super::webhooks::WebhookQueue;
+use super::MessageBroadcast;
+use super::{digest, maintenance, presence};
+use crate::config::Config;
+use crate::ChatApp;
+
+/// Whether this server offers the feature. Features listed as missing are still announced, so clients know the
+/// server is aware of them.
+fn offers(feature: Feature) -> bool {
+    match feature {
+        Feature::SavedMessages
+        | Feature::Forwarding
+        | Feature::MessageContext
+        | Feature::InitialSync
+        | Feature::MessageStream
+        | Feature::ReadMarkers
+        | Feature::Pagination
+        | Feature::IdempotencyKeys
+        | Feature::Channels
+        | Feature::DirectMessages
+        | Feature::ServerClock
+        | Feature::Drafts
+        | Feature::PushNotifications
+        | Feature::Attachments
+        | Feature::Search
+        | Feature::Moderation
+        | Feature::Profiles
+        | Feature::ContextAround
+        | Feature::Presence
+        | Feature::Sessions
+        | Feature::Impersonation
+        | Feature::AddressBans
+        | Feature::SlowMode
+        | Feature::ReadReceipts
+        | Feature::ChannelNotifications
+        | Feature::Blocking
+        | Feature::UserDirectory
+        | Feature::Kicking
+        | Feature::Invites
+        | Feature::Bots
+        | Feature::Webhooks
+        | Feature::Topics => true,
+        Feature::Reactions | Feature::E2ee => false,
+    }
+}
+
+/// The prefix of the paths other tenants are served under, followed by their key.
+const PREFIX: &str = "/t/";
+
+/// One community hosted by the server, with its own users, messages and configuration.
+pub struct Tenant {
+    pub(super) app: Arc<Mutex<ChatApp>>,
+    pub(super) broadcast: MessageBroadcast,
+    pub(super) ingestion: IngestionQueue,
+    pub(super) push: PushQueue,
+    pub(super) webhooks: WebhookQueue,
+    pub(super) attachments: Arc<AttachmentStore>,
+    pub(super) info: ServerInfo,
+    pub(super) terms: Option<Terms>,
+}
+
+impl Tenant {
+    /// Sets up a tenant from its configuration. Relative paths in it are resolved against the given directories.
+    ///
+    /// Exits the process if the terms of service could not be read, the minimum client version or accent color is
+    /// not valid, the attachment directory could not be created or the database could not be opened.
+    pub fn new(config: Config, paths: &Paths) -> Self {
+        let terms = config.terms.as_ref().map(|terms| {
+            let file = paths.config.join(&terms.file);
+            match fs::read_to_string(&file) {
+                Ok(text) => Terms {
+                    version: terms.version.clone(),
+                    text,
+                },
+                Err(e) => {
+                    println!(
+                        "Could not read the terms of service from {}:\n{e}",
+                        file.display()
+                    );
+                    std::process::exit(1)
+                }
+            }
+        });
+        if let Some(version) = &config.min_client_version {
+            if version.parse::<Version>().is_err() {
+                println!("The minimum client version {version:?} is not a version like 1.2.0");
+                std::process::exit(1)
+            }
+        }
+        if !config.branding.has_valid_accent_color() {
+            println!(
+                "The accent color of {} is not a color like #3b82f6",
+                config.name
+            );
+            std::process::exit(1)
+        }
+        let info = ServerInfo {
+            name: config.name.clone(),
+            description: config.branding.description.clone(),
+            banner: config.branding.banner.clone(),
+            accent_color: config.branding.accent_color.clone(),
+            version: env!("CARGO_PKG_VERSION").into(),
+            terms_version: terms.as_ref().map(|terms| terms.version.clone()),
+            guests: config.guests.enabled,
+            invite_only: config.invites.required,
+            min_client_version: config.min_client_version.clone(),
+            features: Some(
+                Feature::ALL
+                    .iter()
+                    .map(|feature| (feature.key().to_string(), offers(*feature)))
+                    .collect(),
+            ),
+            protocol_version: PROTOCOL_VERSION,
+        };
+        let attachments = paths.data.join(&config.attachments);
+        if let Err(e) = fs::create_dir_all(&attachments) {
+            println!(
+                "Could not create the attachment directory {}:\n{e}",
+                attachments.display()
+            );
+            std::process::exit(1)
+        }
+        let attachments = Arc::new(AttachmentStore::new(attachments, config.uploads.clone()));
+        let database = paths.data.join(&config.database);
+        let app = match ChatApp::new(config, &database) {
+            Ok(app) => Arc::new(Mutex::new(app)),
+            Err(e) => {
+                println!("Could not create app:\n{e}");
+                std::process::exit(1)
+            }
+        };
+
+        Self {
+            app,
+            broadcast: MessageBroadcast::new(),
+            ingestion: IngestionQueue::new(),
+            push: PushQueue::new(),
+            webhooks: WebhookQueue::new(),
+            attachments,
+            info,
+            terms,
+        }
+    }
+
+    /// Starts writing the messages sent to the tenant, pushing notifications about them and posting them to
+    /// webhooks, announcing expired logins as offline, removing attachment files that are not used anymore and
+    /// posting the weekly digest.
+    pub(super) fn start(&self) {
+        self.ingestion.start(
+            Arc::clone(&self.app),
+            self.broadcast.tx.clone(),
+            self.push.sender(),
+            self.webhooks.sender(),
+        );
+        self.push.start(Arc::clone(&self.app));
+        self.webhooks.start(Arc::clone(&self.app));
+        presence::start(Arc::clone(&self.app), &self.broadcast.tx);
+        maintenance::start(Arc::clone(&self.app), Arc::clone(&self.attachments));
+        digest::start(Arc::clone(&self.app), self.ingestion.sender());
+    }
+}
+
+/// All tenants of the server. The default one is served at the root, the others under `/t/<key>`.
+pub struct Tenants {
+    pub default: Tenant,
+    pub others: HashMap<String, Tenant>,
+}
+
+impl Tenants {
+    /// Returns the default tenant followed by the others.
+    pub fn all(&self) -> impl Iterator<Item = &Tenant> {
+        std::iter::once(&self.default).chain(self.others.values())
+    }
+}
+
+/// Returns true if the key can be used in paths as is.
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The tenant key taken from the path of a request, if it had one.
+struct TenantKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Tenant {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(tenants) = req.rocket().state::<Tenants>() else {
+            panic!("Tenants are not managed by the server")
+        };
+
+        let TenantKey(key) = req.local_cache(|| TenantKey(None));
+        match key {
+            None => Outcome::Success(&tenants.default),
+            Some(key) => match tenants.others.get(key) {
+                Some(tenant) => Outcome::Success(tenant),
+                None => Outcome::Failure((Status::NotFound, ())),
+            },
+        }
+    }
+}
+
+/// Strips `/t/<key>` from the path of requests, so the same routes serve every tenant. The key is kept for the
+/// `Tenant` request guard. Paths with a key no tenant has are left as they are, so they are not found.
+pub struct TenantRouting;
+
+#[rocket::async_trait]
+impl Fairing for TenantRouting {
+    fn info(&self) -> Info {
+        Info {
+            name: "Tenant routing",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let Some(rest) = req.uri().path().as_str().strip_prefix(PREFIX) else {
+            return;
+        };
+        let (key, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let known = req
+            .rocket()
+            .state::<Tenants>()
+            .is_some_and(|tenants| tenants.others.contains_key(key));
+        if !known {
+            return;
+        }
+        let uri = match req.uri().query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path.to_string(),
+        };
+        let key = key.to_string();
+        if let Ok(uri) = Origin::parse_owned(uri) {
+            req.set_uri(uri);
+            req.local_cache(|| TenantKey(Some(key)));
+        }
+    }
+}
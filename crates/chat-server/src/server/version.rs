@@ -0,0 +1,25 @@
+//! Tells clients which version of the protocol the server speaks.
+use chat_core::protocol::{PROTOCOL_VERSION, PROTOCOL_VERSION_HEADER};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::{Request, Response};
+
+/// Adds the version of the protocol to every response, in the `Protocol-Version` header.
+pub struct ProtocolVersion;
+
+#[rocket::async_trait]
+impl Fairing for ProtocolVersion {
+    fn info(&self) -> Info {
+        Info {
+            name: "Protocol version",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
+        response.set_header(Header::new(
+            PROTOCOL_VERSION_HEADER,
+            PROTOCOL_VERSION.to_string(),
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+//! Posts new messages to the webhooks admins registered, trying again a few times when an endpoint fails.
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
+use std::time::Duration;
+
+use chat_core::models::Message;
+use reqwest::StatusCode;
+use rocket::futures::lock::Mutex;
+use rocket::tokio::sync::mpsc;
+use rocket::tokio::time;
+
+use crate::{ChatApp, WebhookDelivery};
+
+/// How long to wait for a webhook to answer before counting the attempt as failed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a message is posted to a webhook at most, including the first attempt.
+const MAX_ATTEMPTS: u32 = 5;
+/// How long to wait before the first retry. Doubles with every further retry, so an endpoint that is down for a
+/// while is not flooded.
+const BASE_DELAY: Duration = Duration::from_secs(2);
+/// How many messages can wait to be posted before the ingestion worker has to wait for room.
+const QUEUE_SIZE: usize = 1024;
+
+/// The messages sent, waiting to be posted to the webhooks. Unlike the broadcast to the event streams, which skips
+/// what a slow receiver missed, every message queued here is looked at.
+pub struct WebhookQueue {
+    tx: mpsc::Sender<Message>,
+    /// Taken by the poster once it starts.
+    rx: StdMutex<Option<mpsc::Receiver<Message>>>,
+}
+
+impl WebhookQueue {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        Self {
+            tx,
+            rx: StdMutex::new(Some(rx)),
+        }
+    }
+
+    /// A handle to queue messages with, for the ingestion worker.
+    pub fn sender(&self) -> mpsc::Sender<Message> {
+        self.tx.clone()
+    }
+
+    /// Starts posting the queued messages to the webhooks of the app. Does nothing if the poster was started already.
+    pub fn start(&self, app: Arc<Mutex<ChatApp>>) {
+        let Some(mut rx) = self
+            .rx
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return;
+        };
+        let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                println!("Could not set up webhooks:\n{e}");
+                return;
+            }
+        };
+        rocket::tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let Ok(deliveries) = app.lock().await.webhook_deliveries(&message) else {
+                    continue;
+                };
+                for delivery in deliveries {
+                    // Sent on their own, so a slow or failing endpoint does not hold up the others
+                    rocket::tokio::spawn(deliver(client.clone(), delivery));
+                }
+            }
+        });
+    }
+}
+
+/// Posts the message to the webhook, waiting longer after every failed attempt. Failures are only logged, as the
+/// message itself was sent fine.
+async fn deliver(client: reqwest::Client, delivery: WebhookDelivery) {
+    let mut delay = BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        let request = client.post(&delivery.url).json(&delivery.payload);
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => {
+                let status = response.status();
+                // The endpoint refused the payload, which posting it again would not change
+                if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                    println!(
+                        "Webhook {} refused a message with {status}",
+                        delivery.webhookid
+                    );
+                    return;
+                }
+                status.to_string()
+            }
+            Err(e) => e.to_string(),
+        };
+        if attempt >= MAX_ATTEMPTS {
+            println!(
+                "Could not post a message to webhook {} after {MAX_ATTEMPTS} attempts: {error}",
+                delivery.webhookid
+            );
+            return;
+        }
+        time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
@@ -0,0 +1,93 @@
+//! Tells when a message repeats an earlier one, so users sending the same text over and over can be stopped.
+
+/// Whether the texts are the same, ignoring case, punctuation and spacing. Texts without any letters or digits, like
+/// a row of emoji, only match when they are the same apart from spacing. Empty texts, like the ones of messages with
+/// only attachments, never match.
+pub fn is_copy(text: &str, other: &str) -> bool {
+    let (text, other) = (normalize(text), normalize(other));
+    !text.is_empty() && text == other
+}
+
+/// Finds the latest of the recent messages that the text copies, if sending it would make one copy more than
+/// `max_copies` allows. The recent messages come newest first.
+pub fn repeated<M>(
+    text: &str,
+    recent: impl IntoIterator<Item = M>,
+    message_text: impl Fn(&M) -> &str,
+    max_copies: u32,
+) -> Option<M> {
+    let mut copies = recent
+        .into_iter()
+        .filter(|message| is_copy(text, message_text(message)));
+    let latest = copies.next()?;
+    (1 + copies.count() >= max_copies as usize).then_some(latest)
+}
+
+fn normalize(text: &str) -> String {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_copy, repeated};
+
+    #[test]
+    fn case_punctuation_and_spacing_do_not_matter() {
+        assert!(is_copy("Hello there", "hello there"));
+        assert!(is_copy("Hello, there!", "hello there"));
+        assert!(is_copy("  hello   there ", "hello\tthere"));
+        assert!(is_copy("BUY NOW!!!", "buy... now"));
+    }
+
+    #[test]
+    fn near_duplicates_are_not_copies() {
+        assert!(!is_copy("hello there", "hello their"));
+        assert!(!is_copy("hello there", "hello there again"));
+        assert!(!is_copy("hello there", "hellothere"));
+        assert!(!is_copy("2 apples", "3 apples"));
+    }
+
+    #[test]
+    fn texts_without_letters_only_ignore_spacing() {
+        assert!(is_copy("🎉🎉", " 🎉🎉 "));
+        assert!(is_copy("!!! ???", "!!!   ???"));
+        assert!(!is_copy("🎉🎉", "🎉🎉🎉"));
+        assert!(!is_copy("!!!", "?!?"));
+    }
+
+    #[test]
+    fn empty_texts_never_match() {
+        assert!(!is_copy("", ""));
+        assert!(!is_copy("   ", " \n "));
+        assert!(!is_copy("", "hello"));
+    }
+
+    #[test]
+    fn copies_up_to_the_maximum_are_allowed() {
+        let recent = ["Hello", "something else", "hello!"].map(String::from);
+        let older = recent[1..].to_vec();
+        let found = |text, recent: &[String], max_copies| {
+            repeated(text, recent.to_vec(), String::as_str, max_copies)
+        };
+        // Two copies were sent already, so a third one is one too many with a maximum of two
+        assert_eq!(found("HELLO", &recent, 2).as_deref(), Some("Hello"));
+        assert_eq!(found("HELLO", &recent, 3), None);
+        assert_eq!(found("hello", &older, 2), None);
+        assert_eq!(found("hello", &older, 1).as_deref(), Some("hello!"));
+        assert_eq!(found("bye", &recent, 1), None);
+    }
+}
@@ -0,0 +1,282 @@
+//! Checks the server against the protocol its clients speak: the bodies they send and the headers they rely on.
+use std::fs;
+
+use chat_core::models::{Channel, Motd, Profile, Role};
+use chat_core::paths::Paths;
+use chat_core::protocol::{
+    ApiError, Credentials, ErrorCode, LoginResult, Overview, PROTOCOL_VERSION,
+    PROTOCOL_VERSION_HEADER,
+};
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use serde_json::json;
+
+/// Starts a server with the default configuration, keeping its files in a directory of its own.
+fn client(name: &str) -> Client {
+    let dir = std::env::temp_dir().join(format!("chat-server-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let paths = Paths {
+        data: dir.join("data"),
+        config: dir.join("config"),
+        cache: dir.join("cache"),
+    };
+    paths.create().unwrap();
+    let figment = rocket::Config::figment().merge(("log_level", "off"));
+    Client::tracked(chat_server::server::rocket(figment, &paths)).unwrap()
+}
+
+fn credentials() -> Credentials {
+    Credentials {
+        username: "alice".into(),
+        password: "correct horse battery staple".into(),
+        remember_me: false,
+        accepted_tos_version: None,
+        invite_code: None,
+    }
+}
+
+fn protocol_version(response: &LocalResponse) -> Option<String> {
+    response
+        .headers()
+        .get_one(PROTOCOL_VERSION_HEADER)
+        .map(String::from)
+}
+
+#[test]
+fn body_with_unknown_field_is_refused() {
+    let client = client("unknown-field");
+    let body = json!({
+        "username": "alice",
+        "password": "correct horse battery staple",
+        "remember": true,
+    });
+    let response = client
+        .post("/register")
+        .header(ContentType::JSON)
+        .body(body.to_string())
+        .dispatch();
+
+    assert!([Status::BadRequest, Status::UnprocessableEntity].contains(&response.status()));
+    let error: ApiError = response.into_json().unwrap();
+    assert_eq!(error.code, ErrorCode::MalformedRequest);
+    assert!(error.details.unwrap().contains("remember"));
+}
+
+#[test]
+fn valid_body_is_accepted() {
+    let client = client("valid-body");
+    let response = client.post("/register").json(&credentials()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client.post("/auth/login").json(&credentials()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let login: LoginResult = response.into_json().unwrap();
+    assert!(!login.token.is_empty());
+}
+
+#[test]
+fn responses_carry_the_protocol_version() {
+    let client = client("protocol-version");
+    let expected = Some(PROTOCOL_VERSION.to_string());
+
+    let response = client.get("/info").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(protocol_version(&response), expected);
+
+    let response = client.post("/register").json(&credentials()).dispatch();
+    assert_eq!(protocol_version(&response), expected);
+
+    // Errors and the catchers answering them say it too
+    let response = client.get("/does/not/exist").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+    assert_eq!(protocol_version(&response), expected);
+
+    let response = client
+        .post("/register")
+        .header(ContentType::JSON)
+        .body("{")
+        .dispatch();
+    assert_eq!(protocol_version(&response), expected);
+}
+
+#[test]
+fn change_based_on_an_outdated_version_is_refused() {
+    let client = client("if-match");
+    client.post("/register").json(&credentials()).dispatch();
+    let response = client.post("/auth/login").json(&credentials()).dispatch();
+    let login: LoginResult = response.into_json().unwrap();
+    let authorization = || Header::new("Authorization", format!("Bearer {}", login.token));
+    let update = |version: &str, bio: &str| {
+        client
+            .patch("/profile")
+            .header(authorization())
+            .header(Header::new("If-Match", version.to_string()))
+            .json(&json!({ "bio": bio }))
+            .dispatch()
+    };
+
+    let response = update("\"1\"", "first");
+    assert_eq!(response.status(), Status::Ok);
+    let profile: Profile = response.into_json().unwrap();
+    assert_eq!(profile.version, 2);
+
+    // Another edit based on the first version would overwrite the one made meanwhile
+    let response = update("\"1\"", "second");
+    assert_eq!(response.status(), Status::PreconditionFailed);
+    let error: ApiError = response.into_json().unwrap();
+    assert_eq!(error.code, ErrorCode::VersionConflict);
+    assert_eq!(error.details.as_deref(), Some("2"));
+
+    let response = update("*", "second");
+    assert_eq!(response.status(), Status::Ok);
+    let profile: Profile = response.into_json().unwrap();
+    assert_eq!((profile.bio.as_str(), profile.version), ("second", 3));
+
+    let response = update("3", "third");
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn forwarded_address_does_not_escape_the_limits() {
+    let client = client("forwarded-address");
+    // The default configuration trusts no proxy, so every request counts for the address of the connection
+    let remote = "203.0.113.7:40000".parse().unwrap();
+    let statuses: Vec<Status> = (0..11)
+        .map(|i| {
+            client
+                .post("/auth/refresh")
+                .remote(remote)
+                .header(Header::new("X-Real-IP", format!("192.0.2.{i}")))
+                .json(&json!({ "refresh_token": "not a token" }))
+                .dispatch()
+                .status()
+        })
+        .collect();
+
+    assert!(statuses[..10]
+        .iter()
+        .all(|status| *status != Status::TooManyRequests));
+    assert_eq!(statuses[10], Status::TooManyRequests);
+}
+
+#[test]
+fn forwarded_messages_count_as_repeated_texts() {
+    let client = client("forward-duplicates");
+    client.post("/register").json(&credentials()).dispatch();
+    let response = client.post("/auth/login").json(&credentials()).dispatch();
+    let login: LoginResult = response.into_json().unwrap();
+    let authorization = || Header::new("Authorization", format!("Bearer {}", login.token));
+    let response = client
+        .post("/message")
+        .header(authorization())
+        .body("Hello")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let forward = || {
+        client
+            .post("/message/forward")
+            .header(authorization())
+            .json(&json!({ "message_id": 1, "target": "chat" }))
+            .dispatch()
+    };
+
+    // The default configuration allows two copies of a text within a minute, the original being the first
+    assert_eq!(forward().status(), Status::Ok);
+    let response = forward();
+    assert_eq!(response.status(), Status::TooManyRequests);
+    let error: ApiError = response.into_json().unwrap();
+    assert_eq!(error.code, ErrorCode::DuplicateMessage);
+}
+
+#[test]
+fn overview_is_paged() {
+    let client = client("overview-pages");
+    client.post("/register").json(&credentials()).dispatch();
+    let response = client.post("/auth/login").json(&credentials()).dispatch();
+    let login: LoginResult = response.into_json().unwrap();
+    let authorization = || Header::new("Authorization", format!("Bearer {}", login.token));
+    for name in ["general", "random"] {
+        let response = client
+            .post("/channels")
+            .header(authorization())
+            .json(&json!({ "name": name }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+    let overview = |query: &str| -> Overview {
+        let response = client
+            .get(format!("/sync/overview{query}"))
+            .header(authorization())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        response.into_json().unwrap()
+    };
+    let keys = |overview: &Overview| -> Vec<String> {
+        overview
+            .conversations
+            .iter()
+            .map(|conversation| conversation.conversation.key())
+            .collect()
+    };
+
+    let first = overview("?limit=2");
+    assert_eq!(keys(&first), ["chat", "channel-1"]);
+    assert_eq!(first.next.as_deref(), Some("channel-1"));
+    let second = overview("?limit=2&cursor=channel-1");
+    assert_eq!(keys(&second), ["channel-2"]);
+    assert_eq!(second.next, None);
+
+    let response = client
+        .get("/sync/overview?cursor=channel")
+        .header(authorization())
+        .dispatch();
+    let error: ApiError = response.into_json().unwrap();
+    assert_eq!(error.code, ErrorCode::MalformedRequest);
+}
+
+#[test]
+fn topic_and_motd_changes_based_on_an_outdated_version_are_refused() {
+    let name = "topic-if-match";
+    let client = client(name);
+    client.post("/register").json(&credentials()).dispatch();
+    let database = std::env::temp_dir()
+        .join(format!("chat-server-{name}-{}", std::process::id()))
+        .join("data")
+        .join("data.db");
+    let conn = &mut chat_server::establish_connection(&database).unwrap();
+    chat_server::set_role(conn, 1, Role::Admin).unwrap();
+    let response = client.post("/auth/login").json(&credentials()).dispatch();
+    let login: LoginResult = response.into_json().unwrap();
+    let authorization = || Header::new("Authorization", format!("Bearer {}", login.token));
+    let response = client
+        .post("/channels")
+        .header(authorization())
+        .json(&json!({ "name": "general" }))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let change = |path: &str, version: &str, body: serde_json::Value| {
+        client
+            .put(path.to_string())
+            .header(authorization())
+            .header(Header::new("If-Match", version.to_string()))
+            .json(&body)
+            .dispatch()
+    };
+
+    let response = change("/channels/1/topic", "\"1\"", json!({ "topic": "News" }));
+    assert_eq!(response.status(), Status::Ok);
+    let channel: Channel = response.into_json().unwrap();
+    assert_eq!((channel.topic.as_str(), channel.version), ("News", 2));
+    let response = change("/channels/1/topic", "\"1\"", json!({ "topic": "Other" }));
+    assert_eq!(response.status(), Status::PreconditionFailed);
+
+    let response = client.get("/motd").header(authorization()).dispatch();
+    let motd: Motd = response.into_json().unwrap();
+    assert_eq!((motd.text.as_str(), motd.version), ("", 1));
+    let response = change("/motd", "\"1\"", json!({ "text": "Welcome" }));
+    assert_eq!(response.status(), Status::Ok);
+    let response = change("/motd", "\"1\"", json!({ "text": "Hello" }));
+    assert_eq!(response.status(), Status::PreconditionFailed);
+    let error: ApiError = response.into_json().unwrap();
+    assert_eq!(error.details.as_deref(), Some("2"));
+}
@@ -0,0 +1,174 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use chat_client::config::NetworkConfig;
+use eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use toml::Table;
+
+use crate::migrations::Schema;
+
+/// The file the client configuration is read from, in the config directory.
+pub const CONFIG_FILE: &str = "client.toml";
+
+/// The versions ``client.toml`` went through. Files from before it was versioned need no changes.
+const SCHEMA: Schema = Schema {
+    name: CONFIG_FILE,
+    migrations: &[|_| Ok(())],
+};
+
+/// Configuration for the client application.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    /// The version of the schema the file was written with, see ``migrations``.
+    pub version: u32,
+    pub network: NetworkConfig,
+    pub ui: UiConfig,
+    pub spellcheck: SpellcheckConfig,
+}
+
+/// Controls how the user interface behaves.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Whether the mouse can be used to switch tabs, select messages and scroll.
+    pub mouse: bool,
+    /// Avoids colors, marking active elements with text instead. Also enabled by the ``NO_COLOR`` environment
+    /// variable.
+    pub accessible: bool,
+    /// The language of the ui, like ``de``. Taken from the environment if not set.
+    pub locale: Option<String>,
+    pub clock: Clock,
+    /// The time zone times are shown in, like ``Europe/Berlin``. Uses the time zone of the system if not set.
+    pub timezone: Option<String>,
+    pub own_messages: OwnMessages,
+    /// Whether the terminal bell rings when messages arrive in a window that is not active.
+    pub bell: bool,
+}
+
+/// Whether times are shown with 24 hours or with 12 hours and AM/PM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Clock {
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+    #[serde(rename = "12h")]
+    TwelveHour,
+}
+
+/// How messages written by the logged in user are told apart from the others. They are always shown in bold, or
+/// marked with text in the accessible mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnMessages {
+    #[default]
+    Bold,
+    /// Also shown in color.
+    Color,
+    /// Also aligned to the right of the message list.
+    Right,
+}
+
+impl UiConfig {
+    /// Returns true if colors should not be used, either because of the configuration or ``NO_COLOR``.
+    pub fn no_color(&self) -> bool {
+        self.accessible || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            mouse: true,
+            accessible: false,
+            locale: None,
+            clock: Clock::default(),
+            timezone: None,
+            own_messages: OwnMessages::default(),
+            bell: false,
+        }
+    }
+}
+
+/// Controls spell checking of the message composer.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SpellcheckConfig {
+    /// Whether misspelled words get underlined. Needs a Hunspell dictionary for the language.
+    pub enabled: bool,
+    /// The name of the dictionary, like ``en_US`` for ``en_US.aff`` and ``en_US.dic``.
+    pub language: String,
+    /// The directory the dictionaries are in, relative to the data directory.
+    pub dictionaries: String,
+}
+
+impl Default for SpellcheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            language: "en_US".into(),
+            dictionaries: "dictionaries".into(),
+        }
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA.version(),
+            network: NetworkConfig::default(),
+            ui: UiConfig::default(),
+            spellcheck: SpellcheckConfig::default(),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Loads the configuration from ``client.toml`` in the directory, using the defaults if the file does not exist.
+    /// Files written by older clients are migrated, the file itself is left as it is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file could not be read, is not valid or could not be migrated.
+    pub fn load(directory: &Path) -> Result<Self> {
+        Self::load_from(&directory.join(CONFIG_FILE))
+    }
+
+    /// Returns true if there is a ``client.toml`` in the directory. Without one, the client is started for the
+    /// first time.
+    pub fn exists(directory: &Path) -> bool {
+        directory.join(CONFIG_FILE).exists()
+    }
+
+    /// Writes the configuration to ``client.toml`` in the directory, replacing the file if there already is one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file could not be written, or if it was written by a newer client,
+    /// as what this client does not know about would be lost.
+    pub fn save(&self, directory: &Path) -> Result<()> {
+        if self.version > SCHEMA.version() {
+            return Err(eyre!(
+                "{CONFIG_FILE} was written by a newer version of the client and is not replaced"
+            ));
+        }
+        let content = toml::to_string(self).wrap_err("Could not serialize the configuration")?;
+        let path = directory.join(CONFIG_FILE);
+        fs::write(&path, content).wrap_err_with(|| format!("Could not write {}", path.display()))
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                let mut table: Table = toml::from_str(&content)
+                    .wrap_err_with(|| format!("Could not parse {}", path.display()))?;
+                SCHEMA.migrate(&mut table)?;
+                table
+                    .try_into()
+                    .wrap_err_with(|| format!("Could not parse {}", path.display()))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).wrap_err_with(|| format!("Could not read {}", path.display())),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::{io, panic, path::PathBuf, process};
+
+use chat_core::crash;
+use crossterm::{
+    cursor::Show,
+    event::DisableMouseCapture,
+    execute,
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
+};
+
+/// Restores the terminal when dropped, so it is usable again however the ui ends. Create it right after enabling
+/// raw mode.
+pub struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Leaves raw mode and the alternate screen and shows the cursor again. Failures are ignored, as this is the last
+/// thing done before exiting anyway.
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        Show
+    );
+}
+
+/// Makes panics restore the terminal before printing the panic with a backtrace, and write a crash report to the
+/// data directory. The client exits afterwards, even if only a background task panicked, as the ui can not go on
+/// without the terminal.
+pub fn install_panic_hook(data: PathBuf) {
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        let description = crash::describe("client", info);
+        eprintln!("{description}");
+        match crash::write_report(&data, "client", &description) {
+            Ok(path) => eprintln!(
+                "A crash report was written to {}. Please attach it when reporting the crash.",
+                path.display()
+            ),
+            Err(e) => eprintln!("Could not write a crash report: {e}"),
+        }
+        process::exit(101);
+    }));
+}
@@ -0,0 +1,231 @@
+//! Downloading attachments in the background, so large files do not hold up the ui, with their progress shown
+//! while they arrive.
+use std::{
+    fs::{self, OpenOptions},
+    future, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chat_client::Client;
+use chat_core::models::Attachment;
+use tokio::{
+    fs::File,
+    io::AsyncWriteExt,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+use tracing::warn;
+
+use crate::{errors::Localized, i18n::tf};
+
+/// How often a download reports its progress at most.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A download in progress.
+pub struct Download {
+    id: u64,
+    /// The title of the window the download was started in, which is told once it is done.
+    pub window: String,
+    pub name: String,
+    /// How many bytes arrived so far.
+    pub received: u64,
+    /// The size of the file as the message described it.
+    pub size: u64,
+}
+
+impl Download {
+    /// Describes how far along the download is.
+    pub fn progress(&self) -> String {
+        let percent = match self.size {
+            0 => 100,
+            size => self.received.saturating_mul(100) / size,
+        };
+        tf(
+            "downloads.progress",
+            &[
+                ("name", &self.name),
+                ("percent", &percent.min(100)),
+                ("received", &format_size(self.received)),
+                ("size", &format_size(self.size)),
+            ],
+        )
+    }
+}
+
+/// What the task running a download reports.
+pub enum DownloadUpdate {
+    Progress {
+        id: u64,
+        received: u64,
+    },
+    /// The download is over, with where the file was written to or why it failed.
+    Finished {
+        id: u64,
+        result: Result<PathBuf, String>,
+    },
+}
+
+/// The downloads of all sessions.
+pub struct Downloads {
+    /// Where files are written to.
+    directory: PathBuf,
+    active: Vec<Download>,
+    next_id: u64,
+    tx: UnboundedSender<DownloadUpdate>,
+    rx: UnboundedReceiver<DownloadUpdate>,
+}
+
+impl Downloads {
+    pub fn new(directory: PathBuf) -> Self {
+        let (tx, rx) = unbounded_channel();
+        Self {
+            directory,
+            active: Vec::new(),
+            next_id: 0,
+            tx,
+            rx,
+        }
+    }
+
+    /// The downloads in progress, the oldest first.
+    pub fn active(&self) -> &[Download] {
+        &self.active
+    }
+
+    /// Starts downloading the attachment into the download directory, under the name it was uploaded with. The
+    /// file is created right away, so downloads of files with the same name do not overwrite each other.
+    ///
+    /// # Errors
+    ///
+    /// Returns why the file could not be created.
+    pub fn start(
+        &mut self,
+        window: &str,
+        client: Arc<Client>,
+        attachment: &Attachment,
+    ) -> io::Result<()> {
+        // Only the name is used, should the server have left directories in it
+        let name = Path::new(&attachment.name)
+            .file_name()
+            .map_or_else(|| "download".into(), |name| name.to_string_lossy());
+        fs::create_dir_all(&self.directory)?;
+        let (path, file) = create_file(&self.directory, &name)?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.active.push(Download {
+            id,
+            window: window.to_string(),
+            name: attachment.name.clone(),
+            received: 0,
+            size: u64::try_from(attachment.size).unwrap_or_default(),
+        });
+        let tx = self.tx.clone();
+        let attachment_id = attachment.id;
+        tokio::spawn(async move {
+            let file = File::from_std(file);
+            let result = download(&client, attachment_id, file, id, &tx).await;
+            let result = match result {
+                Ok(()) => Ok(path),
+                Err(error) => {
+                    // A partial file is of no use
+                    if let Err(e) = tokio::fs::remove_file(&path).await {
+                        warn!(path = %path.display(), error = ?e, "Could not remove a failed download");
+                    }
+                    Err(error)
+                }
+            };
+            let _ = tx.send(DownloadUpdate::Finished { id, result });
+        });
+        Ok(())
+    }
+
+    /// Waits for the next update of a download.
+    pub async fn next(&mut self) -> DownloadUpdate {
+        match self.rx.recv().await {
+            Some(update) => update,
+            // Never happens, as the sender is kept alongside
+            None => future::pending().await,
+        }
+    }
+
+    /// Applies the update. Returns the download along with its outcome once it is over.
+    pub fn update(
+        &mut self,
+        update: DownloadUpdate,
+    ) -> Option<(Download, Result<PathBuf, String>)> {
+        match update {
+            DownloadUpdate::Progress { id, received } => {
+                if let Some(download) = self.active.iter_mut().find(|download| download.id == id) {
+                    download.received = received;
+                }
+                None
+            }
+            DownloadUpdate::Finished { id, result } => {
+                let index = self.active.iter().position(|download| download.id == id)?;
+                Some((self.active.remove(index), result))
+            }
+        }
+    }
+}
+
+/// Downloads the attachment into the file, reporting the progress now and then.
+async fn download(
+    client: &Client,
+    attachment_id: i32,
+    mut file: File,
+    id: u64,
+    tx: &UnboundedSender<DownloadUpdate>,
+) -> Result<(), String> {
+    let mut download = client
+        .download_attachment(attachment_id)
+        .await
+        .map_err(|e| e.localized())?;
+    let mut reported = Instant::now();
+    while let Some(chunk) = download.next().await.map_err(|e| e.localized())? {
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            let received = download.received;
+            let _ = tx.send(DownloadUpdate::Progress { id, received });
+        }
+    }
+    file.flush().await.map_err(|e| e.to_string())
+}
+
+/// Creates a new file with that name in the directory, numbered if a file with the name exists already.
+fn create_file(directory: &Path, name: &str) -> io::Result<(PathBuf, fs::File)> {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (name, String::new()),
+    };
+    let mut number = 0;
+    loop {
+        let path = match number {
+            0 => directory.join(name),
+            number => directory.join(format!("{stem} ({number}){extension}")),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Formats a file size in bytes for people to read.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
@@ -0,0 +1,80 @@
+//! The errors of the client library in the locale selected for the ui.
+use chat_client::{AddressProblem, ConnectionFailure, Error};
+
+use crate::{
+    downloads::format_size,
+    i18n::{t, tf},
+};
+
+/// Gives the message of an error in the locale selected for the ui.
+pub trait Localized {
+    fn localized(&self) -> String;
+}
+
+impl Localized for Error {
+    fn localized(&self) -> String {
+        match self {
+            Error::ClientCreationFailed(_) => t("error.client_creation_failed").into(),
+            Error::InvalidHeader(header) => tf("error.invalid_header", &[("header", header)]),
+            Error::EventSourceCreationFailed(_) => t("error.event_source_creation_failed").into(),
+            Error::UnexpectedStatusCode { code, endpoint } => tf(
+                "error.unexpected_status",
+                &[("code", code), ("endpoint", endpoint)],
+            ),
+            Error::InvalidRespone(_) => t("error.invalid_response").into(),
+            Error::Generic(_) => t("error.generic").into(),
+            Error::ConnectionFailure(_) => match self.connection_failure() {
+                Some(ConnectionFailure::Refused) => t("error.connection_refused").into(),
+                Some(ConnectionFailure::UnknownHost) => t("error.unknown_host").into(),
+                _ => t("error.connection_failure").into(),
+            },
+            Error::TimedOut => t("error.timed_out").into(),
+            Error::Cancelled => t("error.cancelled").into(),
+            Error::RateLimited => t("error.rate_limited").into(),
+            Error::QuotaExceeded(_) => t("error.quota_exceeded").into(),
+            Error::SlowMode(_) => t("error.slow_mode").into(),
+            Error::DuplicateMessage => t("error.duplicate_message").into(),
+            Error::FileTooLarge(Some(max)) => {
+                tf("error.file_too_large_max", &[("size", &format_size(*max))])
+            }
+            Error::FileTooLarge(None) => t("error.file_too_large").into(),
+            Error::PermissionDenied => t("error.permission_denied").into(),
+            Error::FileTypeNotAllowed(Some(mime)) => {
+                tf("error.file_type_not_allowed_named", &[("type", mime)])
+            }
+            Error::FileTypeNotAllowed(None) => t("error.file_type_not_allowed").into(),
+            Error::NotAuthorized => t("error.not_authorized").into(),
+            Error::LoginFailed => t("error.login_failed").into(),
+            Error::AccountBanned => t("error.account_banned").into(),
+            Error::DeserializingFailed(_)
+            | Error::InvalidJson(_)
+            | Error::InvalidMsgpack(_)
+            | Error::DecompressionFailed(_) => t("error.invalid_data").into(),
+            Error::UsernameInUse => t("error.username_in_use").into(),
+            Error::TermsNotAccepted => t("error.terms_not_accepted").into(),
+            Error::InviteInvalid => t("error.invite_invalid").into(),
+            // Messages from the server are shown as the server sent them
+            Error::Api(error) => error.message.clone(),
+            Error::InvalidAddress(problem) => match problem {
+                AddressProblem::Empty => t("error.address_empty").into(),
+                AddressProblem::UnsupportedScheme(scheme) => {
+                    tf("error.address_scheme", &[("scheme", scheme)])
+                }
+                AddressProblem::MissingHost => t("error.address_host").into(),
+                AddressProblem::InvalidHost => t("error.address_invalid_host").into(),
+                AddressProblem::InvalidPort(port) => tf("error.address_port", &[("port", port)]),
+                AddressProblem::HasPath => t("error.address_path").into(),
+            },
+            Error::NotAChatServer => t("error.not_a_chat_server").into(),
+            Error::Replaying => t("error.replaying").into(),
+            Error::OnionWithoutProxy => t("error.onion_without_proxy").into(),
+        }
+    }
+}
+
+/// Returns the message of an error in the locale selected for the ui, if it is one of the client errors.
+pub fn localize(report: &eyre::Report) -> String {
+    report
+        .downcast_ref::<Error>()
+        .map_or_else(|| report.to_string(), Error::localized)
+}
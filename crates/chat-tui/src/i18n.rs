@@ -0,0 +1,194 @@
+use std::{collections::HashMap, fmt::Display, fs, io::ErrorKind, path::Path, sync::OnceLock};
+
+use eyre::{eyre, Result, WrapErr};
+
+/// The directory translations are loaded from, relative to the working directory. Files in it take precedence
+/// over the translations built into the client.
+pub const LOCALE_DIR: &str = "locales";
+
+/// The locale used for messages that are missing in the selected one.
+const FALLBACK_LOCALE: &str = "en";
+
+/// The translations that are built into the client.
+const BUILTIN: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.toml")),
+    ("de", include_str!("../locales/de.toml")),
+];
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// The messages of the selected locale, with the fallback locale for the ones it does not have.
+#[derive(Debug)]
+struct Catalog {
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Catalog {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.messages
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(String::as_str)
+    }
+}
+
+/// A piece of a message, as returned by ``parts``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part<'a> {
+    Text(&'a str),
+    /// A name in braces, which is replaced by a value when the message is formatted.
+    Placeholder(&'a str),
+}
+
+/// Selects the locale messages are shown in. If no locale is given, it is taken from the environment, using
+/// English if the environment asks for a language there is no translation for.
+///
+/// # Errors
+///
+/// This function will return an error if the given locale does not exist or its messages could not be read.
+pub fn init(locale: Option<&str>) -> Result<()> {
+    let (locale, explicit) = match locale {
+        Some(locale) => (locale.to_string(), true),
+        None => (locale_from_env(), false),
+    };
+
+    let fallback = load(FALLBACK_LOCALE)?.ok_or_else(|| eyre!("Missing the English messages"))?;
+    let messages = match load(&locale)? {
+        Some(messages) => messages,
+        None if explicit => return Err(eyre!("There are no messages for the locale {locale}")),
+        None => HashMap::new(),
+    };
+
+    // If messages were already used, they stay in the locale they were first shown in
+    let _ = CATALOG.set(Catalog { messages, fallback });
+    Ok(())
+}
+
+/// Returns the message for the key in the selected locale. Unknown keys are returned as they are, so a missing
+/// message is noticeable without breaking the ui.
+pub fn t(key: &'static str) -> &'static str {
+    catalog().get(key).unwrap_or(key)
+}
+
+/// Returns the message for the key, with its placeholders replaced by the matching arguments.
+pub fn tf(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    parts(key)
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => text.to_string(),
+            Part::Placeholder(name) => args
+                .iter()
+                .find(|(arg, _)| *arg == name)
+                .map_or_else(|| format!("{{{name}}}"), |(_, value)| value.to_string()),
+        })
+        .collect()
+}
+
+/// Splits the message for the key into text and placeholders, for when placeholders are shown differently
+/// than the text around them.
+pub fn parts(key: &'static str) -> Vec<Part<'static>> {
+    let mut parts = Vec::new();
+    let mut rest = t(key);
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        parts.push(Part::Placeholder(&rest[start + 1..start + length]));
+        rest = &rest[start + length + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    parts
+}
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| {
+        let fallback = parse(builtin(FALLBACK_LOCALE).unwrap_or_default()).unwrap_or_default();
+        Catalog {
+            messages: HashMap::new(),
+            fallback,
+        }
+    })
+}
+
+/// Reads the language from the variables the C library uses, turning values like ``de_DE.UTF-8`` into ``de``.
+fn locale_from_env() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| {
+            let language = value.split(['_', '.', '@']).next().unwrap_or_default();
+            match language {
+                "" | "C" | "POSIX" => None,
+                language => Some(language.to_lowercase()),
+            }
+        })
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string())
+}
+
+/// Loads the messages of a locale, preferring a file in ``LOCALE_DIR`` over the built in translation.
+fn load(locale: &str) -> Result<Option<HashMap<String, String>>> {
+    // Locales are used in a path, so they must not be able to point anywhere else
+    if !locale
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Ok(None);
+    }
+
+    let path = Path::new(LOCALE_DIR).join(format!("{locale}.toml"));
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => match builtin(locale) {
+            Some(content) => content.to_string(),
+            None => return Ok(None),
+        },
+        Err(e) => return Err(e).wrap_err_with(|| format!("Could not read {}", path.display())),
+    };
+
+    parse(&content)
+        .map(Some)
+        .wrap_err_with(|| format!("Could not parse the messages for the locale {locale}"))
+}
+
+fn builtin(locale: &str) -> Option<&'static str> {
+    BUILTIN
+        .iter()
+        .find(|(name, _)| *name == locale)
+        .map(|(_, content)| *content)
+}
+
+/// Parses a message file, turning nested tables into dotted keys like ``login.title``.
+fn parse(content: &str) -> Result<HashMap<String, String>> {
+    fn flatten(
+        prefix: &str,
+        table: toml::Table,
+        messages: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        for (key, value) in table {
+            let key = if prefix.is_empty() {
+                key
+            } else {
+                format!("{prefix}.{key}")
+            };
+            match value {
+                toml::Value::String(message) => {
+                    messages.insert(key, message);
+                }
+                toml::Value::Table(table) => flatten(&key, table, messages)?,
+                _ => return Err(eyre!("The message {key} is not a string")),
+            }
+        }
+        Ok(())
+    }
+
+    let mut messages = HashMap::new();
+    flatten("", toml::from_str(content)?, &mut messages)?;
+    Ok(messages)
+}
@@ -0,0 +1,246 @@
+use std::{net::TcpListener, sync::Arc};
+
+use chat_client::{parse_address, ChatTransport, Client, Error, MessageStream, StreamEvent};
+use chat_core::{
+    models::{Message, Motd, User},
+    paths::Paths,
+    protocol::{
+        ApiError, ChannelNotifications, Conversation, Draft, ErrorCode, Feature, InitialSync,
+        MessageContext, MessageFilter, Overview, PageRequest, Presence, ReadReceipt,
+    },
+};
+use chat_server::{AppError, ChatApp, DbError};
+use chrono::{DateTime, Utc};
+use eyre::{eyre, Result};
+use rocket::{config::LogLevel, fairing::AdHoc, futures::lock::Mutex};
+use tokio::sync::{mpsc::Receiver, oneshot};
+use tracing::warn;
+
+use crate::{recording::SessionRecording, session::SessionData};
+
+/// The database used by the embedded server, in the data directory.
+pub const LOCAL_DATABASE: &str = "local.db";
+
+/// The server running inside the client process in local mode.
+pub struct LocalServer {
+    address: String,
+    /// Shared with the server, so sessions can read from it without going through HTTP.
+    app: Arc<Mutex<ChatApp>>,
+}
+
+impl LocalServer {
+    /// Returns the address the server accepts connections on.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Starts a session of the client. If the client is logged in to this server, the session reads the chat
+    /// straight from it. Anything that other users need to know about, like the events, the presence and read
+    /// markers, still goes through the server.
+    pub fn start_session(
+        &self,
+        client: Client,
+        recording: Option<SessionRecording>,
+    ) -> Result<SessionData> {
+        if parse_address(&self.address).ok().as_deref() != Some(client.address()) {
+            return SessionData::start(client, recording);
+        }
+        let client = Arc::new(client);
+        let transport = LocalTransport {
+            app: Arc::clone(&self.app),
+            client: Arc::clone(&client),
+        };
+        SessionData::start_with(Arc::new(transport), client, recording)
+    }
+}
+
+/// Starts a server inside the client process, for using the chat without a separate server. Returns the server
+/// once it accepts connections.
+///
+/// # Errors
+///
+/// This function will return an error if no free port could be found or the server failed to start.
+pub async fn start_server(paths: &Paths) -> Result<LocalServer> {
+    let port = free_port()?;
+    let figment = rocket::Config::figment()
+        .merge((rocket::Config::ADDRESS, "127.0.0.1"))
+        .merge((rocket::Config::PORT, port))
+        // Anything the server prints would end up in the middle of the ui
+        .merge((rocket::Config::LOG_LEVEL, LogLevel::Off))
+        .merge((rocket::Config::CLI_COLORS, false))
+        .merge(("shutdown.ctrlc", false))
+        .merge(("chat.database", LOCAL_DATABASE));
+
+    let (ready_sender, ready) = oneshot::channel();
+    let rocket = chat_server::server::rocket(figment, paths).attach(AdHoc::on_liftoff(
+        "Local server ready",
+        move |_| {
+            Box::pin(async move {
+                let _ = ready_sender.send(());
+            })
+        },
+    ));
+    let app = chat_server::server::default_app(&rocket)
+        .ok_or_else(|| eyre!("The local server has no chat"))?;
+    let server = tokio::spawn(rocket.launch());
+
+    if ready.await.is_err() {
+        // The sender only gets dropped without sending if the server stopped before it was ready
+        return match server.await? {
+            Ok(_) => Err(eyre!("The local server stopped unexpectedly")),
+            Err(e) => Err(eyre!("Could not start the local server: {e}")),
+        };
+    }
+
+    Ok(LocalServer {
+        address: format!("127.0.0.1:{port}"),
+        app,
+    })
+}
+
+/// Asks the operating system for a port that is currently not in use.
+fn free_port() -> Result<u16> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Answers the requests of a session from the chat of the local server. Only what has to reach the other users goes
+/// through the client.
+struct LocalTransport {
+    app: Arc<Mutex<ChatApp>>,
+    client: Arc<Client>,
+}
+
+impl ChatTransport for LocalTransport {
+    fn user_id(&self) -> i32 {
+        self.client.user_id()
+    }
+
+    fn supports(&self, feature: Feature) -> bool {
+        self.client.supports(feature)
+    }
+
+    fn server_now(&self) -> DateTime<Utc> {
+        // Both run on the same clock
+        Utc::now()
+    }
+
+    fn get_events(&self) -> Result<Receiver<StreamEvent>, Error> {
+        self.client.get_events()
+    }
+
+    async fn get_initial_sync(&self) -> Result<InitialSync, Error> {
+        let mut app = self.app.lock().await;
+        app.initial_sync(self.user_id()).map_err(api_error)
+    }
+
+    async fn get_overview(&self) -> Result<Overview, Error> {
+        let mut app = self.app.lock().await;
+        app.get_overview(self.user_id(), &PageRequest::default())
+            .map_err(api_error)
+    }
+
+    async fn get_messages_of(
+        &self,
+        conversation: Conversation,
+        filter: MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, Error> {
+        let userid = self.user_id();
+        let mut app = self.app.lock().await;
+        match conversation {
+            Conversation::Chat => app.get_messages(userid, &filter, limit),
+            Conversation::Channel(id) => app.get_channel_messages(userid, id, &filter, limit),
+            Conversation::Direct(other) => app.get_direct_messages(userid, other, &filter, limit),
+        }
+        .map_err(api_error)
+    }
+
+    async fn stream_messages(&self, filter: MessageFilter) -> Result<MessageStream, Error> {
+        self.client.stream_messages(filter).await
+    }
+
+    async fn get_message_context(
+        &self,
+        message_id: i32,
+        around: Option<u32>,
+    ) -> Result<MessageContext, Error> {
+        let mut app = self.app.lock().await;
+        app.get_message_context(self.user_id(), message_id, around)
+            .map_err(api_error)
+    }
+
+    async fn get_all_users(&self) -> Result<Vec<User>, Error> {
+        let mut app = self.app.lock().await;
+        app.get_all_users().map_err(api_error)
+    }
+
+    async fn get_drafts(&self) -> Result<Vec<Draft>, Error> {
+        let mut app = self.app.lock().await;
+        app.get_drafts(self.user_id()).map_err(api_error)
+    }
+
+    async fn save_draft(&self, conversation: Conversation, text: &str) -> Result<(), Error> {
+        let mut app = self.app.lock().await;
+        app.save_draft(self.user_id(), conversation, text)
+            .map_err(api_error)
+    }
+
+    async fn get_presence(&self) -> Result<Presence, Error> {
+        // The server keeps track of who is online, not the chat
+        self.client.get_presence().await
+    }
+
+    async fn get_motd(&self) -> Result<Motd, Error> {
+        let mut app = self.app.lock().await;
+        app.get_motd().map_err(api_error)
+    }
+
+    async fn mark_read(&self, conversation: Conversation, message_id: i32) -> Result<(), Error> {
+        // The server tells the others that the user read the conversation
+        self.client.mark_read(conversation, message_id).await
+    }
+
+    async fn get_read_receipts(
+        &self,
+        conversation: Conversation,
+    ) -> Result<Vec<ReadReceipt>, Error> {
+        let mut app = self.app.lock().await;
+        app.get_read_receipts(self.user_id(), conversation)
+            .map_err(api_error)
+    }
+
+    async fn get_channel_notifications(&self) -> Result<Vec<ChannelNotifications>, Error> {
+        let mut app = self.app.lock().await;
+        app.get_channel_notifications(self.user_id())
+            .map_err(api_error)
+    }
+}
+
+/// Turns the error into the one the server would have answered with, so the session handles both the same.
+fn api_error(error: AppError) -> Error {
+    let (code, message) = match error {
+        AppError::PermissionDenied => return Error::PermissionDenied,
+        AppError::NotAMember => (
+            ErrorCode::NotAMember,
+            "Only members of the channel can read and write in it.",
+        ),
+        AppError::DatabaseError(DbError::ChannelNotFound) => {
+            (ErrorCode::NotFound, "The channel does not exist.")
+        }
+        AppError::DatabaseError(DbError::UserNotFound) => {
+            (ErrorCode::NotFound, "The user does not exist.")
+        }
+        AppError::DatabaseError(DbError::MessageNotFound) => {
+            (ErrorCode::NotFound, "The message does not exist.")
+        }
+        e => {
+            warn!(error = %e, "The local server failed to answer a request");
+            (
+                ErrorCode::InternalError,
+                "The server encountered an error whilst handling the request.",
+            )
+        }
+    };
+    Error::Api(ApiError::new(code, message))
+}
@@ -0,0 +1,109 @@
+//! Logging to a file in the data directory, as the terminal belongs to the ui. The file is rotated once it grows
+//! too large, keeping a few of the previous ones.
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use eyre::{Result, WrapErr};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// The directory the log files are written to, inside the data directory.
+const LOG_DIR: &str = "logs";
+const LOG_FILE: &str = "client.log";
+/// The log file is rotated once it would grow beyond this many bytes.
+const MAX_SIZE: u64 = 5 * 1024 * 1024;
+/// How many of the rotated log files are kept, numbered from the newest to the oldest.
+const KEEP: usize = 3;
+
+/// Returns where the log is written to, for the given data directory.
+pub fn log_file(data: &Path) -> PathBuf {
+    data.join(LOG_DIR).join(LOG_FILE)
+}
+
+/// Starts writing the log to the data directory. Only events the user is told about anyway and a few more are
+/// logged, unless ``verbose`` is set, which logs every request too.
+///
+/// # Errors
+///
+/// This function will return an error if the log file could not be opened.
+pub fn init(data: &Path, verbose: bool) -> Result<()> {
+    let dir = data.join(LOG_DIR);
+    fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("Could not create the log directory {}", dir.display()))?;
+    let path = log_file(data);
+    let file = RotatingFile::open(path.clone())
+        .wrap_err_with(|| format!("Could not open the log file {}", path.display()))?;
+    let level = if verbose {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+    // The other libraries log every connection when debugging, which would bury what the client logs
+    let filter = Targets::new()
+        .with_target(env!("CARGO_BIN_NAME"), level)
+        .with_target("chat_client", level)
+        .with_default(LevelFilter::WARN);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(Mutex::new(file))
+                .with_ansi(false),
+        )
+        .with(filter)
+        .init();
+    Ok(())
+}
+
+/// A log file that is moved aside once it reaches ``MAX_SIZE``. ``client.log`` becomes ``client.log.1``, which
+/// becomes ``client.log.2`` and so on, dropping the oldest one.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size })
+    }
+
+    fn rotated(&self, number: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{number}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for number in (1..KEEP).rev() {
+            let from = self.rotated(number);
+            if from.exists() {
+                fs::rename(from, self.rotated(number + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))?;
+        *self = Self::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > MAX_SIZE {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
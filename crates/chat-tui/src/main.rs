@@ -0,0 +1,1071 @@
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    io::{self},
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chat_client::ShutdownHandler;
+use chat_core::paths::Paths;
+use config::{ClientConfig, CONFIG_FILE};
+use crash::TerminalGuard;
+use downloads::{DownloadUpdate, Downloads};
+use notifications::{Notifications, Severity};
+use recording::{Recorder, ReplayedSession};
+
+use chat_client::ClientBuilder;
+use crossterm::{
+    event::{
+        EnableMouseCapture, Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+    terminal::{enable_raw_mode, EnterAlternateScreen},
+};
+use errors::Localized;
+use eyre::{eyre, Result};
+use futures_util::{future::join_all, StreamExt};
+use i18n::{t, tf, Part};
+use screens::{notification_text, severity_style, Window};
+use session::{SessionData, SessionUpdate};
+use spelling::SpellChecker;
+use time_format::TimeFormat;
+use tokio::{
+    sync::{mpsc::Receiver, oneshot},
+    time::{Instant, MissedTickBehavior},
+};
+use tracing::info;
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Tabs, Wrap},
+    Frame, Terminal,
+};
+use tui_support::{ActiveVec, Theme};
+use updates::UpdateNotice;
+
+mod config;
+mod crash;
+mod downloads;
+mod errors;
+mod i18n;
+#[cfg(feature = "local")]
+mod local;
+#[cfg(feature = "local")]
+use local::LocalServer;
+mod logging;
+mod migrations;
+mod notifications;
+mod recording;
+mod screens;
+#[cfg(test)]
+mod scripted;
+mod session;
+mod spelling;
+mod time_format;
+mod tui_support;
+mod updates;
+mod welcome;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let paths = Paths::resolve(&args)?;
+    paths.create()?;
+    crash::install_panic_hook(paths.data.clone());
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    logging::init(&paths.data, verbose)?;
+    info!(
+        version = env!("CARGO_PKG_VERSION"),
+        verbose, "Client started"
+    );
+    if Path::new(CONFIG_FILE).exists() && !ClientConfig::exists(&paths.config) {
+        eprintln!(
+            "There is a {CONFIG_FILE} in the working directory, but the configuration is now read from {}. Move \
+             it there or start the client with --config-dir . to keep using it.",
+            paths.config.display()
+        );
+    }
+    let first_run = !ClientConfig::exists(&paths.config);
+    let config = ClientConfig::load(&paths.config)?;
+    i18n::init(config.ui.locale.as_deref())?;
+    let time_format = TimeFormat::from_config(&config.ui)?;
+    let spelling = SpellChecker::from_config(&config.spellcheck, &paths.data)?;
+    // In local mode, the client brings its own server and connects to it
+    let local = if args.iter().any(|arg| arg == "--local") {
+        Some(start_local_server(&paths).await?)
+    } else {
+        None
+    };
+    let recorder = match path_option(&args, "--record")? {
+        Some(path) => {
+            info!(path = %path.display(), "Recording sessions");
+            Some(Arc::new(Recorder::create(&path)?))
+        }
+        None => None,
+    };
+    let replay = match path_option(&args, "--replay")? {
+        Some(path) => {
+            info!(path = %path.display(), "Replaying sessions");
+            Some(recording::load(&path)?)
+        }
+        None => None,
+    };
+
+    // setup terminal
+    enable_raw_mode()?;
+    let guard = TerminalGuard;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    if config.ui.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // create app and run it
+    let (mut app, mut shutdown_receiver) = App::new(
+        config,
+        &paths,
+        time_format,
+        spelling,
+        local,
+        recorder,
+        first_run,
+    );
+    if let Some(sessions) = replay {
+        app.replay(sessions)?;
+    }
+    let app_task = tokio::spawn(async move {
+        let result = run_app(&mut terminal, &mut app).await;
+        // Only cancel once the app is done, so the logouts on exit are not cancelled too early
+        app.shutdown.cancel();
+        drop(guard);
+
+        result
+    });
+    let app_result = app_task.await?;
+    shutdown_receiver.recv().await;
+    app_result
+}
+
+/// Returns the path following the option, or ``None`` if the option is not given.
+fn path_option(args: &[String], option: &str) -> Result<Option<PathBuf>> {
+    let Some(index) = args.iter().position(|arg| arg == option) else {
+        return Ok(None);
+    };
+    args.get(index + 1)
+        .map(|path| Some(PathBuf::from(path)))
+        .ok_or_else(|| eyre!("{option} needs a path"))
+}
+
+/// Starts the embedded server of local mode.
+#[cfg(feature = "local")]
+async fn start_local_server(paths: &Paths) -> Result<LocalServer> {
+    local::start_server(paths).await
+}
+
+/// Local mode needs the server, which is left out of clients built without the ``local`` feature.
+#[cfg(not(feature = "local"))]
+async fn start_local_server(_paths: &Paths) -> Result<LocalServer> {
+    Err(eyre!(
+        "This client was built without local mode. Build it with the local feature to use --local"
+    ))
+}
+
+/// Stands in for the embedded server in clients built without local mode, which never have one.
+#[cfg(not(feature = "local"))]
+enum LocalServer {}
+
+#[cfg(not(feature = "local"))]
+impl LocalServer {
+    fn address(&self) -> &str {
+        match *self {}
+    }
+
+    fn start_session(
+        &self,
+        _client: chat_client::Client,
+        _recording: Option<recording::SessionRecording>,
+    ) -> Result<session::SessionData> {
+        match *self {}
+    }
+}
+
+/// How often the ui is checked for changes that are not caused by any input.
+const TICK: Duration = Duration::from_secs(1);
+
+/// What woke up the main loop.
+enum Input {
+    Terminal(Option<io::Result<Event>>),
+    /// An update from the background task of the session with the given username. ``None`` once the task ended.
+    Session(String, Option<SessionUpdate>),
+    Download(DownloadUpdate),
+    Tick,
+}
+
+/// Main loop for running the app. The ui is only drawn again if something changed.
+async fn run_app<B>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()>
+where
+    B: Backend + std::io::Write,
+{
+    let mut terminal_events = EventStream::new();
+    let mut tick = tokio::time::interval(TICK);
+    // Handling an input can take a while, catching up on the missed ticks afterwards would be pointless
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut redraw = true;
+
+    loop {
+        redraw |= app.poll_update_check();
+        let area = ui_layout(terminal.size()?, app)[1];
+        if let Some(screen) = app.screens.get_active_mut() {
+            if let Some(session) = app.chat.logins.get_mut(&screen.title()) {
+                redraw |=
+                    screen.update(session, &app.chat.notifications, app.chat.time_format, area);
+            }
+        }
+        if redraw {
+            terminal.draw(|f| ui(f, app))?;
+            redraw = false;
+        }
+
+        let input = tokio::select! {
+            event = terminal_events.next() => Input::Terminal(event),
+            (username, update) = next_session_update(&mut app.chat.logins) => Input::Session(username, update),
+            update = app.chat.downloads.next() => Input::Download(update),
+            _ = tick.tick() => Input::Tick,
+        };
+        let event = match input {
+            Input::Terminal(Some(event)) => event?,
+            Input::Terminal(None) => break,
+            Input::Session(username, update) => {
+                if let Some(session) = app.chat.logins.get_mut(&username) {
+                    if session.update(&username, update, &mut app.chat.notifications)
+                        && app.mark_unread(&[username])
+                        && app.chat.config.ui.bell
+                    {
+                        let backend = terminal.backend_mut();
+                        backend.write_all(b"\x07")?;
+                        Backend::flush(backend)?;
+                    }
+                }
+                redraw = true;
+                continue;
+            }
+            Input::Download(update) => {
+                if let Some((download, result)) = app.chat.downloads.update(update) {
+                    let (severity, text) = match result {
+                        Ok(path) => (
+                            Severity::Info,
+                            tf("attachments.downloaded", &[("path", &path.display())]),
+                        ),
+                        Err(error) => (
+                            Severity::Error,
+                            tf("attachments.download_failed", &[("error", &error)]),
+                        ),
+                    };
+                    app.chat
+                        .notifications
+                        .push(severity, &download.window, text);
+                }
+                redraw = true;
+                continue;
+            }
+            // Only things changing with time, like status messages expiring, need to be checked
+            Input::Tick => continue,
+        };
+        redraw = true;
+
+        // Whilst asking whether to quit, nothing else can be done
+        if let Some(selected) = app.quit_dialog {
+            if let Event::Key(key) = event {
+                match key.code {
+                    KeyCode::Left | KeyCode::BackTab => {
+                        app.quit_dialog = Some(selected.prev());
+                    }
+                    KeyCode::Right | KeyCode::Tab => app.quit_dialog = Some(selected.next()),
+                    KeyCode::Esc => app.quit_dialog = None,
+                    KeyCode::Enter => match selected {
+                        QuitChoice::SendAndQuit => {
+                            // If anything could not be sent, stay so the user can see what failed
+                            if app.send_drafts().await {
+                                break;
+                            }
+                            app.quit_dialog = None;
+                        }
+                        QuitChoice::Discard => break,
+                        QuitChoice::Cancel => app.quit_dialog = None,
+                    },
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        if let Event::Mouse(mouse) = event {
+            if app.chat.config.ui.mouse {
+                app.handle_mouse(&mouse, terminal.size()?);
+            }
+        }
+        if let Event::Key(key) = event {
+            match key {
+                KeyEvent {
+                    code: KeyCode::Char('q'),
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: _,
+                    state: _,
+                } => {
+                    if app.drafts().is_empty() {
+                        break;
+                    }
+                    app.quit_dialog = Some(QuitChoice::Cancel);
+                }
+                KeyEvent {
+                    code: KeyCode::Char('n'),
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: _,
+                    state: _,
+                } => app.open_window(),
+                KeyEvent {
+                    code: KeyCode::Char('w'),
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: _,
+                    state: _,
+                } => app.close_window().await,
+                KeyEvent {
+                    code: KeyCode::Left,
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: _,
+                    state: _,
+                } => app.move_window(false),
+                KeyEvent {
+                    code: KeyCode::Right,
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: _,
+                    state: _,
+                } => app.move_window(true),
+                KeyEvent {
+                    code: KeyCode::Tab,
+                    modifiers: _,
+                    kind: _,
+                    state: _,
+                } => app.screens.next(),
+                KeyEvent {
+                    code: KeyCode::Char('e'),
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: _,
+                    state: _,
+                } => {
+                    app.show_notifications = !app.show_notifications;
+                    app.notification_scroll = 0;
+                }
+                KeyEvent {
+                    code: KeyCode::PageUp,
+                    modifiers: _,
+                    kind: _,
+                    state: _,
+                } if app.show_notifications => {
+                    let max_scroll = app.chat.notifications.len().saturating_sub(1);
+                    app.notification_scroll = (app.notification_scroll + 1).min(max_scroll);
+                }
+                KeyEvent {
+                    code: KeyCode::PageDown,
+                    modifiers: _,
+                    kind: _,
+                    state: _,
+                } if app.show_notifications => {
+                    app.notification_scroll = app.notification_scroll.saturating_sub(1);
+                }
+                KeyEvent {
+                    code: KeyCode::BackTab,
+                    modifiers: _,
+                    kind: _,
+                    state: _,
+                } => app.screens.prev(),
+                _ => {
+                    if let Some(screen) = app.screens.get_active_mut() {
+                        screen.handle_input(&mut app.chat, &event).await;
+                    }
+                }
+            }
+        };
+
+        // Terminals supporting OSC 52 put the text on the system clipboard, even over SSH
+        if let Some(text) = app.chat.clipboard.take() {
+            let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+            let backend = terminal.backend_mut();
+            backend.write_all(sequence.as_bytes())?;
+            Backend::flush(backend)?;
+        }
+    }
+
+    if app.chat.logins.is_empty() {
+        return Ok(());
+    }
+    let theme = app.chat.theme;
+    terminal.draw(|f| f.render_widget(logout_screen(&[], theme), f.size()))?;
+    let failures = app.logout_all().await;
+    if !failures.is_empty() {
+        terminal.draw(|f| f.render_widget(logout_screen(&failures, theme), f.size()))?;
+        // Keep the failures on screen until they were read
+        while let Some(event) = terminal_events.next().await {
+            if let Event::Key(key) = event? {
+                if key.kind == KeyEventKind::Press {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits for the next update from the background task of any session, returning the username of the session
+/// with it.
+async fn next_session_update(
+    logins: &mut HashMap<String, SessionData>,
+) -> (String, Option<SessionUpdate>) {
+    poll_fn(|cx| {
+        for (username, session) in logins.iter_mut() {
+            if let Some(updates) = &mut session.updates {
+                if let Poll::Ready(update) = updates.poll_recv(cx) {
+                    return Poll::Ready((username.clone(), update));
+                }
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// How long logging out on exit may take, before the remaining requests are cancelled.
+const LOGOUT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Creates the screen shown whilst logging out on exit. Once done, it lists the sessions that could not be
+/// logged out, if any.
+fn logout_screen<'a>(failures: &[(String, String)], theme: Theme) -> Paragraph<'a> {
+    let mut lines = Vec::new();
+    if failures.is_empty() {
+        lines.push(Spans::from(t("logout.in_progress")));
+    } else {
+        lines.push(Spans::from(Span::styled(
+            t("logout.failed"),
+            severity_style(Severity::Error, theme),
+        )));
+        lines.push(Spans::default());
+        lines.extend(
+            failures
+                .iter()
+                .map(|(username, error)| Spans::from(format!("{username}: {error}"))),
+        );
+        lines.push(Spans::default());
+        lines.push(Spans::from(t("logout.exit_hint")));
+    }
+
+    Paragraph::new(lines)
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL))
+}
+
+/// Update the ui.
+fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
+    let chunks = ui_layout(f.size(), app);
+
+    let theme = app.chat.theme;
+    let titles = &app.tab_titles();
+    let tabs = Tabs::new(tab_titles_to_spans(titles, theme));
+    f.render_widget(tabs, chunks[0]);
+
+    if let Some(window) = app.screens.get_active() {
+        f.render_widget(window, chunks[1]);
+        if let Some((x, y)) = window.cursor(chunks[1]) {
+            if app.quit_dialog.is_none() {
+                f.set_cursor(x, y);
+            }
+        }
+    }
+
+    if app.show_notifications {
+        f.render_widget(
+            notification_list(
+                &app.chat.notifications,
+                app.notification_scroll,
+                chunks[2],
+                theme,
+                app.chat.time_format,
+            ),
+            chunks[2],
+        );
+    }
+
+    if let Some(notice) = &app.chat.update_notice {
+        let severity = match notice {
+            UpdateNotice::Required { .. } => Severity::Error,
+            UpdateNotice::Available(_) => Severity::Info,
+        };
+        let banner = Paragraph::new(Span::styled(notice.text(), severity_style(severity, theme)));
+        f.render_widget(banner, chunks[3]);
+    }
+
+    f.render_widget(download_list(&app.chat.downloads), chunks[4]);
+
+    f.render_widget(help_text(theme), chunks[5]);
+
+    if let Some(selected) = app.quit_dialog {
+        let area = dialog_area(f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(quit_dialog(&app.drafts(), selected, theme), area);
+    }
+}
+
+/// Returns the area dialogs are shown in, in the middle of the screen.
+fn dialog_area(size: Rect) -> Rect {
+    let width = size.width.min(60);
+    let height = size.height.min(7);
+    Rect::new(
+        size.x + (size.width - width) / 2,
+        size.y + (size.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Creates the dialog asking what to do with the unsent messages in the given windows before quitting.
+fn quit_dialog<'a>(drafts: &[String], selected: QuitChoice, theme: Theme) -> Paragraph<'a> {
+    let options: Vec<Span> = QuitChoice::ALL
+        .iter()
+        .flat_map(|&choice| {
+            let focused = choice == selected;
+            [
+                Span::raw("  "),
+                Span::styled(
+                    format!("{}{}", theme.marker(focused), choice.text()),
+                    theme.focus(focused),
+                ),
+            ]
+        })
+        .collect();
+
+    Paragraph::new(vec![
+        Spans::from(tf("quit.drafts", &[("windows", &drafts.join(", "))])),
+        Spans::default(),
+        Spans::from(options),
+    ])
+    .alignment(Alignment::Center)
+    .wrap(Wrap { trim: true })
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(t("quit.title")),
+    )
+}
+
+/// What to do with unsent messages when quitting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuitChoice {
+    SendAndQuit,
+    Discard,
+    Cancel,
+}
+
+impl QuitChoice {
+    /// All choices, in the order they are shown in.
+    const ALL: [QuitChoice; 3] = [Self::SendAndQuit, Self::Discard, Self::Cancel];
+
+    fn text(self) -> &'static str {
+        match self {
+            QuitChoice::SendAndQuit => t("quit.send"),
+            QuitChoice::Discard => t("quit.discard"),
+            QuitChoice::Cancel => t("quit.cancel"),
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            QuitChoice::SendAndQuit => QuitChoice::Discard,
+            QuitChoice::Discard | QuitChoice::Cancel => QuitChoice::Cancel,
+        }
+    }
+
+    fn prev(self) -> Self {
+        match self {
+            QuitChoice::SendAndQuit | QuitChoice::Discard => QuitChoice::SendAndQuit,
+            QuitChoice::Cancel => QuitChoice::Discard,
+        }
+    }
+}
+
+/// How many rows the notification area takes up, including its border.
+const NOTIFICATION_HEIGHT: u16 = 8;
+/// The most downloads shown at once. Any others are summed up in the last line.
+const MAX_DOWNLOAD_LINES: usize = 3;
+
+/// Splits the screen into the tab bar, the active window, the notification area and the help text. The
+/// notification area has no height, if it is not shown.
+fn ui_layout(size: Rect, app: &App) -> Vec<Rect> {
+    let notification_height = if app.show_notifications {
+        NOTIFICATION_HEIGHT
+    } else {
+        0
+    };
+    let banner_height = u16::from(app.chat.update_notice.is_some());
+    let downloads_height = app.chat.downloads.active().len().min(MAX_DOWNLOAD_LINES);
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(1),
+                Constraint::Min(9),
+                Constraint::Length(notification_height),
+                Constraint::Length(banner_height),
+                Constraint::Length(u16::try_from(downloads_height).unwrap_or_default()),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(size)
+}
+
+/// Creates a ``List`` with the progress of the downloads, one line per download. If there are more than fit, the
+/// last line tells how many more there are.
+fn download_list<'a>(downloads: &Downloads) -> List<'a> {
+    let active = downloads.active();
+    let mut lines: Vec<ListItem> = active
+        .iter()
+        .take(MAX_DOWNLOAD_LINES)
+        .map(|download| ListItem::new(download.progress()))
+        .collect();
+    if active.len() > MAX_DOWNLOAD_LINES {
+        let more = active.len() - MAX_DOWNLOAD_LINES + 1;
+        lines[MAX_DOWNLOAD_LINES - 1] = ListItem::new(tf("downloads.more", &[("count", &more)]));
+    }
+    List::new(lines)
+}
+
+/// Creates a ``List`` of the notifications fitting into the area, newest at the bottom. ``scroll`` skips
+/// that many of the newest notifications.
+fn notification_list<'a>(
+    notifications: &Notifications,
+    scroll: usize,
+    area: Rect,
+    theme: Theme,
+    time_format: TimeFormat,
+) -> List<'a> {
+    let count = usize::from(area.height.saturating_sub(2));
+    let mut items: Vec<ListItem> = notifications
+        .iter()
+        .rev()
+        .skip(scroll)
+        .take(count)
+        .map(|notification| {
+            let mut text = format!(
+                "{} [{}] {}",
+                time_format.time_with_seconds(notification.time),
+                notification.source,
+                notification_text(notification, theme)
+            );
+            if notification.count > 1 {
+                text.push_str(&format!(" (x{})", notification.count));
+            }
+            ListItem::new(Span::styled(
+                text,
+                severity_style(notification.severity, theme),
+            ))
+        })
+        .collect();
+    items.reverse();
+
+    List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(t("notifications.title")),
+    )
+}
+
+/// Creates a ``Paragraph`` holding the help text shown at the bottom.
+fn help_text<'a>(theme: Theme) -> Paragraph<'a> {
+    let normal = Style::default();
+    let highlight = theme.color(Color::Green);
+
+    // The key names are not translated, as they are printed on the keyboard
+    let spans: Vec<Span> = i18n::parts("help.text")
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => Span::styled(text, normal),
+            Part::Placeholder("quit") => Span::styled("Ctrl+q", highlight),
+            Part::Placeholder("switch") => Span::styled("Tab", highlight),
+            Part::Placeholder("new") => Span::styled("Ctrl+n", highlight),
+            Part::Placeholder("close") => Span::styled("Ctrl+w", highlight),
+            Part::Placeholder("notifications") => Span::styled("Ctrl+e", highlight),
+            Part::Placeholder(name) => Span::styled(name, normal),
+        })
+        .collect();
+
+    Paragraph::new(Spans::from(spans))
+}
+
+/// Converts instances of ``TabTitle`` to a collection of ``Spans``.
+fn tab_titles_to_spans(titles: &[TabTitle], theme: Theme) -> Vec<Spans<'_>> {
+    titles
+        .iter()
+        .map(|title| match title {
+            TabTitle::Active(text, accent) => Spans::from(Span::styled(text, theme.color(*accent))),
+            TabTitle::Inactive(text) => Spans::from(Span::styled(text, Style::default())),
+        })
+        .collect()
+}
+
+/// Holds the name and state of the title for the tabs. The active one is shown in the accent color of its server.
+enum TabTitle {
+    Active(String, Color),
+    Inactive(String),
+}
+
+impl TabTitle {
+    fn text(&self) -> &str {
+        match self {
+            TabTitle::Active(text, _) | TabTitle::Inactive(text) => text,
+        }
+    }
+}
+
+/// Finds the tab at the given column of the tab bar, mirroring how ``Tabs`` lays out its titles.
+fn tab_at(titles: &[TabTitle], column: u16) -> Option<usize> {
+    let mut x: u16 = 0;
+    for (index, title) in titles.iter().enumerate() {
+        let width = u16::try_from(Span::raw(title.text()).width()).unwrap_or(u16::MAX);
+        // Each title is padded by a space on both sides and followed by a divider
+        let end = x.saturating_add(width).saturating_add(2);
+        if (x..end).contains(&column) {
+            return Some(index);
+        }
+        x = end.saturating_add(1);
+    }
+
+    None
+}
+
+/// Holds the current state of the the app and ui.
+struct App {
+    chat: ChatData,
+    screens: ActiveVec<Window>,
+    /// Whether the notification area is shown.
+    show_notifications: bool,
+    /// How many of the newest notifications are scrolled past.
+    notification_scroll: usize,
+    /// The selected choice of the quit dialog, whilst it is shown.
+    quit_dialog: Option<QuitChoice>,
+    /// Receives the result of looking for a newer release, until it arrived.
+    update_check: Option<oneshot::Receiver<UpdateNotice>>,
+    shutdown: ShutdownHandler,
+}
+
+/// Holds the data relating to the current state of the application
+struct ChatData {
+    logins: HashMap<String, SessionData>,
+    config: ClientConfig,
+    theme: Theme,
+    time_format: TimeFormat,
+    /// Checks messages in the composer, if spell checking is turned on.
+    spelling: Option<SpellChecker>,
+    /// Recent events, like failed requests or lost connections.
+    notifications: Notifications,
+    /// The embedded server, if running in local mode.
+    local: Option<LocalServer>,
+    /// Where ``client.toml`` is written to.
+    config_dir: PathBuf,
+    /// Text to copy to the clipboard, written to the terminal after handling the input.
+    clipboard: Option<String>,
+    /// Records the updates of all sessions, if started with ``--record``.
+    recorder: Option<Arc<Recorder>>,
+    /// Where the log is written to.
+    log_file: PathBuf,
+    /// The attachments being downloaded.
+    downloads: Downloads,
+    /// Why the client should be updated, shown in a banner.
+    update_notice: Option<UpdateNotice>,
+    /// Handed to the clients, so their requests get cancelled on shutdown.
+    shutdown: ShutdownHandler,
+}
+
+impl ChatData {
+    /// Creates a new ``Window``, already pointed at the embedded server in local mode or the configured server.
+    fn new_window(&self) -> Window {
+        match self.local.as_ref().map(LocalServer::address).or(self
+            .config
+            .network
+            .server
+            .as_deref())
+        {
+            Some(address) => Window::with_address(address, self.theme),
+            None => Window::new(self.theme),
+        }
+    }
+
+    /// Shows the notice in the banner, unless a more important one is shown already.
+    fn notice_update(&mut self, notice: UpdateNotice) {
+        if self
+            .update_notice
+            .as_ref()
+            .is_none_or(|shown| !shown.outranks(&notice))
+        {
+            self.update_notice = Some(notice);
+        }
+    }
+}
+
+impl App {
+    /// Create a new instance of ``App``.
+    fn new(
+        config: ClientConfig,
+        paths: &Paths,
+        time_format: TimeFormat,
+        spelling: Option<SpellChecker>,
+        local: Option<LocalServer>,
+        recorder: Option<Arc<Recorder>>,
+        first_run: bool,
+    ) -> (Self, Receiver<()>) {
+        let (shutdown, receiver) = ShutdownHandler::new();
+
+        let chat = ChatData {
+            logins: HashMap::new(),
+            theme: Theme::new(!config.ui.no_color()),
+            time_format,
+            spelling,
+            config,
+            notifications: Notifications::default(),
+            local,
+            config_dir: paths.config.clone(),
+            clipboard: None,
+            recorder,
+            log_file: logging::log_file(&paths.data),
+            downloads: Downloads::new(
+                directories::UserDirs::new()
+                    .and_then(|dirs| dirs.download_dir().map(Path::to_path_buf))
+                    .unwrap_or_else(|| paths.data.join("downloads")),
+            ),
+            update_notice: None,
+            shutdown: shutdown.child(),
+        };
+        let update_check = updates::check(&chat.config.network, shutdown.child());
+
+        let mut screen: ActiveVec<Window> = ActiveVec::new();
+        // The first window guides through the configuration, if there is none yet
+        if first_run {
+            screen.push(Window::welcome(&chat));
+        } else {
+            screen.push(chat.new_window());
+        }
+
+        (
+            App {
+                chat,
+                screens: screen,
+                show_notifications: false,
+                notification_scroll: 0,
+                quit_dialog: None,
+                update_check,
+                shutdown,
+            },
+            receiver,
+        )
+    }
+
+    /// Replaces the windows with one for each recorded session, playing back what happened in it.
+    fn replay(&mut self, sessions: Vec<ReplayedSession>) -> Result<()> {
+        let start = Instant::now();
+        let mut screens = ActiveVec::new();
+        for session in sessions {
+            let client = ClientBuilder::new()
+                .shutdown(self.chat.shutdown.child())
+                .replay(&session.address, session.user_id)?;
+            let data = SessionData::replay(client, session.updates, start);
+            self.chat.logins.insert(session.username.clone(), data);
+            screens.push(Window::chat(&session.username, &self.chat));
+        }
+        self.screens = screens;
+        Ok(())
+    }
+
+    /// Shows the newer release in the banner once the check for it is done. Returns true if it arrived.
+    fn poll_update_check(&mut self) -> bool {
+        let Some(check) = &mut self.update_check else {
+            return false;
+        };
+        match check.try_recv() {
+            Ok(notice) => {
+                self.update_check = None;
+                self.chat.notice_update(notice);
+                true
+            }
+            Err(oneshot::error::TryRecvError::Empty) => false,
+            Err(oneshot::error::TryRecvError::Closed) => {
+                self.update_check = None;
+                false
+            }
+        }
+    }
+
+    /// Opens a new window right after the active one and switches to it.
+    fn open_window(&mut self) {
+        let index = self.screens.get_active_index().map_or(0, |index| index + 1);
+        self.screens.insert(index, self.chat.new_window());
+        self.screens.set_active(index);
+    }
+
+    /// Closes the active window, logging out of its session unless another window still shows it. There is
+    /// always at least one window, so closing the last one replaces it with a new one.
+    async fn close_window(&mut self) {
+        let Some(index) = self.screens.get_active_index() else {
+            return;
+        };
+        let Some(window) = self.screens.remove(index) else {
+            return;
+        };
+
+        let title = window.title();
+        let still_shown = self.screens.iter().any(|other| other.title() == title);
+        if !still_shown {
+            if let Some(session) = self.chat.logins.remove(&title) {
+                // The window is gone either way, so there is nowhere left to show a failure
+                let _ = session.client.logout().await;
+            }
+        }
+
+        if self.screens.get_active().is_none() {
+            self.screens.push(self.chat.new_window());
+        }
+    }
+
+    /// Moves the active window one position to the right or left, if there is room.
+    fn move_window(&mut self, right: bool) {
+        let Some(index) = self.screens.get_active_index() else {
+            return;
+        };
+        let target = if right {
+            index + 1
+        } else if let Some(target) = index.checked_sub(1) {
+            target
+        } else {
+            return;
+        };
+        if target < self.screens.iter().len() {
+            self.screens.swap(index, target);
+        }
+    }
+
+    /// Switches tabs when the tab bar is clicked and passes all other mouse events on to the active window.
+    fn handle_mouse(&mut self, mouse: &MouseEvent, size: Rect) {
+        let chunks = ui_layout(size, self);
+        if mouse.row == chunks[0].y {
+            if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                if let Some(index) = tab_at(&self.tab_titles(), mouse.column) {
+                    self.screens.set_active(index);
+                }
+            }
+        } else if let Some(screen) = self.screens.get_active_mut() {
+            screen.handle_mouse(mouse, chunks[1]);
+        }
+    }
+
+    /// Logs out all sessions at the same time. Requests still running after ``LOGOUT_TIMEOUT`` are cancelled.
+    /// Returns the sessions that could not be logged out, with the reason.
+    async fn logout_all(&self) -> Vec<(String, String)> {
+        let logouts = join_all(
+            self.chat
+                .logins
+                .iter()
+                .map(|(username, session)| async move {
+                    (username.clone(), session.client.logout().await)
+                }),
+        );
+        let mut logouts = pin!(logouts);
+
+        let results = tokio::select! {
+            results = &mut logouts => results,
+            () = tokio::time::sleep(LOGOUT_TIMEOUT) => {
+                // Cancelled requests return right away, so this only waits for the results to be collected
+                self.shutdown.cancel();
+                logouts.await
+            }
+        };
+
+        results
+            .into_iter()
+            .filter_map(|(username, result)| match result {
+                Ok(()) => None,
+                Err(chat_client::Error::Cancelled) => {
+                    Some((username, t("logout.timed_out").into()))
+                }
+                Err(e) => Some((username, e.localized())),
+            })
+            .collect()
+    }
+
+    /// Returns the titles of the windows with unsent messages.
+    fn drafts(&self) -> Vec<String> {
+        self.screens
+            .iter()
+            .filter(|screen| screen.draft().is_some())
+            .map(Window::title)
+            .collect()
+    }
+
+    /// Sends the unsent messages of all windows. Returns false if any of them could not be sent.
+    async fn send_drafts(&mut self) -> bool {
+        let mut sent = true;
+        for screen in self.screens.iter_mut() {
+            if screen.draft().is_some() {
+                sent &= screen.send_draft(&mut self.chat).await;
+            }
+        }
+        sent
+    }
+
+    /// Marks the windows of sessions that received new messages as unread, unless they are active. Returns true
+    /// if any window was not marked as unread before.
+    fn mark_unread(&mut self, updated: &[String]) -> bool {
+        let active_index = self.screens.get_active_index();
+        let mut newly_unread = false;
+        for (index, screen) in self.screens.iter_mut().enumerate() {
+            if Some(index) == active_index {
+                screen.set_unread(false);
+            } else if updated.contains(&screen.title()) {
+                newly_unread |= !screen.is_unread();
+                screen.set_unread(true);
+            }
+        }
+        newly_unread
+    }
+
+    /// Get the ``TabTitle``s to show.
+    fn tab_titles(&self) -> Vec<TabTitle> {
+        if let Some(active_index) = self.screens.get_active_index() {
+            self.screens
+                .iter()
+                .enumerate()
+                .map(|(index, screen)| {
+                    let title = screen.tab_title(&self.chat);
+                    let accent = screen.accent_color(&self.chat);
+                    if index == active_index && self.chat.theme.colors() {
+                        TabTitle::Active(title, accent)
+                    } else if index == active_index {
+                        TabTitle::Active(format!("[{title}]"), accent)
+                    } else if screen.is_unread() {
+                        TabTitle::Inactive(format!("{title} *"))
+                    } else {
+                        TabTitle::Inactive(title)
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+}
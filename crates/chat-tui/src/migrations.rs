@@ -0,0 +1,79 @@
+//! Upgrading the files the client keeps between runs, so a newer client never loses what an older one wrote. Each
+//! file carries the version of its schema in a ``version`` key, and its schema lists how to get from every older
+//! version to the next. Files are migrated in memory whenever they are read and only written back in the current
+//! version when the client saves them anyway, so they stay readable by the client that wrote them until then.
+use std::cmp::Ordering;
+
+use eyre::{eyre, Result};
+use toml::{Table, Value};
+use tracing::{info, warn};
+
+/// The key holding the version of the schema a file was written with. Files without one are version 0, from
+/// before the file was versioned.
+pub const VERSION_KEY: &str = "version";
+
+/// Turns a file of one version into the next version.
+pub type Migration = fn(&mut Table) -> Result<()>;
+
+/// The versions a file went through.
+pub struct Schema {
+    /// The name of the file, for the log and error messages.
+    pub name: &'static str,
+    /// The migration from version ``n`` to ``n + 1`` at index ``n``. Only ever append to these, as files of every
+    /// older version may still be around.
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    /// The version files are written with.
+    pub const fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    /// Migrates the contents of a file to the current version, setting its ``version`` key. Files written by a
+    /// newer client are left as they are, to be read as well as possible by ignoring what this client does not
+    /// know about.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the version is not a valid number or a migration failed.
+    pub fn migrate(&self, table: &mut Table) -> Result<()> {
+        let version = match table.get(VERSION_KEY) {
+            None => 0,
+            Some(Value::Integer(version)) => u32::try_from(*version)
+                .map_err(|_| eyre!("{} has an invalid version {version}", self.name))?,
+            Some(value) => return Err(eyre!("{} has an invalid version {value}", self.name)),
+        };
+        match version.cmp(&self.version()) {
+            Ordering::Less => {}
+            Ordering::Equal => return Ok(()),
+            Ordering::Greater => {
+                warn!(
+                    file = self.name,
+                    version,
+                    current = self.version(),
+                    "The file was written by a newer client"
+                );
+                return Ok(());
+            }
+        }
+
+        for (from, migration) in self.migrations.iter().enumerate().skip(version as usize) {
+            migration(table).map_err(|e| {
+                eyre!(
+                    "Could not migrate {} from version {from} to {}: {e}",
+                    self.name,
+                    from + 1
+                )
+            })?;
+        }
+        table.insert(VERSION_KEY.into(), Value::Integer(self.version().into()));
+        info!(
+            file = self.name,
+            from = version,
+            to = self.version(),
+            "Migrated the file"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+/// How many notifications are kept before the oldest ones get dropped.
+const CAPACITY: usize = 100;
+
+/// How long a notification is shown in the status line of its window.
+const SHOWN_FOR: Duration = Duration::seconds(5);
+
+/// How serious a ``Notification`` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Something that happened in the client the user should know about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub time: DateTime<Utc>,
+    pub severity: Severity,
+    /// The session the notification is about, or the window it came from.
+    pub source: String,
+    pub text: String,
+    /// How often the same notification happened in a row.
+    pub count: u32,
+}
+
+/// Keeps the most recent notifications, so they can still be looked at after they left the status line.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    entries: VecDeque<Notification>,
+}
+
+impl Notifications {
+    /// Adds a notification, dropping the oldest one if there are too many. If it is the same as the previous
+    /// one, the previous one is counted again instead.
+    pub fn push(&mut self, severity: Severity, source: &str, text: impl Into<String>) {
+        let text = text.into();
+        match severity {
+            Severity::Info => info!(source, "{text}"),
+            Severity::Warning => warn!(source, "{text}"),
+            Severity::Error => error!(source, "{text}"),
+        }
+        if let Some(last) = self.entries.back_mut() {
+            if last.severity == severity && last.source == source && last.text == text {
+                last.time = Utc::now();
+                last.count += 1;
+                return;
+            }
+        }
+        if self.entries.len() == CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Notification {
+            time: Utc::now(),
+            severity,
+            source: source.to_string(),
+            text,
+            count: 1,
+        });
+    }
+
+    /// Returns the latest notification from the given source, if it is recent enough to still be shown.
+    pub fn current(&self, source: &str) -> Option<&Notification> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|notification| notification.source == source)
+            .filter(|notification| Utc::now() - notification.time < SHOWN_FOR)
+    }
+
+    /// Returns an iterator over the notifications, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
@@ -0,0 +1,169 @@
+//! Recording sessions and replaying them offline. A recording holds every update the background tasks passed to
+//! the ui, so rendering bugs can be reproduced from the recording alone, without access to the server.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use chat_client::Client;
+use eyre::{eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+use crate::session::SessionUpdate;
+
+/// One line of a recording. Updates are borrowed whilst recording and owned whilst replaying.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Entry<U> {
+    /// A session was logged in.
+    Session {
+        username: String,
+        address: String,
+        user_id: i32,
+        /// The version of the client that made the recording.
+        version: String,
+    },
+    /// The background task of the session passed an update to the ui, this many milliseconds after the recording
+    /// started.
+    Update {
+        username: String,
+        elapsed: u64,
+        update: U,
+    },
+}
+
+/// Writes the updates of all sessions to a file, one JSON object per line. Each line is written out right away,
+/// so the recording is complete up to the moment the client crashed.
+pub struct Recorder {
+    start: Instant,
+    file: Mutex<LineWriter<File>>,
+}
+
+impl Recorder {
+    /// Creates the recording, replacing the file if it exists.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file could not be created.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Could not create the recording {}", path.display()))?;
+        Ok(Self {
+            start: Instant::now(),
+            file: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    /// Starts recording the session of the user, which is logged in with the client.
+    pub fn session(self: &Arc<Self>, username: &str, client: &Client) -> SessionRecording {
+        self.write(&Entry::<()>::Session {
+            username: username.to_string(),
+            address: client.address().to_string(),
+            user_id: client.user_id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        });
+        SessionRecording {
+            recorder: Arc::clone(self),
+            username: username.to_string(),
+        }
+    }
+
+    fn write<U: Serialize>(&self, entry: &Entry<U>) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        // A recording that can not be written should not take the session down with it
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+/// Records the updates of a single session.
+pub struct SessionRecording {
+    recorder: Arc<Recorder>,
+    username: String,
+}
+
+impl SessionRecording {
+    pub fn record(&self, update: &SessionUpdate) {
+        let elapsed = self.recorder.start.elapsed().as_millis();
+        self.recorder.write(&Entry::Update {
+            username: self.username.clone(),
+            elapsed: u64::try_from(elapsed).unwrap_or(u64::MAX),
+            update,
+        });
+    }
+}
+
+/// A session read from a recording.
+pub struct ReplayedSession {
+    pub username: String,
+    pub address: String,
+    pub user_id: i32,
+    /// The updates of the session, with how long after the first update of the recording they were made.
+    pub updates: Vec<(Duration, SessionUpdate)>,
+}
+
+/// Reads the sessions from a recording, in the order they were logged in.
+///
+/// # Errors
+///
+/// This function will return an error if the file could not be read or is not a recording.
+pub fn load(path: &Path) -> Result<Vec<ReplayedSession>> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("Could not open the recording {}", path.display()))?;
+    let mut sessions: Vec<ReplayedSession> = Vec::new();
+    // The time before the first update is not worth waiting for
+    let mut first = None;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.wrap_err("Could not read the recording")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry<SessionUpdate> = serde_json::from_str(&line)
+            .wrap_err_with(|| format!("Line {} of the recording is not valid", number + 1))?;
+        match entry {
+            Entry::Session {
+                username,
+                address,
+                user_id,
+                version: _,
+            } => {
+                // A user that logged in again continues the same session
+                if !sessions.iter().any(|session| session.username == username) {
+                    sessions.push(ReplayedSession {
+                        username,
+                        address,
+                        user_id,
+                        updates: Vec::new(),
+                    });
+                }
+            }
+            Entry::Update {
+                username,
+                elapsed,
+                update,
+            } => {
+                let session = sessions
+                    .iter_mut()
+                    .find(|session| session.username == username)
+                    .ok_or_else(|| {
+                        eyre!(
+                            "Line {} of the recording belongs to an unknown session",
+                            number + 1
+                        )
+                    })?;
+                let first = *first.get_or_insert(elapsed);
+                let after = Duration::from_millis(elapsed.saturating_sub(first));
+                session.updates.push((after, update));
+            }
+        }
+    }
+    if sessions.is_empty() {
+        return Err(eyre!("The recording {} holds no sessions", path.display()));
+    }
+    Ok(sessions)
+}
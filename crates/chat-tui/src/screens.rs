@@ -0,0 +1,3156 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use chat_client::{AuthDetails, ClientBuilder, Error, FileUpload};
+use chat_core::{
+    models::{Attachment, Message, Profile, Role},
+    protocol::{
+        Conversation, ErrorCode, Feature, ForwardTarget, NotificationLevel, PageRequest,
+        ProfileUpdate, PushProvider, PushSettings, SearchFilter, SearchQuery, SearchSyntaxError,
+        ServerInfo, Session, SystemEventKind, Terms, UserEntry, UserFilter, UserSort,
+    },
+};
+use chrono::{Duration, NaiveDate};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use tracing::info;
+use tui::{
+    buffer::Buffer,
+    layout::{Alignment::Center, Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Widget, Wrap},
+};
+
+use crate::{
+    config::OwnMessages,
+    downloads::format_size,
+    errors::{localize, Localized},
+    i18n::{t, tf},
+    notifications::{Notification, Notifications, Severity},
+    session::SessionData,
+    time_format::TimeFormat,
+    tui_support::{form_element_ui, FormElement, Theme, Visibility},
+    updates::UpdateNotice,
+    welcome::WelcomeWindow,
+    ChatData, LocalServer,
+};
+
+/// Used to hold the current window state.
+#[derive(Clone)]
+pub struct Window {
+    state: MenuState,
+    theme: Theme,
+    /// Whether new messages arrived since the window was last looked at.
+    unread: bool,
+}
+
+/// Keeps track of what ste the ``Window`` currently is in.
+#[derive(Clone)]
+enum MenuState {
+    Chat(ChatWindow),
+    Login(LoginWindow),
+    Welcome(WelcomeWindow),
+}
+
+/// Holds the current state of the chat window.
+#[derive(Clone)]
+struct ChatWindow {
+    title: String,
+    /// The conversation of the session shown, and its name as the title of the message list.
+    conversation: (Conversation, String),
+    message_list: Vec<ChatLine>,
+    /// The session revision ``message_list`` was laid out for.
+    revision: Option<u64>,
+    /// Formatted messages by id. Only messages that were shown are formatted, so long histories do not slow
+    /// down drawing.
+    formatted: HashMap<i32, FormattedMessage>,
+    /// The system events in the message list, formatted when it is laid out as there are only a few.
+    system_lines: Vec<String>,
+    own_messages: OwnMessages,
+    message_composer: String,
+    /// The latest notification about the session, while it is recent enough to be shown.
+    status: Option<Notification>,
+    /// The retry in progress, if the client is retrying a failed request.
+    retrying: Option<u32>,
+    /// How many of the newest messages are hidden, because the user scrolled up.
+    scroll: usize,
+    /// Whether the oldest and the newest message in memory were visible, when the window was last updated.
+    showing_ends: (bool, bool),
+    /// The index of the selected message. The composer has focus if no message is selected.
+    selected: Option<usize>,
+    /// Where the misspelled words in the composer are.
+    misspelled: Vec<Range<usize>>,
+    /// Replacements offered for a misspelled word, while they are shown.
+    suggestions: Option<Suggestions>,
+    /// The messages the user saved, while they are listed.
+    saved: Option<ItemList>,
+    /// The files attached to the selected message, while they are listed, in the same order as in the list.
+    attachments: Option<(ItemList, Vec<Attachment>)>,
+    /// The words searched for with the messages found, while they are listed.
+    search: Option<(String, ItemList)>,
+    /// The names of the users that are online, shown next to the message list. ``None`` if it is not known.
+    online: Option<Vec<String>>,
+    /// The wait before another message can be sent to a channel in slow mode, counted down in the composer.
+    cooldown: Option<Cooldown>,
+}
+
+/// How long the user has to wait before sending another message to a channel in slow mode.
+#[derive(Clone, Copy)]
+struct Cooldown {
+    conversation: Conversation,
+    until: Instant,
+    /// The seconds left when the window was last updated.
+    shown: u64,
+}
+
+impl Cooldown {
+    fn new(conversation: Conversation, seconds: u64) -> Self {
+        Self {
+            conversation,
+            until: Instant::now() + std::time::Duration::from_secs(seconds),
+            shown: seconds,
+        }
+    }
+
+    /// The seconds left to wait, rounded up so the countdown ends when sending is possible again.
+    fn seconds_left(&self) -> u64 {
+        let left = self.until.saturating_duration_since(Instant::now());
+        left.as_secs() + u64::from(left.subsec_nanos() > 0)
+    }
+}
+
+/// Things to pick from, shown over the message list.
+#[derive(Clone)]
+struct ItemList {
+    /// The ids of the items with their text, formatted when the list was opened.
+    items: Vec<(i32, String)>,
+    selected: usize,
+}
+
+impl ItemList {
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.items.len() - 1);
+    }
+
+    /// The id of the selected item.
+    fn selected_id(&self) -> i32 {
+        self.items[self.selected].0
+    }
+
+    /// Draws the list over the area, scrolled just far enough to show the selected item.
+    fn render(&self, title: &str, area: Rect, buf: &mut Buffer, theme: Theme) {
+        let height = usize::from(area.height.saturating_sub(2));
+        let skip = (self.selected + 1).saturating_sub(height);
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(index, (_, text))| {
+                let selected = index == self.selected;
+                ListItem::new(format!("{}{text}", theme.marker(selected)))
+                    .style(theme.focus(selected))
+            })
+            .collect();
+        Clear.render(area, buf);
+        tui::widgets::Widget::render(
+            List::new(items).block(Block::default().borders(Borders::ALL).title(title)),
+            area,
+            buf,
+        );
+    }
+}
+
+/// Replacements for a misspelled word in the composer, shown above it.
+#[derive(Clone)]
+struct Suggestions {
+    /// Where the word is in the composer.
+    word: Range<usize>,
+    options: Vec<String>,
+    selected: usize,
+}
+
+/// A message as it is shown in the message list.
+#[derive(Clone)]
+struct FormattedMessage {
+    /// The session revision it was formatted in.
+    revision: u64,
+    text: String,
+    /// Whether the logged in user wrote it.
+    own: bool,
+    /// Whether it was formatted as the continuation of a group.
+    continued: bool,
+}
+
+/// A line of the message list.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChatLine {
+    /// Shown above the first message of each day.
+    Date(NaiveDate),
+    /// A system event, with its position in the formatted system events of the window.
+    System(usize),
+    /// Who read the conversation up to the message above, with the position of the text in the formatted system
+    /// events of the window.
+    Read(usize),
+    /// A message, with its position in the messages of the session it was laid out for. Messages continuing a
+    /// group of messages from the same author are shown without the time and name.
+    Message {
+        id: i32,
+        index: usize,
+        continued: bool,
+    },
+}
+
+/// How long after a message the next one from the same author is still shown as part of the same group.
+const GROUP_WINDOW: Duration = Duration::minutes(5);
+
+/// Holds the current state of the login window.
+#[derive(Clone)]
+struct LoginWindow {
+    address: FormElement,
+    username: FormElement,
+    password: FormElement,
+    intent: Intent,
+    /// The invite code, only asked for when registering on a server that needs one.
+    invite: FormElement,
+    remember_me: bool,
+    focus: LoginWindowFocus,
+    status_message: Option<String>,
+    /// The address the server was last checked at, with what the server told about itself.
+    server: Option<(String, ServerInfo)>,
+    /// The terms of service of the server, while they are shown before registering.
+    terms: Option<TermsOverlay>,
+    /// The version of the terms of service the user accepted.
+    accepted_terms: Option<String>,
+}
+
+/// Shows the terms of service over the login window, so they can be read and accepted.
+#[derive(Clone)]
+struct TermsOverlay {
+    terms: Terms,
+    /// How many lines are scrolled past.
+    scroll: u16,
+}
+
+/// What does the user wanna do when they hit enter?
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Intent {
+    Login,
+    Register,
+    /// Use a temporary account, without a username or password.
+    Guest,
+}
+
+/// Keeps track of what element in the ``LoginWindow`` is currently in focus.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LoginWindowFocus {
+    Address,
+    Username,
+    Pasword,
+    Intent,
+    Invite,
+    RememberMe,
+}
+
+impl LoginWindow {
+    /// Returns what the server at the address entered told about itself, once it was checked.
+    fn checked_server(&self) -> Option<&ServerInfo> {
+        self.server
+            .as_ref()
+            .filter(|(checked, _)| checked == self.address.content.trim())
+            .map(|(_, info)| info)
+    }
+
+    /// Whether the invite code field is shown, which is when registering on a server that only accepts invited
+    /// users.
+    fn asks_invite(&self) -> bool {
+        self.intent == Intent::Register
+            && self.checked_server().is_some_and(|info| info.invite_only)
+    }
+}
+
+impl Window {
+    /// Creates a new ``Window`` instance.
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            theme,
+            unread: false,
+            state: MenuState::Login(LoginWindow {
+                address: FormElement::new(t("login.address"), Visibility::Visible),
+                username: FormElement::new(t("login.username"), Visibility::Visible),
+                password: FormElement::new(t("login.password"), Visibility::Hidden),
+                intent: Intent::Login,
+                invite: FormElement::new(t("login.invite_code"), Visibility::Visible),
+                remember_me: false,
+                focus: LoginWindowFocus::Address,
+                status_message: None,
+                server: None,
+                terms: None,
+                accepted_terms: None,
+            }),
+        }
+    }
+
+    /// Creates a new ``Window`` instance with the server address already filled in.
+    pub fn with_address(address: &str, theme: Theme) -> Self {
+        let mut window = Self::new(theme);
+        if let MenuState::Login(login) = &mut window.state {
+            login.address.content = address.to_string();
+            login.focus = LoginWindowFocus::Username;
+        }
+        window
+    }
+
+    /// Creates a new ``Window`` showing the welcome wizard, for when the client is started for the first time.
+    pub(crate) fn welcome(data: &ChatData) -> Self {
+        Self {
+            theme: data.theme,
+            unread: false,
+            state: MenuState::Welcome(WelcomeWindow::new(
+                data.local.as_ref().map(LocalServer::address),
+                data,
+            )),
+        }
+    }
+
+    /// Creates a new ``Window`` showing the session of the user, which has to be in ``ChatData::logins`` already.
+    pub(crate) fn chat(username: &str, data: &ChatData) -> Self {
+        Self {
+            theme: data.theme,
+            unread: false,
+            state: MenuState::Chat(ChatWindow::new(username, data)),
+        }
+    }
+
+    /// Returns the message being written in the window, if there is one.
+    pub fn draft(&self) -> Option<&str> {
+        match &self.state {
+            MenuState::Chat(chat) if !chat.message_composer.trim().is_empty() => {
+                Some(&chat.message_composer)
+            }
+            _ => None,
+        }
+    }
+
+    /// Sends the message being written in the window. Returns false if it could not be sent.
+    pub(crate) async fn send_draft(&mut self, data: &mut ChatData) -> bool {
+        match &mut self.state {
+            MenuState::Chat(chat) => send_composer(chat, data).await,
+            MenuState::Login(_) | MenuState::Welcome(_) => false,
+        }
+    }
+
+    pub fn is_unread(&self) -> bool {
+        self.unread
+    }
+
+    pub fn set_unread(&mut self, unread: bool) {
+        self.unread = unread;
+    }
+
+    /// Returns where the terminal cursor belongs, so it follows the input. Screen readers rely on this to
+    /// know what is being typed.
+    pub(crate) fn cursor(&self, area: Rect) -> Option<(u16, u16)> {
+        let inner = window_inner(area);
+        let (field, content) = match &self.state {
+            MenuState::Chat(chat) => {
+                if chat.selected.is_some() {
+                    return None;
+                }
+                (
+                    chat_layout(inner, chat.online.is_some())[1],
+                    chat.message_composer.as_str(),
+                )
+            }
+            MenuState::Login(login) => {
+                if login.terms.is_some() {
+                    return None;
+                }
+                let layout = login_layout(inner, login.asks_invite());
+                match login.focus {
+                    LoginWindowFocus::Address => (layout[0], login.address.content.as_str()),
+                    LoginWindowFocus::Username => (layout[1], login.username.content.as_str()),
+                    LoginWindowFocus::Pasword => (layout[2], login.password.content.as_str()),
+                    LoginWindowFocus::Intent => return Some((layout[3].x, layout[3].y)),
+                    LoginWindowFocus::Invite => (layout[4], login.invite.content.as_str()),
+                    LoginWindowFocus::RememberMe => return Some((layout[5].x, layout[5].y)),
+                }
+            }
+            MenuState::Welcome(welcome) => return welcome.cursor(inner),
+        };
+        let width = u16::try_from(Span::raw(content).width()).unwrap_or(u16::MAX);
+        let column = (field.x + 1)
+            .saturating_add(width)
+            .min(field.right().saturating_sub(2));
+
+        Some((column, field.y + 1))
+    }
+
+    /// Get the current title for the window.
+    pub fn title(&self) -> String {
+        match &self.state {
+            MenuState::Chat(window) => window.title.clone(),
+            MenuState::Login(_) => t("login.title").into(),
+            MenuState::Welcome(_) => t("welcome.title").into(),
+        }
+    }
+
+    /// Returns the title shown in the tab of the window, which names the server of a session, so sessions on
+    /// different servers can be told apart. Channels that are muted or only notify about mentions are marked.
+    pub(crate) fn tab_title(&self, data: &ChatData) -> String {
+        let title = match self.server_info(data) {
+            Some(info) if matches!(self.state, MenuState::Chat(_)) => tf(
+                "chat.tab_title",
+                &[("username", &self.title()), ("server", &info.name)],
+            ),
+            _ => self.title(),
+        };
+        let MenuState::Chat(chat) = &self.state else {
+            return title;
+        };
+        let level = data
+            .logins
+            .get(&chat.title)
+            .map(|session| session.notification_level(session.conversation));
+        match level.and_then(notification_marker) {
+            Some(marker) => format!("{title} {marker}"),
+            None => title,
+        }
+    }
+
+    /// Returns the color the server of the window is highlighted in.
+    pub(crate) fn accent_color(&self, data: &ChatData) -> Color {
+        accent_color(self.server_info(data))
+    }
+
+    /// Returns what the server of the session, or the one found at the address entered, told about itself.
+    fn server_info<'a>(&'a self, data: &'a ChatData) -> Option<&'a ServerInfo> {
+        match &self.state {
+            MenuState::Chat(chat) => data.logins.get(&chat.title)?.client.server_info(),
+            MenuState::Login(login) => login.checked_server(),
+            MenuState::Welcome(_) => None,
+        }
+    }
+
+    /// Handles the input for the window and apply changes to it and the ``ChatData`` as necessary.
+    pub(crate) async fn handle_input(&mut self, data: &mut ChatData, event: &Event) {
+        // If the event is from a a key release, we ignore it
+        if let Event::Key(KeyEvent {
+            code: _,
+            modifiers: _,
+            kind: KeyEventKind::Release,
+            state: _,
+        }) = event
+        {
+            return;
+        }
+        match &mut self.state {
+            MenuState::Chat(chat) => {
+                handle_chat_window_input(chat, event, data).await;
+                // Whatever changed the composer, the draft follows it
+                if let Some(session_data) = data.logins.get_mut(&chat.title) {
+                    session_data.edit_draft(&chat.message_composer);
+                }
+            }
+            MenuState::Login(form) => {
+                // Clone the state and passing it in like that is a bit awkward.
+                // But so far the best solution I could come up with as I otherwise
+                // need to pass in two mutable instances of self which causes a compiler error.
+                // There must be a better way to go about this, but I am not seeing it yet.
+                let mut form = form.clone();
+                self.handle_login_window_input(&mut form, event, data).await;
+                if let MenuState::Login(_) = self.state {
+                    self.state = MenuState::Login(form);
+                }
+            }
+            MenuState::Welcome(welcome) => {
+                if let Event::Key(key) = event {
+                    if let Some(welcome) = welcome.handle_key(key.code, data) {
+                        let mut window = Self::with_address(&welcome.address, data.theme);
+                        if let MenuState::Login(login) = &mut window.state {
+                            if welcome.register {
+                                login.intent = Intent::Register;
+                            }
+                        }
+                        *self = window;
+                    }
+                }
+                // The theme can be changed in the wizard
+                self.theme = data.theme;
+            }
+        }
+    }
+
+    async fn handle_login_window_input(
+        &mut self,
+        form: &mut LoginWindow,
+        event: &Event,
+        data: &mut ChatData,
+    ) {
+        if form.status_message.is_some() {
+            //form.status_message = None;
+        }
+        if let Some(overlay) = &mut form.terms {
+            if let Event::Key(key) = event {
+                let last_line = overlay.terms.text.lines().count().saturating_sub(1);
+                let last_line = u16::try_from(last_line).unwrap_or(u16::MAX);
+                match key.code {
+                    KeyCode::Up => overlay.scroll = overlay.scroll.saturating_sub(1),
+                    KeyCode::Down => overlay.scroll = (overlay.scroll + 1).min(last_line),
+                    KeyCode::PageUp => overlay.scroll = overlay.scroll.saturating_sub(10),
+                    KeyCode::PageDown => overlay.scroll = (overlay.scroll + 10).min(last_line),
+                    KeyCode::Esc => form.terms = None,
+                    KeyCode::Enter => {
+                        form.accepted_terms = Some(overlay.terms.version.clone());
+                        form.terms = None;
+                        self.submit_form(form, data).await;
+                    }
+                    _ => {}
+                }
+            }
+            return;
+        }
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers: _,
+            kind: _,
+            state: _,
+        }) = event
+        {
+            match code {
+                KeyCode::Up => {
+                    form.focus = match form.focus {
+                        LoginWindowFocus::Address | LoginWindowFocus::Username => {
+                            LoginWindowFocus::Address
+                        }
+                        LoginWindowFocus::Pasword => LoginWindowFocus::Username,
+                        LoginWindowFocus::Intent => LoginWindowFocus::Pasword,
+                        LoginWindowFocus::Invite => LoginWindowFocus::Intent,
+                        LoginWindowFocus::RememberMe if form.asks_invite() => {
+                            LoginWindowFocus::Invite
+                        }
+                        LoginWindowFocus::RememberMe => LoginWindowFocus::Intent,
+                    };
+                }
+                KeyCode::Down => {
+                    let leaving_address = form.focus == LoginWindowFocus::Address;
+                    form.focus = match form.focus {
+                        LoginWindowFocus::Address => LoginWindowFocus::Username,
+                        LoginWindowFocus::Username => LoginWindowFocus::Pasword,
+                        LoginWindowFocus::Pasword => LoginWindowFocus::Intent,
+                        LoginWindowFocus::Intent if form.asks_invite() => LoginWindowFocus::Invite,
+                        LoginWindowFocus::Intent
+                        | LoginWindowFocus::Invite
+                        | LoginWindowFocus::RememberMe => LoginWindowFocus::RememberMe,
+                    };
+                    // Tell right away if the server can not be used, not only after entering the credentials
+                    if leaving_address {
+                        check_server(form, data).await;
+                    }
+                }
+                KeyCode::Left if form.focus == LoginWindowFocus::Intent => {
+                    form.intent = match form.intent {
+                        Intent::Login | Intent::Register => Intent::Login,
+                        Intent::Guest => Intent::Register,
+                    };
+                }
+                KeyCode::Right if form.focus == LoginWindowFocus::Intent => {
+                    form.intent = match form.intent {
+                        Intent::Login => Intent::Register,
+                        Intent::Register | Intent::Guest => Intent::Guest,
+                    };
+                }
+                KeyCode::Char(' ') if form.focus == LoginWindowFocus::RememberMe => {
+                    form.remember_me = !form.remember_me;
+                }
+                KeyCode::Enter => {
+                    self.submit_form(form, data).await;
+                }
+                KeyCode::Char(c) => match form.focus {
+                    LoginWindowFocus::Address => form.address.content.push(*c),
+                    LoginWindowFocus::Username => form.username.content.push(*c),
+                    LoginWindowFocus::Pasword => form.password.content.push(*c),
+                    LoginWindowFocus::Invite => form.invite.content.push(*c),
+                    LoginWindowFocus::Intent | LoginWindowFocus::RememberMe => {}
+                },
+                KeyCode::Backspace => {
+                    match form.focus {
+                        LoginWindowFocus::Address => {
+                            form.address.content.pop();
+                        }
+                        LoginWindowFocus::Username => {
+                            form.username.content.pop();
+                        }
+                        LoginWindowFocus::Pasword => {
+                            form.password.content.pop();
+                        }
+                        LoginWindowFocus::Invite => {
+                            form.invite.content.pop();
+                        }
+                        LoginWindowFocus::Intent | LoginWindowFocus::RememberMe => {}
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn submit_form(&mut self, form: &mut LoginWindow, data: &mut ChatData) {
+        if !check_server(form, data).await {
+            return;
+        }
+        // The terms of service are shown before registering, unless the current ones were already accepted
+        let terms_version = form
+            .server
+            .as_ref()
+            .and_then(|(_, info)| info.terms_version.clone());
+        if form.intent != Intent::Login
+            && terms_version.is_some()
+            && form.accepted_terms != terms_version
+        {
+            let builder =
+                ClientBuilder::from_config(&data.config.network).shutdown(data.shutdown.child());
+            match builder.terms(&form.address.content).await {
+                Ok(terms) => form.terms = Some(TermsOverlay { terms, scroll: 0 }),
+                Err(e) => {
+                    form.status_message =
+                        Some(tf("login.terms_failed", &[("error", &e.localized())]));
+                }
+            }
+            return;
+        }
+        let auth_details = AuthDetails::new(
+            &form.address.content,
+            &form.username.content,
+            &form.password.content,
+            form.remember_me,
+        )
+        .accepted_terms(form.accepted_terms.clone())
+        .invite_code(form.asks_invite().then(|| form.invite.content.clone()));
+        let builder =
+            ClientBuilder::from_config(&data.config.network).shutdown(data.shutdown.child());
+        let username = form.username.content.clone();
+        let result = match form.intent {
+            Intent::Login => builder
+                .login(auth_details)
+                .await
+                .map(|client| (client, username)),
+            Intent::Register => builder
+                .register(auth_details)
+                .await
+                .map(|client| (client, username)),
+            Intent::Guest => builder
+                .guest(&form.address.content, form.accepted_terms.clone())
+                .await
+                .map(|(client, guest)| {
+                    let expires = data.time_format.time(guest.valid_until);
+                    let text = tf("login.guest_expires", &[("time", &expires)]);
+                    data.notifications
+                        .push(Severity::Info, &guest.username, text);
+                    (client, guest.username)
+                }),
+        };
+        match result {
+            Ok((mut client, username)) => {
+                info!(username, address = client.address(), "Logged in");
+                if let Some((_, info)) = &form.server {
+                    client.set_server_info(info.clone());
+                }
+                let username = &username;
+                let recording = data
+                    .recorder
+                    .as_ref()
+                    .map(|recorder| recorder.session(username, &client));
+                let session = match &data.local {
+                    Some(local) => local.start_session(client, recording),
+                    None => SessionData::start(client, recording),
+                };
+                match session {
+                    Ok(session) => {
+                        data.logins.insert(username.clone(), session);
+                        self.state = MenuState::Chat(ChatWindow::new(username, data));
+                    }
+                    Err(e) => {
+                        form.status_message =
+                            Some(tf("login.session_failed", &[("error", &localize(&e))]));
+                    }
+                }
+            }
+            Err(Error::TimedOut) => {
+                form.status_message = Some(t("login.timed_out").into());
+            }
+            Err(e @ Error::TermsNotAccepted) => {
+                // Check the server again, so the new terms are shown on the next attempt
+                form.server = None;
+                form.status_message = Some(e.localized());
+            }
+            Err(e) => {
+                form.status_message = Some(tf("login.failed", &[("error", &e.localized())]));
+            }
+        }
+    }
+
+    /// Updates the ui state with the ``SessionData`` and its latest notification, formatting the messages that
+    /// are visible when drawn in the given area. Asks for more of the history when scrolled to the end of what is
+    /// in memory. Returns true if anything changed, so the window needs to be drawn again.
+    pub(crate) fn update(
+        &mut self,
+        data: &mut SessionData,
+        notifications: &Notifications,
+        time_format: TimeFormat,
+        area: Rect,
+    ) -> bool {
+        let MenuState::Chat(chat) = &mut self.state else {
+            return false;
+        };
+        let mut changed = false;
+
+        if chat.revision != Some(data.revision) {
+            let (lines, system_lines) = layout_messages(data, time_format);
+            // Another conversation has nothing in common with the lines shown
+            if chat.conversation.0 != data.conversation {
+                chat.message_list.clear();
+                chat.scroll = 0;
+                chat.selected = None;
+                // It was kept as the draft of the conversation before
+                chat.message_composer.clear();
+                chat.misspelled.clear();
+            }
+            chat.conversation = (data.conversation, data.conversation_name());
+
+            // Keep showing and selecting the same messages when messages are added or dropped around them. Whilst
+            // not scrolled up, new messages are followed instead.
+            let end = chat.message_list.len().saturating_sub(chat.scroll);
+            let moved = chat.message_list[..end]
+                .iter()
+                .rposition(|line| matches!(line, ChatLine::Message { .. }))
+                .and_then(|old| {
+                    let anchor = chat.message_list[old];
+                    let new = lines.iter().position(|line| same_message(*line, anchor))?;
+                    Some((old, new))
+                });
+            if let Some((old, new)) = moved {
+                if chat.scroll > 0 {
+                    chat.scroll = lines.len().saturating_sub(new + end - old);
+                }
+                chat.selected = chat.selected.map(|selected| {
+                    (selected + new)
+                        .saturating_sub(old)
+                        .min(lines.len().saturating_sub(1))
+                });
+            } else if chat.scroll > 0 {
+                chat.scroll += lines.len().saturating_sub(chat.message_list.len());
+            }
+
+            // Messages that were dropped from the session do not need their formatted text anymore
+            let ids: HashSet<i32> = data.messages.iter().map(|m| m.id).collect();
+            chat.formatted.retain(|id, _| ids.contains(id));
+            chat.message_list = lines;
+            chat.system_lines = system_lines;
+            chat.revision = Some(data.revision);
+            changed = true;
+        }
+
+        // A draft never replaces what the user typed meanwhile
+        if let Some(draft) = data.draft.take() {
+            if chat.message_composer.is_empty() && !draft.is_empty() {
+                chat.message_composer = draft;
+                changed = true;
+            }
+        }
+
+        let online = data.online_names();
+        if online != chat.online {
+            chat.online = online;
+            changed = true;
+        }
+
+        if let Some(cooldown) = &mut chat.cooldown {
+            let seconds = cooldown.seconds_left();
+            if seconds != cooldown.shown {
+                cooldown.shown = seconds;
+                changed = true;
+            }
+            if seconds == 0 {
+                chat.cooldown = None;
+            }
+        }
+
+        let list = chat_layout(window_inner(area), chat.online.is_some())[0];
+        data.fit_history(list.height);
+
+        if let Some(jump) = data.jump_to.take() {
+            let index = chat
+                .message_list
+                .iter()
+                .position(|line| matches!(line, ChatLine::Message { id, .. } if *id == jump));
+            if let Some(index) = index {
+                // Shown in the middle of the list, unless it is too close to either end
+                let height = usize::from(list.height);
+                let end = (index + 1 + height / 2)
+                    .max(height)
+                    .min(chat.message_list.len());
+                chat.scroll = chat.message_list.len() - end;
+                chat.selected = Some(index);
+                changed = true;
+            }
+        }
+
+        let visible = chat.visible_messages(list.height);
+
+        // More of the history is asked for when the end of what is in memory comes into view, and again once a
+        // part arrived, in case it was not enough to fill the list
+        let showing_ends = (visible.start == 0, visible.end == chat.message_list.len());
+        if showing_ends.0 && (!chat.showing_ends.0 || changed) {
+            data.load_older();
+        }
+        if showing_ends.1 && (!chat.showing_ends.1 || changed) {
+            data.load_newer();
+        }
+        // Only the active window is updated, so the newest message is in front of the user
+        if showing_ends.1 {
+            data.mark_read();
+        }
+        chat.showing_ends = showing_ends;
+
+        for line in &chat.message_list[visible] {
+            let ChatLine::Message {
+                id,
+                index,
+                continued,
+            } = *line
+            else {
+                continue;
+            };
+            // Renaming a user or editing the message makes the formatted text outdated
+            let changed_in = data.edited.get(&id).map_or(data.names_revision, |&edited| {
+                edited.max(data.names_revision)
+            });
+            if chat.formatted.get(&id).is_some_and(|formatted| {
+                formatted.revision >= changed_in && formatted.continued == continued
+            }) {
+                continue;
+            }
+
+            let message = &data.messages[index];
+            let name = match data.shown_name(message.userid) {
+                Some(name) => name.clone(),
+                None => message.userid.to_string(),
+            };
+            let time = time_format.time(message.date);
+            let mut header = format!("{time} {name}: ");
+            if continued {
+                // Lined up with the text of the message above
+                header = " ".repeat(Span::raw(header.as_str()).width());
+            }
+            let forwarded = message.forwardeduserid.map(|userid| {
+                let author = match data.shown_name(userid) {
+                    Some(name) => name.clone(),
+                    None => userid.to_string(),
+                };
+                tf("chat.forwarded_from", &[("name", &author)])
+            });
+            // Attachments are only described, their files are downloaded when asked for
+            let files = message
+                .attachments
+                .iter()
+                .map(|attachment| {
+                    tf(
+                        "attachments.inline",
+                        &[("label", &attachment_label(attachment))],
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let separator = if message.messagetext.is_empty() || files.is_empty() {
+                ""
+            } else {
+                " "
+            };
+            let text = format!(
+                "{header}{}{}{separator}{files}",
+                forwarded.unwrap_or_default(),
+                message.messagetext
+            );
+            let formatted = FormattedMessage {
+                revision: data.revision,
+                text,
+                own: message.userid == data.client.user_id(),
+                continued,
+            };
+            chat.formatted.insert(id, formatted);
+            changed = true;
+        }
+
+        let retrying = data.client.retrying();
+        let status = notifications.current(&chat.title).cloned();
+        if retrying != chat.retrying || status != chat.status {
+            chat.retrying = retrying;
+            chat.status = status;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Handles mouse events within the area the window is drawn in.
+    pub(crate) fn handle_mouse(&mut self, event: &MouseEvent, area: Rect) {
+        let MenuState::Chat(chat) = &mut self.state else {
+            return;
+        };
+        let layout = chat_layout(window_inner(area), chat.online.is_some());
+        let (list, composer) = (layout[0], layout[1]);
+        let visible = chat.visible_messages(list.height);
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let list_inner = list.inner(&Margin {
+                    vertical: 1,
+                    horizontal: 1,
+                });
+                if contains(list_inner, event.column, event.row) {
+                    let index = visible.start + usize::from(event.row - list_inner.y);
+                    if visible.contains(&index) {
+                        chat.selected = Some(index);
+                    }
+                } else if contains(composer, event.column, event.row) {
+                    chat.selected = None;
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                let hidden_above = visible.start;
+                chat.scroll += hidden_above.min(SCROLL_STEP);
+            }
+            MouseEventKind::ScrollDown => {
+                chat.scroll = chat.scroll.saturating_sub(SCROLL_STEP);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lays out the message list of a session, returning its lines and the formatted system events they point to.
+/// System events are shown between the messages by date, as far as they are within the messages in memory.
+fn layout_messages(data: &SessionData, time_format: TimeFormat) -> (Vec<ChatLine>, Vec<String>) {
+    let oldest = data.messages.first().filter(|_| data.has_older);
+    let newest = data.messages.last().filter(|_| data.has_newer);
+    let mut system = data
+        .system
+        .iter()
+        .filter(|event| {
+            oldest.is_none_or(|oldest| event.date >= oldest.date)
+                && newest.is_none_or(|newest| event.date <= newest.date)
+        })
+        .peekable();
+    let mut messages = data.messages.iter().enumerate().peekable();
+
+    // Markers further than the messages in memory point at messages not shown
+    let mut readers: HashMap<usize, Vec<String>> = HashMap::new();
+    for (userid, read) in &data.receipts {
+        if newest.is_some_and(|newest| read > &newest.id) {
+            continue;
+        }
+        if let Some(index) = data
+            .messages
+            .iter()
+            .rposition(|message| message.id <= *read)
+        {
+            let name = data
+                .shown_name(*userid)
+                .cloned()
+                .unwrap_or(userid.to_string());
+            readers.entry(index).or_default().push(name);
+        }
+    }
+
+    let mut lines = Vec::with_capacity(data.messages.len());
+    let mut system_lines = Vec::new();
+    let mut last_date = None;
+    let mut previous: Option<&Message> = None;
+    loop {
+        let next_message = messages.peek().map(|(_, message)| message.date);
+        let next_event = system.peek().map(|event| event.date);
+        let date = match (next_message, next_event) {
+            (Some(message), Some(event)) => message.min(event),
+            (Some(date), None) | (None, Some(date)) => date,
+            (None, None) => break,
+        };
+
+        let day = time_format.date(date);
+        if last_date != Some(day) {
+            lines.push(ChatLine::Date(day));
+            last_date = Some(day);
+            previous = None;
+        }
+
+        if next_event.is_some_and(|event| next_message.is_none_or(|message| event < message)) {
+            let Some(event) = system.next() else {
+                break;
+            };
+            let time = time_format.time(event.date);
+            system_lines.push(format!("{time} {}", system_text(&event.kind)));
+            lines.push(ChatLine::System(system_lines.len() - 1));
+            // A system event in between ends a group of messages
+            previous = None;
+        } else if let Some((index, message)) = messages.next() {
+            let continued = previous.is_some_and(|previous| {
+                previous.userid == message.userid && message.date - previous.date < GROUP_WINDOW
+            });
+            lines.push(ChatLine::Message {
+                id: message.id,
+                index,
+                continued,
+            });
+            previous = Some(message);
+            if let Some(mut names) = readers.remove(&index) {
+                names.sort_by_key(|name| name.to_lowercase());
+                system_lines.push(tf("chat.read_by", &[("names", &names.join(", "))]));
+                lines.push(ChatLine::Read(system_lines.len() - 1));
+                previous = None;
+            }
+        }
+    }
+    (lines, system_lines)
+}
+
+/// Describes a system event.
+fn system_text(kind: &SystemEventKind) -> String {
+    match kind {
+        SystemEventKind::UserJoined { username, .. } => tf("system.joined", &[("name", username)]),
+        SystemEventKind::UserRenamed {
+            old_name, new_name, ..
+        } => tf("system.renamed", &[("old", old_name), ("new", new_name)]),
+        SystemEventKind::UserBanned { username, .. } => tf("system.banned", &[("name", username)]),
+        SystemEventKind::TopicChanged { topic, .. } => tf("system.topic", &[("topic", topic)]),
+        SystemEventKind::MotdChanged { text } if text.is_empty() => t("system.motd_removed").into(),
+        SystemEventKind::MotdChanged { text } => tf("system.motd", &[("text", text)]),
+        SystemEventKind::MessageDeleted { .. } => t("system.deleted").into(),
+        SystemEventKind::DisplayNameChanged {
+            username,
+            displayname: Some(displayname),
+            ..
+        } => tf(
+            "system.display_name",
+            &[("name", username), ("display_name", displayname)],
+        ),
+        SystemEventKind::DisplayNameChanged { username, .. } => {
+            tf("system.display_name_removed", &[("name", username)])
+        }
+        SystemEventKind::PresenceChanged {
+            username, online, ..
+        } => {
+            let key = if *online {
+                "system.online"
+            } else {
+                "system.offline"
+            };
+            tf(key, &[("name", username)])
+        }
+        SystemEventKind::SlowModeChanged { seconds: 0, .. } => t("system.slow_mode_off").into(),
+        SystemEventKind::SlowModeChanged { seconds, .. } => {
+            tf("system.slow_mode", &[("seconds", &seconds.to_string())])
+        }
+        SystemEventKind::MessagesRead { userid, .. } => {
+            tf("system.read", &[("userid", &userid.to_string())])
+        }
+    }
+}
+
+/// Returns the text and style of a system event, centered in a list of the given width.
+fn system_line(text: &str, width: usize, theme: Theme) -> (String, Style) {
+    // Without colors, the position alone does not tell it apart from messages
+    let text = if theme.colors() {
+        text.to_string()
+    } else {
+        format!("{}{text}", t("system.prefix"))
+    };
+    let padding = width.saturating_sub(Span::raw(text.as_str()).width()) / 2;
+    let style = theme.color(Color::DarkGray).add_modifier(Modifier::DIM);
+    (format!("{}{text}", " ".repeat(padding)), style)
+}
+
+/// Returns the text and style of the line telling who read the conversation up to the message above it, at the
+/// right of a list of the given width.
+fn read_line(text: &str, width: usize, theme: Theme) -> (String, Style) {
+    let padding = width.saturating_sub(Span::raw(text).width());
+    let style = theme.color(Color::DarkGray).add_modifier(Modifier::ITALIC);
+    (format!("{}{text}", " ".repeat(padding)), style)
+}
+
+/// Returns the text and style of a message the logged in user wrote, in a list of the given width.
+fn own_message(
+    text: &str,
+    own_messages: OwnMessages,
+    width: usize,
+    theme: Theme,
+) -> (String, Style) {
+    // Without colors, bold text might not be noticeable either
+    let text = if theme.colors() {
+        text.to_string()
+    } else {
+        format!("{}{text}", t("chat.own_marker"))
+    };
+    let style = Style::default().add_modifier(Modifier::BOLD);
+    match own_messages {
+        OwnMessages::Bold => (text, style),
+        OwnMessages::Color => (text, style.patch(theme.color(Color::Cyan))),
+        OwnMessages::Right => {
+            let padding = width.saturating_sub(Span::raw(text.as_str()).width());
+            (format!("{}{text}", " ".repeat(padding)), style)
+        }
+    }
+}
+
+/// Returns true if both lines show the same message, even if it moved.
+fn same_message(a: ChatLine, b: ChatLine) -> bool {
+    match (a, b) {
+        (ChatLine::Message { id: a, .. }, ChatLine::Message { id: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+/// How many messages a single turn of the mouse wheel scrolls.
+const SCROLL_STEP: usize = 3;
+
+impl ChatWindow {
+    fn new(username: &str, data: &ChatData) -> Self {
+        Self {
+            title: username.to_string(),
+            conversation: (Conversation::Chat, t("chat.main_chat").into()),
+            message_list: Vec::new(),
+            revision: None,
+            formatted: HashMap::new(),
+            system_lines: Vec::new(),
+            own_messages: data.config.ui.own_messages,
+            message_composer: String::new(),
+            status: None,
+            retrying: None,
+            scroll: 0,
+            showing_ends: (false, false),
+            selected: None,
+            misspelled: Vec::new(),
+            suggestions: None,
+            saved: None,
+            attachments: None,
+            search: None,
+            online: None,
+            cooldown: None,
+        }
+    }
+
+    /// Returns the range of messages shown in a message list of the given height.
+    fn visible_messages(&self, height: u16) -> Range<usize> {
+        let count = usize::from(height.saturating_sub(2));
+        let end = self.message_list.len().saturating_sub(self.scroll);
+        end.saturating_sub(count)..end
+    }
+}
+
+/// Returns the style notifications of the given severity are shown in.
+pub fn severity_style(severity: Severity, theme: Theme) -> Style {
+    match severity {
+        Severity::Info => Style::default(),
+        Severity::Warning => theme.color(Color::Yellow),
+        Severity::Error => theme.color(Color::Red),
+    }
+}
+
+/// Returns the text of a notification, stating its severity if colors can not show it.
+pub fn notification_text(notification: &Notification, theme: Theme) -> String {
+    let prefix = match notification.severity {
+        _ if theme.colors() => "",
+        Severity::Info => "",
+        Severity::Warning => t("notifications.warning"),
+        Severity::Error => t("notifications.error"),
+    };
+    format!("{prefix}{}", notification.text)
+}
+
+/// Checks the address in the login window and whether a chat server answers at it, unless that was already done
+/// for the address. Shows what is wrong in the status line. Returns true if the server can be used.
+async fn check_server(form: &mut LoginWindow, data: &mut ChatData) -> bool {
+    let address = form.address.content.trim();
+    if form
+        .server
+        .as_ref()
+        .is_some_and(|(checked, _)| checked == address)
+    {
+        return true;
+    }
+
+    form.server = None;
+    let builder = ClientBuilder::from_config(&data.config.network).shutdown(data.shutdown.child());
+    match builder.probe(address).await {
+        Ok(info) => {
+            if let Some(notice) = UpdateNotice::for_server(&info) {
+                data.notice_update(notice);
+            }
+            form.server = Some((address.to_string(), info));
+            form.status_message = None;
+            true
+        }
+        Err(e) => {
+            form.status_message = Some(e.localized());
+            false
+        }
+    }
+}
+
+/// Creates a row of options to choose one from, like whether to login or register.
+pub fn choice_ui<'a>(options: &[&str], selected: usize, focused: bool, theme: Theme) -> Spans<'a> {
+    let style = theme.focus(focused);
+    let unselected = style.patch(theme.color(Color::DarkGray));
+    let mut spans = vec![Span::raw(theme.marker(focused))];
+    for (index, option) in options.iter().enumerate() {
+        if index > 0 {
+            spans.push(Span::styled(" | ", Style::default()));
+        }
+        let (choice, style) = if index == selected {
+            ("(*) ", style.add_modifier(Modifier::UNDERLINED))
+        } else {
+            ("( ) ", unselected)
+        };
+        // Without colors, the underline alone is too easy to miss
+        let choice = if theme.colors() { "" } else { choice };
+        spans.push(Span::styled(format!("{choice}{option}"), style));
+    }
+    Spans::from(spans)
+}
+
+/// Splits the login window into the three form fields, the intent row, the invite code field, the remember me
+/// row, the status line and the hint. The invite code field takes no space unless it is asked for.
+fn login_layout(area: Rect, invite: bool) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Length(if invite { 3 } else { 0 }),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            // The banner and description of the server, below the form so it does not move
+            Constraint::Min(0),
+        ])
+        .split(area)
+}
+
+/// Returns the color the server is highlighted in, its accent color if it has one.
+fn accent_color(info: Option<&ServerInfo>) -> Color {
+    match info.and_then(ServerInfo::accent_rgb) {
+        Some((red, green, blue)) => Color::Rgb(red, green, blue),
+        None => Color::Yellow,
+    }
+}
+
+/// Shows the banner of the server in its accent color, followed by its description.
+fn branding_text(info: &ServerInfo, theme: Theme) -> Text<'static> {
+    let mut lines = Vec::new();
+    if let Some(banner) = &info.banner {
+        let style = theme.color(accent_color(Some(info)));
+        // Every line is padded to the same width, so centering them keeps the art in shape
+        let width = banner.lines().map(|line| Span::raw(line).width()).max();
+        let width = width.unwrap_or(0);
+        lines.push(Spans::default());
+        lines.extend(banner.lines().map(|line| {
+            let padding = width - Span::raw(line).width();
+            Spans::from(Span::styled(
+                format!("{line}{}", " ".repeat(padding)),
+                style,
+            ))
+        }));
+    }
+    if let Some(description) = &info.description {
+        lines.push(Spans::default());
+        lines.extend(
+            description
+                .lines()
+                .map(|line| Spans::from(line.to_string())),
+        );
+    }
+    Text::from(lines)
+}
+
+/// Returns the area within the border of a ``Window``.
+fn window_inner(area: Rect) -> Rect {
+    Block::default().borders(Borders::TOP).inner(area)
+}
+
+/// How wide the list of the users that are online is.
+const PRESENCE_WIDTH: u16 = 24;
+
+/// Splits the chat window into the message list, the composer, the status line and the list of the users that are
+/// online. That list is left empty without ``presence``, or if the window is too narrow to spare the room.
+fn chat_layout(area: Rect, presence: bool) -> Vec<Rect> {
+    let mut layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(10),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .split(area);
+    let width = if presence && area.width >= PRESENCE_WIDTH * 3 {
+        PRESENCE_WIDTH
+    } else {
+        0
+    };
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(10), Constraint::Length(width)])
+        .split(layout[0]);
+    layout[0] = top[0];
+    layout.push(top[1]);
+    layout
+}
+
+/// Splits the content of the composer into spans, underlining the misspelled words.
+fn composer_spans<'a>(content: &'a str, misspelled: &[Range<usize>], theme: Theme) -> Spans<'a> {
+    let style = theme.color(Color::Red).add_modifier(Modifier::UNDERLINED);
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for word in misspelled {
+        spans.push(Span::raw(&content[end..word.start]));
+        spans.push(Span::styled(&content[word.clone()], style));
+        end = word.end;
+    }
+    spans.push(Span::raw(&content[end..]));
+    Spans::from(spans)
+}
+
+/// Returns the area the suggestions are shown in, right above their word in the composer.
+fn suggestions_area(composer: Rect, content: &str, suggestions: &Suggestions) -> Rect {
+    let width_of = |text: &str| u16::try_from(Span::raw(text).width()).unwrap_or(u16::MAX);
+    let longest = suggestions
+        .options
+        .iter()
+        .map(|option| width_of(option))
+        .chain([width_of(t("spelling.title"))])
+        .max()
+        .unwrap_or_default();
+    // Room for the borders and the marker of the selected option
+    let width = longest.saturating_add(4).min(composer.width);
+    let height = u16::try_from(suggestions.options.len() + 2).unwrap_or(u16::MAX);
+
+    let word_column = composer.x + 1 + width_of(&content[..suggestions.word.start]);
+    let x = word_column.min(composer.right().saturating_sub(width));
+    Rect::new(x, composer.y.saturating_sub(height), width, height)
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    (area.left()..area.right()).contains(&column) && (area.top()..area.bottom()).contains(&row)
+}
+
+async fn handle_chat_window_input(chat: &mut ChatWindow, event: &Event, data: &mut ChatData) {
+    if let Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind: _,
+        state: _,
+    }) = event
+    {
+        // Whilst the saved messages are listed, keys only go to the list
+        if chat.saved.is_some() {
+            handle_saved_input(chat, *code, data).await;
+            return;
+        }
+        if chat.attachments.is_some() {
+            handle_attachments_input(chat, *code, data);
+            return;
+        }
+        if chat.search.is_some() {
+            handle_search_input(chat, *code, data);
+            return;
+        }
+
+        if let Some(suggestions) = &mut chat.suggestions {
+            match code {
+                KeyCode::Up => suggestions.selected = suggestions.selected.saturating_sub(1),
+                KeyCode::Down => {
+                    suggestions.selected =
+                        (suggestions.selected + 1).min(suggestions.options.len() - 1);
+                }
+                KeyCode::Enter => {
+                    let replacement = &suggestions.options[suggestions.selected];
+                    chat.message_composer
+                        .replace_range(suggestions.word.clone(), replacement);
+                    check_spelling(chat, data);
+                }
+                KeyCode::Esc => chat.suggestions = None,
+                // Any other key closes the suggestions and is handled as usual
+                _ => chat.suggestions = None,
+            }
+            if chat.suggestions.is_some() || matches!(code, KeyCode::Enter | KeyCode::Esc) {
+                return;
+            }
+        }
+
+        match code {
+            KeyCode::Char('s') if modifiers.contains(KeyModifiers::CONTROL) => {
+                suggest_spelling(chat, data);
+            }
+            KeyCode::Char('f') if modifiers.contains(KeyModifiers::CONTROL) => {
+                forward_selected(chat, data).await;
+            }
+            KeyCode::Char('b') if modifiers.contains(KeyModifiers::CONTROL) => {
+                save_selected(chat, data).await;
+            }
+            KeyCode::Char('g') if modifiers.contains(KeyModifiers::CONTROL) => {
+                open_saved(chat, data).await;
+            }
+            KeyCode::Char('l') if modifiers.contains(KeyModifiers::CONTROL) => {
+                copy_permalink(chat, data);
+            }
+            KeyCode::Char('o') if modifiers.contains(KeyModifiers::CONTROL) => {
+                open_permalink(chat, data);
+            }
+            KeyCode::Char('d') if modifiers.contains(KeyModifiers::CONTROL) => {
+                open_attachments(chat, data);
+            }
+            KeyCode::Enter => {
+                send_composer(chat, data).await;
+            }
+            KeyCode::Char(c) => {
+                chat.message_composer.push(*c);
+                check_spelling(chat, data);
+            }
+            KeyCode::Backspace => {
+                chat.message_composer.pop();
+                check_spelling(chat, data);
+            }
+            KeyCode::Up => {
+                if let Some(selected) = chat.selected {
+                    chat.selected = Some(selected.saturating_sub(1));
+                }
+            }
+            KeyCode::Down => {
+                if let Some(selected) = chat.selected {
+                    chat.selected =
+                        Some((selected + 1).min(chat.message_list.len().saturating_sub(1)));
+                }
+            }
+            KeyCode::Esc => chat.selected = None,
+            _ => {}
+        }
+    }
+}
+
+/// Sends the content of the composer, clearing it on success. Returns false if the message could not be sent.
+async fn send_composer(chat: &mut ChatWindow, data: &mut ChatData) -> bool {
+    if chat.message_composer.trim() == "/debug" {
+        debug_command(chat, data);
+        return true;
+    }
+    if let Some(command) = ConversationCommand::parse(&chat.message_composer) {
+        conversation_command(chat, data, command).await;
+        return true;
+    }
+    if let Some(command) = PushCommand::parse(&chat.message_composer) {
+        push_command(chat, data, command).await;
+        return true;
+    }
+    if let Some(command) = ProfileCommand::parse(&chat.message_composer) {
+        profile_command(chat, data, command).await;
+        return true;
+    }
+    if let Some(command) = BlockCommand::parse(&chat.message_composer) {
+        block_command(chat, data, command).await;
+        return true;
+    }
+    if let Some(command) = UsersCommand::parse(&chat.message_composer) {
+        users_command(chat, data, command).await;
+        return true;
+    }
+    if chat.message_composer.trim() == "/sessions" {
+        sessions_command(chat, data).await;
+        return true;
+    }
+    if let Some(query) = chat.message_composer.strip_prefix("/search ") {
+        let query = query.trim().to_string();
+        search(chat, data, &query).await;
+        return true;
+    }
+    if let Some(path) = chat.message_composer.strip_prefix("/attach ") {
+        let path = expand_home(path.trim());
+        return attach_file(chat, data, &path).await;
+    }
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return false;
+    };
+    let client = &session_data.client;
+    let conversation = session_data.conversation;
+    let mut result = client.send_to(conversation, &chat.message_composer).await;
+    // The session might have expired, so try to login again with the refresh token
+    if result.as_ref().err().and_then(Error::code) == Some(ErrorCode::NotAuthorized)
+        && client.can_refresh()
+        && client.refresh().await.is_ok()
+    {
+        result = client.send_to(conversation, &chat.message_composer).await;
+    }
+    let sent = result.is_ok();
+    if let Err(Error::SlowMode(Some(seconds))) = result {
+        chat.cooldown = Some(Cooldown::new(conversation, seconds));
+    }
+    let (severity, message) = match result {
+        Ok(()) => {
+            chat.message_composer.clear();
+            chat.misspelled.clear();
+            (Severity::Info, t("chat.sent").into())
+        }
+        Err(e) => sending_failed(e, data),
+    };
+
+    data.notifications.push(severity, &chat.title, message);
+    sent
+}
+
+/// A command typed into the composer that moves between conversations.
+enum ConversationCommand {
+    /// ``/channels`` lists the channels.
+    List,
+    /// ``/join <name>`` joins the channel and shows it.
+    Join(String),
+    /// ``/create <name>`` creates a channel and shows it.
+    Create(String),
+    /// ``/leave`` leaves the channel shown and goes back to the main chat.
+    Leave,
+    /// ``/slowmode <seconds>`` makes the members of the channel shown wait between their messages, ``0`` turns it
+    /// off again.
+    SlowMode(u32),
+    /// ``/notify all``, ``/notify mentions`` or ``/notify mute`` sets what the user is told about in the channel
+    /// shown.
+    Notify(NotificationLevel),
+    /// ``/dm <name>`` shows the direct messages with the user.
+    Direct(String),
+    /// ``/chat`` goes back to the main chat.
+    Chat,
+}
+
+impl ConversationCommand {
+    fn parse(text: &str) -> Option<Self> {
+        let (command, argument) = match text.trim().split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (text.trim(), ""),
+        };
+        let argument = argument.trim_start_matches(['#', '@']).to_string();
+        match (command, argument.is_empty()) {
+            ("/channels", true) => Some(Self::List),
+            ("/join", false) => Some(Self::Join(argument)),
+            ("/create", false) => Some(Self::Create(argument)),
+            ("/leave", true) => Some(Self::Leave),
+            ("/slowmode", false) => argument.parse().ok().map(Self::SlowMode),
+            ("/notify", false) => match argument.as_str() {
+                "mute" => Some(Self::Notify(NotificationLevel::Muted)),
+                level => NotificationLevel::from_key(level).map(Self::Notify),
+            },
+            ("/dm", false) => Some(Self::Direct(argument)),
+            ("/chat", true) => Some(Self::Chat),
+            _ => None,
+        }
+    }
+}
+
+/// Runs the command, switching the session to another conversation if it leads to one. The outcome is shown as a
+/// notification.
+async fn conversation_command(
+    chat: &mut ChatWindow,
+    data: &mut ChatData,
+    command: ConversationCommand,
+) {
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let Some(session_data) = data.logins.get_mut(&chat.title) else {
+        return;
+    };
+    let feature = match command {
+        ConversationCommand::Chat => None,
+        ConversationCommand::Direct(_) => Some(Feature::DirectMessages),
+        ConversationCommand::SlowMode(_) => Some(Feature::SlowMode),
+        ConversationCommand::Notify(_) => Some(Feature::ChannelNotifications),
+        _ => Some(Feature::Channels),
+    };
+    if feature
+        .is_some_and(|feature| !supported(chat, session_data, feature, &mut data.notifications))
+    {
+        return;
+    }
+    let client = session_data.client.clone();
+    let result = match command {
+        ConversationCommand::List => client.get_channels(false).await.map(|channels| {
+            let names: Vec<String> = channels
+                .into_iter()
+                .map(|channel| {
+                    session_data
+                        .channels
+                        .insert(channel.id, channel.name.clone());
+                    let level = session_data.notification_level(Conversation::Channel(channel.id));
+                    match notification_marker(level) {
+                        Some(marker) => format!("#{} {marker}", channel.name),
+                        None => format!("#{}", channel.name),
+                    }
+                })
+                .collect();
+            if names.is_empty() {
+                (Severity::Info, t("channels.none").into())
+            } else {
+                (
+                    Severity::Info,
+                    tf("channels.list", &[("channels", &names.join(", "))]),
+                )
+            }
+        }),
+        ConversationCommand::Join(name) => match client.get_channels(false).await {
+            Ok(channels) => match channels.into_iter().find(|channel| channel.name == name) {
+                Some(channel) => client.join_channel(channel.id).await.map(|channel| {
+                    session_data
+                        .channels
+                        .insert(channel.id, channel.name.clone());
+                    session_data.switch_to(Conversation::Channel(channel.id));
+                    (
+                        Severity::Info,
+                        tf("channels.joined", &[("channel", &channel.name)]),
+                    )
+                }),
+                None => Ok((
+                    Severity::Warning,
+                    tf("channels.not_found", &[("channel", &name)]),
+                )),
+            },
+            Err(e) => Err(e),
+        },
+        ConversationCommand::Create(name) => client.create_channel(&name).await.map(|channel| {
+            session_data
+                .channels
+                .insert(channel.id, channel.name.clone());
+            session_data.switch_to(Conversation::Channel(channel.id));
+            (
+                Severity::Info,
+                tf("channels.created", &[("channel", &channel.name)]),
+            )
+        }),
+        ConversationCommand::Leave => match session_data.conversation {
+            Conversation::Channel(id) => client.leave_channel(id).await.map(|()| {
+                let name = session_data.conversation_name();
+                session_data.switch_to(Conversation::Chat);
+                (Severity::Info, tf("channels.left", &[("channel", &name)]))
+            }),
+            _ => Ok((Severity::Warning, t("channels.not_in_channel").into())),
+        },
+        ConversationCommand::SlowMode(seconds) => match session_data.conversation {
+            Conversation::Channel(id) => client.set_slow_mode(id, seconds).await.map(|_| {
+                let name = session_data.conversation_name();
+                let text = if seconds == 0 {
+                    tf("channels.slow_mode_off", &[("channel", &name)])
+                } else {
+                    tf(
+                        "channels.slow_mode",
+                        &[("channel", &name), ("seconds", &seconds.to_string())],
+                    )
+                };
+                (Severity::Info, text)
+            }),
+            _ => Ok((Severity::Warning, t("channels.slow_mode_no_channel").into())),
+        },
+        ConversationCommand::Notify(level) => match session_data.conversation {
+            Conversation::Channel(id) => {
+                client.set_channel_notifications(id, level).await.map(|()| {
+                    if level == NotificationLevel::All {
+                        session_data.notification_levels.remove(&id);
+                    } else {
+                        session_data.notification_levels.insert(id, level);
+                    }
+                    let name = session_data.conversation_name();
+                    let key = match level {
+                        NotificationLevel::All => "channels.notify_all",
+                        NotificationLevel::Mentions => "channels.notify_mentions",
+                        NotificationLevel::Muted => "channels.notify_muted",
+                    };
+                    (Severity::Info, tf(key, &[("channel", &name)]))
+                })
+            }
+            _ => Ok((Severity::Warning, t("channels.notify_no_channel").into())),
+        },
+        ConversationCommand::Direct(name) => {
+            let user = session_data
+                .known_usernames
+                .iter()
+                .find(|(_, username)| **username == name)
+                .map(|(id, _)| *id);
+            match user {
+                Some(id) => {
+                    session_data.switch_to(Conversation::Direct(id));
+                    Ok((Severity::Info, tf("channels.direct", &[("name", &name)])))
+                }
+                None => Ok((
+                    Severity::Warning,
+                    tf("channels.unknown_user", &[("name", &name)]),
+                )),
+            }
+        }
+        ConversationCommand::Chat => {
+            session_data.switch_to(Conversation::Chat);
+            return;
+        }
+    };
+    let (severity, text) = result.unwrap_or_else(|e| {
+        (
+            Severity::Error,
+            tf("channels.failed", &[("error", &e.localized())]),
+        )
+    });
+    data.notifications.push(severity, &chat.title, text);
+}
+
+/// The mark put next to a channel that notifies about less than every message. ``None`` for the ones that notify
+/// about every message.
+fn notification_marker(level: NotificationLevel) -> Option<&'static str> {
+    match level {
+        NotificationLevel::All => None,
+        NotificationLevel::Mentions => Some(t("channels.mentions_marker")),
+        NotificationLevel::Muted => Some(t("channels.muted_marker")),
+    }
+}
+
+/// A command typed into the composer that sets up push notifications for mentions and direct messages.
+enum PushCommand {
+    /// ``/push`` tells where notifications are pushed to.
+    Show,
+    /// ``/push ntfy <url> [token]`` or ``/push gotify <url> <token>`` pushes them there.
+    Set(PushSettings),
+    /// ``/push off`` stops pushing them.
+    Off,
+    /// Any other ``/push`` command, which is answered with how to use it.
+    Usage,
+}
+
+impl PushCommand {
+    fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        if words.next() != Some("/push") {
+            return None;
+        }
+        let words: Vec<&str> = words.collect();
+        let provider = match words.first() {
+            None => return Some(Self::Show),
+            Some(&"off") if words.len() == 1 => return Some(Self::Off),
+            Some(&"ntfy") if (2..=3).contains(&words.len()) => PushProvider::Ntfy,
+            Some(&"gotify") if words.len() == 3 => PushProvider::Gotify,
+            Some(_) => return Some(Self::Usage),
+        };
+        Some(Self::Set(PushSettings {
+            provider,
+            url: words[1].to_string(),
+            token: words.get(2).map(|token| (*token).to_string()),
+            mentions: true,
+            direct_messages: true,
+        }))
+    }
+}
+
+/// Runs the command. The outcome is shown as a notification.
+async fn push_command(chat: &mut ChatWindow, data: &mut ChatData, command: PushCommand) {
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::PushNotifications,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let client = &session_data.client;
+    let result = match command {
+        PushCommand::Show => client
+            .get_push_settings()
+            .await
+            .map(|settings| match settings {
+                Some(settings) => tf("push.current", &[("url", &settings.url)]),
+                None => t("push.none").into(),
+            }),
+        PushCommand::Set(settings) => client
+            .set_push_settings(&settings)
+            .await
+            .map(|()| tf("push.set", &[("url", &settings.url)])),
+        PushCommand::Off => client
+            .delete_push_settings()
+            .await
+            .map(|()| t("push.off").into()),
+        PushCommand::Usage => Ok(t("push.usage").into()),
+    };
+    let (severity, text) = match result {
+        Ok(text) => (Severity::Info, text),
+        Err(e) => (
+            Severity::Error,
+            tf("push.failed", &[("error", &e.localized())]),
+        ),
+    };
+    data.notifications.push(severity, &chat.title, text);
+}
+
+/// A command typed into the composer that shows or changes profiles.
+enum ProfileCommand {
+    /// ``/profile [name]`` shows the profile of the user, or the own one without a name.
+    Show(Option<String>),
+    /// ``/nick [name]`` sets the display name, or removes it without a name.
+    DisplayName(Option<String>),
+    /// ``/bio [text]`` sets the bio, or empties it without a text.
+    Bio(String),
+    /// ``/lastseen on|off`` shows others when the user was last active, or stops doing so.
+    ShowLastSeen(bool),
+}
+
+impl ProfileCommand {
+    fn parse(text: &str) -> Option<Self> {
+        let (command, argument) = match text.trim().split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (text.trim(), ""),
+        };
+        let argument = (!argument.is_empty()).then(|| argument.to_string());
+        match command {
+            "/profile" => Some(Self::Show(
+                argument.map(|name| name.trim_start_matches('@').to_string()),
+            )),
+            "/nick" => Some(Self::DisplayName(argument)),
+            "/bio" => Some(Self::Bio(argument.unwrap_or_default())),
+            "/lastseen" => match argument.as_deref() {
+                Some("on") => Some(Self::ShowLastSeen(true)),
+                Some("off") => Some(Self::ShowLastSeen(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Runs the command. The outcome is shown as a notification.
+async fn profile_command(chat: &mut ChatWindow, data: &mut ChatData, command: ProfileCommand) {
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::Profiles,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let client = &session_data.client;
+    let result = match command {
+        ProfileCommand::Show(None) => client
+            .get_profile(None)
+            .await
+            .map(|profile| profile_text(&profile, &data.time_format)),
+        ProfileCommand::Show(Some(name)) => {
+            let user = session_data
+                .known_usernames
+                .iter()
+                .find(|(_, username)| **username == name)
+                .map(|(id, _)| *id);
+            match user {
+                Some(id) => client
+                    .get_profile(Some(id))
+                    .await
+                    .map(|profile| profile_text(&profile, &data.time_format)),
+                None => Ok(tf("channels.unknown_user", &[("name", &name)])),
+            }
+        }
+        ProfileCommand::DisplayName(name) => {
+            let update = ProfileUpdate {
+                displayname: Some(name),
+                ..ProfileUpdate::default()
+            };
+            client
+                .update_profile(&update)
+                .await
+                .map(|profile| match profile.displayname {
+                    Some(name) => tf("profile.display_name_set", &[("name", &name)]),
+                    None => t("profile.display_name_removed").into(),
+                })
+        }
+        ProfileCommand::Bio(bio) => {
+            let update = ProfileUpdate {
+                bio: Some(bio),
+                ..ProfileUpdate::default()
+            };
+            client
+                .update_profile(&update)
+                .await
+                .map(|_| t("profile.bio_set").into())
+        }
+        ProfileCommand::ShowLastSeen(show) => {
+            let update = ProfileUpdate {
+                show_last_seen: Some(show),
+                ..ProfileUpdate::default()
+            };
+            client.update_profile(&update).await.map(|_| {
+                if show {
+                    t("profile.last_seen_shown").into()
+                } else {
+                    t("profile.last_seen_hidden").into()
+                }
+            })
+        }
+    };
+    let (severity, text) = match result {
+        Ok(text) => (Severity::Info, text),
+        Err(e) => (
+            Severity::Error,
+            tf("profile.failed", &[("error", &e.localized())]),
+        ),
+    };
+    data.notifications.push(severity, &chat.title, text);
+}
+
+/// A command typed into the composer that hides the messages of other users or shows them again.
+enum BlockCommand {
+    /// ``/blocked`` lists the users that are blocked.
+    List,
+    /// ``/block <name>`` blocks the user, so their messages are no longer shown.
+    Block(String),
+    /// ``/unblock <name>`` shows the messages of the user again.
+    Unblock(String),
+}
+
+impl BlockCommand {
+    fn parse(text: &str) -> Option<Self> {
+        let (command, argument) = match text.trim().split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (text.trim(), ""),
+        };
+        let argument = argument.trim_start_matches('@').to_string();
+        match (command, argument.is_empty()) {
+            ("/blocked", true) => Some(Self::List),
+            ("/block", false) => Some(Self::Block(argument)),
+            ("/unblock", false) => Some(Self::Unblock(argument)),
+            _ => None,
+        }
+    }
+}
+
+/// Runs the command. The outcome is shown as a notification.
+async fn block_command(chat: &mut ChatWindow, data: &mut ChatData, command: BlockCommand) {
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let Some(session_data) = data.logins.get_mut(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::Blocking,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let client = session_data.client.clone();
+    let result = match command {
+        BlockCommand::List => client.get_blocked_users().await.map(|users| {
+            if users.is_empty() {
+                t("blocks.none").into()
+            } else {
+                let mut names: Vec<&str> =
+                    users.iter().map(|user| user.username.as_str()).collect();
+                names.sort_unstable();
+                tf("blocks.list", &[("names", &names.join(", "))])
+            }
+        }),
+        BlockCommand::Block(name) => {
+            let user = session_data
+                .known_usernames
+                .iter()
+                .find(|(_, username)| **username == name)
+                .map(|(id, _)| *id);
+            match user {
+                Some(id) => client.block_user(id).await.map(|()| {
+                    session_data.hide_user(id);
+                    tf("blocks.blocked", &[("name", &name)])
+                }),
+                None => Ok(tf("channels.unknown_user", &[("name", &name)])),
+            }
+        }
+        // Blocked users may not have written anything shown, so they are looked up among the blocked ones
+        BlockCommand::Unblock(name) => match client.get_blocked_users().await {
+            Ok(users) => match users.into_iter().find(|user| user.username == name) {
+                Some(user) => client
+                    .unblock_user(user.id)
+                    .await
+                    .map(|()| tf("blocks.unblocked", &[("name", &name)])),
+                None => Ok(tf("blocks.not_blocked", &[("name", &name)])),
+            },
+            Err(e) => Err(e),
+        },
+    };
+    let (severity, text) = match result {
+        Ok(text) => (Severity::Info, text),
+        Err(e) => (
+            Severity::Error,
+            tf("blocks.failed", &[("error", &e.localized())]),
+        ),
+    };
+    data.notifications.push(severity, &chat.title, text);
+}
+
+/// ``/users`` followed by an order and filters in any order, like ``/users active moderator online``, lists the
+/// users for moderators and admins.
+enum UsersCommand {
+    List(UserSort, UserFilter),
+    /// A word that is neither an order nor a filter.
+    Invalid(String),
+}
+
+impl UsersCommand {
+    fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        if words.next() != Some("/users") {
+            return None;
+        }
+        let mut sort = UserSort::default();
+        let mut filter = UserFilter::default();
+        for word in words {
+            match word {
+                "online" => filter.online = Some(true),
+                "offline" => filter.online = Some(false),
+                "banned" => filter.banned = Some(true),
+                "unbanned" => filter.banned = Some(false),
+                _ => match (UserSort::from_key(word), Role::from_key(word)) {
+                    (Some(order), _) => sort = order,
+                    (None, Some(role)) => filter.role = Some(role),
+                    (None, None) => return Some(Self::Invalid(word.to_string())),
+                },
+            }
+        }
+        Some(Self::List(sort, filter))
+    }
+}
+
+/// Runs the command. The first page of users is shown as a notification.
+async fn users_command(chat: &mut ChatWindow, data: &mut ChatData, command: UsersCommand) {
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let (sort, filter) = match command {
+        UsersCommand::List(sort, filter) => (sort, filter),
+        UsersCommand::Invalid(word) => {
+            data.notifications.push(
+                Severity::Warning,
+                &chat.title,
+                tf("users.unknown_word", &[("word", &word)]),
+            );
+            return;
+        }
+    };
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::UserDirectory,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let result = session_data
+        .client
+        .get_user_directory(sort, &filter, &PageRequest::default())
+        .await;
+    let (severity, text) = match result {
+        Ok(page) if page.items.is_empty() => (Severity::Info, t("users.none").into()),
+        Ok(page) => {
+            let users: Vec<String> = page.items.iter().map(user_entry).collect();
+            let key = if page.next.is_some() {
+                "users.list_more"
+            } else {
+                "users.list"
+            };
+            (Severity::Info, tf(key, &[("users", &users.join(", "))]))
+        }
+        Err(e) => (
+            Severity::Error,
+            tf("users.failed", &[("error", &e.localized())]),
+        ),
+    };
+    data.notifications.push(severity, &chat.title, text);
+}
+
+/// Describes the user as listed by ``/users``, with their role unless it is the plain one and whether they are
+/// online or banned.
+fn user_entry(entry: &UserEntry) -> String {
+    let mut details = Vec::new();
+    if entry.user.role != Role::User {
+        details.push(entry.user.role.key().to_string());
+    }
+    if entry.online {
+        details.push(t("users.online").into());
+    }
+    if entry.banned {
+        details.push(t("users.banned").into());
+    }
+    if details.is_empty() {
+        entry.user.username.clone()
+    } else {
+        format!("{} ({})", entry.user.username, details.join(", "))
+    }
+}
+
+/// Describes the profile in one line, for a notification.
+fn profile_text(profile: &Profile, time_format: &TimeFormat) -> String {
+    let name = match &profile.displayname {
+        Some(displayname) => format!("{displayname} ({})", profile.username),
+        None => profile.username.clone(),
+    };
+    let mut text = if profile.bio.is_empty() {
+        tf("profile.show", &[("name", &name)])
+    } else {
+        tf(
+            "profile.show_bio",
+            &[("name", &name), ("bio", &profile.bio)],
+        )
+    };
+    if let Some(avatar) = &profile.avatar {
+        text.push(' ');
+        text.push_str(&tf("profile.avatar", &[("url", avatar)]));
+    }
+    if let Some(last_seen) = profile.last_seen {
+        text.push(' ');
+        text.push_str(&tf(
+            "profile.last_seen",
+            &[("time", &time_format.time(last_seen))],
+        ));
+    }
+    if let Some(joined) = profile.joined {
+        let date = time_format.date(joined).format("%Y-%m-%d").to_string();
+        text.push(' ');
+        text.push_str(&tf("profile.joined", &[("date", &date)]));
+    }
+    text
+}
+
+/// Lists the logins of the user in a notification. It is a warning if admins are acting as the user, so it stands
+/// out.
+async fn sessions_command(chat: &mut ChatWindow, data: &mut ChatData) {
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::Sessions,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let (severity, text) = match session_data.client.get_sessions().await {
+        Ok(sessions) => {
+            let list = sessions
+                .iter()
+                .map(|session| session_text(session, &data.time_format))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let text = tf(
+                "sessions.list",
+                &[("count", &sessions.len()), ("sessions", &list)],
+            );
+            if sessions
+                .iter()
+                .any(|session| session.impersonated_by.is_some())
+            {
+                (Severity::Warning, text)
+            } else {
+                (Severity::Info, text)
+            }
+        }
+        Err(e) => (
+            Severity::Error,
+            tf("sessions.failed", &[("error", &e.localized())]),
+        ),
+    };
+    data.notifications.push(severity, &chat.title, text);
+}
+
+/// Describes a login of the user in a few words, for the list of sessions.
+fn session_text(session: &Session, time_format: &TimeFormat) -> String {
+    let since = time_format.time(session.created);
+    let until = time_format.time(session.valid_until);
+    match (&session.impersonated_by, session.current) {
+        (Some(admin), _) => tf(
+            "sessions.impersonated",
+            &[("admin", admin), ("since", &since), ("until", &until)],
+        ),
+        (None, true) => tf("sessions.current", &[("since", &since)]),
+        (None, false) => tf("sessions.other", &[("since", &since), ("until", &until)]),
+    }
+}
+
+/// Writes the state of the session and the window to the log, so it can be sent along with a bug report.
+fn debug_command(chat: &mut ChatWindow, data: &mut ChatData) {
+    if let Some(session) = data.logins.get(&chat.title) {
+        session.log_state(&chat.title);
+    }
+    info!(
+        window = chat.title,
+        lines = chat.message_list.len(),
+        formatted = chat.formatted.len(),
+        system_lines = chat.system_lines.len(),
+        revision = ?chat.revision,
+        scroll = chat.scroll,
+        selected = ?chat.selected,
+        showing_ends = ?chat.showing_ends,
+        conversation = ?chat.conversation,
+        composer = chat.message_composer.len(),
+        "Window state"
+    );
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let path = data.log_file.display();
+    let text = tf("chat.debug_written", &[("path", &path)]);
+    data.notifications.push(Severity::Info, &chat.title, text);
+}
+
+/// Forwards the selected message to the main chat, if a message is selected.
+async fn forward_selected(chat: &mut ChatWindow, data: &mut ChatData) {
+    let Some(ChatLine::Message { id, .. }) = chat.selected.map(|index| chat.message_list[index])
+    else {
+        return;
+    };
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::Forwarding,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let client = &session_data.client;
+    let mut result = client.forward_message(id, ForwardTarget::Chat).await;
+    if result.as_ref().err().and_then(Error::code) == Some(ErrorCode::NotAuthorized)
+        && client.can_refresh()
+        && client.refresh().await.is_ok()
+    {
+        result = client.forward_message(id, ForwardTarget::Chat).await;
+    }
+    let (severity, message) = match result {
+        Ok(()) => (Severity::Info, t("chat.forwarded").into()),
+        Err(e) => sending_failed(e, data),
+    };
+
+    data.notifications.push(severity, &chat.title, message);
+}
+
+/// Saves the selected message, if a message is selected.
+async fn save_selected(chat: &ChatWindow, data: &mut ChatData) {
+    let Some(ChatLine::Message { id, .. }) = chat.selected.map(|index| chat.message_list[index])
+    else {
+        return;
+    };
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::SavedMessages,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let (severity, message) = match session_data.client.save_message(id).await {
+        Ok(()) => (Severity::Info, t("saved.saved").into()),
+        Err(e) => (
+            Severity::Error,
+            tf("saved.save_failed", &[("error", &e.localized())]),
+        ),
+    };
+    data.notifications.push(severity, &chat.title, message);
+}
+
+/// Fetches the saved messages and lists them.
+async fn open_saved(chat: &mut ChatWindow, data: &mut ChatData) {
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::SavedMessages,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let saved = match session_data.client.get_saved_messages().await {
+        Ok(saved) if saved.is_empty() => {
+            data.notifications
+                .push(Severity::Info, &chat.title, t("saved.empty"));
+            return;
+        }
+        Ok(saved) => saved,
+        Err(e) => {
+            let text = tf("saved.fetch_failed", &[("error", &e.localized())]);
+            data.notifications.push(Severity::Error, &chat.title, text);
+            return;
+        }
+    };
+
+    let items = saved
+        .iter()
+        .map(|saved| list_entry(&saved.message, session_data, &data.time_format))
+        .collect();
+    chat.saved = Some(ItemList { items, selected: 0 });
+}
+
+/// Formats the message for a list, with the date, as it could have been sent long ago.
+fn list_entry(
+    message: &Message,
+    session_data: &SessionData,
+    time_format: &TimeFormat,
+) -> (i32, String) {
+    let name = match session_data.shown_name(message.userid) {
+        Some(name) => name.clone(),
+        None => message.userid.to_string(),
+    };
+    let date = time_format.date(message.date).format("%Y-%m-%d");
+    let time = time_format.time(message.date);
+    (
+        message.id,
+        format!("{date} {time} {name}: {}", message.messagetext),
+    )
+}
+
+/// Searches the conversation shown for the words and lists the messages found, the newest first.
+async fn search(chat: &mut ChatWindow, data: &mut ChatData, query: &str) {
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(chat, session_data, Feature::Search, &mut data.notifications) {
+        return;
+    }
+    // The server reads the filters itself, they are only read here to search everywhere when a channel is named
+    let parsed = match SearchQuery::parse(query) {
+        Ok(parsed) => parsed,
+        Err(SearchSyntaxError::InvalidDate(date)) => {
+            let text = tf("search.invalid_date", &[("date", &date)]);
+            data.notifications.push(Severity::Error, &chat.title, text);
+            return;
+        }
+        Err(SearchSyntaxError::UnknownHas(what)) => {
+            let text = tf("search.unknown_has", &[("what", &what)]);
+            data.notifications.push(Severity::Error, &chat.title, text);
+            return;
+        }
+    };
+    let filter = SearchFilter {
+        conversation: parsed
+            .channel
+            .is_none()
+            .then_some(session_data.conversation),
+        ..SearchFilter::default()
+    };
+    let page = PageRequest::default();
+    let found = match session_data
+        .client
+        .search_messages(query, &filter, &page)
+        .await
+    {
+        Ok(found) if found.items.is_empty() => {
+            let text = tf("search.none", &[("query", &query)]);
+            data.notifications.push(Severity::Info, &chat.title, text);
+            return;
+        }
+        Ok(found) => found.items,
+        Err(e) => {
+            let text = tf("search.failed", &[("error", &e.localized())]);
+            data.notifications.push(Severity::Error, &chat.title, text);
+            return;
+        }
+    };
+
+    chat.message_composer.clear();
+    chat.misspelled.clear();
+    let items = found
+        .iter()
+        .map(|message| list_entry(message, session_data, &data.time_format))
+        .collect();
+    chat.search = Some((query.to_string(), ItemList { items, selected: 0 }));
+}
+
+/// Handles keys whilst the messages found are listed. Enter jumps to the selected message.
+fn handle_search_input(chat: &mut ChatWindow, code: KeyCode, data: &mut ChatData) {
+    let Some((_, found)) = &mut chat.search else {
+        return;
+    };
+    match code {
+        KeyCode::Up => found.select_previous(),
+        KeyCode::Down => found.select_next(),
+        KeyCode::Enter => {
+            let id = found.selected_id();
+            chat.search = None;
+            if let Some(session_data) = data.logins.get_mut(&chat.title) {
+                session_data.jump_to_message(id);
+            }
+        }
+        KeyCode::Esc => chat.search = None,
+        _ => {}
+    }
+}
+
+/// Handles keys whilst the saved messages are listed. Enter jumps to the selected message and Delete removes it.
+async fn handle_saved_input(chat: &mut ChatWindow, code: KeyCode, data: &mut ChatData) {
+    let Some(saved) = &mut chat.saved else {
+        return;
+    };
+    match code {
+        KeyCode::Up => saved.select_previous(),
+        KeyCode::Down => saved.select_next(),
+        KeyCode::Enter => {
+            let id = saved.selected_id();
+            chat.saved = None;
+            if let Some(session_data) = data.logins.get_mut(&chat.title) {
+                session_data.jump_to_message(id);
+            }
+        }
+        KeyCode::Delete => {
+            let Some(session_data) = data.logins.get(&chat.title) else {
+                return;
+            };
+            let id = saved.selected_id();
+            match session_data.client.unsave_message(id).await {
+                Ok(()) => {
+                    saved.items.remove(saved.selected);
+                    saved.selected = saved.selected.min(saved.items.len().saturating_sub(1));
+                    if saved.items.is_empty() {
+                        chat.saved = None;
+                    }
+                }
+                Err(e) => {
+                    let text = tf("saved.remove_failed", &[("error", &e.localized())]);
+                    data.notifications.push(Severity::Error, &chat.title, text);
+                }
+            }
+        }
+        KeyCode::Esc => chat.saved = None,
+        _ => {}
+    }
+}
+
+/// Sends the file at the path to the conversation shown, with the composer cleared on success. Returns false if it
+/// could not be sent.
+async fn attach_file(chat: &mut ChatWindow, data: &mut ChatData, path: &Path) -> bool {
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return false;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::Attachments,
+        &mut data.notifications,
+    ) {
+        return false;
+    }
+    let file = match FileUpload::read(path) {
+        Ok(file) => file,
+        Err(e) => {
+            let text = tf(
+                "attachments.read_failed",
+                &[("path", &path.display()), ("error", &e)],
+            );
+            data.notifications.push(Severity::Error, &chat.title, text);
+            return false;
+        }
+    };
+    let client = &session_data.client;
+    let conversation = session_data.conversation;
+    let files = [file];
+    let mut result = client.send_attachments(conversation, "", &files).await;
+    if result.as_ref().err().and_then(Error::code) == Some(ErrorCode::NotAuthorized)
+        && client.can_refresh()
+        && client.refresh().await.is_ok()
+    {
+        result = client.send_attachments(conversation, "", &files).await;
+    }
+    let sent = result.is_ok();
+    if let Err(Error::SlowMode(Some(seconds))) = result {
+        chat.cooldown = Some(Cooldown::new(conversation, seconds));
+    }
+    let (severity, message) = match result {
+        Ok(()) => {
+            chat.message_composer.clear();
+            chat.misspelled.clear();
+            (
+                Severity::Info,
+                tf("attachments.sent", &[("name", &files[0].name)]),
+            )
+        }
+        Err(e) => sending_failed(e, data),
+    };
+    data.notifications.push(severity, &chat.title, message);
+    sent
+}
+
+/// Resolves a leading ``~`` to the home directory, as the path is not passed through a shell.
+fn expand_home(path: &str) -> PathBuf {
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Lists the files attached to the selected message, as described in the message.
+fn open_attachments(chat: &mut ChatWindow, data: &mut ChatData) {
+    let Some(ChatLine::Message { index, .. }) = chat.selected.map(|index| chat.message_list[index])
+    else {
+        return;
+    };
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    if !supported(
+        chat,
+        session_data,
+        Feature::Attachments,
+        &mut data.notifications,
+    ) {
+        return;
+    }
+    let attachments = session_data.messages[index].attachments.clone();
+    if attachments.is_empty() {
+        data.notifications
+            .push(Severity::Info, &chat.title, t("attachments.none"));
+        return;
+    }
+
+    let items = attachments
+        .iter()
+        .map(|attachment| (attachment.id, attachment_label(attachment)))
+        .collect();
+    chat.attachments = Some((ItemList { items, selected: 0 }, attachments));
+}
+
+/// Describes the attachment by its name and size.
+fn attachment_label(attachment: &Attachment) -> String {
+    let size = format_size(u64::try_from(attachment.size).unwrap_or_default());
+    tf(
+        "attachments.label",
+        &[("name", &attachment.name), ("size", &size)],
+    )
+}
+
+/// Handles keys whilst the attachments of a message are listed. Enter starts downloading the selected one.
+fn handle_attachments_input(chat: &mut ChatWindow, code: KeyCode, data: &mut ChatData) {
+    let Some((list, attachments)) = &mut chat.attachments else {
+        return;
+    };
+    match code {
+        KeyCode::Up => list.select_previous(),
+        KeyCode::Down => list.select_next(),
+        KeyCode::Enter => {
+            let attachment = attachments.swap_remove(list.selected);
+            chat.attachments = None;
+            let Some(session_data) = data.logins.get(&chat.title) else {
+                return;
+            };
+            let client = Arc::clone(&session_data.client);
+            if let Err(e) = data.downloads.start(&chat.title, client, &attachment) {
+                let text = tf("attachments.download_failed", &[("error", &e)]);
+                data.notifications.push(Severity::Error, &chat.title, text);
+            }
+        }
+        KeyCode::Esc => chat.attachments = None,
+        _ => {}
+    }
+}
+
+/// Copies the link to the selected message, if a message is selected.
+fn copy_permalink(chat: &ChatWindow, data: &mut ChatData) {
+    let Some(ChatLine::Message { id, .. }) = chat.selected.map(|index| chat.message_list[index])
+    else {
+        return;
+    };
+    let Some(session_data) = data.logins.get(&chat.title) else {
+        return;
+    };
+    data.clipboard = Some(session_data.client.permalink(id));
+    data.notifications
+        .push(Severity::Info, &chat.title, t("chat.link_copied"));
+}
+
+/// Jumps to the message linked in the composer, or else in the selected message. A link taken from the composer
+/// is cleared from it.
+fn open_permalink(chat: &mut ChatWindow, data: &mut ChatData) {
+    let Some(session_data) = data.logins.get_mut(&chat.title) else {
+        return;
+    };
+    let client = &session_data.client;
+    let selected = match chat.selected.map(|index| chat.message_list[index]) {
+        Some(ChatLine::Message { index, .. }) => Some(&session_data.messages[index].messagetext),
+        _ => None,
+    };
+    let id = match client.find_permalink(&chat.message_composer) {
+        Some(id) => {
+            chat.message_composer.clear();
+            chat.misspelled.clear();
+            Some(id)
+        }
+        None => selected.and_then(|text| client.find_permalink(text)),
+    };
+    match id {
+        Some(id) => session_data.jump_to_message(id),
+        None => {
+            data.notifications
+                .push(Severity::Warning, &chat.title, t("chat.no_link"));
+        }
+    }
+}
+
+/// Returns whether the server of the session offers the feature, telling the user if it does not.
+fn supported(
+    chat: &ChatWindow,
+    session_data: &SessionData,
+    feature: Feature,
+    notifications: &mut Notifications,
+) -> bool {
+    let supported = session_data.client.supports(feature);
+    if !supported {
+        let text = tf("chat.unsupported", &[("feature", &feature_name(feature))]);
+        notifications.push(Severity::Warning, &chat.title, text);
+    }
+    supported
+}
+
+/// Returns the localized name of the feature.
+fn feature_name(feature: Feature) -> &'static str {
+    t(match feature {
+        Feature::SavedMessages => "features.saved_messages",
+        Feature::Forwarding => "features.forwarding",
+        Feature::MessageContext => "features.message_context",
+        Feature::InitialSync => "features.initial_sync",
+        Feature::MessageStream => "features.message_stream",
+        Feature::ReadMarkers => "features.read_markers",
+        Feature::Reactions => "features.reactions",
+        Feature::Attachments => "features.attachments",
+        Feature::Search => "features.search",
+        Feature::Moderation => "features.moderation",
+        Feature::Profiles => "features.profiles",
+        Feature::ContextAround => "features.context_around",
+        Feature::Presence => "features.presence",
+        Feature::Sessions => "features.sessions",
+        Feature::Impersonation => "features.impersonation",
+        Feature::Channels => "features.channels",
+        Feature::DirectMessages => "features.direct_messages",
+        Feature::ServerClock => "features.server_clock",
+        Feature::Drafts => "features.drafts",
+        Feature::PushNotifications => "features.push_notifications",
+        Feature::E2ee => "features.e2ee",
+        Feature::Pagination => "features.pagination",
+        Feature::IdempotencyKeys => "features.idempotency_keys",
+        Feature::AddressBans => "features.address_bans",
+        Feature::SlowMode => "features.slow_mode",
+        Feature::ReadReceipts => "features.read_receipts",
+        Feature::ChannelNotifications => "features.channel_notifications",
+        Feature::Blocking => "features.blocking",
+        Feature::UserDirectory => "features.user_directory",
+        Feature::Kicking => "features.kicking",
+        Feature::Invites => "features.invites",
+        Feature::Bots => "features.bots",
+        Feature::Webhooks => "features.webhooks",
+        Feature::Topics => "features.topics",
+    })
+}
+
+/// Describes why a message could not be sent.
+fn sending_failed(error: Error, data: &ChatData) -> (Severity, String) {
+    match error {
+        Error::TimedOut => (Severity::Error, t("chat.send_timed_out").into()),
+        Error::QuotaExceeded(Some(available_at)) => (
+            Severity::Warning,
+            tf(
+                "chat.quota_exceeded",
+                &[("time", &data.time_format.time(available_at))],
+            ),
+        ),
+        Error::SlowMode(Some(seconds)) => (
+            Severity::Warning,
+            tf("chat.slow_mode", &[("seconds", &seconds.to_string())]),
+        ),
+        e @ (Error::RateLimited
+        | Error::QuotaExceeded(None)
+        | Error::SlowMode(None)
+        | Error::DuplicateMessage) => (Severity::Warning, e.localized()),
+        e => (
+            Severity::Error,
+            tf("chat.send_failed", &[("error", &e.localized())]),
+        ),
+    }
+}
+
+/// Marks the misspelled words in the composer, if spell checking is turned on.
+fn check_spelling(chat: &mut ChatWindow, data: &ChatData) {
+    chat.suggestions = None;
+    chat.misspelled = match &data.spelling {
+        Some(spelling) => spelling.misspelled(&chat.message_composer),
+        None => Vec::new(),
+    };
+}
+
+/// Offers replacements for the last misspelled word in the composer, which is the one closest to the cursor.
+fn suggest_spelling(chat: &mut ChatWindow, data: &mut ChatData) {
+    let (Some(spelling), Some(word)) = (&data.spelling, chat.misspelled.last()) else {
+        return;
+    };
+    let options = spelling.suggest(&chat.message_composer[word.clone()]);
+    if options.is_empty() {
+        let word = &chat.message_composer[word.clone()];
+        data.notifications.push(
+            Severity::Info,
+            &chat.title,
+            tf("spelling.no_suggestions", &[("word", &word)]),
+        );
+        return;
+    }
+
+    chat.suggestions = Some(Suggestions {
+        word: word.clone(),
+        options,
+        selected: 0,
+    });
+}
+
+impl Widget for &Window {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let inner = window_inner(area);
+        Block::default().borders(Borders::TOP).render(area, buf);
+
+        let theme = self.theme;
+        match &self.state {
+            // Rendering logic for the chat screen
+            MenuState::Chat(chat) => {
+                let layout = chat_layout(inner, chat.online.is_some());
+
+                let visible = chat.visible_messages(layout[0].height);
+                let width = usize::from(layout[0].width.saturating_sub(2));
+                let items: Vec<ListItem> = chat.message_list[visible.clone()]
+                    .iter()
+                    .zip(visible)
+                    .map(|(line, index)| {
+                        let selected = chat.selected == Some(index);
+                        let marker = theme.marker(selected);
+                        let (text, style) = match line {
+                            ChatLine::Date(date) => (
+                                format!("{marker}── {} ──", date.format("%Y-%m-%d")),
+                                theme.color(Color::DarkGray),
+                            ),
+                            ChatLine::System(index) => system_line(
+                                &chat.system_lines[*index],
+                                width.saturating_sub(Span::raw(marker).width()),
+                                theme,
+                            ),
+                            ChatLine::Read(index) => read_line(
+                                &chat.system_lines[*index],
+                                width.saturating_sub(Span::raw(marker).width()),
+                                theme,
+                            ),
+                            ChatLine::Message { id, .. } => match chat.formatted.get(id) {
+                                Some(message) if message.own => {
+                                    own_message(&message.text, chat.own_messages, width, theme)
+                                }
+                                Some(message) => (message.text.clone(), Style::default()),
+                                None => (String::new(), Style::default()),
+                            },
+                        };
+                        let style = if selected {
+                            style.add_modifier(Modifier::REVERSED)
+                        } else {
+                            style
+                        };
+                        ListItem::new(Text::from(format!("{marker}{text}"))).style(style)
+                    })
+                    .collect();
+                tui::widgets::Widget::render(
+                    List::new(items).block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(chat.conversation.1.as_str()),
+                    ),
+                    layout[0],
+                    buf,
+                );
+
+                let composer_focused = chat.selected.is_none();
+                let composer_style = theme.focus(composer_focused);
+                let composer_title = match chat.cooldown {
+                    Some(cooldown) if cooldown.conversation == chat.conversation.0 => tf(
+                        "chat.composer_slow_mode",
+                        &[("seconds", &cooldown.shown.to_string())],
+                    ),
+                    _ => t("chat.composer").into(),
+                };
+                Paragraph::new(composer_spans(
+                    &chat.message_composer,
+                    &chat.misspelled,
+                    theme,
+                ))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(composer_style)
+                        .title(Span::styled(
+                            format!("{}{composer_title}", theme.marker(composer_focused)),
+                            composer_style,
+                        )),
+                )
+                .render(layout[1], buf);
+
+                if let Some(suggestions) = &chat.suggestions {
+                    let area = suggestions_area(layout[1], &chat.message_composer, suggestions);
+                    let items: Vec<ListItem> = suggestions
+                        .options
+                        .iter()
+                        .enumerate()
+                        .map(|(index, option)| {
+                            let selected = index == suggestions.selected;
+                            ListItem::new(format!("{}{option}", theme.marker(selected)))
+                                .style(theme.focus(selected))
+                        })
+                        .collect();
+                    Clear.render(area, buf);
+                    tui::widgets::Widget::render(
+                        List::new(items).block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .title(t("spelling.title")),
+                        ),
+                        area,
+                        buf,
+                    );
+                }
+
+                if let Some(online) = chat.online.as_ref().filter(|_| layout[3].width > 0) {
+                    let items: Vec<ListItem> = online
+                        .iter()
+                        .map(|name| ListItem::new(name.as_str()))
+                        .collect();
+                    tui::widgets::Widget::render(
+                        List::new(items).block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .title(tf("presence.title", &[("count", &online.len())])),
+                        ),
+                        layout[3],
+                        buf,
+                    );
+                }
+
+                if let Some(saved) = &chat.saved {
+                    saved.render(t("saved.title"), layout[0], buf, theme);
+                }
+                if let Some((attachments, _)) = &chat.attachments {
+                    attachments.render(t("attachments.title"), layout[0], buf, theme);
+                }
+                if let Some((query, found)) = &chat.search {
+                    let title = tf("search.title", &[("query", query)]);
+                    found.render(&title, layout[0], buf, theme);
+                }
+
+                if let Some(retry) = chat.retrying {
+                    Paragraph::new(Span::styled(
+                        tf("chat.retrying", &[("attempt", &(retry + 1))]),
+                        theme.color(Color::Yellow),
+                    ))
+                    .render(layout[2], buf);
+                } else if let Some(status) = &chat.status {
+                    Paragraph::new(Span::styled(
+                        notification_text(status, theme),
+                        severity_style(status.severity, theme),
+                    ))
+                    .render(layout[2], buf);
+                }
+            }
+            // Rendering logic for the login screen
+            MenuState::Login(login) => {
+                let layout = login_layout(inner, login.asks_invite());
+
+                let mut address = login.address.clone();
+                let server = login.checked_server();
+                if let Some(info) = server {
+                    address.title = tf(
+                        "login.address_checked",
+                        &[("name", &info.name), ("version", &info.version)],
+                    );
+                    Paragraph::new(branding_text(info, theme))
+                        .alignment(Center)
+                        .wrap(Wrap { trim: false })
+                        .render(layout[8], buf);
+                }
+                form_element_ui(&address, login.focus == LoginWindowFocus::Address, theme)
+                    .render(layout[0], buf);
+                form_element_ui(
+                    &login.username,
+                    login.focus == LoginWindowFocus::Username,
+                    theme,
+                )
+                .render(layout[1], buf);
+                form_element_ui(
+                    &login.password,
+                    login.focus == LoginWindowFocus::Pasword,
+                    theme,
+                )
+                .render(layout[2], buf);
+
+                Paragraph::new(choice_ui(
+                    &[
+                        t("login.intent_login"),
+                        t("login.intent_register"),
+                        t("login.intent_guest"),
+                    ],
+                    login.intent as usize,
+                    login.focus == LoginWindowFocus::Intent,
+                    theme,
+                ))
+                .render(layout[3], buf);
+
+                if login.asks_invite() {
+                    form_element_ui(
+                        &login.invite,
+                        login.focus == LoginWindowFocus::Invite,
+                        theme,
+                    )
+                    .render(layout[4], buf);
+                }
+
+                let remember_focused = login.focus == LoginWindowFocus::RememberMe;
+                let checkbox = if login.remember_me { "[x]" } else { "[ ]" };
+                Paragraph::new(Span::styled(
+                    format!(
+                        "{}{checkbox} {}",
+                        theme.marker(remember_focused),
+                        t("login.remember_me")
+                    ),
+                    theme.focus(remember_focused),
+                ))
+                .render(layout[5], buf);
+
+                if let Some(message) = &login.status_message {
+                    Paragraph::new(Span::styled(message, Style::default()))
+                        .alignment(Center)
+                        .render(layout[6], buf);
+                }
+
+                Paragraph::new(Span::styled(t("login.submit_hint"), Style::default()))
+                    .alignment(Center)
+                    .render(layout[7], buf);
+
+                if let Some(overlay) = &login.terms {
+                    Clear.render(inner, buf);
+                    let layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(1)])
+                        .split(inner);
+                    Paragraph::new(overlay.terms.text.as_str())
+                        .wrap(Wrap { trim: false })
+                        .scroll((overlay.scroll, 0))
+                        .block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .title(tf("terms.title", &[("version", &overlay.terms.version)])),
+                        )
+                        .render(layout[0], buf);
+                    Paragraph::new(t("terms.hint"))
+                        .alignment(Center)
+                        .render(layout[1], buf);
+                }
+            }
+            MenuState::Welcome(welcome) => welcome.render(inner, buf, theme),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chat_client::ShutdownHandler;
+    use tui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use crate::{
+        config::{ClientConfig, UiConfig},
+        downloads::Downloads,
+        scripted::{self, start, wait_until, ScriptedTransport},
+    };
+
+    /// The size of the terminal the windows are drawn in.
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 48,
+        height: 16,
+    };
+
+    /// The state of a client logged in as ``user``, with times shown in UTC.
+    fn chat_data(session: SessionData) -> ChatData {
+        let ui = UiConfig {
+            timezone: Some("UTC".into()),
+            ..UiConfig::default()
+        };
+        let time_format = TimeFormat::from_config(&ui).unwrap();
+        ChatData {
+            logins: HashMap::from([("user".to_string(), session)]),
+            config: ClientConfig::default(),
+            theme: Theme::new(false),
+            time_format,
+            spelling: None,
+            notifications: Notifications::default(),
+            local: None,
+            config_dir: PathBuf::new(),
+            clipboard: None,
+            recorder: None,
+            log_file: PathBuf::new(),
+            downloads: Downloads::new(PathBuf::new()),
+            update_notice: None,
+            shutdown: ShutdownHandler::new().0,
+        }
+    }
+
+    /// A session of the user with a history of messages alice and bob sent in turns.
+    async fn loaded(count: i32) -> ChatData {
+        let messages = (1..=count)
+            .map(|id| scripted::message(id, 2 + id % 2))
+            .collect();
+        let users = vec![
+            scripted::user(1, "user"),
+            scripted::user(2, "alice"),
+            scripted::user(3, "bob"),
+        ];
+        let (transport, _events) = ScriptedTransport::new(messages, users);
+        let (mut session, _) = start(transport);
+        let mut notifications = Notifications::default();
+        wait_until(&mut session, &mut notifications, |session| {
+            !session.messages.is_empty() && session.known_usernames.len() == 3
+        })
+        .await;
+        chat_data(session)
+    }
+
+    /// Brings the window up to date with the session of the user and draws it, returning the text of each row.
+    fn draw(window: &mut Window, data: &mut ChatData) -> Vec<String> {
+        if let Some(session) = data.logins.get_mut("user") {
+            window.update(session, &data.notifications, data.time_format, AREA);
+        }
+        let mut terminal = Terminal::new(TestBackend::new(AREA.width, AREA.height)).unwrap();
+        terminal
+            .draw(|frame| frame.render_widget(&*window, frame.size()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..AREA.height)
+            .map(|y| {
+                let row: String = (0..AREA.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// The rows of the message list and the composer in a window of ``AREA``, below the line of tabs.
+    fn chat_rows(messages: std::ops::RangeInclusive<i32>) -> Vec<String> {
+        let mut rows = vec![format!("┌Chat{}┐", "─".repeat(42))];
+        for id in messages {
+            let sender = if id % 2 == 0 { "alice" } else { "bob" };
+            let text = format!("22:{:02} {sender}: message {id}", id + 13);
+            rows.push(format!("│{text:<46}│"));
+        }
+        rows.push(format!("└{}┘", "─".repeat(46)));
+        rows.push(format!("┌> Message{}┐", "─".repeat(37)));
+        rows.push(format!("│{}│", " ".repeat(46)));
+        rows.push(format!("└{}┘", "─".repeat(46)));
+        rows
+    }
+
+    #[tokio::test]
+    async fn login_window_asks_for_the_server_and_the_credentials() {
+        let mut data = loaded(1).await;
+        let mut window = Window::new(Theme::new(false));
+
+        let rows = draw(&mut window, &mut data);
+
+        assert_eq!(
+            rows[1..11],
+            [
+                "┌> Server Address──────────────────────────────┐",
+                "│                                              │",
+                "└──────────────────────────────────────────────┘",
+                "┌Username──────────────────────────────────────┐",
+                "│                                              │",
+                "└──────────────────────────────────────────────┘",
+                "┌Password──────────────────────────────────────┐",
+                "│                                              │",
+                "└──────────────────────────────────────────────┘",
+                "(*) Login as a existing user | ( ) Register as a",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_window_shows_the_newest_messages_with_their_senders() {
+        let mut data = loaded(30).await;
+        let mut window = Window::chat("user", &data);
+
+        let rows = draw(&mut window, &mut data);
+
+        assert_eq!(rows[1..15], chat_rows(22..=30));
+        assert_eq!(rows[15], "");
+    }
+
+    #[tokio::test]
+    async fn scrolling_up_shows_older_messages() {
+        let mut data = loaded(30).await;
+        let mut window = Window::chat("user", &data);
+        draw(&mut window, &mut data);
+
+        let scroll = MouseEvent {
+            kind: MouseEventKind::ScrollUp,
+            column: 10,
+            row: 5,
+            modifiers: KeyModifiers::NONE,
+        };
+        window.handle_mouse(&scroll, AREA);
+        let rows = draw(&mut window, &mut data);
+
+        assert_eq!(rows[1..15], chat_rows(19..=27));
+    }
+
+    #[tokio::test]
+    async fn errors_of_the_session_are_shown_below_the_composer() {
+        let mut data = loaded(30).await;
+        let mut window = Window::chat("user", &data);
+        draw(&mut window, &mut data);
+
+        data.notifications
+            .push(Severity::Error, "user", "The server did not respond");
+        let rows = draw(&mut window, &mut data);
+
+        assert_eq!(rows[1..15], chat_rows(22..=30));
+        assert_eq!(rows[15], "Error: The server did not respond");
+    }
+}
@@ -0,0 +1,221 @@
+//! A transport answering the requests of a session from a script, so sessions and the windows showing them can be
+//! tested without a server.
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chat_client::{ChatTransport, ClientBuilder, Error, MessageStream, StreamEvent};
+use chat_core::{
+    models::{Message, Motd, Role, User},
+    protocol::{
+        ChannelNotifications, Conversation, Draft, Feature, InitialSync, MessageContext,
+        MessageFilter, Overview, Presence, ReadReceipt,
+    },
+};
+use chrono::{DateTime, TimeZone, Utc};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::{notifications::Notifications, session::SessionData};
+
+/// A server with a main chat and nothing else. It offers no features besides its clock, so a session only asks it
+/// for the history, the names of the users and to mark messages as read.
+pub struct ScriptedTransport {
+    /// The messages of the main chat, oldest first.
+    messages: Vec<Message>,
+    users: Vec<User>,
+    /// Whether fetching the history fails, as if the server did not answer.
+    history_fails: bool,
+    /// The messages the session marked as read, in the order it did.
+    pub read: Mutex<Vec<(Conversation, i32)>>,
+    events: Mutex<Option<Receiver<StreamEvent>>>,
+}
+
+impl ScriptedTransport {
+    /// Creates a transport with the messages and the users who sent them, together with the sender of its event
+    /// stream. Dropping the sender closes the stream, like a server shutting down.
+    pub fn new(messages: Vec<Message>, users: Vec<User>) -> (Self, Sender<StreamEvent>) {
+        let (sender, events) = channel(8);
+        let transport = Self {
+            messages,
+            users,
+            history_fails: false,
+            read: Mutex::new(Vec::new()),
+            events: Mutex::new(Some(events)),
+        };
+        (transport, sender)
+    }
+
+    /// Makes every request for the history time out.
+    pub fn failing_history(mut self) -> Self {
+        self.history_fails = true;
+        self
+    }
+}
+
+/// A message of the main chat with the id, which is also its sequence number, sent a minute after the one before.
+pub fn message(id: i32, userid: i32) -> Message {
+    Message {
+        id,
+        date: Utc.timestamp_opt(1_700_000_000, 0).unwrap()
+            + chrono::Duration::minutes(i64::from(id)),
+        messagetext: format!("message {id}"),
+        userid,
+        forwardedid: None,
+        forwardeduserid: None,
+        seq: i64::from(id),
+        channelid: None,
+        recipientid: None,
+        attachments: Vec::new(),
+    }
+}
+
+pub fn user(id: i32, username: &str) -> User {
+    User {
+        id,
+        username: username.to_string(),
+        role: Role::default(),
+        displayname: None,
+    }
+}
+
+/// Starts a session of the user ``user`` whose background task asks the transport. The ui side gets a client
+/// that can not reach any server.
+pub fn start(transport: ScriptedTransport) -> (SessionData, Arc<ScriptedTransport>) {
+    let client = ClientBuilder::new().replay("localhost", 1).unwrap();
+    let transport = Arc::new(transport);
+    let session = SessionData::start_with(Arc::clone(&transport), Arc::new(client), None).unwrap();
+    (session, transport)
+}
+
+/// Applies the updates of the background task until the session is as expected. Fails the test if that takes
+/// longer than a few seconds.
+pub async fn wait_until(
+    session: &mut SessionData,
+    notifications: &mut Notifications,
+    expected: impl Fn(&SessionData) -> bool,
+) {
+    let applying = async {
+        while !expected(session) {
+            let Some(updates) = &mut session.updates else {
+                panic!("the session ended");
+            };
+            let update = updates.recv().await;
+            session.update("user", update, notifications);
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), applying)
+        .await
+        .expect("the session did not get there in time");
+}
+
+impl ChatTransport for ScriptedTransport {
+    fn user_id(&self) -> i32 {
+        1
+    }
+
+    fn supports(&self, feature: Feature) -> bool {
+        feature == Feature::ServerClock
+    }
+
+    fn server_now(&self) -> DateTime<Utc> {
+        self.messages
+            .last()
+            .map_or_else(Utc::now, |message| message.date)
+    }
+
+    fn get_events(&self) -> Result<Receiver<StreamEvent>, Error> {
+        self.events.lock().unwrap().take().ok_or(Error::Cancelled)
+    }
+
+    async fn get_initial_sync(&self) -> Result<InitialSync, Error> {
+        Err(Error::Cancelled)
+    }
+
+    async fn get_overview(&self) -> Result<Overview, Error> {
+        Err(Error::Cancelled)
+    }
+
+    async fn get_messages_of(
+        &self,
+        _conversation: Conversation,
+        filter: MessageFilter,
+        limit: Option<u32>,
+    ) -> Result<Vec<Message>, Error> {
+        if self.history_fails {
+            return Err(Error::TimedOut);
+        }
+        let limit = limit.map_or(20, |limit| limit as usize);
+        let newest_first =
+            |found: Vec<&Message>| found.into_iter().rev().take(limit).cloned().collect();
+        let oldest_first = |found: Vec<&Message>| found.into_iter().take(limit).cloned().collect();
+        let messages = self.messages.iter();
+        Ok(match filter {
+            MessageFilter::Latest(count) => messages
+                .rev()
+                .take(limit.min(count as usize))
+                .cloned()
+                .collect(),
+            MessageFilter::Before(date) => {
+                newest_first(messages.filter(|m| m.date < date).collect())
+            }
+            MessageFilter::BeforeSeq(seq) => {
+                newest_first(messages.filter(|m| m.seq < seq).collect())
+            }
+            MessageFilter::After(date) => {
+                oldest_first(messages.filter(|m| m.date > date).collect())
+            }
+            MessageFilter::AfterSeq(seq) => {
+                oldest_first(messages.filter(|m| m.seq > seq).collect())
+            }
+        })
+    }
+
+    async fn stream_messages(&self, _filter: MessageFilter) -> Result<MessageStream, Error> {
+        Err(Error::Cancelled)
+    }
+
+    async fn get_message_context(
+        &self,
+        _message_id: i32,
+        _around: Option<u32>,
+    ) -> Result<MessageContext, Error> {
+        Err(Error::Cancelled)
+    }
+
+    async fn get_all_users(&self) -> Result<Vec<User>, Error> {
+        Ok(self.users.clone())
+    }
+
+    async fn get_drafts(&self) -> Result<Vec<Draft>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn save_draft(&self, _conversation: Conversation, _text: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn get_presence(&self) -> Result<Presence, Error> {
+        Err(Error::Cancelled)
+    }
+
+    async fn get_motd(&self) -> Result<Motd, Error> {
+        Err(Error::Cancelled)
+    }
+
+    async fn mark_read(&self, conversation: Conversation, message_id: i32) -> Result<(), Error> {
+        self.read.lock().unwrap().push((conversation, message_id));
+        Ok(())
+    }
+
+    async fn get_read_receipts(
+        &self,
+        _conversation: Conversation,
+    ) -> Result<Vec<ReadReceipt>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn get_channel_notifications(&self) -> Result<Vec<ChannelNotifications>, Error> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,1528 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chat_client::{ChatTransport, Client, StreamEvent};
+use chat_core::{
+    models::{Message, User},
+    protocol::{
+        mentions, ChannelNotifications, Conversation, ConversationOverview, Draft, ErrorCode,
+        Feature, MessageContext, MessageFilter, NotificationLevel, ReadReceipt, SystemEvent,
+        SystemEventKind,
+    },
+};
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{
+        channel, error::TryRecvError, unbounded_channel, Receiver, Sender, UnboundedReceiver,
+        UnboundedSender,
+    },
+    time::{sleep_until, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{
+    errors::Localized,
+    i18n::{t, tf},
+    notifications::{Notifications, Severity},
+    recording::SessionRecording,
+};
+
+/// How many updates can be waiting for the ui before the background task of a session waits for it.
+const UPDATE_QUEUE: usize = 32;
+
+/// How many messages of a session are kept in memory. Messages further away from the part of the history that
+/// is shown are dropped, and fetched again once scrolled to.
+const MAX_MESSAGES: usize = 2000;
+
+/// How many messages are asked for in each part of the history until a window showed the session, after which it
+/// is as many as fit into its message list.
+const HISTORY_PAGE: u32 = 20;
+
+/// How long after the last change to a draft it is sent to the server, so it is not sent on every key.
+const DRAFT_DELAY: Duration = Duration::from_secs(2);
+
+/// A change to a session, worked out by its background task. The ui only has to apply it.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SessionUpdate {
+    /// New messages from the event stream.
+    Messages(Vec<Message>),
+    /// The part of the history that was asked for with the filter. ``None`` if it could not be fetched.
+    History(MessageFilter, Option<Vec<Message>>),
+    /// The message with that id and the ones around it, to jump to. ``None`` if they could not be fetched.
+    Context(i32, Option<MessageContext>),
+    /// The names of users that sent messages.
+    Usernames(Vec<User>),
+    /// The session now shows the conversation. Its messages follow as history.
+    Switched(Conversation),
+    /// A new message from the event stream, sent in a conversation that is not shown.
+    Elsewhere(Message),
+    /// The drafts the user left on the server, possibly on another device.
+    Drafts(Vec<Draft>),
+    /// The ids of the users that are online. Changes after that come as system events.
+    Presence(Vec<i32>),
+    /// How far the users read the conversation. Changes after that come as system events.
+    Receipts(Conversation, Vec<ReadReceipt>),
+    /// The channels the user gets fewer notifications for than every message.
+    ChannelNotifications(Vec<ChannelNotifications>),
+    System(SystemEvent),
+    Notification(Severity, String),
+}
+
+/// Holds the data for a users session. Everything that needs the network is done by a background task, which
+/// sends the results as ``SessionUpdate``s, so a slow server never holds up the ui.
+pub struct SessionData {
+    pub client: Arc<Client>,
+    /// Updates from the background task. ``None`` once the task ended, which happens when the server closed
+    /// the event stream.
+    pub updates: Option<Receiver<SessionUpdate>>,
+    /// Asks the background task for more of the history.
+    history: Sender<MessageFilter>,
+    /// Asks the background task for a message to jump to and the ones around it.
+    jumps: Sender<i32>,
+    /// Tells the background task which message the user read up to, and in which conversation.
+    reads: Sender<(Conversation, i32)>,
+    /// Asks the background task to show another conversation.
+    switches: Sender<Conversation>,
+    /// Tells the background task what the user has written in a conversation so far. Unbounded, as a dropped
+    /// change could leave an outdated draft on the server.
+    draft_edits: UnboundedSender<(Conversation, String)>,
+    /// What the user has written but not sent in each conversation, empty ones left out.
+    drafts: HashMap<Conversation, String>,
+    /// The draft to put into the composer, once the window showing the session gets to it.
+    pub draft: Option<String>,
+    /// How many messages to ask for in each part of the history. Shared with the background task.
+    page_size: Arc<AtomicU32>,
+    /// The conversation shown, the main chat unless the user switched to a channel or another user.
+    pub conversation: Conversation,
+    /// The conversation asked to switch to, until the background task did. Parts of the history arriving meanwhile
+    /// belong to the conversation shown before and are dropped.
+    switching: Option<Conversation>,
+    /// The names of the channels the user saw, by id.
+    pub channels: HashMap<i32, String>,
+    /// How the user is told about new messages in the channels they did not leave at the default, by id.
+    pub notification_levels: HashMap<i32, NotificationLevel>,
+    /// The newest message of the conversation shown the server was told the user read.
+    read_up_to: Option<i32>,
+    /// The newest message of the conversation shown each of the other users read, by their ids.
+    pub receipts: HashMap<i32, i32>,
+    /// The part of the history that is in memory, at most ``MAX_MESSAGES`` long.
+    pub messages: Vec<Message>,
+    /// Whether the server has messages older than the ones in memory.
+    pub has_older: bool,
+    /// Whether messages newer than the ones in memory were dropped, because an older part of the history is shown.
+    /// New messages are not added until the newer ones were fetched again, so there is no gap between them.
+    pub has_newer: bool,
+    /// Whether a part of the history is being fetched.
+    loading: bool,
+    /// The message to select and scroll to, once the window showing the session gets to it.
+    pub jump_to: Option<i32>,
+    /// The system events received since the session started, oldest first. They are not part of the history, so
+    /// the ones older than the messages in memory are dropped with them.
+    pub system: Vec<SystemEvent>,
+    pub known_usernames: HashMap<i32, String>,
+    /// The display names of the users that chose one, shown in place of their usernames. Users are still looked up
+    /// by their usernames.
+    pub display_names: HashMap<i32, String>,
+    /// The ids of the users that are online. ``None`` until it is known, or if the server does not tell.
+    pub online: Option<HashSet<i32>>,
+    /// Goes up whenever the messages or usernames change, so windows know when to lay out the messages again.
+    pub revision: u64,
+    /// The revision the usernames last changed in. Messages formatted before it may show outdated names.
+    pub names_revision: u64,
+    /// The revision each edited message was last changed in.
+    pub edited: HashMap<i32, u64>,
+}
+
+impl SessionData {
+    /// Subscribes to new messages and starts the background task, which fetches the latest part of the history
+    /// first. The updates of the task are recorded if a recording is given.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the event stream could not be created.
+    pub fn start(client: Client, recording: Option<SessionRecording>) -> Result<Self> {
+        let client = Arc::new(client);
+        Self::start_with(Arc::clone(&client), client, recording)
+    }
+
+    /// Like ``start``, but the background task makes its requests through the transport. The ui still uses the
+    /// client for what it sends itself.
+    pub(crate) fn start_with<T: ChatTransport>(
+        transport: Arc<T>,
+        client: Arc<Client>,
+        recording: Option<SessionRecording>,
+    ) -> Result<Self> {
+        let events = transport.get_events()?;
+        let (sender, receiver) = channel(UPDATE_QUEUE);
+        let (history, history_requests) = channel(1);
+        let (jumps, jump_requests) = channel(1);
+        let (reads, read_messages) = channel(1);
+        let (switches, switch_requests) = channel(1);
+        let (draft_edits, drafts) = unbounded_channel();
+        let page_size = Arc::new(AtomicU32::new(HISTORY_PAGE));
+        let newest = transport.server_now();
+        let task = Task {
+            client: transport,
+            updates: sender,
+            known_users: HashSet::new(),
+            conversation: Conversation::Chat,
+            newest,
+            newest_seq: 0,
+            drafts: HashMap::new(),
+            save_drafts_at: None,
+            page_size: page_size.clone(),
+            recording,
+        };
+        let requests = Requests {
+            history: history_requests,
+            jumps: jump_requests,
+            reads: read_messages,
+            switches: switch_requests,
+            drafts,
+        };
+        tokio::spawn(task.run(events, requests));
+
+        let requests = (history, jumps, reads, switches, draft_edits);
+        Ok(Self::new(client, receiver, requests, page_size))
+    }
+
+    /// Plays back the recorded updates of a session, each after the time it took when recorded, counted from
+    /// ``start``. Whatever the ui asks for is ignored, as the client can not reach the server.
+    pub fn replay(client: Client, updates: Vec<(Duration, SessionUpdate)>, start: Instant) -> Self {
+        let (sender, receiver) = channel(UPDATE_QUEUE);
+        let (history, history_requests) = channel(1);
+        let (jumps, jump_requests) = channel(1);
+        let (reads, read_messages) = channel(1);
+        let (switches, switch_requests) = channel(1);
+        let (draft_edits, drafts) = unbounded_channel();
+        tokio::spawn(async move {
+            // Kept until the end, so the requests of the ui do not fail as if the task died
+            let _requests = (
+                history_requests,
+                jump_requests,
+                read_messages,
+                switch_requests,
+                drafts,
+            );
+            for (after, update) in updates {
+                tokio::time::sleep_until(start + after).await;
+                if sender.send(update).await.is_err() {
+                    return;
+                }
+            }
+            let finished =
+                SessionUpdate::Notification(Severity::Info, t("session.replay_finished").into());
+            let _ = sender.send(finished).await;
+            // The ui treats a session whose task ended as closed by the server
+            sender.closed().await;
+        });
+
+        let requests = (history, jumps, reads, switches, draft_edits);
+        let page_size = Arc::new(AtomicU32::new(HISTORY_PAGE));
+        Self::new(Arc::new(client), receiver, requests, page_size)
+    }
+
+    fn new(
+        client: Arc<Client>,
+        updates: Receiver<SessionUpdate>,
+        (history, jumps, reads, switches, draft_edits): RequestSenders,
+        page_size: Arc<AtomicU32>,
+    ) -> Self {
+        Self {
+            client,
+            updates: Some(updates),
+            history,
+            jumps,
+            reads,
+            switches,
+            draft_edits,
+            drafts: HashMap::new(),
+            draft: None,
+            page_size,
+            conversation: Conversation::Chat,
+            switching: None,
+            channels: HashMap::new(),
+            notification_levels: HashMap::new(),
+            read_up_to: None,
+            receipts: HashMap::new(),
+            messages: Vec::new(),
+            // Until the first part of the history arrived, it is not known whether there is any
+            has_older: true,
+            has_newer: false,
+            loading: true,
+            jump_to: None,
+            system: Vec::new(),
+            known_usernames: HashMap::new(),
+            display_names: HashMap::new(),
+            online: None,
+            revision: 0,
+            names_revision: 0,
+            edited: HashMap::new(),
+        }
+    }
+
+    /// Writes what the session holds to the log, for troubleshooting.
+    pub fn log_state(&self, username: &str) {
+        info!(
+            session = username,
+            address = self.client.address(),
+            user_id = self.client.user_id(),
+            conversation = ?self.conversation,
+            switching = ?self.switching,
+            channels = self.channels.len(),
+            drafts = self.drafts.len(),
+            messages = self.messages.len(),
+            oldest = ?self.messages.first().map(|m| (m.id, m.seq, m.date)),
+            newest = ?self.messages.last().map(|m| (m.id, m.seq, m.date)),
+            has_older = self.has_older,
+            has_newer = self.has_newer,
+            loading = self.loading,
+            jump_to = ?self.jump_to,
+            read_up_to = ?self.read_up_to,
+            system_events = self.system.len(),
+            known_usernames = self.known_usernames.len(),
+            display_names = self.display_names.len(),
+            online = ?self.online.as_ref().map(HashSet::len),
+            revision = self.revision,
+            names_revision = self.names_revision,
+            edited = self.edited.len(),
+            task_running = self.updates.is_some(),
+            retrying = ?self.client.retrying(),
+            "Session state"
+        );
+    }
+
+    /// Applies an update from the background task and any others that are already waiting. ``None`` means the
+    /// task ended. Returns true if new messages arrived.
+    pub fn update(
+        &mut self,
+        username: &str,
+        update: Option<SessionUpdate>,
+        notifications: &mut Notifications,
+    ) -> bool {
+        let mut new_messages = self.apply(username, update, notifications);
+        // Apply everything that piled up at once, so it is only drawn once
+        while let Some(updates) = &mut self.updates {
+            let update = match updates.try_recv() {
+                Ok(update) => Some(update),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => None,
+            };
+            new_messages |= self.apply(username, update, notifications);
+        }
+        new_messages
+    }
+
+    /// Asks for the messages before the oldest one in memory, unless there are none or the history is already
+    /// being fetched.
+    pub fn load_older(&mut self) {
+        if self.has_older && !self.loading {
+            let filter = self.messages.first().map_or_else(
+                || latest(self.client.as_ref(), self.page_size.load(Ordering::Relaxed)),
+                MessageFilter::before,
+            );
+            self.loading = self.history.try_send(filter).is_ok();
+        }
+    }
+
+    /// Asks for the messages after the newest one in memory, if any were dropped and the history is not already
+    /// being fetched.
+    pub fn load_newer(&mut self) {
+        if let Some(newest) = self.messages.last() {
+            if self.has_newer && !self.loading {
+                self.loading = self.history.try_send(MessageFilter::after(newest)).is_ok();
+            }
+        }
+    }
+
+    /// Jumps to the message with that id. If it is not in memory, it is fetched together with the messages around
+    /// it, which replace the part of the history in memory.
+    pub fn jump_to_message(&mut self, id: i32) {
+        if self.messages.iter().any(|m| m.id == id) {
+            self.jump_to = Some(id);
+        } else if self.jumps.try_send(id).is_ok() {
+            // No more history is asked for until the jump is done, as it would not fit to the new part
+            self.loading = true;
+        }
+    }
+
+    /// Asks to show the conversation instead of the current one. Does nothing if it is already shown.
+    pub fn switch_to(&mut self, conversation: Conversation) {
+        if self.switching.unwrap_or(self.conversation) != conversation
+            && self.switches.try_send(conversation).is_ok()
+        {
+            self.switching = Some(conversation);
+            self.loading = true;
+        }
+    }
+
+    /// Keeps what the user has written in the conversation shown so far, so it is still there when they come back
+    /// to it. If the server keeps drafts, it is sent there too, a little after the user stopped typing.
+    pub fn edit_draft(&mut self, text: &str) {
+        let known = self
+            .drafts
+            .get(&self.conversation)
+            .map_or("", String::as_str);
+        if known == text {
+            return;
+        }
+        if text.is_empty() {
+            self.drafts.remove(&self.conversation);
+        } else {
+            self.drafts.insert(self.conversation, text.to_string());
+        }
+        if self.client.supports(Feature::Drafts) {
+            let _ = self.draft_edits.send((self.conversation, text.to_string()));
+        }
+    }
+
+    /// Asks for as many messages in each part of the history as fit into a message list of that height, as each
+    /// message takes up at least one line.
+    pub fn fit_history(&self, height: u16) {
+        self.page_size
+            .store(u32::from(height).max(1), Ordering::Relaxed);
+    }
+
+    /// The name of the conversation shown, as the user knows it.
+    pub fn conversation_name(&self) -> String {
+        match self.conversation {
+            Conversation::Chat => t("chat.main_chat").into(),
+            Conversation::Channel(id) => match self.channels.get(&id) {
+                Some(name) => format!("#{name}"),
+                None => format!("#{id}"),
+            },
+            Conversation::Direct(userid) => match self.shown_name(userid) {
+                Some(name) => format!("@{name}"),
+                None => format!("@{userid}"),
+            },
+        }
+    }
+
+    /// Drops the messages of the user with that id, after they were blocked. The server no longer sends new ones.
+    pub fn hide_user(&mut self, userid: i32) {
+        let shown = self.messages.len();
+        self.messages.retain(|message| message.userid != userid);
+        if self.messages.len() != shown {
+            self.revision += 1;
+        }
+    }
+
+    /// How the user is told about new messages in the conversation. Only channels can be set to anything but all.
+    pub fn notification_level(&self, conversation: Conversation) -> NotificationLevel {
+        match conversation {
+            Conversation::Channel(id) => self
+                .notification_levels
+                .get(&id)
+                .copied()
+                .unwrap_or_default(),
+            Conversation::Chat | Conversation::Direct(_) => NotificationLevel::All,
+        }
+    }
+
+    /// Whether the user wants to be told about the new message, going by the notification level of its
+    /// conversation.
+    fn notifies(&self, message: &Message, username: &str) -> bool {
+        let conversation = Conversation::of(message, self.client.user_id());
+        match self.notification_level(conversation) {
+            NotificationLevel::All => true,
+            NotificationLevel::Mentions => {
+                let username = self
+                    .known_usernames
+                    .get(&self.client.user_id())
+                    .map_or(username, String::as_str);
+                mentions(&message.messagetext, username)
+            }
+            NotificationLevel::Muted => false,
+        }
+    }
+
+    /// The name to show for the user with that id: their display name, or their username if they have none.
+    pub fn shown_name(&self, userid: i32) -> Option<&String> {
+        self.display_names
+            .get(&userid)
+            .or_else(|| self.known_usernames.get(&userid))
+    }
+
+    /// The names of the users that are online, sorted. ``None`` if it is not known who is online.
+    pub fn online_names(&self) -> Option<Vec<String>> {
+        let mut names: Vec<String> = self
+            .online
+            .as_ref()?
+            .iter()
+            .map(|userid| {
+                self.shown_name(*userid)
+                    .cloned()
+                    .unwrap_or(userid.to_string())
+            })
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        Some(names)
+    }
+
+    /// Remembers the display name of the user, or that they have none. Returns whether it changed.
+    fn set_display_name(&mut self, userid: i32, displayname: Option<String>) -> bool {
+        match displayname {
+            Some(name) => self.display_names.insert(userid, name.clone()) != Some(name),
+            None => self.display_names.remove(&userid).is_some(),
+        }
+    }
+
+    /// Marks the newest message as read, as the user has seen it. Does nothing whilst newer messages were dropped,
+    /// as the user has not seen those, or if the server does not keep read markers for the conversation. Servers
+    /// without read receipts only keep them for the main chat.
+    pub fn mark_read(&mut self) {
+        let kept = if self.conversation == Conversation::Chat {
+            self.client.supports(Feature::ReadMarkers)
+        } else {
+            self.client.supports(Feature::ReadReceipts)
+        };
+        if self.has_newer || !kept {
+            return;
+        }
+        if let Some(newest) = self.messages.last() {
+            // If the task is still busy with the last one, it is tried again with the next update
+            if self.read_up_to.is_none_or(|read| read < newest.id)
+                && self.reads.try_send((self.conversation, newest.id)).is_ok()
+            {
+                self.read_up_to = Some(newest.id);
+            }
+        }
+    }
+
+    fn apply(
+        &mut self,
+        username: &str,
+        update: Option<SessionUpdate>,
+        notifications: &mut Notifications,
+    ) -> bool {
+        match update {
+            // They belong to the conversation that is about to be replaced
+            Some(
+                SessionUpdate::Messages(_)
+                | SessionUpdate::History(..)
+                | SessionUpdate::Context(..),
+            ) if self.switching.is_some() => {}
+            // They are fetched again with the newer messages, once the user scrolls back to them
+            Some(SessionUpdate::Messages(_)) if self.has_newer => return true,
+            Some(SessionUpdate::Messages(messages)) => {
+                let revision = self.revision;
+                let wanted = messages
+                    .iter()
+                    .any(|message| self.notifies(message, username));
+                self.merge(messages);
+                self.trim(true);
+                return wanted && self.revision != revision;
+            }
+            Some(SessionUpdate::History(filter, messages)) => {
+                self.loading = false;
+                let older = filter.is_before();
+                match messages {
+                    Some(messages) if messages.is_empty() && older => self.has_older = false,
+                    Some(messages) if messages.is_empty() => self.has_newer = false,
+                    Some(messages) => {
+                        self.merge(messages);
+                        self.trim(!older);
+                    }
+                    None => {}
+                }
+            }
+            Some(SessionUpdate::Context(id, context)) => {
+                self.loading = false;
+                if let Some(context) = context {
+                    self.has_older = !context.before.is_empty();
+                    self.has_newer = !context.after.is_empty();
+                    self.messages = context.before;
+                    self.messages.push(context.message);
+                    self.messages.extend(context.after);
+                    self.edited.clear();
+                    if let Some(oldest) = self.messages.first() {
+                        self.system.retain(|event| event.date >= oldest.date);
+                    }
+                    self.revision += 1;
+                    self.jump_to = Some(id);
+                }
+            }
+            Some(SessionUpdate::Switched(conversation)) => {
+                if self
+                    .switching
+                    .is_some_and(|switching| switching != conversation)
+                {
+                    // Jumped to a message elsewhere before the switch asked for was done
+                    return false;
+                }
+                self.switching = None;
+                self.conversation = conversation;
+                self.draft = Some(self.drafts.get(&conversation).cloned().unwrap_or_default());
+                self.messages.clear();
+                self.edited.clear();
+                self.read_up_to = None;
+                self.receipts.clear();
+                self.has_older = true;
+                self.has_newer = false;
+                self.loading = true;
+                self.revision += 1;
+            }
+            Some(SessionUpdate::Elsewhere(message)) if !self.notifies(&message, username) => {}
+            Some(SessionUpdate::Elsewhere(message)) => {
+                let text = match Conversation::of(&message, self.client.user_id()) {
+                    Conversation::Channel(id) => {
+                        let name = self.channels.get(&id).cloned().unwrap_or(id.to_string());
+                        tf("session.new_in_channel", &[("channel", &name)])
+                    }
+                    _ => {
+                        let name = self
+                            .shown_name(message.userid)
+                            .cloned()
+                            .unwrap_or(message.userid.to_string());
+                        tf("session.new_direct", &[("name", &name)])
+                    }
+                };
+                notifications.push(Severity::Info, username, text);
+            }
+            Some(SessionUpdate::Drafts(drafts)) => {
+                for draft in drafts {
+                    if draft.conversation == self.conversation {
+                        self.draft = Some(draft.text.clone());
+                    }
+                    // Changes made here since are newer
+                    self.drafts.entry(draft.conversation).or_insert(draft.text);
+                }
+            }
+            Some(SessionUpdate::Presence(online)) => {
+                self.online = Some(online.into_iter().collect());
+            }
+            Some(SessionUpdate::ChannelNotifications(overrides)) => {
+                self.notification_levels = overrides
+                    .into_iter()
+                    .map(|channel| (channel.channelid, channel.level))
+                    .collect();
+            }
+            Some(SessionUpdate::Receipts(conversation, receipts)) => {
+                if conversation == self.conversation {
+                    let own = self.client.user_id();
+                    self.receipts = receipts
+                        .into_iter()
+                        .filter(|receipt| receipt.userid != own)
+                        .map(|receipt| (receipt.userid, receipt.message_id))
+                        .collect();
+                    self.revision += 1;
+                }
+            }
+            Some(SessionUpdate::Usernames(users)) => {
+                let mut changed = false;
+                for user in users {
+                    changed |= self.set_display_name(user.id, user.displayname);
+                    if self.known_usernames.get(&user.id) != Some(&user.username) {
+                        self.known_usernames.insert(user.id, user.username);
+                        changed = true;
+                    }
+                }
+                if changed {
+                    self.revision += 1;
+                    self.names_revision = self.revision;
+                }
+            }
+            Some(SessionUpdate::System(event)) => {
+                if let SystemEventKind::PresenceChanged {
+                    userid,
+                    username,
+                    online,
+                } = event.kind
+                {
+                    // Shown next to the messages rather than among them
+                    if let Some(users) = &mut self.online {
+                        if online {
+                            users.insert(userid);
+                        } else {
+                            users.remove(&userid);
+                        }
+                    }
+                    if self.known_usernames.get(&userid) != Some(&username) {
+                        self.known_usernames.insert(userid, username);
+                        self.revision += 1;
+                        self.names_revision = self.revision;
+                    }
+                    return false;
+                }
+                if let SystemEventKind::MessagesRead {
+                    userid,
+                    conversation,
+                    message_id,
+                } = event.kind
+                {
+                    // Shown next to the message read rather than as an event of its own
+                    if conversation == self.conversation && userid != self.client.user_id() {
+                        self.receipts.insert(userid, message_id);
+                        self.revision += 1;
+                    }
+                    return false;
+                }
+                if let SystemEventKind::SlowModeChanged { channelid, .. } = event.kind {
+                    // Only matters to those looking at the channel
+                    if self.conversation != Conversation::Channel(channelid) {
+                        return false;
+                    }
+                }
+                if let SystemEventKind::MessageDeleted { messageid } = event.kind {
+                    // Only worth mentioning where the message was shown
+                    let shown = self.messages.len();
+                    self.messages.retain(|message| message.id != messageid);
+                    if self.messages.len() == shown {
+                        return false;
+                    }
+                    self.edited.remove(&messageid);
+                }
+                let renamed = match &event.kind {
+                    SystemEventKind::UserJoined { userid, username }
+                    | SystemEventKind::UserRenamed {
+                        userid,
+                        new_name: username,
+                        ..
+                    } => self
+                        .known_usernames
+                        .insert(*userid, username.clone())
+                        .is_some_and(|old| old != *username),
+                    SystemEventKind::DisplayNameChanged {
+                        userid,
+                        username,
+                        displayname,
+                    } => {
+                        self.known_usernames.insert(*userid, username.clone());
+                        self.set_display_name(*userid, displayname.clone())
+                    }
+                    _ => false,
+                };
+                self.system.push(event);
+                if self.system.len() > MAX_MESSAGES {
+                    self.system.remove(0);
+                }
+                self.revision += 1;
+                if renamed {
+                    self.names_revision = self.revision;
+                }
+            }
+            Some(SessionUpdate::Notification(severity, text)) => {
+                notifications.push(severity, username, text);
+            }
+            None => self.updates = None,
+        }
+        false
+    }
+
+    /// Adds messages, replacing the ones we already have if they were edited.
+    fn merge(&mut self, messages: Vec<Message>) {
+        let revision = self.revision + 1;
+        let mut positions: HashMap<i32, usize> = self
+            .messages
+            .iter()
+            .enumerate()
+            .map(|(index, m)| (m.id, index))
+            .collect();
+        let mut changed = false;
+        for message in messages {
+            match positions.get(&message.id) {
+                // Fetched messages, like the ones caught up on after a reconnect, can also come in over the event
+                // stream, so a message we already have was only edited if it changed
+                Some(&index) => {
+                    let known = &mut self.messages[index];
+                    if known.messagetext != message.messagetext
+                        || known.date != message.date
+                        || known.userid != message.userid
+                    {
+                        self.edited.insert(message.id, revision);
+                        *known = message;
+                        changed = true;
+                    }
+                }
+                None => {
+                    positions.insert(message.id, self.messages.len());
+                    self.messages.push(message);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            // Servers that do not number messages leave all of them at 0, which falls back to the date
+            self.messages.sort_by_key(|m| (m.seq, m.date));
+            self.revision = revision;
+        }
+    }
+
+    /// Drops the messages beyond ``MAX_MESSAGES``, either the oldest or the newest ones.
+    fn trim(&mut self, keep_newest: bool) {
+        let excess = self.messages.len().saturating_sub(MAX_MESSAGES);
+        if excess == 0 {
+            return;
+        }
+        let dropped = if keep_newest {
+            self.has_older = true;
+            0..excess
+        } else {
+            self.has_newer = true;
+            MAX_MESSAGES..self.messages.len()
+        };
+        for message in self.messages.drain(dropped) {
+            self.edited.remove(&message.id);
+        }
+        if let Some(oldest) = self.messages.first().filter(|_| keep_newest) {
+            self.system.retain(|event| event.date >= oldest.date);
+        }
+    }
+}
+
+/// The latest part of the history. Asked for by the server's clock, as the one of the client may be off.
+fn latest(client: &impl ChatTransport, page_size: u32) -> MessageFilter {
+    if client.supports(Feature::ServerClock) {
+        MessageFilter::Latest(page_size)
+    } else {
+        MessageFilter::Before(client.server_now())
+    }
+}
+
+/// The background task of a session, which does everything that needs the network.
+struct Task<T> {
+    client: Arc<T>,
+    updates: Sender<SessionUpdate>,
+    /// The users whose names were already sent.
+    known_users: HashSet<i32>,
+    /// The conversation the ui shows. New messages of other conversations are only announced.
+    conversation: Conversation,
+    /// The date of the newest message sent, or when the session started if there was none. Whatever is newer is
+    /// fetched when the event stream reconnects, as it could have been missed whilst disconnected.
+    newest: DateTime<Utc>,
+    /// The sequence number of the newest message sent, 0 if there was none or the server does not number them.
+    newest_seq: i64,
+    /// The drafts changed since they were last sent to the server.
+    drafts: HashMap<Conversation, String>,
+    /// When to send the changed drafts, ``DRAFT_DELAY`` after the last change.
+    save_drafts_at: Option<Instant>,
+    /// How many messages to ask for in each part of the history, as set by the ui.
+    page_size: Arc<AtomicU32>,
+    recording: Option<SessionRecording>,
+}
+
+/// What the ui asks the background task of a session for.
+struct Requests {
+    history: Receiver<MessageFilter>,
+    jumps: Receiver<i32>,
+    reads: Receiver<(Conversation, i32)>,
+    switches: Receiver<Conversation>,
+    drafts: UnboundedReceiver<(Conversation, String)>,
+}
+
+/// The ends of ``Requests`` the ui holds.
+type RequestSenders = (
+    Sender<MessageFilter>,
+    Sender<i32>,
+    Sender<(Conversation, i32)>,
+    Sender<Conversation>,
+    UnboundedSender<(Conversation, String)>,
+);
+
+impl<T: ChatTransport> Task<T> {
+    /// Runs the task until the event stream closes or the session is dropped.
+    async fn run(mut self, mut events: Receiver<StreamEvent>, mut requests: Requests) {
+        if !self.initial_sync().await
+            || !self.report_motd().await
+            || !self.fetch_presence().await
+            || !self.fetch_receipts().await
+            || !self.fetch_channel_notifications().await
+        {
+            return;
+        }
+
+        loop {
+            let sent = tokio::select! {
+                event = events.recv() => match event {
+                    Some(StreamEvent::Message(message))
+                        if Conversation::of(&message, self.client.user_id()) != self.conversation =>
+                    {
+                        self.send(SessionUpdate::Elsewhere(message)).await
+                    }
+                    Some(StreamEvent::Message(message)) => {
+                        self.send_messages(vec![message], SessionUpdate::Messages).await
+                    }
+                    Some(StreamEvent::System(event)) => {
+                        if let SystemEventKind::UserJoined { userid, .. }
+                        | SystemEventKind::PresenceChanged { userid, .. } = &event.kind
+                        {
+                            self.known_users.insert(*userid);
+                        }
+                        self.send(SessionUpdate::System(event)).await
+                    }
+                    Some(StreamEvent::Disconnected) => {
+                        self.notify(Severity::Warning, t("session.disconnected").into()).await
+                    }
+                    Some(StreamEvent::Reconnected) => {
+                        self.notify(Severity::Info, t("session.reconnected").into()).await
+                            && self.catch_up().await
+                            && self.fetch_presence().await
+                            && self.fetch_receipts().await
+                            && self.fetch_channel_notifications().await
+                    }
+                    None => {
+                        self.notify(Severity::Error, t("session.closed").into()).await;
+                        return;
+                    }
+                },
+                Some(filter) = requests.history.recv() => self.fetch_history(filter).await,
+                Some(id) = requests.jumps.recv() => self.fetch_context(id).await,
+                Some(conversation) = requests.switches.recv() => self.switch(conversation).await,
+                Some((conversation, id)) = requests.reads.recv() => {
+                    // Not worth bothering the user with, reading the next message moves the marker anyway
+                    let _ = self.client.mark_read(conversation, id).await;
+                    true
+                }
+                Some((conversation, text)) = requests.drafts.recv() => {
+                    self.drafts.insert(conversation, text);
+                    self.save_drafts_at = Some(Instant::now() + DRAFT_DELAY);
+                    true
+                }
+                () = sleep_until(self.save_drafts_at.unwrap_or_else(Instant::now)),
+                    if self.save_drafts_at.is_some() =>
+                {
+                    self.save_drafts().await;
+                    true
+                }
+                () = self.updates.closed() => {
+                    self.save_drafts().await;
+                    return;
+                }
+            };
+            if !sent {
+                return;
+            }
+        }
+    }
+
+    /// Fetches the latest messages, the names of the users and how much was not read yet in one request, and
+    /// sends them. Servers without ``GET /sync/initial`` are asked for each part separately. Returns false if the
+    /// session was dropped.
+    async fn initial_sync(&mut self) -> bool {
+        let filter = latest(self.client.as_ref(), self.page_size());
+        if !self.client.supports(Feature::InitialSync) {
+            return self.report_unread().await
+                && self.fetch_history(filter).await
+                && self.fetch_drafts().await;
+        }
+        let sync = match self.client.get_initial_sync().await {
+            Ok(sync) => sync,
+            Err(e) if e.code() == Some(ErrorCode::NotFound) => {
+                return self.report_unread().await
+                    && self.fetch_history(filter).await
+                    && self.fetch_drafts().await;
+            }
+            Err(e) => {
+                let text = tf("session.history_failed", &[("error", &e.localized())]);
+                return self.notify(Severity::Error, text).await
+                    && self.send(SessionUpdate::History(filter, None)).await;
+            }
+        };
+
+        self.known_users
+            .extend(sync.users.iter().map(|user| user.id));
+        let Some(chat) = sync
+            .conversations
+            .into_iter()
+            .find(|conversation| conversation.overview.conversation == Conversation::Chat)
+        else {
+            return self.send(SessionUpdate::History(filter, None)).await;
+        };
+        let update = |messages| SessionUpdate::History(filter, Some(messages));
+        self.send(SessionUpdate::Usernames(sync.users)).await
+            && self.send_messages(chat.messages, update).await
+            && self.notify_unread(&chat.overview).await
+            && (sync.drafts.is_empty() || self.send(SessionUpdate::Drafts(sync.drafts)).await)
+    }
+
+    /// Fetches the drafts the user left on the server and sends them, for servers whose initial sync does not
+    /// include them. Returns false if the session was dropped.
+    async fn fetch_drafts(&self) -> bool {
+        if !self.client.supports(Feature::Drafts) {
+            return true;
+        }
+        match self.client.get_drafts().await {
+            Ok(drafts) if drafts.is_empty() => true,
+            Ok(drafts) => self.send(SessionUpdate::Drafts(drafts)).await,
+            Err(e) => {
+                let text = tf("session.drafts_failed", &[("error", &e.localized())]);
+                self.notify(Severity::Warning, text).await
+            }
+        }
+    }
+
+    /// Shows the message of the day, if the server has one. Returns false if the session was dropped.
+    async fn report_motd(&self) -> bool {
+        if !self.client.supports(Feature::Topics) {
+            return true;
+        }
+        match self.client.get_motd().await {
+            Ok(motd) if motd.text.is_empty() => true,
+            Ok(motd) => {
+                let text = tf("system.motd", &[("text", &motd.text)]);
+                self.notify(Severity::Info, text).await
+            }
+            Err(e) => {
+                let text = tf("session.motd_failed", &[("error", &e.localized())]);
+                self.notify(Severity::Warning, text).await
+            }
+        }
+    }
+
+    /// Fetches who is online and sends it, followed by the names of the users not known yet. Servers without
+    /// presence are not asked. Returns false if the session was dropped.
+    async fn fetch_presence(&mut self) -> bool {
+        if !self.client.supports(Feature::Presence) {
+            return true;
+        }
+        match self.client.get_presence().await {
+            Ok(presence) => {
+                let names_missing = presence
+                    .online
+                    .iter()
+                    .any(|userid| !self.known_users.contains(userid));
+                self.send(SessionUpdate::Presence(presence.online)).await
+                    && (!names_missing || self.fetch_usernames().await)
+            }
+            Err(e) => {
+                let text = tf("session.presence_failed", &[("error", &e.localized())]);
+                self.notify(Severity::Warning, text).await
+            }
+        }
+    }
+
+    /// Fetches how far the others read the conversation shown and sends it. Servers without read receipts are not
+    /// asked. Returns false if the session was dropped.
+    async fn fetch_receipts(&mut self) -> bool {
+        if !self.client.supports(Feature::ReadReceipts) {
+            return true;
+        }
+        let conversation = self.conversation;
+        match self.client.get_read_receipts(conversation).await {
+            Ok(receipts) => {
+                self.send(SessionUpdate::Receipts(conversation, receipts))
+                    .await
+            }
+            Err(e) => {
+                let text = tf("session.receipts_failed", &[("error", &e.localized())]);
+                self.notify(Severity::Warning, text).await
+            }
+        }
+    }
+
+    /// Fetches the channels the user gets fewer notifications for and sends them. Servers without per-channel
+    /// notifications are not asked. Returns false if the session was dropped.
+    async fn fetch_channel_notifications(&mut self) -> bool {
+        if !self.client.supports(Feature::ChannelNotifications) {
+            return true;
+        }
+        match self.client.get_channel_notifications().await {
+            Ok(overrides) => {
+                self.send(SessionUpdate::ChannelNotifications(overrides))
+                    .await
+            }
+            Err(e) => {
+                let text = tf("session.notifications_failed", &[("error", &e.localized())]);
+                self.notify(Severity::Warning, text).await
+            }
+        }
+    }
+
+    /// Sends the drafts changed since they were last sent to the server. A draft that could not be sent is left
+    /// as it is on the server, the next change sends it again.
+    async fn save_drafts(&mut self) {
+        self.save_drafts_at = None;
+        for (conversation, text) in self.drafts.drain() {
+            if let Err(e) = self.client.save_draft(conversation, &text).await {
+                warn!(?conversation, error = ?e, "Could not save draft");
+            }
+        }
+    }
+
+    /// Tells the user how many messages arrived since they last read the chat, and whether they were mentioned.
+    /// Returns false if the session was dropped.
+    async fn report_unread(&self) -> bool {
+        // Without read markers everything would count as unread
+        if !self.client.supports(Feature::ReadMarkers) {
+            return true;
+        }
+        let overview = match self.client.get_overview().await {
+            Ok(overview) => overview,
+            Err(e) => {
+                let text = tf("session.overview_failed", &[("error", &e.localized())]);
+                return self.notify(Severity::Warning, text).await;
+            }
+        };
+        match overview
+            .conversations
+            .iter()
+            .find(|conversation| conversation.conversation == Conversation::Chat)
+        {
+            Some(chat) => self.notify_unread(chat).await,
+            None => true,
+        }
+    }
+
+    /// Tells the user about the unread messages of the conversation, if there are any. Returns false if the
+    /// session was dropped.
+    async fn notify_unread(&self, overview: &ConversationOverview) -> bool {
+        if overview.unread == 0 {
+            return true;
+        }
+        let key = if overview.mentioned {
+            "session.unread_mentioned"
+        } else {
+            "session.unread"
+        };
+        let text = tf(key, &[("count", &overview.unread)]);
+        self.notify(Severity::Info, text).await
+    }
+
+    /// Fetches a part of the history and sends it. Failures are reported as a notification. Returns false if the
+    /// session was dropped.
+    async fn fetch_history(&mut self, filter: MessageFilter) -> bool {
+        let page_size = Some(self.page_size());
+        match self
+            .client
+            .get_messages_of(self.conversation, filter, page_size)
+            .await
+        {
+            Ok(messages) => {
+                let update = |messages| SessionUpdate::History(filter, Some(messages));
+                self.send_messages(messages, update).await
+            }
+            Err(e) => {
+                let text = tf("session.history_failed", &[("error", &e.localized())]);
+                self.notify(Severity::Error, text).await
+                    && self.send(SessionUpdate::History(filter, None)).await
+            }
+        }
+    }
+
+    /// Fetches the message to jump to and the ones around it. Failures are reported as a notification. Returns
+    /// false if the session was dropped.
+    async fn fetch_context(&mut self, id: i32) -> bool {
+        if !self.client.supports(Feature::MessageContext) {
+            return self
+                .notify(Severity::Warning, t("session.jump_unsupported").into())
+                .await
+                && self.send(SessionUpdate::Context(id, None)).await;
+        }
+        // A screen of messages on each side, so the message is shown in the middle of its conversation
+        let around = Some(self.page_size());
+        match self.client.get_message_context(id, around).await {
+            Ok(context) => {
+                // Messages can be linked from anywhere, so the jump may lead to another conversation
+                let conversation = Conversation::of(&context.message, self.client.user_id());
+                if conversation != self.conversation && !self.switched(conversation).await {
+                    return false;
+                }
+                let mut messages = context.before.clone();
+                messages.push(context.message.clone());
+                messages.extend(context.after.iter().cloned());
+                self.send_messages(messages, |_| SessionUpdate::Context(id, Some(context)))
+                    .await
+            }
+            Err(e) => {
+                let text = match e.code() {
+                    Some(ErrorCode::NotFound) => t("session.message_not_found").into(),
+                    _ => tf("session.jump_failed", &[("error", &e.localized())]),
+                };
+                self.notify(Severity::Error, text).await
+                    && self.send(SessionUpdate::Context(id, None)).await
+            }
+        }
+    }
+
+    /// Shows the conversation instead of the current one, starting with its latest messages. Returns false if the
+    /// session was dropped.
+    async fn switch(&mut self, conversation: Conversation) -> bool {
+        self.switched(conversation).await
+            && self
+                .fetch_history(latest(self.client.as_ref(), self.page_size()))
+                .await
+    }
+
+    /// Tells the ui that the conversation is shown now, and forgets about the newest message of the one before.
+    /// Returns false if the session was dropped.
+    async fn switched(&mut self, conversation: Conversation) -> bool {
+        self.conversation = conversation;
+        self.newest = self.client.server_now();
+        self.newest_seq = 0;
+        self.send(SessionUpdate::Switched(conversation)).await && self.fetch_receipts().await
+    }
+
+    /// Fetches the messages sent after the newest one we know of, sending them on as they arrive. They are sent as
+    /// new messages, the ui drops the ones that also came in over the event stream. Returns false if the session
+    /// was dropped.
+    async fn catch_up(&mut self) -> bool {
+        // Only the main chat can be streamed
+        if self.conversation != Conversation::Chat || !self.client.supports(Feature::MessageStream)
+        {
+            return self.catch_up_paged().await;
+        }
+        let mut stream = match self.client.stream_messages(self.newer()).await {
+            Ok(stream) => stream,
+            Err(e) if e.code() == Some(ErrorCode::NotFound) => return self.catch_up_paged().await,
+            Err(e) => {
+                let text = tf("session.catch_up_failed", &[("error", &e.localized())]);
+                return self.notify(Severity::Warning, text).await;
+            }
+        };
+        loop {
+            match stream.next().await {
+                Ok(Some(messages)) if messages.is_empty() => {}
+                Ok(Some(messages)) => {
+                    if !self.send_messages(messages, SessionUpdate::Messages).await {
+                        return false;
+                    }
+                }
+                Ok(None) => return true,
+                Err(e) => {
+                    let text = tf("session.catch_up_failed", &[("error", &e.localized())]);
+                    return self.notify(Severity::Warning, text).await;
+                }
+            }
+        }
+    }
+
+    /// Like ``catch_up``, but page by page, for servers that can not stream the history.
+    async fn catch_up_paged(&mut self) -> bool {
+        loop {
+            let messages = match self
+                .client
+                .get_messages_of(self.conversation, self.newer(), None)
+                .await
+            {
+                Ok(messages) if messages.is_empty() => return true,
+                Ok(messages) => messages,
+                Err(e) => {
+                    let text = tf("session.catch_up_failed", &[("error", &e.localized())]);
+                    return self.notify(Severity::Warning, text).await;
+                }
+            };
+            if !self.send_messages(messages, SessionUpdate::Messages).await {
+                return false;
+            }
+        }
+    }
+
+    /// Sends the messages, followed by the names of their senders if any of them are not known yet. The messages
+    /// go first, so they are shown right away even if the user directory is slow. Returns false if the session
+    /// was dropped.
+    async fn send_messages(
+        &mut self,
+        messages: Vec<Message>,
+        update: impl FnOnce(Vec<Message>) -> SessionUpdate,
+    ) -> bool {
+        if let Some(newest) = messages.iter().map(|m| m.date).max() {
+            self.newest = self.newest.max(newest);
+        }
+        if let Some(newest) = messages.iter().map(|m| m.seq).max() {
+            self.newest_seq = self.newest_seq.max(newest);
+        }
+        let names_missing = messages
+            .iter()
+            .flat_map(|m| [Some(m.userid), m.forwardeduserid])
+            .flatten()
+            .any(|userid| !self.known_users.contains(&userid));
+        self.send(update(messages)).await && (!names_missing || self.fetch_usernames().await)
+    }
+
+    /// Fetches the names of all users and sends them. Returns false if the session was dropped.
+    async fn fetch_usernames(&mut self) -> bool {
+        // On failure, the names are fetched again the next time an unknown user shows up
+        let update = match self.client.get_all_users().await {
+            Ok(users) => {
+                self.known_users.extend(users.iter().map(|user| user.id));
+                SessionUpdate::Usernames(users)
+            }
+            Err(e) => SessionUpdate::Notification(
+                Severity::Warning,
+                tf("session.usernames_failed", &[("error", &e.localized())]),
+            ),
+        };
+        self.send(update).await
+    }
+
+    fn page_size(&self) -> u32 {
+        self.page_size.load(Ordering::Relaxed)
+    }
+
+    /// The messages newer than the newest one sent, by sequence number if the server numbers them.
+    fn newer(&self) -> MessageFilter {
+        if self.newest_seq > 0 {
+            MessageFilter::AfterSeq(self.newest_seq)
+        } else {
+            MessageFilter::After(self.newest)
+        }
+    }
+
+    async fn notify(&self, severity: Severity, text: String) -> bool {
+        self.send(SessionUpdate::Notification(severity, text)).await
+    }
+
+    /// Passes an update on to the ui. Returns false if the session was dropped.
+    async fn send(&self, update: SessionUpdate) -> bool {
+        if let Some(recording) = &self.recording {
+            recording.record(&update);
+        }
+        self.updates.send(update).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chat_client::ClientBuilder;
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::scripted::{self, start, wait_until, ScriptedTransport};
+
+    fn session() -> SessionData {
+        let client = ClientBuilder::new().replay("localhost", 1).unwrap();
+        let (_, updates) = channel(1);
+        let requests = (
+            channel(1).0,
+            channel(1).0,
+            channel(1).0,
+            channel(1).0,
+            unbounded_channel().0,
+        );
+        let page_size = Arc::new(AtomicU32::new(HISTORY_PAGE));
+        SessionData::new(Arc::new(client), updates, requests, page_size)
+    }
+
+    fn message(id: i32, seq: i64) -> Message {
+        Message {
+            id,
+            date: Utc.timestamp_opt(1_700_000_000 + seq, 0).unwrap(),
+            messagetext: format!("message {id}"),
+            userid: 2,
+            forwardedid: None,
+            forwardeduserid: None,
+            seq,
+            channelid: None,
+            recipientid: None,
+            attachments: Vec::new(),
+        }
+    }
+
+    fn apply(session: &mut SessionData, update: SessionUpdate) -> bool {
+        session.apply("user", Some(update), &mut Notifications::default())
+    }
+
+    fn ids(session: &SessionData) -> Vec<i32> {
+        session.messages.iter().map(|m| m.id).collect()
+    }
+
+    fn seqs(session: &SessionData) -> Vec<i64> {
+        session.messages.iter().map(|m| m.seq).collect()
+    }
+
+    #[test]
+    fn catch_up_overlapping_the_stream_adds_no_duplicates() {
+        let mut session = session();
+        let history = vec![message(1, 1), message(2, 2), message(3, 3)];
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::Latest(20), Some(history)),
+        );
+        // Sent while reconnecting, so it arrives over the stream and again in the catch-up
+        apply(&mut session, SessionUpdate::Messages(vec![message(4, 4)]));
+        let catch_up = vec![message(3, 3), message(4, 4), message(5, 5)];
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::AfterSeq(2), Some(catch_up)),
+        );
+
+        assert_eq!(ids(&session), [1, 2, 3, 4, 5]);
+        assert_eq!(seqs(&session), [1, 2, 3, 4, 5]);
+        assert!(session.edited.is_empty());
+    }
+
+    #[test]
+    fn stream_repeating_caught_up_messages_changes_nothing() {
+        let mut session = session();
+        let catch_up = vec![message(1, 1), message(2, 2)];
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::AfterSeq(0), Some(catch_up)),
+        );
+        let revision = session.revision;
+
+        let new_messages = apply(&mut session, SessionUpdate::Messages(vec![message(2, 2)]));
+
+        assert!(!new_messages);
+        assert_eq!(session.revision, revision);
+        assert_eq!(ids(&session), [1, 2]);
+        assert!(session.edited.is_empty());
+    }
+
+    #[test]
+    fn messages_arriving_out_of_order_are_kept_in_sequence() {
+        let mut session = session();
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::Latest(20), Some(vec![message(1, 1)])),
+        );
+        // The stream delivered the newest message before the catch-up fetched the ones missed
+        apply(&mut session, SessionUpdate::Messages(vec![message(4, 4)]));
+        let catch_up = vec![message(2, 2), message(3, 3), message(4, 4)];
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::AfterSeq(1), Some(catch_up)),
+        );
+        apply(
+            &mut session,
+            SessionUpdate::Messages(vec![message(3, 3), message(5, 5)]),
+        );
+
+        assert_eq!(ids(&session), [1, 2, 3, 4, 5]);
+        assert_eq!(seqs(&session), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn message_changed_meanwhile_replaces_the_one_seen() {
+        let mut session = session();
+        let history = vec![message(1, 1), message(2, 2)];
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::Latest(20), Some(history)),
+        );
+        let mut edited = message(2, 2);
+        edited.messagetext = "edited".into();
+        apply(
+            &mut session,
+            SessionUpdate::History(MessageFilter::AfterSeq(1), Some(vec![edited])),
+        );
+
+        assert_eq!(ids(&session), [1, 2]);
+        assert_eq!(session.messages[1].messagetext, "edited");
+        assert!(session.edited.contains_key(&2));
+    }
+
+    fn history(count: i32) -> Vec<Message> {
+        (1..=count).map(|id| scripted::message(id, 2)).collect()
+    }
+
+    #[tokio::test]
+    async fn starting_shows_the_latest_messages_and_who_sent_them() {
+        let users = vec![scripted::user(1, "user"), scripted::user(2, "alice")];
+        let (transport, _events) = ScriptedTransport::new(history(30), users);
+        let (mut session, _) = start(transport);
+        let mut notifications = Notifications::default();
+
+        wait_until(&mut session, &mut notifications, |session| {
+            !session.loading && session.known_usernames.contains_key(&2)
+        })
+        .await;
+
+        assert_eq!(ids(&session), (11..=30).collect::<Vec<_>>());
+        assert!(session.has_older);
+        assert_eq!(session.known_usernames[&2], "alice");
+    }
+
+    #[tokio::test]
+    async fn scrolling_up_fetches_the_older_messages() {
+        let (transport, _events) = ScriptedTransport::new(history(30), Vec::new());
+        let (mut session, _) = start(transport);
+        let mut notifications = Notifications::default();
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+
+        session.load_older();
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+        assert_eq!(ids(&session), (1..=30).collect::<Vec<_>>());
+
+        // Only an empty page shows there is nothing older.
+        session.load_older();
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+        assert_eq!(session.messages.len(), 30);
+        assert!(!session.has_older);
+    }
+
+    #[tokio::test]
+    async fn messages_from_the_event_stream_are_added() {
+        let (transport, events) = ScriptedTransport::new(history(3), Vec::new());
+        let (mut session, _) = start(transport);
+        let mut notifications = Notifications::default();
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+
+        events
+            .send(StreamEvent::Message(scripted::message(4, 2)))
+            .await
+            .unwrap();
+        wait_until(&mut session, &mut notifications, |session| {
+            session.messages.len() == 4
+        })
+        .await;
+
+        assert_eq!(ids(&session), [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn failing_history_is_reported() {
+        let (transport, _events) = ScriptedTransport::new(history(3), Vec::new());
+        let (mut session, _) = start(transport.failing_history());
+        let mut notifications = Notifications::default();
+
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+
+        assert!(session.messages.is_empty());
+        let latest = notifications
+            .iter()
+            .last()
+            .expect("the failure is reported");
+        assert_eq!(latest.severity, Severity::Error);
+    }
+
+    #[tokio::test]
+    async fn reading_marks_the_newest_message_on_the_server() {
+        let (transport, _events) = ScriptedTransport::new(history(3), Vec::new());
+        let (mut session, transport) = start(transport);
+        let mut notifications = Notifications::default();
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+
+        session.mark_read();
+        let marked = async {
+            while transport.read.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), marked)
+            .await
+            .unwrap();
+
+        assert_eq!(*transport.read.lock().unwrap(), [(Conversation::Chat, 3)]);
+    }
+
+    #[tokio::test]
+    async fn closed_event_stream_ends_the_session() {
+        let (transport, events) = ScriptedTransport::new(history(3), Vec::new());
+        let (mut session, _) = start(transport);
+        let mut notifications = Notifications::default();
+        wait_until(&mut session, &mut notifications, |session| !session.loading).await;
+
+        drop(events);
+        wait_until(&mut session, &mut notifications, |session| {
+            session.updates.is_none()
+        })
+        .await;
+
+        assert_eq!(
+            notifications
+                .iter()
+                .last()
+                .map(|notification| notification.severity),
+            Some(Severity::Error)
+        );
+    }
+}
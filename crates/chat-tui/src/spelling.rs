@@ -0,0 +1,360 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::Path,
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::config::SpellcheckConfig;
+
+/// How many replacements are offered for a misspelled word at most.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Checks words against a Hunspell dictionary. Only the prefix and suffix rules of the affix file are supported,
+/// which is enough for the common dictionaries of most languages.
+#[derive(Debug)]
+pub struct SpellChecker {
+    words: HashSet<String>,
+    /// The characters tried when looking for replacements, most common first.
+    alphabet: Vec<char>,
+}
+
+/// A prefix or suffix rule of an affix file.
+#[derive(Debug)]
+struct Affix {
+    strip: String,
+    add: String,
+    condition: Vec<Condition>,
+}
+
+/// Matches a single character of a word an affix is applied to.
+#[derive(Debug)]
+enum Condition {
+    Any,
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+/// The rules of an affix file, keyed by flag.
+#[derive(Debug, Default)]
+struct AffixRules {
+    flag_type: FlagType,
+    prefixes: HashMap<String, (bool, Vec<Affix>)>,
+    suffixes: HashMap<String, (bool, Vec<Affix>)>,
+    alphabet: Vec<char>,
+}
+
+/// How flags are written in the dictionary, as set by ``FLAG`` in the affix file.
+#[derive(Debug, Default, Clone, Copy)]
+enum FlagType {
+    #[default]
+    Char,
+    Long,
+    Numeric,
+}
+
+impl SpellChecker {
+    /// Loads the dictionary selected in the configuration, or returns ``None`` if spell checking is turned off.
+    /// The dictionary directory is looked up in the data directory, unless it is absolute.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the dictionary could not be read.
+    pub fn from_config(config: &SpellcheckConfig, data_dir: &Path) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let directory = data_dir.join(&config.dictionaries);
+        let affixes = directory.join(format!("{}.aff", config.language));
+        let dictionary = directory.join(format!("{}.dic", config.language));
+        Self::load(&affixes, &dictionary).map(Some)
+    }
+
+    /// Loads a dictionary from its affix and word files.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either file could not be read.
+    pub fn load(affixes: &Path, dictionary: &Path) -> Result<Self> {
+        let affixes = fs::read_to_string(affixes)
+            .wrap_err_with(|| format!("Could not read {}", affixes.display()))?;
+        let dictionary = fs::read_to_string(dictionary)
+            .wrap_err_with(|| format!("Could not read {}", dictionary.display()))?;
+
+        let rules = AffixRules::parse(&affixes)?;
+        let mut words = HashSet::new();
+        // The first line holds the number of words
+        for line in dictionary.lines().skip(1) {
+            let entry = line.split_whitespace().next().unwrap_or_default();
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            if !word.is_empty() {
+                rules.expand(word, &rules.flags(flags), &mut words);
+            }
+        }
+
+        Ok(Self {
+            words,
+            alphabet: rules.alphabet,
+        })
+    }
+
+    /// Returns true if the word is in the dictionary. Capitalized words, like at the start of a sentence, and words
+    /// written in capitals are accepted as well.
+    pub fn check(&self, word: &str) -> bool {
+        if self.words.contains(word) || self.words.contains(&decapitalize(word)) {
+            return true;
+        }
+        let lowercase = word.to_lowercase();
+        word.chars().all(|c| !c.is_lowercase())
+            && (self.words.contains(&lowercase) || self.words.contains(&capitalize(&lowercase)))
+    }
+
+    /// Returns words from the dictionary that are one edit away from the given word.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let mut candidates: Vec<String> = Vec::new();
+
+        // Swapped neighbours and single wrong characters are the most common typos, so they are tried first
+        for i in 1..chars.len() {
+            let mut swapped = chars.clone();
+            swapped.swap(i - 1, i);
+            candidates.push(swapped.into_iter().collect());
+        }
+        for i in 0..chars.len() {
+            for &c in &self.alphabet {
+                let mut replaced = chars.clone();
+                replaced[i] = c;
+                candidates.push(replaced.into_iter().collect());
+            }
+        }
+        for i in 0..chars.len() {
+            let mut removed = chars.clone();
+            removed.remove(i);
+            candidates.push(removed.into_iter().collect());
+        }
+        for i in 0..=chars.len() {
+            for &c in &self.alphabet {
+                let mut inserted = chars.clone();
+                inserted.insert(i, c);
+                candidates.push(inserted.into_iter().collect());
+            }
+        }
+
+        let mut suggestions: Vec<String> = Vec::new();
+        for candidate in candidates {
+            if candidate != word && !suggestions.contains(&candidate) && self.check(&candidate) {
+                suggestions.push(candidate);
+                if suggestions.len() == MAX_SUGGESTIONS {
+                    break;
+                }
+            }
+        }
+        suggestions
+    }
+
+    /// Returns where the misspelled words in the text are. Words containing digits and anything that looks like
+    /// an address are skipped.
+    pub fn misspelled(&self, text: &str) -> Vec<Range<usize>> {
+        words(text)
+            .into_iter()
+            .filter(|word| {
+                let has_digits = text[word.clone()].chars().any(char::is_numeric);
+                !has_digits && !in_address(text, word) && !self.check(&text[word.clone()])
+            })
+            .collect()
+    }
+}
+
+/// Returns where the words in the text are. Apostrophes belong to a word only in the middle, like in "don't".
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        let in_word = c.is_alphanumeric() || c == '\'';
+        match start {
+            None if in_word && c != '\'' => start = Some(index),
+            Some(begin) if !in_word => {
+                let word = text[begin..index].trim_end_matches('\'');
+                words.push(begin..begin + word.len());
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Returns true if the word is part of something like a link or an email address.
+fn in_address(text: &str, word: &Range<usize>) -> bool {
+    let before = text[..word.start]
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or_default();
+    let after = text[word.end..]
+        .split(char::is_whitespace)
+        .next()
+        .unwrap_or_default();
+    let chunk = format!("{before}{}{after}", &text[word.clone()]);
+    chunk.contains("://") || chunk.contains('@') || chunk.starts_with("www.")
+}
+
+impl AffixRules {
+    fn parse(content: &str) -> Result<Self> {
+        let mut rules = Self::default();
+        let mut lines = content.lines();
+        while let Some(line) = lines.next() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => rules.flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => rules.flag_type = FlagType::Numeric,
+                ["TRY", characters, ..] => rules.alphabet = characters.chars().collect(),
+                [kind @ ("PFX" | "SFX"), flag, cross_product, count, ..] => {
+                    let count: usize = count
+                        .parse()
+                        .map_err(|_| eyre!("Invalid affix rule header: {line}"))?;
+                    let mut affixes = Vec::with_capacity(count);
+                    for rule in lines.by_ref().take(count) {
+                        let fields: Vec<&str> = rule.split_whitespace().collect();
+                        let [_, _, strip, add, rest @ ..] = fields.as_slice() else {
+                            return Err(eyre!("Invalid affix rule: {rule}"));
+                        };
+                        let add = add.split('/').next().unwrap_or_default();
+                        affixes.push(Affix {
+                            strip: if *strip == "0" { "" } else { strip }.to_string(),
+                            add: if add == "0" { "" } else { add }.to_string(),
+                            condition: parse_condition(rest.first().copied().unwrap_or(".")),
+                        });
+                    }
+                    let affixes = (*cross_product == "Y", affixes);
+                    if *kind == "PFX" {
+                        rules.prefixes.insert((*flag).to_string(), affixes);
+                    } else {
+                        rules.suffixes.insert((*flag).to_string(), affixes);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if rules.alphabet.is_empty() {
+            rules.alphabet = ('a'..='z').collect();
+        }
+        Ok(rules)
+    }
+
+    /// Splits the flags of a dictionary entry.
+    fn flags(&self, flags: &str) -> Vec<String> {
+        match self.flag_type {
+            FlagType::Char => flags.chars().map(String::from).collect(),
+            FlagType::Long => flags
+                .chars()
+                .collect::<Vec<char>>()
+                .chunks(2)
+                .map(|flag| flag.iter().collect())
+                .collect(),
+            FlagType::Numeric => flags.split(',').map(String::from).collect(),
+        }
+    }
+
+    /// Adds the word and every form its flags allow to the set.
+    fn expand(&self, word: &str, flags: &[String], words: &mut HashSet<String>) {
+        words.insert(word.to_string());
+
+        let mut suffixed = Vec::new();
+        for (cross_product, affixes) in flags.iter().filter_map(|flag| self.suffixes.get(flag)) {
+            for affix in affixes {
+                if let Some(form) = affix.apply_suffix(word) {
+                    if *cross_product {
+                        suffixed.push(form.clone());
+                    }
+                    words.insert(form);
+                }
+            }
+        }
+
+        for (cross_product, affixes) in flags.iter().filter_map(|flag| self.prefixes.get(flag)) {
+            for affix in affixes {
+                if let Some(form) = affix.apply_prefix(word) {
+                    words.insert(form);
+                }
+                if *cross_product {
+                    words.extend(suffixed.iter().filter_map(|form| affix.apply_prefix(form)));
+                }
+            }
+        }
+    }
+}
+
+impl Affix {
+    fn apply_suffix(&self, word: &str) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let start = chars.len().checked_sub(self.condition.len())?;
+        if !matches(&self.condition, &chars[start..]) {
+            return None;
+        }
+        let stem = word.strip_suffix(self.strip.as_str())?;
+        Some(format!("{stem}{}", self.add))
+    }
+
+    fn apply_prefix(&self, word: &str) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < self.condition.len() {
+            return None;
+        }
+        if !matches(&self.condition, &chars[..self.condition.len()]) {
+            return None;
+        }
+        let stem = word.strip_prefix(self.strip.as_str())?;
+        Some(format!("{}{stem}", self.add))
+    }
+}
+
+/// Parses conditions like ``[^aeiou]y``, which are a simplified form of regular expressions.
+fn parse_condition(condition: &str) -> Vec<Condition> {
+    let mut result = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => result.push(Condition::Any),
+            '[' => {
+                let group: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                match group.strip_prefix('^') {
+                    Some(excluded) => result.push(Condition::NoneOf(excluded.chars().collect())),
+                    None => result.push(Condition::OneOf(group.chars().collect())),
+                }
+            }
+            c => result.push(Condition::OneOf(vec![c])),
+        }
+    }
+    result
+}
+
+fn matches(condition: &[Condition], chars: &[char]) -> bool {
+    condition
+        .iter()
+        .zip(chars)
+        .all(|(condition, c)| match condition {
+            Condition::Any => true,
+            Condition::OneOf(allowed) => allowed.contains(c),
+            Condition::NoneOf(excluded) => !excluded.contains(c),
+        })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn decapitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_lowercase().chain(chars).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Utc};
+use chrono_tz::Tz;
+use eyre::{eyre, Result};
+
+use crate::config::{Clock, UiConfig};
+
+/// Shows times received from the server, which are in UTC, in the time zone and clock format the user prefers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeFormat {
+    clock: Clock,
+    /// The time zone times are shown in. ``None`` uses the time zone of the system.
+    timezone: Option<Tz>,
+}
+
+impl TimeFormat {
+    /// Creates a ``TimeFormat`` from the ui configuration.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the configured time zone is not known.
+    pub fn from_config(config: &UiConfig) -> Result<Self> {
+        let timezone = config
+            .timezone
+            .as_deref()
+            .map(|name| {
+                name.parse::<Tz>()
+                    .map_err(|_| eyre!("Unknown time zone {name}, use a name like Europe/Berlin"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            clock: config.clock,
+            timezone,
+        })
+    }
+
+    /// Returns the time of day, like ``14:05`` or ``2:05 PM``.
+    pub fn time(&self, time: DateTime<Utc>) -> String {
+        let format = match self.clock {
+            Clock::TwentyFourHour => "%H:%M",
+            Clock::TwelveHour => "%-I:%M %p",
+        };
+        self.local(time).format(format).to_string()
+    }
+
+    /// Returns the time of day including seconds.
+    pub fn time_with_seconds(&self, time: DateTime<Utc>) -> String {
+        let format = match self.clock {
+            Clock::TwentyFourHour => "%H:%M:%S",
+            Clock::TwelveHour => "%-I:%M:%S %p",
+        };
+        self.local(time).format(format).to_string()
+    }
+
+    /// Returns the day the time falls on in the selected time zone.
+    pub fn date(&self, time: DateTime<Utc>) -> NaiveDate {
+        self.local(time).date_naive()
+    }
+
+    fn local(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self.timezone {
+            Some(timezone) => time.with_timezone(&timezone).fixed_offset(),
+            None => time.with_timezone(&Local).fixed_offset(),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::slice::{Iter, IterMut};
+
+/// A wrapper around ``Vec<T>`` holding the index of a element to be considered 'active'.
+#[derive(Debug, Clone)]
+pub struct ActiveVec<T> {
+    items: Vec<T>,
+    active_index: Option<usize>,
+}
+
+impl<T> ActiveVec<T> {
+    /// Creates a new instance of ``ActiveVec<T>``.
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            active_index: None,
+        }
+    }
+
+    /// Appends an element to the back of the collection. Marks the element also as active, if it's the only element in the collection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity exceeds `isize::MAX` bytes.
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+        if self.active_index.is_none() {
+            self.active_index = Some(0);
+        }
+    }
+
+    /// Inserts an element at the given index, shifting all elements after it to the right. The active element
+    /// stays active. Marks the element as active, if it's the only element in the collection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, item: T) {
+        self.items.insert(index, item);
+        self.active_index = match self.active_index {
+            Some(active) if index <= active => Some(active + 1),
+            Some(active) => Some(active),
+            None => Some(0),
+        };
+    }
+
+    /// Removes and returns the element at the given index, or ``None`` if the index is out of bounds. If the
+    /// removed element was active, the element taking its place becomes active, or the new last element if
+    /// there is none.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.items.len() {
+            return None;
+        }
+        let item = self.items.remove(index);
+        self.active_index = match self.active_index {
+            _ if self.items.is_empty() => None,
+            Some(active) if index < active => Some(active - 1),
+            Some(active) => Some(active.min(self.items.len() - 1)),
+            None => None,
+        };
+
+        Some(item)
+    }
+
+    /// Swaps two elements. If one of them is active, it stays active at its new position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` or `b` are out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.items.swap(a, b);
+        self.active_index = match self.active_index {
+            Some(active) if active == a => Some(b),
+            Some(active) if active == b => Some(a),
+            active => active,
+        };
+    }
+
+    /// Marks the element at the given index as active. Returns false and leaves the active element
+    /// unchanged, if the index is out of bounds.
+    pub fn set_active(&mut self, index: usize) -> bool {
+        if index >= self.items.len() {
+            return false;
+        }
+        self.active_index = Some(index);
+        true
+    }
+
+    /// Get the index for the currently active element. Returns ``None`` if the collection is empty.
+    pub fn get_active_index(&self) -> Option<usize> {
+        self.active_index
+    }
+
+    /// Get a reference to the active element. Returns ``None`` if the collection is empty.
+    pub fn get_active(&self) -> Option<&T> {
+        self.active_index.and_then(|i| self.items.get(i))
+    }
+
+    /// Get a mutable reference to the active element. Returns ``None`` if the collection is empty.
+    pub fn get_active_mut(&mut self) -> Option<&mut T> {
+        self.active_index.and_then(|i| self.items.get_mut(i))
+    }
+
+    /// Returns an iterator over the elements in the collection.
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Returns an iterator that allows modifying each element in the collection.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        self.items.iter_mut()
+    }
+
+    /// Increments the index of the active element. Wraps around to the start if the end has been reached.
+    /// If no elemnts are in the collection, nothing happens.
+    pub fn next(&mut self) {
+        if let Some(index) = self.active_index {
+            if index + 1 >= self.items.len() {
+                self.active_index = Some(0);
+            } else {
+                self.active_index = Some(index + 1);
+            }
+        }
+    }
+
+    /// Decrements the index of the active element. Wraps around to the end if the index is at the start.
+    /// If no elemnts are in the collection, nothing happens.
+    pub fn prev(&mut self) {
+        if let Some(index) = self.active_index {
+            if index == 0 {
+                self.active_index = self.items.len().checked_sub(1);
+            } else {
+                self.active_index = Some(index - 1);
+            }
+        }
+    }
+}
+
+impl<T> Default for ActiveVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<T> for ActiveVec<T> {
+    fn from_iter<Iter: IntoIterator<Item = T>>(iter: Iter) -> Self {
+        let items: Vec<T> = iter.into_iter().collect();
+        let active_index = if items.is_empty() { None } else { Some(0) };
+        Self {
+            items,
+            active_index,
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for ActiveVec<T> {
+    fn from(value: Vec<T>) -> Self {
+        let mut active_vec = ActiveVec::new();
+        for ele in value {
+            active_vec.push(ele);
+        }
+
+        active_vec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActiveVec;
+
+    fn items(vec: &ActiveVec<char>) -> Vec<char> {
+        vec.iter().copied().collect()
+    }
+
+    #[test]
+    fn empty_has_no_active_element() {
+        let mut vec: ActiveVec<char> = ActiveVec::new();
+        vec.next();
+        vec.prev();
+        assert_eq!(vec.get_active_index(), None);
+        assert_eq!(vec.get_active(), None);
+        assert!(!vec.set_active(0));
+        assert_eq!(vec.remove(0), None);
+    }
+
+    #[test]
+    fn first_element_becomes_active() {
+        let mut vec = ActiveVec::new();
+        vec.push('a');
+        vec.push('b');
+        assert_eq!(vec.get_active(), Some(&'a'));
+
+        let mut vec = ActiveVec::new();
+        vec.insert(0, 'a');
+        assert_eq!(vec.get_active(), Some(&'a'));
+        assert_eq!(ActiveVec::from(vec!['a', 'b']).get_active(), Some(&'a'));
+        let collected: ActiveVec<_> = ['a', 'b'].into_iter().collect();
+        assert_eq!(collected.get_active(), Some(&'a'));
+    }
+
+    #[test]
+    fn next_wraps_around_at_the_end() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        vec.next();
+        vec.next();
+        assert_eq!(vec.get_active(), Some(&'c'));
+        vec.next();
+        assert_eq!(vec.get_active(), Some(&'a'));
+    }
+
+    #[test]
+    fn prev_wraps_around_at_the_start() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        vec.prev();
+        assert_eq!(vec.get_active(), Some(&'c'));
+        vec.prev();
+        assert_eq!(vec.get_active(), Some(&'b'));
+    }
+
+    #[test]
+    fn next_and_prev_keep_a_single_element_active() {
+        let mut vec = ActiveVec::from(vec!['a']);
+        vec.next();
+        assert_eq!(vec.get_active_index(), Some(0));
+        vec.prev();
+        assert_eq!(vec.get_active_index(), Some(0));
+    }
+
+    #[test]
+    fn set_active_selects_within_bounds_only() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        assert!(vec.set_active(2));
+        assert_eq!(vec.get_active(), Some(&'c'));
+        assert!(!vec.set_active(3));
+        assert_eq!(vec.get_active(), Some(&'c'));
+        assert!(vec.set_active(0));
+        assert_eq!(vec.get_active(), Some(&'a'));
+    }
+
+    #[test]
+    fn insert_keeps_the_active_element() {
+        let mut vec = ActiveVec::from(vec!['a', 'b']);
+        vec.set_active(1);
+        vec.insert(0, 'x');
+        assert_eq!(vec.get_active(), Some(&'b'));
+        vec.insert(3, 'y');
+        assert_eq!(vec.get_active(), Some(&'b'));
+        assert_eq!(items(&vec), ['x', 'a', 'b', 'y']);
+    }
+
+    #[test]
+    fn removing_before_the_active_element_keeps_it() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        vec.set_active(2);
+        assert_eq!(vec.remove(0), Some('a'));
+        assert_eq!(vec.get_active(), Some(&'c'));
+        assert_eq!(vec.remove(0), Some('b'));
+        assert_eq!(vec.get_active(), Some(&'c'));
+    }
+
+    #[test]
+    fn removing_the_active_element_activates_the_next_one() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        vec.set_active(1);
+        assert_eq!(vec.remove(1), Some('b'));
+        assert_eq!(vec.get_active(), Some(&'c'));
+    }
+
+    #[test]
+    fn removing_the_active_last_element_activates_the_new_last_one() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        vec.set_active(2);
+        assert_eq!(vec.remove(2), Some('c'));
+        assert_eq!(vec.get_active(), Some(&'b'));
+    }
+
+    #[test]
+    fn removing_the_only_element_leaves_none_active() {
+        let mut vec = ActiveVec::from(vec!['a']);
+        assert_eq!(vec.remove(0), Some('a'));
+        assert_eq!(vec.get_active_index(), None);
+        vec.prev();
+        assert_eq!(vec.get_active_index(), None);
+        vec.push('b');
+        assert_eq!(vec.get_active(), Some(&'b'));
+    }
+
+    #[test]
+    fn removing_out_of_bounds_changes_nothing() {
+        let mut vec = ActiveVec::from(vec!['a', 'b']);
+        vec.set_active(1);
+        assert_eq!(vec.remove(2), None);
+        assert_eq!(items(&vec), ['a', 'b']);
+        assert_eq!(vec.get_active(), Some(&'b'));
+    }
+
+    #[test]
+    fn swap_moves_the_active_element_along() {
+        let mut vec = ActiveVec::from(vec!['a', 'b', 'c']);
+        vec.swap(0, 2);
+        assert_eq!(vec.get_active(), Some(&'a'));
+        assert_eq!(vec.get_active_index(), Some(2));
+        vec.swap(1, 2);
+        assert_eq!(vec.get_active_index(), Some(1));
+        vec.swap(0, 2);
+        assert_eq!(vec.get_active_index(), Some(1));
+        assert_eq!(items(&vec), ['b', 'a', 'c']);
+    }
+}
@@ -0,0 +1,51 @@
+use tui::{
+    style::Style,
+    text::Span,
+    widgets::{Block, Borders, Paragraph},
+};
+
+use super::Theme;
+
+/// Represents a form element in a ui screen.
+#[derive(Debug, Clone)]
+pub struct FormElement {
+    pub title: String,
+    pub content: String,
+    pub visibility: Visibility,
+}
+
+/// Indicates whether the contents of a ``FormElement`` should be shown or replaced by asterisks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Visible,
+    Hidden,
+}
+
+impl FormElement {
+    /// Creates a new ``FormElement``.
+    pub fn new(title: &str, visibility: Visibility) -> Self {
+        Self {
+            content: String::new(),
+            title: title.into(),
+            visibility,
+        }
+    }
+}
+
+/// Creates a ``Paragraph`` widget for the given ``FormElement``. Active elements are highlighted.
+pub fn form_element_ui<'a>(element: &FormElement, active: bool, theme: Theme) -> Paragraph<'a> {
+    let active_style = theme.focus(active);
+    let title = format!("{}{}", theme.marker(active), element.title);
+
+    let content = match element.visibility {
+        Visibility::Visible => element.content.clone(),
+        Visibility::Hidden => "*".repeat(element.content.len()),
+    };
+
+    Paragraph::new(Span::styled(content, Style::default())).block(
+        Block::default()
+            .title(Span::styled(title, active_style))
+            .borders(Borders::ALL)
+            .border_style(active_style),
+    )
+}
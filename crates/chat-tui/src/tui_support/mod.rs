@@ -0,0 +1,8 @@
+//! Building blocks shared by the terminal user interfaces.
+mod active_vec;
+mod form;
+mod theme;
+
+pub use active_vec::ActiveVec;
+pub use form::{form_element_ui, FormElement, Visibility};
+pub use theme::Theme;
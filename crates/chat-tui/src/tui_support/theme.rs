@@ -0,0 +1,52 @@
+use tui::style::{Color, Modifier, Style};
+
+/// Decides how the ui highlights things. Without colors, textual markers are used instead, so nothing is
+/// conveyed by color alone. This also keeps the output usable with screen readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    colors: bool,
+}
+
+impl Theme {
+    pub fn new(colors: bool) -> Self {
+        Self { colors }
+    }
+
+    /// Returns false, if textual markers have to be used instead of colors.
+    pub fn colors(&self) -> bool {
+        self.colors
+    }
+
+    /// Returns the style for something in the given color, or the default style if colors are disabled.
+    pub fn color(&self, color: Color) -> Style {
+        if self.colors {
+            Style::default().fg(color)
+        } else {
+            Style::default()
+        }
+    }
+
+    /// Returns the style for an element that might have focus.
+    pub fn focus(&self, focused: bool) -> Style {
+        match (focused, self.colors) {
+            (true, true) => Style::default().fg(Color::Yellow),
+            (true, false) => Style::default().add_modifier(Modifier::BOLD),
+            (false, _) => Style::default(),
+        }
+    }
+
+    /// Returns the text to put in front of an element that might have focus. Only used without colors.
+    pub fn marker(&self, focused: bool) -> &'static str {
+        if focused && !self.colors {
+            "> "
+        } else {
+            ""
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
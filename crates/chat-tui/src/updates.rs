@@ -0,0 +1,95 @@
+//! Telling the user when the client is too old for a server, or a newer release is out.
+use chat_client::{config::NetworkConfig, ClientBuilder, Release, ShutdownHandler};
+use chat_core::{protocol::ServerInfo, Version};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::i18n::tf;
+
+/// Why the client should be updated. Shown in a banner until the client is restarted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateNotice {
+    /// The server with that name needs at least that version.
+    Required {
+        server: String,
+        version: String,
+    },
+    Available(Release),
+}
+
+impl UpdateNotice {
+    /// Returns the notice for a server that needs a newer client than this one, if it does.
+    pub fn for_server(info: &ServerInfo) -> Option<Self> {
+        let required = info.min_client_version.as_ref()?;
+        // Servers check their version when starting, so an invalid one is from a server that is not ours
+        let outdated = required
+            .parse::<Version>()
+            .is_ok_and(|required| Version::current() < required);
+        outdated.then(|| Self::Required {
+            server: info.name.clone(),
+            version: required.clone(),
+        })
+    }
+
+    /// Whether the notice matters more than the other one, so it replaces it.
+    pub fn outranks(&self, other: &Self) -> bool {
+        matches!((self, other), (Self::Required { .. }, Self::Available(_)))
+    }
+
+    pub fn text(&self) -> String {
+        match self {
+            Self::Required { server, version } => tf(
+                "updates.required",
+                &[
+                    ("server", server),
+                    ("version", version),
+                    ("current", &env!("CARGO_PKG_VERSION")),
+                ],
+            ),
+            Self::Available(Release { version, url: None }) => {
+                tf("updates.available", &[("version", version)])
+            }
+            Self::Available(Release {
+                version,
+                url: Some(url),
+            }) => tf(
+                "updates.available_at",
+                &[("version", version), ("url", url)],
+            ),
+        }
+    }
+}
+
+/// Looks for a newer release at the configured ``update_url`` in the background. The notice is sent on the
+/// returned receiver if there is one. Returns ``None`` if no ``update_url`` is configured.
+pub fn check(
+    config: &NetworkConfig,
+    shutdown: ShutdownHandler,
+) -> Option<oneshot::Receiver<UpdateNotice>> {
+    let url = config.update_url.clone()?;
+    let builder = ClientBuilder::from_config(config).shutdown(shutdown);
+    let (sender, receiver) = oneshot::channel();
+    tokio::spawn(async move {
+        let release = match builder.latest_release(&url).await {
+            Ok(release) => release,
+            // Not worth bothering the user with, the next start tries again
+            Err(e) => {
+                warn!(url, error = ?e, "Could not look for a newer release");
+                return;
+            }
+        };
+        let Ok(version) = release.version.parse::<Version>() else {
+            warn!(
+                url,
+                version = release.version,
+                "The newest release has an invalid version"
+            );
+            return;
+        };
+        info!(version = release.version, "Found the newest release");
+        if Version::current() < version {
+            let _ = sender.send(UpdateNotice::Available(release));
+        }
+    });
+    Some(receiver)
+}
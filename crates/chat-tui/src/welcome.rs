@@ -0,0 +1,264 @@
+use chat_client::parse_address;
+use crossterm::event::KeyCode;
+use tui::{
+    buffer::Buffer,
+    layout::{Alignment::Center, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Paragraph, Widget, Wrap},
+};
+
+use crate::{
+    errors::Localized,
+    i18n::{t, tf},
+    screens::choice_ui,
+    tui_support::{form_element_ui, FormElement, Theme, Visibility},
+    ChatData,
+};
+
+/// The steps of the welcome wizard, in the order they are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Server,
+    Account,
+    Theme,
+    Bell,
+}
+
+impl Step {
+    const ALL: [Step; 4] = [Self::Server, Self::Account, Self::Theme, Self::Bell];
+
+    fn title(self) -> &'static str {
+        match self {
+            Step::Server => t("welcome.server_title"),
+            Step::Account => t("welcome.account_title"),
+            Step::Theme => t("welcome.theme_title"),
+            Step::Bell => t("welcome.bell_title"),
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Step::Server => t("welcome.server_text"),
+            Step::Account => t("welcome.account_text"),
+            Step::Theme => t("welcome.theme_text"),
+            Step::Bell => t("welcome.bell_text"),
+        }
+    }
+}
+
+/// Guides through the configuration when the client is started for the first time. Once done, the choices are
+/// written to ``client.toml`` and the login window takes over.
+#[derive(Debug, Clone)]
+pub struct WelcomeWindow {
+    step: Step,
+    address: FormElement,
+    /// Whether the server step is skipped, because the client runs its own server.
+    local: bool,
+    register: bool,
+    accessible: bool,
+    bell: bool,
+    status_message: Option<String>,
+}
+
+/// What the login window is prepared for after the welcome wizard.
+pub struct Welcome {
+    pub address: String,
+    pub register: bool,
+}
+
+impl WelcomeWindow {
+    /// Creates a new ``WelcomeWindow``. In local mode, the address of the embedded server is used.
+    pub(crate) fn new(local_address: Option<&str>, data: &ChatData) -> Self {
+        let mut address = FormElement::new(t("login.address"), Visibility::Visible);
+        if let Some(local_address) = local_address {
+            address.content = local_address.to_string();
+        }
+        Self {
+            step: if local_address.is_some() {
+                Step::Account
+            } else {
+                Step::Server
+            },
+            address,
+            local: local_address.is_some(),
+            // Nobody has an account yet on a server that was just started
+            register: local_address.is_some(),
+            accessible: data.config.ui.accessible,
+            bell: data.config.ui.bell,
+            status_message: None,
+        }
+    }
+
+    /// Handles a key press, applying the choices to the configuration right away so they can be seen. Returns
+    /// what to log in with once the last step is done and the configuration was written.
+    pub(crate) fn handle_key(&mut self, code: KeyCode, data: &mut ChatData) -> Option<Welcome> {
+        self.status_message = None;
+        match (code, self.step) {
+            (KeyCode::Char(c), Step::Server) => self.address.content.push(c),
+            (KeyCode::Backspace, Step::Server) => {
+                self.address.content.pop();
+            }
+            (KeyCode::Left, Step::Account) => self.register = false,
+            (KeyCode::Right, Step::Account) => self.register = true,
+            (KeyCode::Left | KeyCode::Right, Step::Theme) => {
+                self.accessible = code == KeyCode::Right;
+                data.config.ui.accessible = self.accessible;
+                data.theme = Theme::new(!data.config.ui.no_color());
+            }
+            (KeyCode::Char(' '), Step::Bell) => {
+                self.bell = !self.bell;
+                data.config.ui.bell = self.bell;
+            }
+            (KeyCode::Esc, _) => {
+                if let Some(previous) = self.previous() {
+                    self.step = previous;
+                }
+            }
+            (KeyCode::Enter, Step::Server) => match parse_address(&self.address.content) {
+                Ok(_) => self.step = Step::Account,
+                Err(e) => self.status_message = Some(e.localized()),
+            },
+            (KeyCode::Enter, _) => match self.next() {
+                Some(next) => self.step = next,
+                None => return self.finish(data),
+            },
+            _ => {}
+        }
+        None
+    }
+
+    /// Writes the configuration. The address of the embedded server changes with every start, so it is not kept.
+    fn finish(&mut self, data: &mut ChatData) -> Option<Welcome> {
+        let address = self.address.content.trim().to_string();
+        if !self.local {
+            data.config.network.server = Some(address.clone());
+        }
+        if let Err(e) = data.config.save(&data.config_dir) {
+            self.status_message = Some(tf("welcome.save_failed", &[("error", &e)]));
+            return None;
+        }
+        Some(Welcome {
+            address,
+            register: self.register,
+        })
+    }
+
+    /// The steps that are shown. The server step is left out in local mode.
+    fn steps(&self) -> Vec<Step> {
+        Step::ALL
+            .into_iter()
+            .filter(|&step| !(self.local && step == Step::Server))
+            .collect()
+    }
+
+    fn next(&self) -> Option<Step> {
+        let steps = self.steps();
+        let index = steps.iter().position(|&step| step == self.step)?;
+        steps.get(index + 1).copied()
+    }
+
+    fn previous(&self) -> Option<Step> {
+        let steps = self.steps();
+        let index = steps.iter().position(|&step| step == self.step)?;
+        steps.get(index.checked_sub(1)?).copied()
+    }
+
+    /// Returns where the terminal cursor belongs, if the current step has a text input.
+    pub fn cursor(&self, area: Rect) -> Option<(u16, u16)> {
+        let control = welcome_layout(area)[2];
+        match self.step {
+            Step::Server => {
+                let width =
+                    u16::try_from(Span::raw(&self.address.content).width()).unwrap_or(u16::MAX);
+                let column = (control.x + 1)
+                    .saturating_add(width)
+                    .min(control.right().saturating_sub(2));
+                Some((column, control.y + 1))
+            }
+            _ => Some((control.x, control.y)),
+        }
+    }
+
+    /// Draws the current step into the area.
+    pub fn render(&self, area: Rect, buf: &mut Buffer, theme: Theme) {
+        let layout = welcome_layout(area);
+        let steps = self.steps();
+        let number = steps
+            .iter()
+            .position(|&step| step == self.step)
+            .unwrap_or(0)
+            + 1;
+
+        Paragraph::new(Span::styled(
+            tf(
+                "welcome.step",
+                &[
+                    ("number", &number),
+                    ("count", &steps.len()),
+                    ("title", &self.step.title()),
+                ],
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+        .render(layout[0], buf);
+        Paragraph::new(self.step.description())
+            .wrap(Wrap { trim: true })
+            .render(layout[1], buf);
+
+        match self.step {
+            Step::Server => form_element_ui(&self.address, true, theme).render(layout[2], buf),
+            Step::Account => Paragraph::new(choice_ui(
+                &[t("login.intent_login"), t("login.intent_register")],
+                usize::from(self.register),
+                true,
+                theme,
+            ))
+            .render(layout[2], buf),
+            Step::Theme => Paragraph::new(choice_ui(
+                &[t("welcome.theme_colors"), t("welcome.theme_accessible")],
+                usize::from(self.accessible),
+                true,
+                theme,
+            ))
+            .render(layout[2], buf),
+            Step::Bell => {
+                let checkbox = if self.bell { "[x]" } else { "[ ]" };
+                Paragraph::new(Span::styled(
+                    format!("{}{checkbox} {}", theme.marker(true), t("welcome.bell")),
+                    theme.focus(true),
+                ))
+                .render(layout[2], buf);
+            }
+        }
+
+        if let Some(message) = &self.status_message {
+            Paragraph::new(Span::styled(message, Style::default()))
+                .alignment(Center)
+                .render(layout[3], buf);
+        }
+        let hint = if self.next().is_some() {
+            t("welcome.next_hint")
+        } else {
+            t("welcome.finish_hint")
+        };
+        Paragraph::new(Spans::from(hint))
+            .alignment(Center)
+            .render(layout[4], buf);
+    }
+}
+
+/// Splits the welcome window into the heading, the description, the control of the step, the status line and
+/// the hint.
+fn welcome_layout(area: Rect) -> Vec<Rect> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(area)
+}